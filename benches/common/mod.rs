@@ -0,0 +1,123 @@
+//! Shared by the pcap benchmarks: UDP payload extraction and splitting into ASTERIX data blocks.
+
+use aiprotodsl::{Codec, ResolvedProtocol};
+use pcap_parser::pcap::LegacyPcapReader;
+use pcap_parser::traits::PcapReaderIterator;
+use pcap_parser::{Linktype, PcapBlockOwned, PcapError};
+use std::fs::File;
+
+fn ethernet_l3(frame: &[u8]) -> Option<&[u8]> {
+    if frame.len() < 14 {
+        return None;
+    }
+    let mut off = 12usize;
+    let mut ethertype = u16::from_be_bytes([frame[off], frame[off + 1]]);
+    off += 2;
+    while ethertype == 0x8100 || ethertype == 0x88a8 {
+        if frame.len() < off + 4 + 2 {
+            return None;
+        }
+        off += 4;
+        ethertype = u16::from_be_bytes([frame[off], frame[off + 1]]);
+        off += 2;
+    }
+    match ethertype {
+        0x0800 => Some(&frame[off..]),
+        _ => None,
+    }
+}
+
+fn ipv4_udp_payload(l3: &[u8]) -> Option<&[u8]> {
+    if l3.len() < 20 {
+        return None;
+    }
+    let ver_ihl = l3[0];
+    if (ver_ihl >> 4) != 4 {
+        return None;
+    }
+    let ihl = (ver_ihl & 0x0f) as usize * 4;
+    if ihl < 20 || l3.len() < ihl {
+        return None;
+    }
+    let total_len = u16::from_be_bytes([l3[2], l3[3]]) as usize;
+    let l3_trunc = if total_len <= l3.len() { &l3[..total_len] } else { l3 };
+    if l3_trunc.len() < ihl + 8 || l3_trunc[9] != 17 {
+        return None;
+    }
+    let udp = &l3_trunc[ihl..];
+    if udp.len() < 8 {
+        return None;
+    }
+    let udp_len = u16::from_be_bytes([udp[4], udp[5]]) as usize;
+    if udp_len < 8 || udp.len() < udp_len {
+        return None;
+    }
+    Some(&udp[8..udp_len])
+}
+
+fn udp_payload(linktype: Linktype, frame: &[u8]) -> Option<&[u8]> {
+    let l3 = match linktype.0 {
+        1 => ethernet_l3(frame)?,
+        101 => frame,
+        113 => {
+            if frame.len() < 16 {
+                return None;
+            }
+            if u16::from_be_bytes([frame[14], frame[15]]) != 0x0800 {
+                return None;
+            }
+            &frame[16..]
+        }
+        _ => return None,
+    };
+    ipv4_udp_payload(l3)
+}
+
+/// ASTERIX data blocks of a pcap as (record message name, block body after the 3-byte category/length header).
+pub fn load_pcap_blocks(
+    pcap_path: &std::path::Path,
+    codec: &Codec,
+    resolved: &ResolvedProtocol,
+) -> Vec<(String, Vec<u8>)> {
+    let mut file = File::open(pcap_path).expect("open pcap");
+    let mut reader = LegacyPcapReader::new(1 << 20, &mut file).expect("pcap reader");
+    let mut linktype = Linktype(1);
+    let mut out = Vec::new();
+    loop {
+        match reader.next() {
+            Ok((offset, block)) => {
+                if let PcapBlockOwned::LegacyHeader(h) = block {
+                    linktype = h.network;
+                } else if let PcapBlockOwned::Legacy(b) = block {
+                    if let Some(payload) = udp_payload(linktype, b.data) {
+                        let mut off = 0usize;
+                        while off + 3 <= payload.len() {
+                            let block_len =
+                                u16::from_be_bytes([payload[off + 1], payload[off + 2]]) as usize;
+                            if block_len < 3 || off + block_len > payload.len() {
+                                break;
+                            }
+                            let block = &payload[off..off + block_len];
+                            if let Ok(tv) = codec.decode_transport(block) {
+                                if let Some(msg_name) = resolved.message_for_transport_values(&tv) {
+                                    out.push((
+                                        msg_name.to_string(),
+                                        block[3..].to_vec(),
+                                    ));
+                                }
+                            }
+                            off += block_len;
+                        }
+                    }
+                }
+                reader.consume(offset);
+            }
+            Err(PcapError::Eof) => break,
+            Err(PcapError::Incomplete(_)) => {
+                reader.refill().expect("refill");
+            }
+            Err(e) => panic!("pcap error: {:?}", e),
+        }
+    }
+    out
+}
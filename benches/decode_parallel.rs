@@ -0,0 +1,101 @@
+//! Benchmark (feature `rayon`): decode every ASTERIX record of cat_034_048.pcap serially
+//! (`decode_message`, one record after the other) and concurrently (`decode_many_parallel`). The records
+//! are split out once with `frame::split_only` and grouped by message name.
+
+use aiprotodsl::{parse, split_only, Codec, Endianness, ResolvedProtocol};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::path::PathBuf;
+
+mod common;
+use common::load_pcap_blocks;
+
+/// Record slices per message name.
+type Groups<'a> = Vec<(String, Vec<&'a [u8]>)>;
+
+fn group_records<'a>(blocks: &'a [(String, Vec<u8>)], codec: &Codec) -> Groups<'a> {
+    let mut groups: Groups<'a> = Vec::new();
+    for (msg_name, body) in blocks {
+        let Ok(ranges) = split_only(codec, msg_name, body, None) else { continue };
+        let slices = ranges.into_iter().map(|(start, end)| &body[start..end]);
+        match groups.iter_mut().find(|(name, _)| name == msg_name) {
+            Some((_, records)) => records.extend(slices),
+            None => groups.push((msg_name.clone(), slices.collect())),
+        }
+    }
+    groups
+}
+
+fn decode_serial(groups: &Groups, codec: &Codec) -> usize {
+    groups
+        .iter()
+        .map(|(name, records)| records.iter().filter(|r| codec.decode_message(name, r).is_ok()).count())
+        .sum()
+}
+
+fn decode_parallel(groups: &Groups, codec: &Codec) -> usize {
+    groups
+        .iter()
+        .map(|(name, records)| codec.decode_many_parallel(name, records).iter().filter(|r| r.is_ok()).count())
+        .sum()
+}
+
+fn bench_decode_parallel(c: &mut Criterion) {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dsl_path = manifest.join("examples/asterix_family.dsl");
+    let pcap_path = manifest.join("assets/cat_034_048.pcap");
+
+    let dsl_src = std::fs::read_to_string(&dsl_path).expect("read dsl");
+    let protocol = parse(&dsl_src).expect("parse dsl");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    if !pcap_path.exists() {
+        eprintln!("skip bench: {} not found", pcap_path.display());
+        return;
+    }
+
+    let blocks = load_pcap_blocks(&pcap_path, &codec, &resolved);
+    let groups = group_records(&blocks, &codec);
+    let records: usize = groups.iter().map(|(_, r)| r.len()).sum();
+    let record_bytes: usize = groups.iter().flat_map(|(_, r)| r.iter().map(|s| s.len())).sum();
+    assert_eq!(decode_serial(&groups, &codec), decode_parallel(&groups, &codec));
+    eprintln!("decode_parallel: {} records in {} message types, {} record bytes", records, groups.len(), record_bytes);
+
+    c.bench_function("decode_serial_cat_034_048_pcap", |b| {
+        b.iter(|| black_box(decode_serial(black_box(&groups), &codec)));
+    });
+    c.bench_function("decode_many_parallel_cat_034_048_pcap", |b| {
+        b.iter(|| black_box(decode_parallel(black_box(&groups), &codec)));
+    });
+
+    // Sustainable data rate, same table layout as walk_pcap.
+    const ITERS: u32 = 500;
+    let time = |f: &dyn Fn() -> usize| {
+        let start = std::time::Instant::now();
+        for _ in 0..ITERS {
+            black_box(f());
+        }
+        start.elapsed().as_nanos() / (ITERS as u128)
+    };
+    let serial_ns = time(&|| decode_serial(&groups, &codec));
+    let parallel_ns = time(&|| decode_parallel(&groups, &codec));
+
+    eprintln!();
+    eprintln!("--- Decode data rate ({} records, {} record bytes, {} threads) ---", records, record_bytes, rayon::current_num_threads());
+    eprintln!("  Strategy             |  µs/pcap |  records/s  |  MB/s");
+    eprintln!("  ---------------------+----------+-------------+--------");
+    for (label, ns) in [("decode (serial)", serial_ns), ("decode_many_parallel", parallel_ns)] {
+        let secs = ns as f64 / 1e9;
+        eprintln!(
+            "  {:20} | {:>8.2} | ~{:.2} M/s   | {:>6.2}",
+            label,
+            ns as f64 / 1000.0,
+            records as f64 / secs / 1e6,
+            record_bytes as f64 / secs / 1e6
+        );
+    }
+    eprintln!("---");
+}
+
+criterion_group!(benches, bench_decode_parallel);
+criterion_main!(benches);
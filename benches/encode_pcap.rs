@@ -0,0 +1,125 @@
+//! Benchmark: encode only. Decodes every ASTERIX record of cat_034_048.pcap once, then repeatedly
+//! re-encodes the stored value maps, with a fresh `Vec` per record (`encode_message`) and with one
+//! reused buffer (`encode_message_into`).
+
+use aiprotodsl::{parse, Codec, Endianness, ResolvedProtocol, Value};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+mod common;
+use common::load_pcap_blocks;
+
+/// One decoded record: message name and field values.
+type Record = (String, HashMap<String, Value>);
+
+/// Decode every record of every block; returns (message name, values) per record and the encoded byte count.
+fn decode_records(blocks: &[(String, Vec<u8>)], codec: &Codec) -> (Vec<Record>, usize) {
+    let mut records = Vec::new();
+    let mut bytes = 0usize;
+    for (msg_name, body) in blocks {
+        let mut offset = 0usize;
+        while offset < body.len() {
+            let (consumed, result) = codec.decode_message_with_extent(msg_name, &body[offset..]);
+            if consumed == 0 {
+                break;
+            }
+            if let Ok(values) = result {
+                records.push((msg_name.clone(), values));
+                bytes += consumed;
+            }
+            offset += consumed;
+        }
+    }
+    (records, bytes)
+}
+
+fn encode_all(records: &[Record], codec: &Codec) -> usize {
+    let mut bytes = 0usize;
+    for (msg_name, values) in records {
+        if let Ok(out) = codec.encode_message(msg_name, values) {
+            bytes += out.len();
+        }
+    }
+    bytes
+}
+
+fn encode_all_into(records: &[Record], codec: &Codec, buf: &mut Vec<u8>) -> usize {
+    let mut bytes = 0usize;
+    for (msg_name, values) in records {
+        buf.clear();
+        if codec.encode_message_into(msg_name, values, buf).is_ok() {
+            bytes += buf.len();
+        }
+    }
+    bytes
+}
+
+fn bench_encode_pcap(c: &mut Criterion) {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dsl_path = manifest.join("examples/asterix_family.dsl");
+    let pcap_path = manifest.join("assets/cat_034_048.pcap");
+
+    let dsl_src = std::fs::read_to_string(&dsl_path).expect("read dsl");
+    let protocol = parse(&dsl_src).expect("parse dsl");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    if !pcap_path.exists() {
+        eprintln!("skip bench: {} not found", pcap_path.display());
+        return;
+    }
+
+    let blocks = load_pcap_blocks(&pcap_path, &codec, &resolved);
+    let (records, record_bytes) = decode_records(&blocks, &codec);
+    eprintln!("encode_pcap: {} records, {} record bytes (decoded once)", records.len(), record_bytes);
+
+    c.bench_function("encode_cat_034_048_pcap", |b| {
+        b.iter(|| black_box(encode_all(black_box(&records), &codec)));
+    });
+
+    let mut buf = Vec::new();
+    c.bench_function("encode_into_cat_034_048_pcap", |b| {
+        b.iter(|| black_box(encode_all_into(black_box(&records), &codec, &mut buf)));
+    });
+
+    // Sustainable data rate, same table layout as walk_pcap.
+    const ITERS: u32 = 2_000;
+    let latency_budget_ms = 1.0;
+    let us_per_budget = latency_budget_ms * 1000.0;
+
+    let start = std::time::Instant::now();
+    for _ in 0..ITERS {
+        encode_all(&records, &codec);
+    }
+    let encode_ns = start.elapsed().as_nanos() / (ITERS as u128);
+
+    let start = std::time::Instant::now();
+    for _ in 0..ITERS {
+        encode_all_into(&records, &codec, &mut buf);
+    }
+    let encode_into_ns = start.elapsed().as_nanos() / (ITERS as u128);
+
+    eprintln!();
+    eprintln!("--- Encode data rate (same pcap, {} records, {} record bytes) ---", records.len(), record_bytes);
+    eprintln!("  Strategy           |  µs/pcap |  records/s  |  MB/s  |  within 1 ms");
+    eprintln!("  ------------------+----------+-------------+--------+------------------");
+    for (label, ns) in [("encode", encode_ns), ("encode_into", encode_into_ns)] {
+        let us = ns as f64 / 1000.0;
+        let records_per_sec = (records.len() as f64) / (ns as f64 / 1e9);
+        let mb_per_sec = (record_bytes as f64) / (ns as f64 / 1e9) / 1e6;
+        eprintln!(
+            "  {:18} | {:>8.2} | ~{:.2} M/s   | {:>6.2} | {:.1} pcaps, {:.0} rec",
+            label,
+            us,
+            records_per_sec / 1e6,
+            mb_per_sec,
+            us_per_budget / us,
+            us_per_budget / us * (records.len() as f64)
+        );
+    }
+    eprintln!("---");
+}
+
+criterion_group!(benches, bench_encode_pcap);
+criterion_main!(benches);
@@ -0,0 +1,104 @@
+//! Benchmark: validate a long list of fixed-size structs (synthetic track of 10 000 points) with the
+//! generic walker (`validate_message_in_place`, which only skips the elements), the batch column check
+//! (`validate_fixed_record_list`, which also checks every element constraint) and a full decode
+//! (which checks the same constraints as the batch path), owning (`decode_message`) or borrowing
+//! (`decode_message_ref`).
+
+use aiprotodsl::{
+    parse, validate_fixed_record_list, validate_message_in_place, Codec, Endianness, ResolvedProtocol, Value,
+    WalkEndianness,
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::collections::HashMap;
+
+const DSL: &str = r#"
+struct Point {
+  lat: i32 [-90000000..90000000];
+  lon: i32 [-180000000..180000000];
+  alt: u16 [0..50000];
+  quality: u8 [0..3];
+  spare: padding(1);
+}
+message Track {
+  id: u16 [1..1000];
+  points: list<Point>;
+}
+"#;
+
+const POINTS: i32 = 10_000;
+
+fn track_bytes(codec: &Codec) -> Vec<u8> {
+    let points = (0..POINTS)
+        .map(|i| {
+            Value::Struct(HashMap::from([
+                ("lat".to_string(), Value::I32(i * 1_000 - 5_000_000)),
+                ("lon".to_string(), Value::I32(i * 2_000)),
+                ("alt".to_string(), Value::U16((i % 50_000) as u16)),
+                ("quality".to_string(), Value::U8((i % 4) as u8)),
+                ("spare".to_string(), Value::Padding),
+            ]))
+        })
+        .collect();
+    let values = HashMap::from([("id".to_string(), Value::U16(7)), ("points".to_string(), Value::List(points))]);
+    codec.encode_message("Track", &values).expect("encode")
+}
+
+fn bench_validate_fixed_list(c: &mut Criterion) {
+    let resolved = ResolvedProtocol::resolve(parse(DSL).expect("parse dsl")).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let bytes = track_bytes(&codec);
+    let endianness = WalkEndianness::Big;
+    assert_eq!(validate_fixed_record_list(&bytes, 0, &resolved, endianness, "Track").expect("valid"), bytes.len());
+    eprintln!("validate_fixed_list: {} points, {} bytes", POINTS, bytes.len());
+
+    c.bench_function("walk_validate_track_10k", |b| {
+        b.iter(|| validate_message_in_place(black_box(&bytes), 0, &resolved, endianness, "Track").is_ok());
+    });
+    c.bench_function("validate_fixed_record_list_track_10k", |b| {
+        b.iter(|| validate_fixed_record_list(black_box(&bytes), 0, &resolved, endianness, "Track").is_ok());
+    });
+    c.bench_function("decode_track_10k", |b| {
+        b.iter(|| codec.decode_message("Track", black_box(&bytes)).is_ok());
+    });
+    c.bench_function("decode_ref_track_10k", |b| {
+        b.iter(|| codec.decode_message_ref("Track", black_box(&bytes)).is_ok());
+    });
+
+    // Throughput, same table layout as walk_pcap.
+    const ITERS: u32 = 200;
+    let time = |f: &dyn Fn() -> bool| {
+        let start = std::time::Instant::now();
+        for _ in 0..ITERS {
+            black_box(f());
+        }
+        start.elapsed().as_nanos() / (ITERS as u128)
+    };
+    let walk_ns = time(&|| validate_message_in_place(&bytes, 0, &resolved, endianness, "Track").is_ok());
+    let batch_ns = time(&|| validate_fixed_record_list(&bytes, 0, &resolved, endianness, "Track").is_ok());
+    let decode_ns = time(&|| codec.decode_message("Track", &bytes).is_ok());
+    let decode_ref_ns = time(&|| codec.decode_message_ref("Track", &bytes).is_ok());
+
+    eprintln!();
+    eprintln!("--- Validate data rate ({} points, {} bytes) ---", POINTS, bytes.len());
+    eprintln!("  Strategy                    |  µs/track |  points/s   |  MB/s");
+    eprintln!("  ----------------------------+-----------+-------------+--------");
+    for (label, ns) in [
+        ("walk_validate (skips elems)", walk_ns),
+        ("validate_fixed_record_list", batch_ns),
+        ("decode", decode_ns),
+        ("decode_message_ref", decode_ref_ns),
+    ] {
+        let secs = ns as f64 / 1e9;
+        eprintln!(
+            "  {:27} | {:>9.2} | ~{:.2} M/s   | {:>6.1}",
+            label,
+            ns as f64 / 1000.0,
+            POINTS as f64 / secs / 1e6,
+            bytes.len() as f64 / secs / 1e6
+        );
+    }
+    eprintln!("---");
+}
+
+criterion_group!(benches, bench_validate_fixed_list);
+criterion_main!(benches);
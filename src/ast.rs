@@ -1,13 +1,18 @@
 //! Abstract Syntax Tree for the Protocol Encoding DSL.
 
+use crate::checksum::ChecksumAlgorithm;
 use std::collections::HashMap;
 
 /// Root protocol definition: transport, payload (messages after transport), type definitions (abstract), enums, messages, structs (encoding).
 #[derive(Debug, Clone)]
 pub struct Protocol {
     pub transport: Option<TransportSection>,
+    /// Per-record header: decoded before each message of a frame, after the outer transport (decoded once).
+    pub inner_transport: Option<TransportSection>,
     /// Which messages can follow the transport and how to select message type from transport fields.
     pub payload: Option<PayloadSection>,
+    /// `endianness from field { .. }`: byte order chosen at run time by a header or message field.
+    pub endianness_switch: Option<EndiannessSwitch>,
     /// Abstract data model definitions (ASN.1-like). Describe WHAT the data is.
     pub type_defs: Vec<TypeDefSection>,
     /// Enumerated types: named constants (name = value). Can be referenced from type definitions.
@@ -63,6 +68,8 @@ pub enum AbstractType {
 #[derive(Debug, Clone)]
 pub struct EnumSection {
     pub name: String,
+    /// Storage width when a field is typed as the enum (`enum Color : u16 { ... }`); `u8` when omitted.
+    pub base: BaseType,
     /// Variant name and its integer/hex value (e.g. NorthMarker=1, SectorCrossing=2).
     pub variants: Vec<(String, Literal)>,
 }
@@ -80,15 +87,124 @@ pub struct PayloadSection {
     pub repeated: bool,
 }
 
+/// `endianness from version { 1: big, 2: little }`: once `field` (a transport, inner transport or message
+/// field) is decoded or encoded, the fields after it use the byte order mapped to its value.
+#[derive(Debug, Clone)]
+pub struct EndiannessSwitch {
+    pub field: String,
+    /// (field value, byte order) pairs, in DSL order.
+    pub cases: Vec<(i64, crate::codec::Endianness)>,
+}
+
+impl EndiannessSwitch {
+    /// Byte order mapped to `value`; None when the directive has no case for it.
+    pub fn endianness_for(&self, value: i64) -> Option<crate::codec::Endianness> {
+        self.cases.iter().find(|(v, _)| *v == value).map(|(_, e)| *e)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PayloadSelector {
-    /// Transport field name (e.g. "category") whose value selects the message type.
+    /// Transport field name (e.g. "category") whose value selects the message type. For a message body
+    /// selector ([`MessageSection::selector`]), a field of that message.
     pub transport_field: String,
+    /// Further key fields of a tuple selector (`selector: (category, subtype) -> { (48, 1): A }`), after
+    /// `transport_field`. Empty for a single-field selector.
+    pub extra_fields: Vec<String>,
     /// (value, message_name, is_list) triples: when transport_field equals value, use this message.
+    /// For a tuple selector the value is a [`Literal::List`] with one element per key field.
     /// `is_list` is true when the DSL uses `list<MessageName>` (one or more records of that type).
     pub value_to_message: Vec<(Literal, String, bool)>,
+    /// Message used when the selector value has no mapping (`default: RawRecord`). None: unmapped values are unknown.
+    pub default_message: Option<String>,
+}
+
+impl PayloadSelector {
+    /// Key fields in tuple order: `transport_field`, then `extra_fields`.
+    pub fn key_fields(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.transport_field.as_str()).chain(self.extra_fields.iter().map(String::as_str))
+    }
+
+    /// First mapping whose value equals the key fields' values (`value_of` gives a field's value) as
+    /// (message_name, is_list). The `default` mapping is not applied.
+    pub fn lookup(&self, value_of: impl Fn(&str) -> Option<i64>) -> Option<(&str, bool)> {
+        self.value_to_message
+            .iter()
+            .find(|(lit, _, _)| match lit {
+                Literal::List(items) => {
+                    items.len() == self.extra_fields.len() + 1
+                        && items.iter().zip(self.key_fields()).all(|(l, f)| l.as_i64().is_some() && l.as_i64() == value_of(f))
+                }
+                _ => self.extra_fields.is_empty() && lit.as_i64().is_some() && lit.as_i64() == value_of(&self.transport_field),
+            })
+            .map(|(_, name, is_list)| (name.as_str(), *is_list))
+    }
+
+    /// Message for the key fields' values: the first matching mapping, else the `default` message.
+    /// None when a key field has no value, or the value has no mapping nor default.
+    pub fn select(&self, value_of: impl Fn(&str) -> Option<i64>) -> Option<&str> {
+        if self.key_fields().any(|f| value_of(f).is_none()) {
+            return None;
+        }
+        self.lookup(&value_of).map(|(msg_name, _)| msg_name).or(self.default_message.as_deref())
+    }
+
+    /// Error text when [`select`](Self::select) finds nothing: `Header: no variant for kind = 3`.
+    pub(crate) fn no_variant_text(&self, owner: &str, value_of: impl Fn(&str) -> Option<i64>) -> String {
+        let values: Vec<i64> = self.key_fields().filter_map(&value_of).collect();
+        if values.len() <= self.extra_fields.len() {
+            return format!("{}: selector field {} has no value", owner, self.key_text());
+        }
+        format!("{}: no variant for {} = {}", owner, self.key_text(), selector_key_text(&values))
+    }
+
+    /// Key field name(s) for messages: `cat` or `(cat, sub)`.
+    pub(crate) fn key_text(&self) -> String {
+        if self.extra_fields.is_empty() {
+            self.transport_field.clone()
+        } else {
+            format!("({})", self.key_fields().collect::<Vec<_>>().join(", "))
+        }
+    }
+}
+
+/// Names of the fields that size another field: `dyn_int` widths, `T[field]` and `repeat(field)` counts, and
+/// the keys of `select<field>`.
+/// The walkers only record the values of such fields (and selector / condition keys).
+fn size_refs<'t>(specs: impl Iterator<Item = &'t TypeSpec>) -> std::collections::HashSet<String> {
+    specs
+        .filter_map(|spec| match spec {
+            TypeSpec::DynInt(field)
+            | TypeSpec::Select { field, .. }
+            | TypeSpec::BytesList(_, field)
+            | TypeSpec::Array(_, ArrayLen::FieldRef(field), _)
+            | TypeSpec::Group { count: ArrayLen::FieldRef(field), .. } => Some(field.clone()),
+            _ => None,
+        })
+        .collect()
 }
 
+/// Struct or message a `select<field>` takes for the key value `value`: its case, else its `default`.
+pub(crate) fn select_case<'s>(cases: &'s [(i64, String)], default: Option<&'s String>, value: i64) -> Option<&'s str> {
+    cases.iter().find(|(k, _)| *k == value).map(|(_, name)| name).or(default).map(String::as_str)
+}
+
+/// Selector key values as text: `48` or `(48, 1)`.
+pub(crate) fn selector_key_text(key: &[i64]) -> String {
+    match key {
+        [v] => v.to_string(),
+        _ => format!("({})", key.iter().map(i64::to_string).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+/// Built-in message for the selector `default` mapping: keeps the remaining record bytes as
+/// `Value::Bytes` under [`RAW_RECORD_FIELD`] and re-encodes them verbatim (lossless pass-through).
+/// A message declared with the same name takes precedence.
+pub const RAW_RECORD: &str = "RawRecord";
+
+/// Field of the built-in [`RAW_RECORD`] message holding the raw bytes.
+pub const RAW_RECORD_FIELD: &str = "data";
+
 #[derive(Debug, Clone)]
 pub struct TransportSection {
     pub fields: Vec<TransportField>,
@@ -102,6 +218,22 @@ pub struct TransportField {
     pub constraint: Option<Constraint>,
     /// Resolution/unit per spec (e.g. "1/256 NM").
     pub quantum: Option<String>,
+    /// What the field measures when declared `length_of(..)` / `count_of(..)` (its `type_spec` is then the
+    /// width, a `Base`); [`crate::codec::Codec::encode_record`] fills it in.
+    pub size_of: Option<TransportSize>,
+}
+
+/// Size held by a transport `length_of` / `count_of` field, in the record it heads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportSize {
+    /// `length_of(record)`: bytes of the whole record, this header included (as ASTERIX data blocks).
+    Record,
+    /// `length_of(message)`: bytes of the message after the header.
+    Message,
+    /// `length_of(field)`: encoded bytes of the message's field `field`.
+    LengthOf(String),
+    /// `count_of(field)`: elements of the message's field `field`.
+    CountOf(String),
 }
 
 /// Byte or bit padding (zero on encode).
@@ -124,6 +256,158 @@ pub enum TransportTypeSpec {
 pub struct MessageSection {
     pub name: String,
     pub fields: Vec<MessageField>,
+    /// Body selector (`selector: kind -> { 1: Position, 2: Status }` after the fields): the message whose
+    /// fields follow these ones on the wire, chosen by this message's own field values. Decoded values
+    /// hold the fields of both messages. None: the message ends after its fields.
+    pub selector: Option<PayloadSelector>,
+    /// Whole-message invariants (`check { kind != 2 || len >= 4 }`), checked after decode and before encode.
+    pub checks: Vec<MessageCheck>,
+}
+
+/// `check { expr }` clause of a message: `expr` must hold for every record.
+#[derive(Debug, Clone)]
+pub struct MessageCheck {
+    pub expr: CheckExpr,
+    /// Source text of the expression, for error messages.
+    pub text: String,
+}
+
+/// Expression of a [`MessageCheck`] over the message's field values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckExpr {
+    Field(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Not(Box<CheckExpr>),
+    Binary(CheckOp, Box<CheckExpr>, Box<CheckExpr>),
+}
+
+/// Operators of a [`CheckExpr`]; `Implies` is `a => b` (`!a || b`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Implies,
+}
+
+/// Value while evaluating a [`CheckExpr`]; integers stay exact, mixing with a float gives a float.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl CheckValue {
+    fn as_f64(self) -> Option<f64> {
+        match self {
+            CheckValue::Int(i) => Some(i as f64),
+            CheckValue::Float(x) => Some(x),
+            CheckValue::Bool(_) => None,
+        }
+    }
+}
+
+impl CheckExpr {
+    /// Names of the fields the expression reads.
+    pub fn fields(&self) -> Vec<&str> {
+        match self {
+            CheckExpr::Field(name) => vec![name.as_str()],
+            CheckExpr::Not(e) => e.fields(),
+            CheckExpr::Binary(_, a, b) => a.fields().into_iter().chain(b.fields()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Evaluates the expression; `value_of` gives a field's value. `&&`, `||` and `=>` short-circuit,
+    /// so `kind != 2 || opt > 0` does not read `opt` when it is absent. Fails on a missing field, a type
+    /// mismatch (arithmetic on booleans, `&&` on numbers), integer overflow or division by zero.
+    pub fn eval(&self, value_of: &impl Fn(&str) -> Option<CheckValue>) -> Result<CheckValue, String> {
+        let bool_of = |e: &CheckExpr| match e.eval(value_of)? {
+            CheckValue::Bool(b) => Ok(b),
+            v => Err(format!("expected a boolean, got {:?}", v)),
+        };
+        let (op, a, b) = match self {
+            CheckExpr::Field(name) => return value_of(name).ok_or_else(|| format!("field {} has no value", name)),
+            CheckExpr::Int(i) => return Ok(CheckValue::Int(*i)),
+            CheckExpr::Float(x) => return Ok(CheckValue::Float(*x)),
+            CheckExpr::Bool(b) => return Ok(CheckValue::Bool(*b)),
+            CheckExpr::Not(e) => return Ok(CheckValue::Bool(!bool_of(e)?)),
+            CheckExpr::Binary(op, a, b) => (*op, a, b),
+        };
+        match op {
+            CheckOp::And => return Ok(CheckValue::Bool(bool_of(a)? && bool_of(b)?)),
+            CheckOp::Or => return Ok(CheckValue::Bool(bool_of(a)? || bool_of(b)?)),
+            CheckOp::Implies => return Ok(CheckValue::Bool(!bool_of(a)? || bool_of(b)?)),
+            _ => {}
+        }
+        let (a, b) = (a.eval(value_of)?, b.eval(value_of)?);
+        if let (CheckOp::Eq | CheckOp::Ne, CheckValue::Bool(x), CheckValue::Bool(y)) = (op, a, b) {
+            return Ok(CheckValue::Bool((x == y) == (op == CheckOp::Eq)));
+        }
+        if let (CheckValue::Int(x), CheckValue::Int(y)) = (a, b) {
+            let overflow = || format!("integer overflow in {} {:?} {}", x, op, y);
+            return match op {
+                CheckOp::Add => x.checked_add(y).map(CheckValue::Int).ok_or_else(overflow),
+                CheckOp::Sub => x.checked_sub(y).map(CheckValue::Int).ok_or_else(overflow),
+                CheckOp::Mul => x.checked_mul(y).map(CheckValue::Int).ok_or_else(overflow),
+                CheckOp::Div | CheckOp::Rem if y == 0 => Err("division by zero".to_string()),
+                CheckOp::Div => x.checked_div(y).map(CheckValue::Int).ok_or_else(overflow),
+                CheckOp::Rem => x.checked_rem(y).map(CheckValue::Int).ok_or_else(overflow),
+                _ => Ok(CheckValue::Bool(compare(op, x.cmp(&y)))),
+            };
+        }
+        let (Some(x), Some(y)) = (a.as_f64(), b.as_f64()) else {
+            return Err(format!("{:?} needs numbers, got {:?} and {:?}", op, a, b));
+        };
+        Ok(match op {
+            CheckOp::Add => CheckValue::Float(x + y),
+            CheckOp::Sub => CheckValue::Float(x - y),
+            CheckOp::Mul => CheckValue::Float(x * y),
+            CheckOp::Div => CheckValue::Float(x / y),
+            CheckOp::Rem => CheckValue::Float(x % y),
+            _ => CheckValue::Bool(x.partial_cmp(&y).is_some_and(|o| compare(op, o))),
+        })
+    }
+}
+
+/// Comparison operator applied to an ordering.
+fn compare(op: CheckOp, o: std::cmp::Ordering) -> bool {
+    use std::cmp::Ordering::*;
+    match op {
+        CheckOp::Eq => o == Equal,
+        CheckOp::Ne => o != Equal,
+        CheckOp::Lt => o == Less,
+        CheckOp::Le => o != Greater,
+        CheckOp::Gt => o == Greater,
+        CheckOp::Ge => o != Less,
+        _ => false,
+    }
+}
+
+impl MessageSection {
+    /// First `check { ... }` that does not hold for `value_of`, as an error text
+    /// (`Frame: check failed: a + b == total`).
+    pub fn failed_check(&self, value_of: impl Fn(&str) -> Option<CheckValue>) -> Option<String> {
+        self.checks.iter().find_map(|c| match c.expr.eval(&value_of) {
+            Ok(CheckValue::Bool(true)) => None,
+            Ok(CheckValue::Bool(false)) => Some(format!("{}: check failed: {}", self.name, c.text)),
+            Ok(v) => Some(format!("{}: check {}: expected a boolean, got {:?}", self.name, c.text, v)),
+            Err(e) => Some(format!("{}: check {}: {}", self.name, c.text, e)),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -137,14 +421,33 @@ pub struct MessageField {
     pub quantum: Option<String>,
     /// Optional description from `@doc "..."` (for tooltips in GUI).
     pub doc: Option<String>,
+    /// Conditional constraints (`[0..10] when mode == 1 [0..100] when mode == 2`). Empty for a plain `[..]` constraint.
+    pub conditional_constraints: Vec<(Option<Condition>, Constraint)>,
     /// Set at resolve: true when constraint saturates the type range (skip range check during validation).
     pub saturating: bool,
+    /// Set at resolve: true when a `when` clause of another field in the same message refers to this field.
+    pub referenced_by_condition: bool,
+    /// Set at resolve: true when another field of the same message takes its size from this field: a `dyn_int`
+    /// width, or a `T[field]` / `repeat(field)` count.
+    pub referenced_by_width: bool,
+    /// Set at resolve: true when the message's body selector keys on this field.
+    pub referenced_by_selector: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct StructSection {
     pub name: String,
     pub fields: Vec<StructField>,
+    /// `#[reversed]`: `fields` are listed in spec order but sent last-first. See [`StructSection::wire_fields`].
+    pub reversed: bool,
+}
+
+impl StructSection {
+    /// Fields in the order they appear on the wire.
+    pub fn wire_fields(&self) -> impl Iterator<Item = &StructField> {
+        let (fwd, rev) = if self.reversed { (None, Some(self.fields.iter().rev())) } else { (Some(self.fields.iter()), None) };
+        fwd.into_iter().flatten().chain(rev.into_iter().flatten())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -156,6 +459,12 @@ pub struct StructField {
     pub condition: Option<Condition>,
     /// Resolution/unit per spec (e.g. "1/256 NM").
     pub quantum: Option<String>,
+    /// Conditional constraints (`[0..10] when mode == 1 [0..100] when mode == 2`). Empty for a plain `[..]` constraint.
+    pub conditional_constraints: Vec<(Option<Condition>, Constraint)>,
+    /// Set at resolve: as [`MessageField::referenced_by_condition`], within the struct.
+    pub referenced_by_condition: bool,
+    /// Set at resolve: as [`MessageField::referenced_by_width`], within the struct.
+    pub referenced_by_width: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -164,6 +473,40 @@ pub struct Condition {
     pub value: Literal,
 }
 
+/// Picks the constraint that applies to a field: with conditional clauses, the first one whose condition
+/// holds (a clause without condition always holds); otherwise the plain constraint.
+/// `value_of` returns the integer value of an already decoded field.
+pub fn active_constraint<'a>(
+    constraint: Option<&'a Constraint>,
+    conditional: &'a [(Option<Condition>, Constraint)],
+    value_of: impl Fn(&str) -> Option<i64>,
+) -> Option<&'a Constraint> {
+    if conditional.is_empty() {
+        return constraint;
+    }
+    conditional
+        .iter()
+        .find(|(cond, _)| match cond {
+            Some(c) => value_of(&c.field) == c.value.as_i64(),
+            None => true,
+        })
+        .map(|(_, c)| c)
+}
+
+impl MessageField {
+    /// Constraint that applies given the values decoded so far (see [`active_constraint`]).
+    pub fn active_constraint(&self, value_of: impl Fn(&str) -> Option<i64>) -> Option<&Constraint> {
+        active_constraint(self.constraint.as_ref(), &self.conditional_constraints, value_of)
+    }
+}
+
+impl StructField {
+    /// Constraint that applies given the values decoded so far (see [`active_constraint`]).
+    pub fn active_constraint(&self, value_of: impl Fn(&str) -> Option<i64>) -> Option<&Constraint> {
+        active_constraint(self.constraint.as_ref(), &self.conditional_constraints, value_of)
+    }
+}
+
 /// Field type specification.
 #[derive(Debug, Clone)]
 pub enum TypeSpec {
@@ -173,22 +516,79 @@ pub enum TypeSpec {
     /// Padding: bytes or bits (zero on encode). Use padding(n) or padding(n, bits) in DSL.
     Padding(PaddingKind),
     Bitfield(u64),
-    LengthOf(String),
-    CountOf(String),
+    /// `length_of(field)`: encoded bytes of `field`, in the given unsigned width (`length_of<u16>(field)`;
+    /// `u32` when omitted).
+    LengthOf(String, BaseType),
+    /// `count_of(field)`: elements of `field`, in the given unsigned width as for [`TypeSpec::LengthOf`].
+    CountOf(String, BaseType),
+    /// `dyn_int(field)`: unsigned integer in `field` bytes (1..8, read at run time), in the codec's endianness.
+    /// Decodes to `Value::U64`; on encode a missing `field` is set to the fewest bytes that hold the value.
+    DynInt(String),
     /// ASN.1-style presence bitmap: n bytes (1, 2, or 4). Following optional fields use bits 0, 1, 2, ...
-    PresenceBits(u64),
+    /// `presence_bits(n, stop: k)` adds a terminator: wire bit k is a control bit (no optional of its own; the
+    /// optionals skip over it) and, when clear, every optional after it is absent. See [`presence_bits_logical`].
+    PresenceBits(u64, Option<u32>),
     /// Bitmap: bitmap(total_bits, presence_per_block). total_bits = number of presence bits (optionals).
     /// presence_per_block = 0 => no FX (consecutive bits); k > 0 => blocks of k presence + 1 FX (FX=0 on last block).
     /// Mapping lists (logical_index, field_name); FX is not a mapped field.
     BitmapPresence { total_bits: u32, presence_per_block: u32, mapping: Vec<(u32, String)> },
     StructRef(String),
-    Array(Box<TypeSpec>, ArrayLen),
+    /// `enum<E>`: an integer of the declared enum `E`'s base type, decoded to
+    /// [`Value::Enum`](crate::value::Value::Enum) with its variant name (None for a value no variant has).
+    Enum(String),
+    /// `select<kind> { 1: Foo, 2: Bar, default: Raw }`: the struct (or message) picked by the value of the
+    /// earlier field `kind`, decoded to its `Value::Struct`. A value with no case takes `default`; without one
+    /// it is a validation error.
+    Select { field: String, cases: Vec<(i64, String)>, default: Option<String> },
+    /// `bits { a: 1, b: 1, code: 6 }`: named sub-fields (name, width in bits) packed into one byte-aligned
+    /// bit group (total a multiple of 8, LSB first like `bitfield(n)`). Decodes to a `Value::Struct` of `U64`.
+    Bits(Vec<(String, u64)>),
+    /// `repeat(n) { ... }`: the inline `fields` repeated `count` times, like an array of an anonymous struct.
+    /// Decodes to a `Value::List` of `Value::Struct`; bit packing and presence are local to each repetition.
+    Group { count: ArrayLen, fields: Vec<StructField> },
+    /// `T[n]`, or `T[n] stride(m)`: each element sits in an `m`-byte slot, followed by `m` minus its size
+    /// bytes of zero padding (`m` must not be smaller than the element).
+    Array(Box<TypeSpec>, ArrayLen, Option<u64>),
     List(Box<TypeSpec>),
     /// List preceded by a 1-byte repetition factor (REP) - common in ASTERIX.
     RepList(Box<TypeSpec>),
+    /// `length_prefixed_list(u16)<T>`: a `u32` count like `list<T>`, then each element as a length of the given
+    /// unsigned width followed by exactly that many bytes holding one T (e.g. self-delimiting records).
+    LengthPrefixedList(Box<TypeSpec>, BaseType),
+    /// `bytes_list(len)<T>`: elements of T, with no count, filling exactly the number of bytes held by the
+    /// earlier field `len` (typically `len: length_of<u16>(items)`), e.g. the values of a TLV payload.
+    BytesList(Box<TypeSpec>, String),
     /// ASTERIX variable-length octets with FX extension: read bytes until byte & 0x80 == 0 (7 bits payload per byte).
     OctetsFx,
-    Optional(Box<TypeSpec>),
+    /// `optional<T>` / `optional(u16)<T>`: T present or absent. Presence comes from the enclosing presence
+    /// bitmap; without one, from a leading flag of the given unsigned width (non-zero = present, `u8` by default).
+    Optional(Box<TypeSpec>, BaseType),
+    /// Integer ticks with a time resolution: timestamp(u32(24), "1/128 s"). `scale` is seconds per tick,
+    /// computed from `quantum` at parse. Decodes to [`Value::Timestamp`](crate::value::Value::Timestamp).
+    Timestamp { base: Box<TypeSpec>, quantum: String, scale: f64 },
+    /// Packed BCD in `bytes` bytes, two decimal digits per byte, most significant digit first. `swapped`
+    /// (`bcd_swapped(n)`) puts the first digit of each byte in the low nibble. Decodes to an unsigned integer.
+    Bcd { bytes: u64, swapped: bool },
+    /// `reverse_bits<T>`: fixed-size `T` whose bytes arrive with reversed bit order (bit 0 first) in each byte.
+    ReverseBits(Box<TypeSpec>),
+    /// `remaining`: every byte from the current position to the end of the decoded buffer, as `Value::Bytes`.
+    /// The buffer end is the boundary: callers bound it (e.g. a data block sliced by its transport length).
+    Remaining,
+    /// `unknown(n)`: n bytes whose meaning is not known yet (reverse engineering), as `Value::Bytes`. Unlike
+    /// padding they are re-encoded verbatim; [`LintRule::IncompleteField`](crate::lint::LintRule::IncompleteField)
+    /// flags them.
+    Unknown(u64),
+    /// `cstring`: bytes up to a terminating 0 byte, as `Value::Bytes` without the terminator.
+    CString,
+    /// `string(n)`: text in exactly n bytes, as `Value::Bytes` with trailing zeros trimmed; zero-padded on encode.
+    FixedString(u64),
+    /// `checksum(alg)` / `checksum(alg, from: field)`: checksum of the message bytes from the start of the
+    /// message (or of `field`, an earlier field) up to this one, in the algorithm's width. Computed on encode
+    /// (any given value is ignored) and verified on decode. Decodes to `Value::U16` / `Value::U32`.
+    Checksum { algorithm: ChecksumAlgorithm, from: Option<String> },
+    /// `custom("name")`: encoded by the [`FieldCodec`](crate::codec::FieldCodec) registered under `name`
+    /// on the codec (and passed to walkers via `with_custom_types`).
+    Custom(String),
 }
 
 #[derive(Debug, Clone)]
@@ -203,6 +603,10 @@ pub enum BaseType {
     U16,
     U32,
     U64,
+    /// 3-byte unsigned integer (telemetry counters), decoded as `Value::U32`.
+    U24,
+    /// 6-byte unsigned integer, decoded as `Value::U64`.
+    U48,
     I8,
     I16,
     I32,
@@ -217,7 +621,60 @@ pub enum BaseType {
 pub enum Constraint {
     /// Intervals (min, max) inclusive; value valid if in any interval.
     Range(Vec<(i64, i64)>),
-    Enum(Vec<Literal>),
+    /// Allowed values: `[(1, 2)]`, or named codes `[in(OK=0, FAIL=1)]`.
+    Enum(Vec<EnumCode>),
+    /// Allowed values with a tolerance, for wire floats that are not exact: `[in(0.0, 0.5, 1.0) +/- 1e-6]`
+    /// accepts `x` when `(x - code).abs() <= tolerance` for some code.
+    EnumTolerance(Vec<EnumCode>, f64),
+}
+
+/// One allowed value of an enum constraint; `name` is set for a named code (`in(OK=0, ...)`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumCode {
+    pub value: Literal,
+    pub name: Option<String>,
+}
+
+impl EnumCode {
+    pub fn as_i64(&self) -> Option<i64> {
+        self.value.as_i64()
+    }
+}
+
+/// Validation error text for `value` outside an enum constraint; lists the codes when they are named.
+pub(crate) fn enum_violation(codes: &[EnumCode], value: i64) -> String {
+    if codes.iter().all(|c| c.name.is_none()) {
+        return "value not in allowed enum".to_string();
+    }
+    let listed: Vec<String> = codes
+        .iter()
+        .map(|c| match (&c.name, c.as_i64()) {
+            (Some(name), Some(v)) => format!("{}={}", name, v),
+            (_, v) => v.map(|v| v.to_string()).unwrap_or_default(),
+        })
+        .collect();
+    format!("value {} not in allowed codes ({})", value, listed.join(", "))
+}
+
+/// Checks a `float`/`double` value: enum codes compare as floats, within the tolerance of
+/// [`Constraint::EnumTolerance`] (exactly for [`Constraint::Enum`]). Range bounds are integers and
+/// are not checked against floats.
+pub(crate) fn check_float_constraint(x: f64, c: &Constraint) -> Result<(), String> {
+    let (codes, tolerance) = match c {
+        Constraint::Range(_) => return Ok(()),
+        Constraint::Enum(codes) => (codes, 0.0),
+        Constraint::EnumTolerance(codes, tolerance) => (codes, *tolerance),
+    };
+    if codes.iter().filter_map(|c| c.value.as_f64()).any(|code| (x - code).abs() <= tolerance) {
+        return Ok(());
+    }
+    let listed: Vec<String> = codes.iter().filter_map(|c| c.value.as_f64()).map(|code| format!("{:?}", code)).collect();
+    Err(format!("value {:?} not within {:?} of allowed values ({})", x, tolerance, listed.join(", ")))
+}
+
+/// Name of the code equal to `value` in a named enum constraint (`in(OK=0, ...)`).
+pub fn enum_code_name(codes: &[EnumCode], value: i64) -> Option<&str> {
+    codes.iter().find(|c| c.as_i64() == Some(value)).and_then(|c| c.name.as_deref())
 }
 
 /// Returns the integer range (min, max) inclusive for types that have a fixed value range.
@@ -232,6 +689,8 @@ pub fn type_spec_integer_range(spec: &TypeSpec) -> Option<(i64, i64)> {
             BaseType::U32 => Some((0, i64::from(u32::MAX))),
             BaseType::I32 => Some((i32::MIN as i64, i32::MAX as i64)),
             BaseType::U64 => Some((0, i64::MAX)), // u64 range truncated to i64 for comparison
+            BaseType::U24 => Some((0, 0xFF_FFFF)),
+            BaseType::U48 => Some((0, 0xFFFF_FFFF_FFFF)),
             BaseType::I64 => Some((i64::MIN, i64::MAX)),
             BaseType::Bool => Some((0, 1)),
             BaseType::Float | BaseType::Double => None,
@@ -255,10 +714,63 @@ pub fn type_spec_integer_range(spec: &TypeSpec) -> Option<(i64, i64)> {
                 Some((0, max))
             }
         }
+        TypeSpec::Bcd { bytes, .. } if *bytes <= 9 => Some((0, 10i64.pow(2 * *bytes as u32) - 1)),
+        TypeSpec::ReverseBits(inner) => type_spec_integer_range(inner),
+        _ => None,
+    }
+}
+
+/// Encoded size in bytes of a fixed-size scalar type (base, sized int, bitfield, BCD, timestamp);
+/// None for variable-size or compound types. Sub-byte sizes round up to whole bytes.
+pub fn type_spec_fixed_bytes(spec: &TypeSpec) -> Option<usize> {
+    match spec {
+        TypeSpec::Base(bt) => Some(match bt {
+            BaseType::U8 | BaseType::I8 | BaseType::Bool => 1,
+            BaseType::U16 | BaseType::I16 => 2,
+            BaseType::U24 => 3,
+            BaseType::U32 | BaseType::I32 | BaseType::Float => 4,
+            BaseType::U48 => 6,
+            BaseType::U64 | BaseType::I64 | BaseType::Double => 8,
+        }),
+        TypeSpec::SizedInt(_, n) | TypeSpec::Bitfield(n) => Some(n.div_ceil(8) as usize),
+        TypeSpec::Bcd { bytes, .. } => Some(*bytes as usize),
+        TypeSpec::Unknown(n) | TypeSpec::FixedString(n) => Some(*n as usize),
+        TypeSpec::Checksum { algorithm, .. } => Some(algorithm.size()),
+        TypeSpec::Timestamp { base, .. } | TypeSpec::ReverseBits(base) => type_spec_fixed_bytes(base),
         _ => None,
     }
 }
 
+/// Presence bits as read from the wire -> one bit per optional (bit i = i-th optional). With a `stop` bit k,
+/// wire bit k is dropped and, when it is clear, all optionals from index k on are absent.
+pub fn presence_bits_logical(wire: u64, stop: Option<u32>) -> u64 {
+    match stop {
+        None => wire,
+        Some(k) if k >= 63 => wire,
+        Some(k) => {
+            let low = wire & ((1u64 << k) - 1);
+            if (wire >> k) & 1 == 0 {
+                low
+            } else {
+                low | ((wire >> (k + 1)) << k)
+            }
+        }
+    }
+}
+
+/// Inverse of [`presence_bits_logical`]: the stop bit is set exactly when an optional at index >= k is present.
+pub fn presence_bits_wire(logical: u64, stop: Option<u32>) -> u64 {
+    match stop {
+        None => logical,
+        Some(k) if k >= 63 => logical,
+        Some(k) => {
+            let low = logical & ((1u64 << k) - 1);
+            let high = logical >> k;
+            low | (u64::from(high != 0) << k) | (high << (k + 1))
+        }
+    }
+}
+
 /// True if the constraint exactly covers the type range (so validation can be skipped when walking).
 pub fn constraint_saturates_range(c: &Constraint, type_min: i64, type_max: i64) -> bool {
     match c {
@@ -269,16 +781,394 @@ pub fn constraint_saturates_range(c: &Constraint, type_min: i64, type_max: i64)
             let (c_min, c_max) = intervals[0];
             c_min == type_min && c_max == type_max
         }
-        Constraint::Enum(_) => false,
+        Constraint::Enum(_) | Constraint::EnumTolerance(..) => false,
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     Int(i64),
+    /// `0.5`, `-1.25`, `1e-6`: a number with a fraction or an exponent.
+    Float(f64),
     Bool(bool),
     Hex(u64),
     String(String),
+    /// `[a, b, ...]`: default of a list/array field.
+    List(Vec<Literal>),
+}
+
+/// Struct/message names that `spec` always embeds (struct refs, possibly inside fixed non-empty arrays).
+/// Lists, optionals and count-based arrays can be empty, so they do not make a reference mandatory.
+fn mandatory_refs<'a>(spec: &'a TypeSpec, out: &mut Vec<&'a str>) {
+    match spec {
+        TypeSpec::StructRef(name) => out.push(name),
+        TypeSpec::Array(elem, ArrayLen::Constant(k), _) if *k > 0 => mandatory_refs(elem, out),
+        TypeSpec::Group { count: ArrayLen::Constant(k), fields } if *k > 0 => {
+            for f in fields.iter().filter(|f| f.condition.is_none()) {
+                mandatory_refs(&f.type_spec, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Rejects structs/messages that embed themselves through mandatory (unconditional) references,
+/// which would recurse forever on decode, encode and walk. A struct shadows a message of the same name.
+/// Selector without `default` must map every value of its transport field; only fields of at most
+/// 16 bits can be covered value by value, wider ones need a `default`.
+fn check_selector_exhaustive(
+    protocol: &Protocol,
+    sel: &PayloadSelector,
+    mapped: &HashMap<Vec<i64>, &str>,
+) -> Result<(), String> {
+    if sel.default_message.is_some() {
+        return Ok(());
+    }
+    if !sel.extra_fields.is_empty() {
+        return Err(format!("payload selector on '{}' is not exhaustive: add a `default` mapping", sel.key_text()));
+    }
+    let field_type = protocol
+        .transport
+        .iter()
+        .chain(protocol.inner_transport.iter())
+        .flat_map(|t| t.fields.iter())
+        .find(|f| f.name == sel.transport_field)
+        .map(|f| &f.type_spec);
+    let bits = match field_type {
+        Some(TransportTypeSpec::Base(BaseType::Bool)) => Some(1),
+        Some(TransportTypeSpec::Base(BaseType::U8)) => Some(8),
+        Some(TransportTypeSpec::Base(BaseType::U16)) => Some(16),
+        Some(TransportTypeSpec::SizedInt(BaseType::U8 | BaseType::U16 | BaseType::U32 | BaseType::U64, n)) => Some(*n),
+        Some(TransportTypeSpec::Bitfield(n)) => Some(*n),
+        _ => None,
+    };
+    let Some(bits) = bits.filter(|&b| b <= 16) else {
+        return Err(format!(
+            "payload selector on '{}' is not exhaustive: add a `default` mapping",
+            sel.transport_field
+        ));
+    };
+    let missing: Vec<String> = (0..1i64 << bits).filter(|v| !mapped.contains_key(&[*v][..])).map(|v| v.to_string()).collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let shown = missing.iter().take(8).cloned().collect::<Vec<_>>().join(", ");
+    let more = if missing.len() > 8 { format!(" and {} more", missing.len() - 8) } else { String::new() };
+    Err(format!(
+        "payload selector on '{}' is not exhaustive: no mapping for {}{}",
+        sel.transport_field, shown, more
+    ))
+}
+
+/// Checks the targets of a payload or message body selector (`owner` prefixes the errors) and returns
+/// the message mapped to each key value.
+fn check_selector_mappings<'p>(
+    sel: &'p PayloadSelector,
+    owner: &str,
+    messages_by_name: &HashMap<String, usize>,
+    options: ResolveOptions,
+) -> Result<HashMap<Vec<i64>, &'p str>, String> {
+    let mut seen: HashMap<Vec<i64>, &str> = HashMap::new();
+    for (lit, msg_name, _) in &sel.value_to_message {
+        if options.check_references && !messages_by_name.contains_key(msg_name) {
+            return Err(format!("{} message '{}' is not a defined message", owner, msg_name));
+        }
+        let key = match lit {
+            Literal::List(items) => items.iter().map(Literal::as_i64).collect::<Option<Vec<_>>>(),
+            _ => lit.as_i64().map(|v| vec![v]),
+        };
+        if let Some(key) = key {
+            let text = selector_key_text(&key);
+            if let Some(prev) = seen.insert(key, msg_name) {
+                if options.check_selector_duplicates {
+                    return Err(format!("{} value {} is mapped twice ('{}' and '{}')", owner, text, prev, msg_name));
+                }
+            }
+        }
+    }
+    if let Some(ref msg_name) = sel.default_message {
+        if options.check_references && msg_name != RAW_RECORD && !messages_by_name.contains_key(msg_name) {
+            return Err(format!("{} default message '{}' is not a defined message", owner, msg_name));
+        }
+    }
+    Ok(seen)
+}
+
+/// A `list<T>` whose elements encode to zero bytes (an empty struct or message) would read up to 2^32 - 1
+/// elements from its count alone, so its count cannot be checked against the data; reject it.
+fn check_zero_size_lists(resolved: &ResolvedProtocol) -> Result<(), String> {
+    let protocol = &resolved.protocol;
+    let field_lists = protocol
+        .messages
+        .iter()
+        .map(|m| (&m.name, m.fields.iter().map(|f| (&f.name, &f.type_spec)).collect::<Vec<_>>()))
+        .chain(protocol.structs.iter().map(|s| (&s.name, s.fields.iter().map(|f| (&f.name, &f.type_spec)).collect())));
+    for (owner, fields) in field_lists {
+        for (name, spec) in fields {
+            if let TypeSpec::List(elem) = spec {
+                if crate::layout::type_spec_max_bits(resolved, elem) == Some(0) {
+                    return Err(format!("{}.{}: list elements have zero size (empty struct or message)", owner, name));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Calls `f(owner, field, spec)` for the type of every message and struct field and for each type nested in
+/// it (list elements, optional and timestamp bases, `repeat` group fields as `field.member`).
+fn visit_field_types(protocol: &Protocol, f: &mut impl FnMut(&str, &str, &TypeSpec) -> Result<(), String>) -> Result<(), String> {
+    fn visit(owner: &str, name: &str, spec: &TypeSpec, f: &mut impl FnMut(&str, &str, &TypeSpec) -> Result<(), String>) -> Result<(), String> {
+        f(owner, name, spec)?;
+        match spec {
+            TypeSpec::Array(elem, _, _)
+            | TypeSpec::List(elem)
+            | TypeSpec::RepList(elem)
+            | TypeSpec::LengthPrefixedList(elem, _)
+            | TypeSpec::BytesList(elem, _)
+            | TypeSpec::Optional(elem, _)
+            | TypeSpec::ReverseBits(elem)
+            | TypeSpec::Timestamp { base: elem, .. } => visit(owner, name, elem, f),
+            TypeSpec::Group { fields, .. } => {
+                fields.iter().try_for_each(|g| visit(owner, &format!("{}.{}", name, g.name), &g.type_spec, f))
+            }
+            _ => Ok(()),
+        }
+    }
+    for m in &protocol.messages {
+        m.fields.iter().try_for_each(|fld| visit(&m.name, &fld.name, &fld.type_spec, f))?;
+    }
+    for s in &protocol.structs {
+        s.fields.iter().try_for_each(|fld| visit(&s.name, &fld.name, &fld.type_spec, f))?;
+    }
+    Ok(())
+}
+
+/// Rejects `u8(n)` and the like whose `n` is wider than the base type (a `u8(10)` would read two bytes),
+/// in transports, messages and structs, including element types and `repeat` groups.
+fn check_sized_int_widths(protocol: &Protocol) -> Result<(), String> {
+    fn check_width(owner: &str, name: &str, bt: &BaseType, n: u64) -> Result<(), String> {
+        let max = match bt {
+            BaseType::U8 | BaseType::I8 => 8,
+            BaseType::U16 | BaseType::I16 => 16,
+            BaseType::U24 => 24,
+            BaseType::U32 | BaseType::I32 => 32,
+            BaseType::U48 => 48,
+            _ => 64,
+        };
+        if n > max {
+            let bt = crate::layout::base_type_text(bt);
+            return Err(format!("{}.{}: {}({}) is wider than {} (at most {} bits)", owner, name, bt, n, bt, max));
+        }
+        Ok(())
+    }
+    let transports = [("transport", &protocol.transport), ("inner_transport", &protocol.inner_transport)];
+    for (owner, section) in transports {
+        for f in section.iter().flat_map(|t| &t.fields) {
+            if let TransportTypeSpec::SizedInt(bt, n) = &f.type_spec {
+                check_width(owner, &f.name, bt, *n)?;
+            }
+        }
+    }
+    visit_field_types(protocol, &mut |owner, name, spec| match spec {
+        TypeSpec::SizedInt(bt, n) => check_width(owner, name, bt, *n),
+        _ => Ok(()),
+    })
+}
+
+/// Rejects transport `length_of(field)` / `count_of(field)` fields whose `field` is a field of no message.
+fn check_transport_sizes(protocol: &Protocol) -> Result<(), String> {
+    let transports = [("transport", &protocol.transport), ("inner_transport", &protocol.inner_transport)];
+    for (owner, section) in transports {
+        for f in section.iter().flat_map(|t| &t.fields) {
+            let (keyword, target) = match &f.size_of {
+                Some(TransportSize::LengthOf(target)) => ("length_of", target),
+                Some(TransportSize::CountOf(target)) => ("count_of", target),
+                _ => continue,
+            };
+            if !protocol.messages.iter().any(|m| m.fields.iter().any(|mf| mf.name == *target)) {
+                return Err(format!("{}.{}: {}({}): no message has a field named {}", owner, f.name, keyword, target, target));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rejects `enum<E>` fields whose `E` is not a declared enum.
+fn check_enum_types(protocol: &Protocol) -> Result<(), String> {
+    visit_field_types(protocol, &mut |owner, name, spec| match spec {
+        TypeSpec::Enum(e) if !protocol.enum_defs.iter().any(|d| d.name == *e) => {
+            Err(format!("{}.{}: enum<{}>: no enum named {}", owner, name, e, e))
+        }
+        _ => Ok(()),
+    })
+}
+
+/// Rejects `select<field>` fields whose `field` is not an earlier field of the same message or struct and,
+/// with `check_references`, cases that name no struct or message.
+fn check_select_fields(protocol: &Protocol, check_references: bool) -> Result<(), String> {
+    let defined = |name: &str| protocol.structs.iter().any(|s| s.name == name) || protocol.messages.iter().any(|m| m.name == name);
+    let field_lists = protocol
+        .messages
+        .iter()
+        .map(|m| (&m.name, m.fields.iter().map(|f| (&f.name, &f.type_spec)).collect::<Vec<_>>()))
+        .chain(protocol.structs.iter().map(|s| (&s.name, s.fields.iter().map(|f| (&f.name, &f.type_spec)).collect())));
+    for (owner, fields) in field_lists {
+        for (i, (name, spec)) in fields.iter().enumerate() {
+            let TypeSpec::Select { field, cases, default } = spec else { continue };
+            if !fields[..i].iter().any(|(earlier, _)| *earlier == field) {
+                return Err(format!("{}.{}: select<{}>: {} is not an earlier field", owner, name, field, field));
+            }
+            let mut targets = cases.iter().map(|(_, target)| target).chain(default);
+            if let Some(target) = targets.find(|t| check_references && !defined(t)) {
+                return Err(format!("{}.{}: select<{}>: no struct or message named {}", owner, name, field, target));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fields of the transports, messages and structs with an `in(...)` constraint (plain or conditional), as
+/// (section keyword, section name, field, field type with `optional` removed, codes). The section name is
+/// empty for transports.
+pub(crate) fn enum_constrained_fields(protocol: &Protocol) -> Vec<(&'static str, &str, &str, TypeSpec, &[EnumCode])> {
+    fn codes(c: &Constraint) -> Option<&[EnumCode]> {
+        match c {
+            Constraint::Enum(codes) | Constraint::EnumTolerance(codes, _) => Some(codes),
+            Constraint::Range(_) => None,
+        }
+    }
+    let mut out = Vec::new();
+    for (keyword, t) in [("transport", &protocol.transport), ("inner_transport", &protocol.inner_transport)] {
+        for f in t.iter().flat_map(|t| &t.fields) {
+            let spec = match &f.type_spec {
+                TransportTypeSpec::Base(bt) => TypeSpec::Base(bt.clone()),
+                TransportTypeSpec::SizedInt(bt, n) => TypeSpec::SizedInt(bt.clone(), *n),
+                TransportTypeSpec::Bitfield(n) => TypeSpec::Bitfield(*n),
+                TransportTypeSpec::Padding(_) | TransportTypeSpec::Magic(_) => continue,
+            };
+            out.extend(f.constraint.as_ref().and_then(codes).map(|c| (keyword, "", f.name.as_str(), spec, c)));
+        }
+    }
+    let unwrap = |spec: &TypeSpec| match spec {
+        TypeSpec::Optional(inner, _) => inner.as_ref().clone(),
+        other => other.clone(),
+    };
+    for m in &protocol.messages {
+        for f in &m.fields {
+            let all = f.constraint.iter().chain(f.conditional_constraints.iter().map(|(_, c)| c));
+            out.extend(all.filter_map(codes).map(|c| ("message", m.name.as_str(), f.name.as_str(), unwrap(&f.type_spec), c)));
+        }
+    }
+    for st in &protocol.structs {
+        for f in &st.fields {
+            let all = f.constraint.iter().chain(f.conditional_constraints.iter().map(|(_, c)| c));
+            out.extend(all.filter_map(codes).map(|c| ("struct", st.name.as_str(), f.name.as_str(), unwrap(&f.type_spec), c)));
+        }
+    }
+    out
+}
+
+/// Rejects a string code in the `in(...)` constraint of a numeric field: the value never equals it, so the
+/// constraint would reject every value (or accept none of the intended ones).
+fn check_enum_constraint_literals(protocol: &Protocol) -> Result<(), String> {
+    for (keyword, section, name, spec, codes) in enum_constrained_fields(protocol) {
+        let numeric = type_spec_integer_range(&spec).is_some() || matches!(spec, TypeSpec::Base(BaseType::Float | BaseType::Double));
+        if !numeric {
+            continue;
+        }
+        if let Some(Literal::String(lit)) = codes.iter().map(|c| &c.value).find(|v| matches!(v, Literal::String(_))) {
+            let owner = if section.is_empty() { keyword } else { section };
+            return Err(format!(
+                "{}.{}: in(...) code \"{}\" is a string but the field is {}",
+                owner,
+                name,
+                lit,
+                crate::layout::type_spec_text(&spec)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Rejects body selector chains that lead back to a message already in the chain (`A` selects `B`,
+/// which selects `A`), which would continue the body forever.
+fn check_variant_cycles(protocol: &Protocol) -> Result<(), String> {
+    let variants: HashMap<&str, Vec<&str>> = protocol
+        .messages
+        .iter()
+        .filter_map(|m| {
+            let sel = m.selector.as_ref()?;
+            let names = sel.value_to_message.iter().map(|(_, name, _)| name.as_str()).chain(sel.default_message.as_deref());
+            Some((m.name.as_str(), names.collect()))
+        })
+        .collect();
+    fn visit<'p>(name: &'p str, variants: &HashMap<&'p str, Vec<&'p str>>, chain: &mut Vec<&'p str>) -> Result<(), String> {
+        if chain.contains(&name) {
+            chain.push(name);
+            return Err(format!("message selector cycle: {}", chain.join(" -> ")));
+        }
+        chain.push(name);
+        for next in variants.get(name).into_iter().flatten() {
+            visit(next, variants, chain)?;
+        }
+        chain.pop();
+        Ok(())
+    }
+    for name in variants.keys() {
+        visit(name, &variants, &mut Vec::new())?;
+    }
+    Ok(())
+}
+
+fn check_reference_cycles(protocol: &Protocol) -> Result<(), String> {
+    let mut deps: HashMap<&str, Vec<&str>> = HashMap::new();
+    for m in &protocol.messages {
+        let mut out = Vec::new();
+        for f in m.fields.iter().filter(|f| f.condition.is_none()) {
+            mandatory_refs(&f.type_spec, &mut out);
+        }
+        deps.insert(m.name.as_str(), out);
+    }
+    for s in &protocol.structs {
+        let mut out = Vec::new();
+        for f in s.fields.iter().filter(|f| f.condition.is_none()) {
+            mandatory_refs(&f.type_spec, &mut out);
+        }
+        deps.insert(s.name.as_str(), out);
+    }
+    fn visit<'a>(
+        name: &'a str,
+        deps: &HashMap<&'a str, Vec<&'a str>>,
+        path: &mut Vec<&'a str>,
+        done: &mut std::collections::HashSet<&'a str>,
+    ) -> Result<(), String> {
+        if let Some(i) = path.iter().position(|&p| p == name) {
+            let mut cycle = path[i..].to_vec();
+            cycle.push(name);
+            return Err(format!("recursive type: {} embeds itself", cycle.join(" -> ")));
+        }
+        if done.contains(name) {
+            return Ok(());
+        }
+        let Some(children) = deps.get(name) else {
+            return Ok(());
+        };
+        path.push(name);
+        for &child in children {
+            visit(child, deps, path, done)?;
+        }
+        path.pop();
+        done.insert(name);
+        Ok(())
+    }
+    let mut names: Vec<&str> = deps.keys().copied().collect();
+    names.sort_unstable();
+    let mut done = std::collections::HashSet::new();
+    for name in names {
+        visit(name, &deps, &mut Vec::new(), &mut done)?;
+    }
+    Ok(())
 }
 
 /// Per-message vec of bool (one per field, same order): true = constraint saturates type range, skip range check.
@@ -314,7 +1204,7 @@ fn build_bitmap_presence_mappings_messages(messages: &[MessageSection]) -> Resul
                 let mut optional_fields = Vec::new();
                 i += 1;
                 while i < msg.fields.len() {
-                    if matches!(msg.fields[i].type_spec, TypeSpec::Optional(_)) {
+                    if matches!(msg.fields[i].type_spec, TypeSpec::Optional(..)) {
                         optional_fields.push(msg.fields[i].name.clone());
                         i += 1;
                     } else {
@@ -357,7 +1247,7 @@ fn build_bitmap_presence_mappings_structs(structs: &[StructSection]) -> Result<H
                 let mut optional_fields = Vec::new();
                 i += 1;
                 while i < s.fields.len() {
-                    if matches!(s.fields[i].type_spec, TypeSpec::Optional(_)) {
+                    if matches!(s.fields[i].type_spec, TypeSpec::Optional(..)) {
                         optional_fields.push(s.fields[i].name.clone());
                         i += 1;
                     } else {
@@ -401,6 +1291,16 @@ impl Literal {
             _ => None,
         }
     }
+
+    /// Numeric literal as a float (integers included).
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Literal::Float(x) => Some(*x),
+            Literal::Int(i) => Some(*i as f64),
+            Literal::Hex(h) => Some(*h as f64),
+            _ => None,
+        }
+    }
 }
 
 /// Mapping from a bitmap field to the optional fields it governs (bit 0 = first, bit 1 = second, ...).
@@ -442,8 +1342,57 @@ pub struct ResolvedProtocol {
     pub struct_bitmap_presence: HashMap<String, BitmapPresenceMapping>,
 }
 
+/// Which resolve-time checks [`ResolvedProtocol::resolve_with_options`] runs.
+///
+/// Duplicate names, enum variant ranges, `remaining` placement and bitmap mappings are always checked:
+/// the codec relies on them. Of the switchable checks, `check_cycles` is safety-critical (a type that
+/// embeds itself makes decode recurse without bound); the others are advisory, since an undefined
+/// or ambiguous target only fails later, when a record actually selects it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolveOptions {
+    /// Reject types that embed themselves unconditionally (`A -> B -> A`).
+    pub check_cycles: bool,
+    /// Reject payload messages and selector targets that are not defined messages.
+    pub check_references: bool,
+    /// Reject payload selector values mapped to more than one message.
+    pub check_selector_duplicates: bool,
+    /// Require the payload selector to have a `default` mapping or to map every value of its
+    /// transport field. Off in [`ResolveOptions::default`].
+    pub check_selector_exhaustive: bool,
+}
+
+impl Default for ResolveOptions {
+    /// The checks done by [`ResolvedProtocol::resolve`].
+    fn default() -> Self {
+        ResolveOptions {
+            check_cycles: true,
+            check_references: true,
+            check_selector_duplicates: true,
+            check_selector_exhaustive: false,
+        }
+    }
+}
+
+impl ResolveOptions {
+    /// Every optional check off: for drafting a DSL with forward references or unfinished selectors.
+    pub fn permissive() -> Self {
+        ResolveOptions {
+            check_cycles: false,
+            check_references: false,
+            check_selector_duplicates: false,
+            check_selector_exhaustive: false,
+        }
+    }
+}
+
 impl ResolvedProtocol {
+    /// Resolve with [`ResolveOptions::default`].
     pub fn resolve(protocol: Protocol) -> Result<Self, String> {
+        Self::resolve_with_options(protocol, ResolveOptions::default())
+    }
+
+    /// Resolve names and run the checks enabled in `options`.
+    pub fn resolve_with_options(protocol: Protocol, options: ResolveOptions) -> Result<Self, String> {
         let mut type_defs_by_name = HashMap::new();
         let mut structs_by_name = HashMap::new();
         let mut messages_by_name = HashMap::new();
@@ -467,21 +1416,95 @@ impl ResolvedProtocol {
             if enums_by_name.insert(e.name.clone(), i).is_some() {
                 return Err(format!("Duplicate enum name: {}", e.name));
             }
+            if structs_by_name.contains_key(&e.name) {
+                return Err(format!("enum '{}' has the same name as a struct", e.name));
+            }
+            let (min, max) = match type_spec_integer_range(&TypeSpec::Base(e.base.clone())) {
+                Some(r) if !matches!(e.base, BaseType::Bool) => r,
+                _ => return Err(format!("enum '{}': base type {:?} is not an integer type", e.name, e.base)),
+            };
+            for (variant, lit) in &e.variants {
+                match lit.as_i64() {
+                    Some(v) if v >= min && v <= max => {}
+                    _ => return Err(format!("enum '{}': variant {} does not fit in {:?}", e.name, variant, e.base)),
+                }
+            }
         }
         if let Some(ref payload) = protocol.payload {
-            for name in &payload.messages {
-                if !messages_by_name.contains_key(name) {
-                    return Err(format!("payload message '{}' is not a defined message", name));
+            if options.check_references {
+                for name in &payload.messages {
+                    if !messages_by_name.contains_key(name) {
+                        return Err(format!("payload message '{}' is not a defined message", name));
+                    }
                 }
             }
             if let Some(ref sel) = payload.selector {
-                for (_, msg_name, _) in &sel.value_to_message {
-                    if !messages_by_name.contains_key(msg_name) {
-                        return Err(format!("payload selector message '{}' is not a defined message", msg_name));
-                    }
+                let seen = check_selector_mappings(sel, "payload selector", &messages_by_name, options)?;
+                if options.check_selector_exhaustive {
+                    check_selector_exhaustive(&protocol, sel, &seen)?;
+                }
+            }
+        }
+        if let Some(ref switch) = protocol.endianness_switch {
+            let header_fields = protocol.transport.iter().chain(&protocol.inner_transport).flat_map(|t| &t.fields);
+            let mut known = header_fields.map(|f| &f.name).chain(protocol.messages.iter().flat_map(|m| m.fields.iter().map(|f| &f.name)));
+            if !known.any(|name| *name == switch.field) {
+                return Err(format!(
+                    "endianness from {}: not a transport, inner transport or message field",
+                    switch.field
+                ));
+            }
+        }
+        for m in &protocol.messages {
+            // A check may read the fields of the message and of the variants its body selector continues with.
+            let variant_fields = m
+                .selector
+                .iter()
+                .flat_map(|sel| sel.value_to_message.iter().map(|(_, name, _)| name).chain(sel.default_message.iter()))
+                .filter_map(|name| messages_by_name.get(name).map(|&i| &protocol.messages[i]))
+                .flat_map(|v| v.fields.iter());
+            let known: Vec<&str> = m.fields.iter().chain(variant_fields).map(|f| f.name.as_str()).collect();
+            for c in &m.checks {
+                if let Some(name) = c.expr.fields().into_iter().find(|name| !known.contains(name)) {
+                    return Err(format!("message {}: check {}: unknown field '{}'", m.name, c.text, name));
                 }
             }
+            let Some(ref sel) = m.selector else { continue };
+            let owner = format!("message {}: selector", m.name);
+            if let Some(key) = sel.key_fields().find(|k| !m.fields.iter().any(|f| f.name == *k)) {
+                return Err(format!("{} field '{}' is not a field of the message", owner, key));
+            }
+            check_selector_mappings(sel, &owner, &messages_by_name, options)?;
+            for variant in sel.value_to_message.iter().map(|(_, name, _)| name).chain(sel.default_message.iter()) {
+                let variant_fields: Vec<&str> = match messages_by_name.get(variant) {
+                    Some(&i) => protocol.messages[i].fields.iter().map(|f| f.name.as_str()).collect(),
+                    None if variant == RAW_RECORD => vec![RAW_RECORD_FIELD],
+                    None => continue,
+                };
+                if let Some(dup) = variant_fields.iter().find(|name| m.fields.iter().any(|f| f.name == **name)) {
+                    return Err(format!("{} variant '{}' repeats field '{}'", owner, variant, dup));
+                }
+            }
+        }
+        if options.check_cycles {
+            check_reference_cycles(&protocol)?;
+            check_variant_cycles(&protocol)?;
+        }
+        let field_lists = protocol
+            .messages
+            .iter()
+            .map(|m| (&m.name, m.fields.iter().map(|f| (&f.name, &f.type_spec)).collect::<Vec<_>>()))
+            .chain(protocol.structs.iter().map(|s| (&s.name, s.fields.iter().map(|f| (&f.name, &f.type_spec)).collect())));
+        for (owner, fields) in field_lists {
+            if let Some((name, _)) = fields.iter().rev().skip(1).find(|(_, t)| matches!(t, TypeSpec::Remaining)) {
+                return Err(format!("{}.{}: `remaining` must be the last field", owner, name));
+            }
         }
+        check_sized_int_widths(&protocol)?;
+        check_transport_sizes(&protocol)?;
+        check_enum_types(&protocol)?;
+        check_select_fields(&protocol, options.check_references)?;
+        check_enum_constraint_literals(&protocol)?;
         let message_bitmap_presence = build_bitmap_presence_mappings_messages(&protocol.messages)?;
         let struct_bitmap_presence = build_bitmap_presence_mappings_structs(&protocol.structs)?;
         let mut protocol = protocol;
@@ -492,8 +1515,33 @@ impl ResolvedProtocol {
                     f.saturating = s;
                 }
             }
+            let condition_refs: std::collections::HashSet<String> = msg
+                .fields
+                .iter()
+                .flat_map(|f| f.conditional_constraints.iter().filter_map(|(c, _)| c.as_ref().map(|c| c.field.clone())))
+                .collect();
+            let width_refs = size_refs(msg.fields.iter().map(|f| &f.type_spec));
+            let selector_keys: Vec<String> =
+                msg.selector.iter().flat_map(|sel| sel.key_fields().map(str::to_string)).collect();
+            for f in &mut msg.fields {
+                f.referenced_by_condition = condition_refs.contains(&f.name);
+                f.referenced_by_width = width_refs.contains(&f.name);
+                f.referenced_by_selector = selector_keys.contains(&f.name);
+            }
+        }
+        for s in &mut protocol.structs {
+            let condition_refs: std::collections::HashSet<String> = s
+                .fields
+                .iter()
+                .flat_map(|f| f.conditional_constraints.iter().filter_map(|(c, _)| c.as_ref().map(|c| c.field.clone())))
+                .collect();
+            let width_refs = size_refs(s.fields.iter().map(|f| &f.type_spec));
+            for f in &mut s.fields {
+                f.referenced_by_condition = condition_refs.contains(&f.name);
+                f.referenced_by_width = width_refs.contains(&f.name);
+            }
         }
-        Ok(ResolvedProtocol {
+        let resolved = ResolvedProtocol {
             protocol,
             type_defs_by_name,
             structs_by_name,
@@ -501,7 +1549,9 @@ impl ResolvedProtocol {
             enums_by_name,
             message_bitmap_presence,
             struct_bitmap_presence,
-        })
+        };
+        check_zero_size_lists(&resolved)?;
+        Ok(resolved)
     }
 
     /// Get an enum definition by name. Used when a type ref (e.g. Cat034MessageType) refers to an enum.
@@ -530,19 +1580,50 @@ impl ResolvedProtocol {
             .unwrap_or(&[])
     }
 
-    /// Resolve which message type to use from decoded transport values using the payload selector.
-    /// Returns None if no payload/selector, or if the selector field is missing or value has no mapping.
+    /// Resolve which message type to use from decoded transport values using the payload selector
+    /// (all key fields of a tuple selector must match). A value with no mapping falls back to the selector `default` message (e.g. [`RAW_RECORD`]).
+    /// Returns None if no payload/selector, or if the selector field is missing or value has no mapping nor default.
     pub fn message_for_transport_values(&self, transport_values: &std::collections::HashMap<String, crate::value::Value>) -> Option<&str> {
         let payload = self.protocol.payload.as_ref()?;
         let sel = payload.selector.as_ref()?;
-        let v = transport_values.get(&sel.transport_field)?;
-        let n = v.as_i64()?;
-        for (lit, msg_name, _) in &sel.value_to_message {
-            if lit.as_i64() == Some(n) {
-                return Some(msg_name);
-            }
-        }
-        None
+        sel.select(|f| transport_values.get(f).and_then(crate::value::Value::as_i64))
+    }
+
+    /// Resolve which message continues `message_name` from its decoded field values, using the message's
+    /// body selector (`selector: kind -> { ... }` inside the message). Falls back to the selector `default`.
+    /// Returns None if the message has no body selector, a key field is missing, or the value has no mapping nor default.
+    pub fn message_for_message_field(
+        &self,
+        message_name: &str,
+        values: &std::collections::HashMap<String, crate::value::Value>,
+    ) -> Option<&str> {
+        let sel = self.get_message(message_name)?.selector.as_ref()?;
+        sel.select(|f| values.get(f).and_then(crate::value::Value::as_i64))
+    }
+
+    /// Upper bound on the encoded size of `message_name` in bytes, e.g. to size receive buffers or reject
+    /// over-long frames early. Counts the worst case: every optional and conditional field present, lists at the
+    /// largest count their `count_of` field's constraint admits (`rep_list` at most 255), arrays sized by a field
+    /// at its constraint or integer maximum, the largest body-selector variant. None when a part is unbounded
+    /// (a `list` without a constrained `count_of`, `octets_fx`, `remaining`, `cstring`, `custom`) or the message is unknown.
+    pub fn max_size(&self, message_name: &str) -> Option<usize> {
+        crate::layout::message_max_bits(self, message_name).map(|bits| bits.div_ceil(8) as usize)
+    }
+
+    /// Every (value, message_name, is_list) mapping of the payload selector, in declaration order.
+    /// Empty if no payload/selector. Use to report which selector values (e.g. ASTERIX categories) are known.
+    pub fn selector_values(&self) -> Vec<(Literal, &str, bool)> {
+        self.protocol
+            .payload
+            .as_ref()
+            .and_then(|p| p.selector.as_ref())
+            .map(|sel| {
+                sel.value_to_message
+                    .iter()
+                    .map(|(lit, msg_name, is_list)| (lit.clone(), msg_name.as_str(), *is_list))
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 
     /// When true, the payload after transport is a list of records (zero or more messages of the selected type per block).
@@ -560,14 +1641,8 @@ impl ResolvedProtocol {
                 return true;
             }
             if let Some(sel) = &payload.selector {
-                if let Some(v) = transport_values.get(&sel.transport_field) {
-                    if let Some(n) = v.as_i64() {
-                        for (lit, _, is_list) in &sel.value_to_message {
-                            if lit.as_i64() == Some(n) {
-                                return *is_list;
-                            }
-                        }
-                    }
+                if let Some((_, is_list)) = sel.lookup(|f| transport_values.get(f).and_then(crate::value::Value::as_i64)) {
+                    return is_list;
                 }
             }
         }
@@ -654,9 +1729,9 @@ impl ResolvedProtocol {
         None
     }
 
-    /// If the type spec is StructRef(name) and name is an enum, return variant name for the given value.
+    /// If the type spec is `enum<name>`, or StructRef(name) and name is an enum, return variant name for the given value.
     pub fn enum_variant_name_for_type_and_value(&self, type_spec: &TypeSpec, value: i64) -> Option<String> {
-        let TypeSpec::StructRef(name) = type_spec else {
+        let (TypeSpec::StructRef(name) | TypeSpec::Enum(name)) = type_spec else {
             return None;
         };
         let enum_sec = self.get_enum(name)?;
@@ -668,12 +1743,15 @@ impl ResolvedProtocol {
         None
     }
 
-    /// If constraint is Enum and the value matches a variant of some protocol enum, returns that variant's name.
-    /// Used when dumping: show enum variant name instead of raw number.
+    /// If constraint is Enum and the value matches one of its named codes (`in(OK=0, ...)`) or a variant
+    /// of some protocol enum, returns that name. Used when dumping: show the name instead of the raw number.
     pub fn enum_variant_name_for_value(&self, constraint: &Constraint, value: i64) -> Option<String> {
         let Constraint::Enum(literals) = constraint else {
             return None;
         };
+        if let Some(name) = enum_code_name(literals, value) {
+            return Some(name.to_string());
+        }
         let constraint_set: std::collections::HashSet<i64> = literals
             .iter()
             .filter_map(|lit| lit.as_i64())
@@ -700,12 +1778,12 @@ impl ResolvedProtocol {
     }
 }
 
-/// Child struct name for StructRef, Optional(StructRef), or List/RepList of StructRef.
+/// Child struct name for StructRef, Optional(StructRef), or List/RepList/LengthPrefixedList/BytesList of StructRef.
 fn type_spec_child_struct(ts: &TypeSpec) -> Option<&str> {
     match ts {
         TypeSpec::StructRef(s) => Some(s.as_str()),
-        TypeSpec::Optional(inner) => type_spec_child_struct(inner),
-        TypeSpec::List(inner) | TypeSpec::RepList(inner) => {
+        TypeSpec::Optional(inner, _) => type_spec_child_struct(inner),
+        TypeSpec::List(inner) | TypeSpec::RepList(inner) | TypeSpec::LengthPrefixedList(inner, _) | TypeSpec::BytesList(inner, _) => {
             if let TypeSpec::StructRef(s) = inner.as_ref() {
                 Some(s.as_str())
             } else {
@@ -1,6 +1,6 @@
-use aiprotodsl::frame::decode_frame;
+use aiprotodsl::frame::{decode_frame, DecodedMessage, RemovalReason, RemovedMessage};
 use aiprotodsl::value::Value;
-use aiprotodsl::{parse, value_to_dump, Codec, Endianness, ResolvedProtocol};
+use aiprotodsl::{json_str, parse, value_to_dump, Codec, Endianness, ResolvedProtocol};
 use pcap_parser::pcapng::Block as PcapNgBlock;
 use pcap_parser::traits::{PcapNGPacketBlock, PcapReaderIterator};
 use pcap_parser::{Linktype, PcapBlockOwned, PcapError};
@@ -9,15 +9,16 @@ use std::fs::File;
 use std::io::{Read, Write};
 use std::path::PathBuf;
 
-/// Write record bytes (block without 3-byte transport) with data offset (0 = first byte of record).
-fn write_record_hex_with_offset(w: &mut dyn Write, block: &[u8]) -> std::io::Result<()> {
+/// Write record bytes (block without 3-byte transport) with data offset: `base` + offset in the record
+/// (base 0: offset 0 = first byte of record; `--abs-offsets`: the record's offset in the UDP payload).
+fn write_record_hex_with_offset(w: &mut dyn Write, block: &[u8], base: usize) -> std::io::Result<()> {
     if block.len() <= 3 {
         return Ok(());
     }
     let record = &block[3..];
     const COLS: usize = 16;
     for (i, chunk) in record.chunks(COLS).enumerate() {
-        let start = i * COLS;
+        let start = base + i * COLS;
         let hex_line = chunk.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
         writeln!(w, "  offset {:3}: {}", start, hex_line)?;
     }
@@ -32,6 +33,20 @@ fn main() -> anyhow::Result<()> {
     } else {
         false
     };
+    // Dump offsets and record ranges relative to the UDP payload instead of the record / block.
+    let abs_offsets = if let Some(pos) = raw_args.iter().position(|a| a == "--abs-offsets") {
+        raw_args.remove(pos);
+        true
+    } else {
+        false
+    };
+    // One JSON object per decoded record on stdout (JSON lines); the summary stays on stderr.
+    let json = if let Some(pos) = raw_args.iter().position(|a| a == "--json") {
+        raw_args.remove(pos);
+        true
+    } else {
+        false
+    };
     let dump_path: Option<PathBuf> = raw_args
         .iter()
         .position(|a| a.starts_with("--dump"))
@@ -52,6 +67,35 @@ fn main() -> anyhow::Result<()> {
             let arg = raw_args.remove(pos);
             arg.strip_prefix("--frame=").and_then(|s| s.parse().ok())
         });
+    // Application header (sequence number, source id, ...) before the first ASTERIX block of each UDP payload.
+    let skip_bytes: usize = match raw_args.iter().position(|a| a.starts_with("--skip-bytes")) {
+        Some(pos) => {
+            let arg = raw_args.remove(pos);
+            let value = arg.strip_prefix("--skip-bytes=").unwrap_or("");
+            value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("--skip-bytes expects a byte count (--skip-bytes=N), got {:?}", arg))?
+        }
+        None => 0,
+    };
+    // Paste-ready `#[test]` functions for the first N decoded records of each category (default 1).
+    let emit_limit: Option<usize> = match raw_args.iter().position(|a| a.starts_with("--emit-test")) {
+        Some(pos) => {
+            let arg = raw_args.remove(pos);
+            match arg.strip_prefix("--emit-test=") {
+                Some(n) => Some(n.parse().map_err(|_| anyhow::anyhow!("--emit-test expects a record count (--emit-test=N), got {:?}", arg))?),
+                None if arg == "--emit-test" => Some(1),
+                None => anyhow::bail!("unknown option {:?}", arg),
+            }
+        }
+        None => None,
+    };
+    if json && dump_path.as_ref().is_some_and(|p| p.as_os_str() == "-") {
+        anyhow::bail!("--json writes to stdout: give the text dump a file (--dump=PATH)");
+    }
+    if emit_limit.is_some() && (json || dump_path.as_ref().is_some_and(|p| p.as_os_str() == "-")) {
+        anyhow::bail!("--emit-test writes to stdout: drop --json and give the text dump a file (--dump=PATH)");
+    }
     let mut args = raw_args.into_iter();
     let pcap_path: PathBuf = args.next().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("assets/asterix.pcap"));
     let dsl_path: PathBuf = args.next().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("examples/asterix_family.dsl"));
@@ -60,23 +104,21 @@ fn main() -> anyhow::Result<()> {
     let protocol = parse(&src).map_err(|e| anyhow::anyhow!(e))?;
     let resolved = ResolvedProtocol::resolve(protocol).map_err(|e| anyhow::anyhow!(e))?;
     let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let emitter = emit_limit.map(|limit| TestEmitter { limit, emitted: HashMap::new() });
+    if emitter.is_some() {
+        print_test_prelude(&dsl_path, &src);
+    }
  
-    let mut pkt_count: u64 = 0;
-    let mut udp_count: u64 = 0;
-    let mut block_count: u64 = 0;
-    let mut decoded_records: u64 = 0;
-    let mut removed_records: u64 = 0;
-    let mut unknown_categories: HashMap<u8, u64> = HashMap::new();
-    let mut known_categories: HashMap<u8, (u64, u64, u64)> = HashMap::new(); // cat -> (blocks, decoded, removed)
-    let mut first_errors: HashMap<u8, String> = HashMap::new();
+    let mut counts = Counts::default();
 
-    let mut dump_writer: Option<Box<dyn Write>> = dump_path.as_ref().map(|p| {
+    let dump: Option<Box<dyn Write>> = dump_path.as_ref().map(|p| {
         if p.as_os_str() == "-" {
             Box::new(std::io::stdout()) as Box<dyn Write>
         } else {
             Box::new(File::create(p).expect("create dump file")) as Box<dyn Write>
         }
     });
+    let mut options = Options { verbose, json, frame_filter, skip_bytes, abs_offsets, emitter, dump };
 
     // Probe file type (pcap vs pcapng) (pcap vs pcapng) using the magic at start of file.
     let mut probe = [0u8; 4];
@@ -91,17 +133,8 @@ fn main() -> anyhow::Result<()> {
             file,
             &codec,
             &resolved,
-            verbose,
-            &mut dump_writer,
-            frame_filter,
-            &mut pkt_count,
-            &mut udp_count,
-            &mut block_count,
-            &mut decoded_records,
-            &mut removed_records,
-            &mut unknown_categories,
-            &mut known_categories,
-            &mut first_errors,
+            &mut options,
+            &mut counts,
         )?;
     } else {
         let file = File::open(&pcap_path)?;
@@ -109,40 +142,34 @@ fn main() -> anyhow::Result<()> {
             file,
             &codec,
             &resolved,
-            verbose,
-            &mut dump_writer,
-            frame_filter,
-            &mut pkt_count,
-            &mut udp_count,
-            &mut block_count,
-            &mut decoded_records,
-            &mut removed_records,
-            &mut unknown_categories,
-            &mut known_categories,
-            &mut first_errors,
+            &mut options,
+            &mut counts,
         )?;
     }
  
     eprintln!("pcap: {}", pcap_path.display());
     eprintln!("dsl:  {}", dsl_path.display());
-    eprintln!("packets: {}", pkt_count);
-    eprintln!("udp payloads: {}", udp_count);
-    eprintln!("asterix blocks (from length field): {}", block_count);
-    eprintln!("decoded records: {}", decoded_records);
-    eprintln!("removed (validation/decoding errors): {}", removed_records);
-    if !known_categories.is_empty() {
-        let mut cats: Vec<_> = known_categories.into_iter().collect();
+    eprintln!("packets: {}", counts.pkt_count);
+    eprintln!("udp payloads: {}", counts.udp_count);
+    if skip_bytes > 0 {
+        eprintln!("skipped bytes per udp payload: {}", skip_bytes);
+    }
+    eprintln!("asterix blocks (from length field): {}", counts.block_count);
+    eprintln!("decoded records: {}", counts.decoded_records);
+    eprintln!("removed (validation/decoding errors): {}", counts.removed_records);
+    if !counts.known_categories.is_empty() {
+        let mut cats: Vec<_> = counts.known_categories.into_iter().collect();
         cats.sort_by_key(|(c, _)| *c);
         eprintln!("known categories summary:");
         for (cat, (blocks, decoded, removed)) in cats {
             eprintln!("  CAT{:03}: blocks={}, decoded={}, removed={}", cat, blocks, decoded, removed);
-            if let Some(err) = first_errors.get(&cat) {
+            if let Some(err) = counts.first_errors.get(&cat) {
                 eprintln!("    first error: {}", err);
             }
         }
     }
-    if !unknown_categories.is_empty() {
-        let mut cats: Vec<_> = unknown_categories.into_iter().collect();
+    if !counts.unknown_categories.is_empty() {
+        let mut cats: Vec<_> = counts.unknown_categories.into_iter().collect();
         cats.sort_by_key(|(c, _)| *c);
         eprintln!("unknown categories (skipped):");
         for (cat, n) in cats {
@@ -157,17 +184,8 @@ fn run_legacy_pcap<R: Read>(
     file: R,
     codec: &Codec,
     resolved: &ResolvedProtocol,
-    verbose: bool,
-    dump: &mut Option<Box<dyn Write>>,
-    frame_filter: Option<u64>,
-    pkt_count: &mut u64,
-    udp_count: &mut u64,
-    block_count: &mut u64,
-    decoded_records: &mut u64,
-    removed_records: &mut u64,
-    unknown_categories: &mut HashMap<u8, u64>,
-    known_categories: &mut HashMap<u8, (u64, u64, u64)>,
-    first_errors: &mut HashMap<u8, String>,
+    options: &mut Options,
+    counts: &mut Counts,
 ) -> anyhow::Result<()> {
     let mut reader = pcap_parser::pcap::LegacyPcapReader::new(1 << 20, file)?;
     let mut linktype: Option<Linktype> = None;
@@ -177,25 +195,11 @@ fn run_legacy_pcap<R: Read>(
                 match block {
                     PcapBlockOwned::LegacyHeader(h) => linktype = Some(h.network),
                     PcapBlockOwned::Legacy(b) => {
-                        *pkt_count += 1;
+                        counts.pkt_count += 1;
                         let lt = linktype.unwrap_or(Linktype(1));
                         if let Some(udp_payload) = udp_payload_from_linktype(lt, b.data) {
-                            *udp_count += 1;
-                            process_udp_payload(
-                                codec,
-                                resolved,
-                                udp_payload,
-                                *pkt_count,
-                                verbose,
-                                dump,
-                                frame_filter,
-                                block_count,
-                                decoded_records,
-                                removed_records,
-                                unknown_categories,
-                                known_categories,
-                                first_errors,
-                            );
+                            counts.udp_count += 1;
+                            process_udp_payload(codec, resolved, udp_payload, counts.pkt_count, options, counts);
                         }
                     }
                     PcapBlockOwned::NG(_) => {}
@@ -218,17 +222,8 @@ fn run_pcapng<R: Read>(
     file: R,
     codec: &Codec,
     resolved: &ResolvedProtocol,
-    verbose: bool,
-    dump: &mut Option<Box<dyn Write>>,
-    frame_filter: Option<u64>,
-    pkt_count: &mut u64,
-    udp_count: &mut u64,
-    block_count: &mut u64,
-    decoded_records: &mut u64,
-    removed_records: &mut u64,
-    unknown_categories: &mut HashMap<u8, u64>,
-    known_categories: &mut HashMap<u8, (u64, u64, u64)>,
-    first_errors: &mut HashMap<u8, String>,
+    options: &mut Options,
+    counts: &mut Counts,
 ) -> anyhow::Result<()> {
     let mut reader = pcap_parser::pcapng::PcapNGReader::new(1 << 20, file)?;
     let mut if_linktypes: Vec<Linktype> = Vec::new();
@@ -239,49 +234,21 @@ fn run_pcapng<R: Read>(
                     match &b {
                         PcapNgBlock::InterfaceDescription(idb) => if_linktypes.push(idb.linktype),
                         PcapNgBlock::EnhancedPacket(epb) => {
-                            *pkt_count += 1;
+                            counts.pkt_count += 1;
                             let lt = if_linktypes.get(epb.if_id as usize).copied().unwrap_or(Linktype(1));
                             let frame = epb.packet_data();
                             if let Some(udp_payload) = udp_payload_from_linktype(lt, frame) {
-                                *udp_count += 1;
-                                process_udp_payload(
-                                    codec,
-                                    resolved,
-                                    udp_payload,
-                                    *pkt_count,
-                                    verbose,
-                                    dump,
-                                    frame_filter,
-                                    block_count,
-                                    decoded_records,
-                                    removed_records,
-                                    unknown_categories,
-                                    known_categories,
-                                    first_errors,
-                                );
+                                counts.udp_count += 1;
+                                process_udp_payload(codec, resolved, udp_payload, counts.pkt_count, options, counts);
                             }
                         }
                         PcapNgBlock::SimplePacket(spb) => {
-                            *pkt_count += 1;
+                            counts.pkt_count += 1;
                             let lt = if_linktypes.first().copied().unwrap_or(Linktype(1));
                             let frame = spb.packet_data();
                             if let Some(udp_payload) = udp_payload_from_linktype(lt, frame) {
-                                *udp_count += 1;
-                                process_udp_payload(
-                                    codec,
-                                    resolved,
-                                    udp_payload,
-                                    *pkt_count,
-                                    verbose,
-                                    dump,
-                                    frame_filter,
-                                    block_count,
-                                    decoded_records,
-                                    removed_records,
-                                    unknown_categories,
-                                    known_categories,
-                                    first_errors,
-                                );
+                                counts.udp_count += 1;
+                                process_udp_payload(codec, resolved, udp_payload, counts.pkt_count, options, counts);
                             }
                         }
                         _ => {}
@@ -301,24 +268,28 @@ fn run_pcapng<R: Read>(
     Ok(())
 }
  
+/// Removal reason with its cause, e.g. `[constraint failed] sac = 300: Validation: ...`.
+fn removal_text(rm: &RemovedMessage) -> String {
+    match &rm.cause {
+        RemovalReason::ConstraintFailed { field, value } => format!("[{}] {} = {}: {}", rm.cause.kind(), field, value, rm.reason),
+        cause => format!("[{}] {}", cause.kind(), rm.reason),
+    }
+}
+
 fn process_udp_payload(
     codec: &Codec,
     resolved: &ResolvedProtocol,
     udp_payload: &[u8],
     packet_index: u64,
-    verbose: bool,
-    dump: &mut Option<Box<dyn Write>>,
-    frame_filter: Option<u64>,
-    block_count: &mut u64,
-    decoded_records: &mut u64,
-    removed_records: &mut u64,
-    unknown_categories: &mut HashMap<u8, u64>,
-    known_categories: &mut HashMap<u8, (u64, u64, u64)>,
-    first_errors: &mut HashMap<u8, String>,
+    options: &mut Options,
+    counts: &mut Counts,
 ) {
+    let Options { verbose, json, frame_filter, skip_bytes, abs_offsets, ref mut emitter, ref mut dump } = *options;
+    let Counts { block_count, decoded_records, removed_records, unknown_categories, known_categories, first_errors, .. } = counts;
     // UDP payload may contain multiple ASTERIX data blocks.
     // Length field = total block size (Category + Length + record data); per Wireshark/commonly used.
-    let mut off = 0usize;
+    // Blocks start after `skip_bytes` of application header; `off` (reported as udp_offset) stays relative to the UDP payload.
+    let mut off = skip_bytes;
     let mut any_block = false;
     while off + 3 <= udp_payload.len() {
         let cat = udp_payload[off];
@@ -327,6 +298,9 @@ fn process_udp_payload(
             break;
         }
         let block = &udp_payload[off..off + block_len];
+        // With --abs-offsets, hex offsets (record-relative) and record ranges (block-relative) are rebased
+        // onto the UDP payload.
+        let (hex_base, range_base) = if abs_offsets { (off + 3, off) } else { (0, 0) };
         *block_count += 1;
         any_block = true;
  
@@ -344,19 +318,32 @@ fn process_udp_payload(
                             entry.2 += res.removed.len() as u64;
                             if first_errors.get(&cat).is_none() {
                                 if let Some(rm) = res.removed.first() {
-                                    first_errors.insert(cat, rm.reason.clone());
+                                    first_errors.insert(cat, removal_text(rm));
                                 }
                             }
+                            if json && !frame_filter.map(|f| f != packet_index).unwrap_or(false) {
+                                let mut out = std::io::stdout().lock();
+                                for msg in &res.messages {
+                                    let _ = writeln!(out, "{}", record_json(packet_index, off, cat, msg));
+                                }
+                            }
+                            if let Some(emitter) = emitter.as_mut().filter(|_| !frame_filter.map(|f| f != packet_index).unwrap_or(false)) {
+                                emitter.emit(codec, cat, packet_index, off, block, &res.messages);
+                            }
                             if let Some(w) = dump.as_mut() {
                                 if frame_filter.map(|f| f != packet_index).unwrap_or(false) {
                                     // skip dump for this packet
                                 } else {
                                     let _ = writeln!(w, "=== packet {}  udp_offset {}  block cat {}  len {} ===", packet_index, off, cat, block_len);
-                                    let _ = writeln!(w, "  data (offset 0 = first byte of record, after 3-byte transport):");
-                                    let _ = write_record_hex_with_offset(&mut **w, block);
+                                    if abs_offsets {
+                                        let _ = writeln!(w, "  data (offsets within the UDP payload, after 3-byte transport):");
+                                    } else {
+                                        let _ = writeln!(w, "  data (offset 0 = first byte of record, after 3-byte transport):");
+                                    }
+                                    let _ = write_record_hex_with_offset(&mut **w, block, hex_base);
                                     for msg in &res.messages {
                                         let (a, b) = msg.byte_range;
-                                        let _ = writeln!(w, "  record bytes [{}-{}]  DECODED {}", a, b, msg.name);
+                                        let _ = writeln!(w, "  record bytes [{}-{}]  DECODED {}", range_base + a, range_base + b, msg.name);
                                         let mut keys: Vec<_> = msg.values.keys().collect();
                                         keys.sort();
                                         for k in keys {
@@ -378,7 +365,7 @@ fn process_udp_payload(
                                     }
                                     for rm in &res.removed {
                                         let (a, b) = rm.byte_range;
-                                        let _ = writeln!(w, "  record bytes [{}-{}]  REMOVED: {}", a, b, rm.reason);
+                                        let _ = writeln!(w, "  record bytes [{}-{}]  REMOVED: {}", range_base + a, range_base + b, removal_text(rm));
                                     }
                                 }
                             }
@@ -393,8 +380,12 @@ fn process_udp_payload(
                                 if frame_filter.map(|f| f != packet_index).unwrap_or(false) {}
                                 else {
                                     let _ = writeln!(w, "=== packet {}  udp_offset {}  block cat {}  len {} ===", packet_index, off, cat, block_len);
-                                    let _ = writeln!(w, "  data (offset 0 = first byte of record):");
-                                    let _ = write_record_hex_with_offset(&mut **w, block);
+                                    if abs_offsets {
+                                        let _ = writeln!(w, "  data (offsets within the UDP payload):");
+                                    } else {
+                                        let _ = writeln!(w, "  data (offset 0 = first byte of record):");
+                                    }
+                                    let _ = write_record_hex_with_offset(&mut **w, block, hex_base);
                                     let _ = writeln!(w, "  block decode error: {}", e);
                                 }
                             }
@@ -405,7 +396,7 @@ fn process_udp_payload(
                     if let Some(w) = dump.as_mut() {
                         if !frame_filter.map(|f| f != packet_index).unwrap_or(false) {
                             let _ = writeln!(w, "=== packet {}  udp_offset {}  block cat {}  len {}  (unknown category, skipped) ===", packet_index, off, cat, block_len);
-                            let _ = write_record_hex_with_offset(&mut **w, block);
+                            let _ = write_record_hex_with_offset(&mut **w, block, hex_base);
                         }
                     }
                 }
@@ -415,7 +406,7 @@ fn process_udp_payload(
                 if let Some(w) = dump.as_mut() {
                     if !frame_filter.map(|f| f != packet_index).unwrap_or(false) {
                         let _ = writeln!(w, "=== packet {}  udp_offset {}  block cat {}  len {}  (transport decode failed) ===", packet_index, off, cat, block_len);
-                        let _ = write_record_hex_with_offset(&mut **w, block);
+                        let _ = write_record_hex_with_offset(&mut **w, block, hex_base);
                     }
                 }
             }
@@ -433,6 +424,196 @@ fn process_udp_payload(
     }
 }
  
+/// Command-line options for the pcap readers and [`process_udp_payload`], with the outputs they write to.
+struct Options {
+    verbose: bool,
+    /// `--json`: one JSON object per decoded record on stdout.
+    json: bool,
+    /// `--frame=N`: dump, JSON and test output for packet N only.
+    frame_filter: Option<u64>,
+    /// `--skip-bytes=N`: application header before the first ASTERIX block of each UDP payload.
+    skip_bytes: usize,
+    /// `--abs-offsets`: dump offsets relative to the UDP payload.
+    abs_offsets: bool,
+    /// `--emit-test`: regression tests printed for the first decoded records.
+    emitter: Option<TestEmitter>,
+    /// `--dump`: text dump of each block.
+    dump: Option<Box<dyn Write>>,
+}
+
+/// Totals reported on stderr at the end of the run.
+#[derive(Default)]
+struct Counts {
+    pkt_count: u64,
+    udp_count: u64,
+    block_count: u64,
+    decoded_records: u64,
+    removed_records: u64,
+    unknown_categories: HashMap<u8, u64>,
+    /// cat -> (blocks, decoded, removed)
+    known_categories: HashMap<u8, (u64, u64, u64)>,
+    first_errors: HashMap<u8, String>,
+}
+
+/// `--emit-test`: prints a regression test for the first `limit` decoded records of each category.
+struct TestEmitter {
+    limit: usize,
+    emitted: HashMap<u8, usize>,
+}
+
+impl TestEmitter {
+    /// One `#[test]` per record of the block, while the category is under the limit.
+    fn emit(&mut self, codec: &Codec, cat: u8, packet_index: u64, udp_offset: usize, block: &[u8], messages: &[DecodedMessage]) {
+        for (index, msg) in messages.iter().enumerate() {
+            let count = self.emitted.entry(cat).or_insert(0);
+            if *count >= self.limit {
+                return;
+            }
+            *count += 1;
+            emit_test(codec, cat, format!("packet{}_offset{}_record{}", packet_index, udp_offset, index), block, msg);
+        }
+    }
+}
+
+/// Prints the test for one record: decode its bytes (after its inner header) and compare every field.
+fn emit_test(codec: &Codec, cat: u8, suffix: String, block: &[u8], msg: &DecodedMessage) {
+    let (start, end) = msg.byte_range;
+    let header_len = if msg.header.is_empty() { 0 } else { codec.decode_inner_transport_with_extent(&block[start..end]).0 };
+    let bytes: Vec<String> = block[start + header_len..end].iter().map(|b| format!("0x{:02x}", b)).collect();
+    let mut fields: Vec<_> = msg.values.iter().collect();
+    fields.sort_by_key(|(k, _)| *k);
+    println!();
+    println!("#[test]");
+    println!("fn capture_cat{:03}_{}() {{", cat, suffix);
+    println!("    let resolved = ResolvedProtocol::resolve(parse(CAPTURE_DSL).unwrap()).unwrap();");
+    println!("    let codec = Codec::new(resolved, Endianness::Big);");
+    println!("    let bytes: &[u8] = &[{}];", bytes.join(", "));
+    println!("    let values = codec.decode_message({:?}, bytes).unwrap();", msg.name);
+    println!("    let expected: HashMap<String, Value> = HashMap::from([");
+    for (k, v) in fields {
+        println!("        ({:?}.to_string(), {}),", k, rust_value(v));
+    }
+    println!("    ]);");
+    println!("    assert_eq!(values, expected);");
+    println!("}}");
+}
+
+/// Imports and the DSL (as `CAPTURE_DSL`) shared by the emitted tests.
+fn print_test_prelude(dsl_path: &std::path::Path, src: &str) {
+    let hashes = (0..).map(|n| "#".repeat(n)).find(|h| !src.contains(&format!("\"{}", h))).unwrap_or_default();
+    println!("// Regression tests captured by `decode_pcap --emit-test` from records decoded with {}.", dsl_path.display());
+    println!("use aiprotodsl::{{parse, Codec, Endianness, ResolvedProtocol, Value}};");
+    println!("use std::collections::HashMap;");
+    println!();
+    println!("const CAPTURE_DSL: &str = r{}\"{}\"{};", hashes, src, hashes);
+}
+
+/// Rust expression building `v`; floats go through their bits so the comparison is exact.
+fn rust_value(v: &Value) -> String {
+    match v {
+        Value::U8(x) => format!("Value::U8({})", x),
+        Value::U16(x) => format!("Value::U16({})", x),
+        Value::U32(x) => format!("Value::U32({})", x),
+        Value::U64(x) => format!("Value::U64({})", x),
+        Value::I8(x) => format!("Value::I8({})", x),
+        Value::I16(x) => format!("Value::I16({})", x),
+        Value::I32(x) => format!("Value::I32({})", x),
+        Value::I64(x) => format!("Value::I64({})", x),
+        Value::Bool(b) => format!("Value::Bool({})", b),
+        Value::Float(x) => format!("Value::Float(f32::from_bits(0x{:08x}))", x.to_bits()),
+        Value::Double(x) => format!("Value::Double(f64::from_bits(0x{:016x}))", x.to_bits()),
+        Value::Bytes(b) => format!("Value::Bytes(vec![{}])", b.iter().map(|b| format!("0x{:02x}", b)).collect::<Vec<_>>().join(", ")),
+        Value::Struct(m) => {
+            let mut fields: Vec<_> = m.iter().collect();
+            fields.sort_by_key(|(k, _)| *k);
+            let fields: Vec<String> = fields.into_iter().map(|(k, v)| format!("({:?}.to_string(), {})", k, rust_value(v))).collect();
+            format!("Value::Struct(HashMap::from([{}]))", fields.join(", "))
+        }
+        Value::List(items) => format!("Value::List(vec![{}])", items.iter().map(rust_value).collect::<Vec<_>>().join(", ")),
+        Value::Padding => "Value::Padding".to_string(),
+        Value::Timestamp { raw, seconds } => {
+            format!("Value::Timestamp {{ raw: {}, seconds: f64::from_bits(0x{:016x}) }}", raw, seconds.to_bits())
+        }
+        Value::Enum { name, variant, raw } => {
+            format!("Value::Enum {{ name: {:?}.to_string(), variant: {}, raw: {} }}", name, match variant {
+                Some(v) => format!("Some({:?}.to_string())", v),
+                None => "None".to_string(),
+            }, raw)
+        }
+    }
+}
+
+/// One decoded record as a JSON object: packet index, block position and category, message name,
+/// record byte range within the block, and the field values (absent optionals omitted, as in the dump).
+fn record_json(packet_index: u64, udp_offset: usize, cat: u8, msg: &DecodedMessage) -> String {
+    let mut out = format!(
+        "{{\"packet\":{},\"udp_offset\":{},\"category\":{},\"message\":{},\"byte_range\":[{},{}],\"values\":",
+        packet_index,
+        udp_offset,
+        cat,
+        json_str(&msg.name),
+        msg.byte_range.0,
+        msg.byte_range.1
+    );
+    let fields = msg.values.iter().filter(|(_, v)| !matches!(v, Value::List(l) if l.is_empty()));
+    write_json_object(&mut out, fields);
+    out.push('}');
+    out
+}
+
+fn write_json_object<'a>(out: &mut String, fields: impl Iterator<Item = (&'a String, &'a Value)>) {
+    let mut fields: Vec<_> = fields.collect();
+    fields.sort_by_key(|(k, _)| *k);
+    out.push('{');
+    for (i, (k, v)) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&json_str(k));
+        out.push(':');
+        write_json_value(out, v);
+    }
+    out.push('}');
+}
+
+/// Numbers as JSON numbers (non-finite floats as null), bytes as a hex string, padding as null.
+fn write_json_value(out: &mut String, v: &Value) {
+    match v {
+        Value::U8(x) => out.push_str(&x.to_string()),
+        Value::U16(x) => out.push_str(&x.to_string()),
+        Value::U32(x) => out.push_str(&x.to_string()),
+        Value::U64(x) => out.push_str(&x.to_string()),
+        Value::I8(x) => out.push_str(&x.to_string()),
+        Value::I16(x) => out.push_str(&x.to_string()),
+        Value::I32(x) => out.push_str(&x.to_string()),
+        Value::I64(x) => out.push_str(&x.to_string()),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Float(x) if x.is_finite() => out.push_str(&x.to_string()),
+        Value::Double(x) if x.is_finite() => out.push_str(&x.to_string()),
+        Value::Float(_) | Value::Double(_) | Value::Padding => out.push_str("null"),
+        Value::Bytes(b) => {
+            let hex: String = b.iter().map(|b| format!("{:02x}", b)).collect();
+            out.push_str(&json_str(&hex));
+        }
+        Value::Struct(m) => write_json_object(out, m.iter()),
+        Value::List(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Timestamp { raw, seconds } => {
+            out.push_str(&format!("{{\"raw\":{},\"seconds\":{}}}", raw, seconds));
+        }
+        Value::Enum { variant: Some(name), .. } => out.push_str(&json_str(name)),
+        Value::Enum { raw, .. } => out.push_str(&raw.to_string()),
+    }
+}
+
 /// Extract UDP payload bytes from a captured frame, using linktype and IPv4/UDP length fields.
 /// This avoids including Ethernet padding in short frames.
 fn udp_payload_from_linktype(linktype: Linktype, frame: &[u8]) -> Option<&[u8]> {
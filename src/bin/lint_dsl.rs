@@ -24,6 +24,15 @@ fn rule_id(rule: LintRule) -> &'static str {
         LintRule::OneFieldPerLine => "one-field-per-line",
         LintRule::ClosingBraceAlone => "closing-brace-alone",
         LintRule::NoTrailingWhitespace => "no-trailing-whitespace",
+        LintRule::UnreachableField => "unreachable-field",
+        LintRule::IncompleteField => "incomplete-field",
+        LintRule::AmbiguousSelector => "ambiguous-selector",
+        LintRule::InvalidQuantum => "invalid-quantum",
+        LintRule::EnumCodeOutOfRange => "enum-code-out-of-range",
+        LintRule::OptionalWithoutPresence => "optional-without-presence",
+        LintRule::DanglingFieldRef => "dangling-field-ref",
+        LintRule::SizeFieldOrder => "size-field-order",
+        LintRule::BitGroupAlignment => "bit-group-alignment",
     }
 }
 
@@ -0,0 +1,98 @@
+//! Checksums for `checksum(alg)` fields: computed over the preceding bytes of the message on encode,
+//! recomputed and compared on decode (see [`TypeSpec::Checksum`](crate::ast::TypeSpec::Checksum)).
+
+use crate::ast::BaseType;
+
+/// Checksum algorithm of a `checksum(alg)` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    /// `crc16_ccitt`: CRC-16/CCITT-FALSE (poly 0x1021, init 0xFFFF, not reflected), a `u16`.
+    Crc16Ccitt,
+    /// `crc32`: CRC-32 (IEEE 802.3, as in zlib and Ethernet), a `u32`.
+    Crc32,
+}
+
+impl ChecksumAlgorithm {
+    /// Algorithm from its DSL name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "crc16_ccitt" => Some(ChecksumAlgorithm::Crc16Ccitt),
+            "crc32" => Some(ChecksumAlgorithm::Crc32),
+            _ => None,
+        }
+    }
+
+    /// DSL name, e.g. `crc32`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Crc16Ccitt => "crc16_ccitt",
+            ChecksumAlgorithm::Crc32 => "crc32",
+        }
+    }
+
+    /// Bytes the checksum takes on the wire.
+    pub fn size(self) -> usize {
+        match self {
+            ChecksumAlgorithm::Crc16Ccitt => 2,
+            ChecksumAlgorithm::Crc32 => 4,
+        }
+    }
+
+    /// Integer type the checksum is written as, in the message's byte order.
+    pub fn base_type(self) -> BaseType {
+        match self {
+            ChecksumAlgorithm::Crc16Ccitt => BaseType::U16,
+            ChecksumAlgorithm::Crc32 => BaseType::U32,
+        }
+    }
+
+    /// Checksum of `data`.
+    pub fn compute(self, data: &[u8]) -> u32 {
+        match self {
+            ChecksumAlgorithm::Crc16Ccitt => crc16_ccitt(data) as u32,
+            ChecksumAlgorithm::Crc32 => crc32(data),
+        }
+    }
+}
+
+const CRC16_CCITT_TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-16/CCITT-FALSE of `data` (`"123456789"` gives 0x29B1).
+pub fn crc16_ccitt(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, &b| (crc << 8) ^ CRC16_CCITT_TABLE[((crc >> 8) as u8 ^ b) as usize])
+}
+
+/// CRC-32 (IEEE) of `data` (`"123456789"` gives 0xCBF43926).
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(0xFFFF_FFFF, |crc, &b| (crc >> 8) ^ CRC32_TABLE[(crc as u8 ^ b) as usize])
+}
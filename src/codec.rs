@@ -4,10 +4,13 @@
 //! length_of/count_of, structs, lists, and validation.
 
 use crate::ast::{PaddingKind, *};
-use crate::value::Value;
+use crate::quantum::parse_quantum;
+use crate::value::{Value, ValueRef};
 use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io::{Cursor, Read, Write};
+use std::ops::Range;
 
 #[cfg(feature = "codec_decode_profile")]
 use std::cell::RefCell;
@@ -23,7 +26,100 @@ pub enum Endianness {
 #[derive(Debug)]
 pub struct Codec {
     pub endianness: Endianness,
+    /// Reject non-minimal `bitmap(n, k)` FSPECs on decode: a last block whose presence bits are all zero
+    /// (the previous block could have ended with FX=0) is a validation error. Off by default.
+    pub strict_fspec: bool,
+    /// How values are completed on encode; see [`EncodeOptions`].
+    pub encode_options: EncodeOptions,
+    /// Decode integer message fields that have a `quantum` into `Value::Double` physical values (raw times the
+    /// quantum's scale, e.g. `1/256 NM`), and encode such fields by dividing back and rounding. Off by default:
+    /// fields keep their raw integers. Constraints and conditions still apply to the raw values.
+    pub quantum_scaling: bool,
+    /// Order in which `bitfield(n)`, `bits { .. }`, bit padding and unaligned sized ints fill each byte; see
+    /// [`BitOrder`]. Presence bitmaps keep their own layout.
+    pub bit_order: BitOrder,
+    /// Bounds on counts and nesting read from untrusted input; see [`DecodeLimits`]. Unbounded by default.
+    pub limits: DecodeLimits,
     resolved: ResolvedProtocol,
+    custom_types: CustomTypes,
+}
+
+/// Encode settings of a [`Codec`] (`codec.encode_options`). The default writes the values map as given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// Compute every `length_of(field)` from the encoded bytes of `field` and every `count_of(field)` from its
+    /// number of elements, ignoring the values map; a length field may come before or after its field.
+    pub auto_length: bool,
+}
+
+/// Bit numbering of bit groups ([`Codec::bit_order`]). Only the codec honours it: walkers
+/// ([`crate::walk`]) always read bit groups [`Lsb0`](BitOrder::Lsb0).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitOrder {
+    /// Fields fill each byte from bit 0 (the least significant) up, low value bits first, as in ASTERIX:
+    /// `bitfield(3)` holding `0b101` is the byte `0x05`.
+    #[default]
+    Lsb0,
+    /// Fields fill each byte from bit 7 (the most significant) down, high value bits first, as in RFC bit
+    /// diagrams: `bitfield(3)` holding `0b101` is the byte `0xA0`.
+    Msb0,
+}
+
+/// Decode bounds of a [`Codec`] ([`Codec::new_with_limits`]), for parsing untrusted captures: a count or
+/// length read from the wire cannot make the decoder allocate or recurse without bound. Exceeding a limit
+/// is a [`CodecError::Validation`] naming it. The default sets no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Most elements of one `list`, `rep_list`, `length_prefixed_list`, array or `repeat` group, checked
+    /// before the elements are read.
+    pub max_list_len: u64,
+    /// Most struct or embedded message references nested inside one another (a message's own fields are
+    /// depth 0, a struct field's fields depth 1).
+    pub max_recursion_depth: usize,
+    /// Longest message a decode accepts, in bytes: the extent of the message (body selector variants
+    /// included), not of the input, which may hold further records (as in a frame).
+    pub max_total_bytes: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits { max_list_len: u64::MAX, max_recursion_depth: usize::MAX, max_total_bytes: usize::MAX }
+    }
+}
+
+/// User-supplied encoding for a `custom("name")` field type, registered with [`Codec::register_custom`].
+/// Custom fields are byte-aligned.
+pub trait FieldCodec: Send + Sync {
+    /// Read one value starting at the cursor position.
+    fn decode(&self, r: &mut Cursor<&[u8]>, endianness: Endianness) -> Result<Value, CodecError>;
+    /// Append the encoding of `v` to `w`.
+    fn encode(&self, w: &mut Vec<u8>, v: &Value) -> Result<(), CodecError>;
+    /// Number of bytes the value starting at `data[pos..]` occupies (used by the zero-copy walker).
+    fn skip(&self, data: &[u8], pos: usize) -> Result<usize, CodecError>;
+}
+
+/// Registry of [`FieldCodec`]s by `custom("name")` type name.
+#[derive(Default)]
+pub struct CustomTypes(HashMap<String, Box<dyn FieldCodec>>);
+
+impl CustomTypes {
+    pub fn insert(&mut self, name: impl Into<String>, codec: impl FieldCodec + 'static) {
+        self.0.insert(name.into(), Box::new(codec));
+    }
+
+    /// The codec registered under `name`, or a validation error naming the missing type.
+    pub fn get(&self, name: &str) -> Result<&dyn FieldCodec, CodecError> {
+        self.0
+            .get(name)
+            .map(|c| c.as_ref())
+            .ok_or_else(|| CodecError::Validation(format!("custom type \"{}\" is not registered", name)))
+    }
+}
+
+impl std::fmt::Debug for CustomTypes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -38,8 +134,83 @@ pub enum CodecError {
     UnknownField(String),
     #[error("Length/count mismatch: {0}")]
     LengthMismatch(String),
+    /// The buffer ended early: `needed` bytes were required at byte `offset` but only `available` were left.
+    /// `field` is the path of the field being read (`pos.x`), empty when not known.
+    /// Unlike `Validation`, more data may make the same bytes decode.
+    #[error("Truncated{}: needed {needed} bytes at offset {offset}, {available} available", field_suffix(.field))]
+    Truncated { field: String, needed: usize, available: usize, offset: usize },
+}
+
+fn field_suffix(field: &str) -> String {
+    if field.is_empty() {
+        String::new()
+    } else {
+        format!(" in {}", field)
+    }
+}
+
+/// Fails with [`CodecError::Truncated`] unless `n` more bytes can be read from `r`.
+fn need(r: &Cursor<&[u8]>, n: usize) -> Result<(), CodecError> {
+    let offset = r.position() as usize;
+    let available = r.get_ref().len().saturating_sub(offset);
+    if available < n {
+        return Err(CodecError::Truncated { field: String::new(), needed: n, available, offset });
+    }
+    Ok(())
+}
+
+/// Padding bytes after an array element of `size` bytes in a `stride(m)` slot; an error when it overflows the slot.
+pub(crate) fn stride_gap(stride: u64, size: usize) -> Result<usize, CodecError> {
+    (stride as usize).checked_sub(size).ok_or_else(|| {
+        CodecError::Validation(format!("array stride({}): element takes {} bytes", stride, size))
+    })
 }
 
+/// Prefix an error with the field it occurred in; truncation is passed through unchanged so callers can
+/// still tell "need more bytes" from invalid data. A validation error keeps a single `Validation:` prefix.
+fn in_field(e: CodecError, field: std::fmt::Arguments) -> CodecError {
+    match e {
+        CodecError::Truncated { .. } => e,
+        CodecError::Validation(msg) => CodecError::Validation(format!("{}: {}", field, msg)),
+        e => CodecError::Validation(format!("{}: {}", field, e)),
+    }
+}
+
+/// [`in_field`] for the field `name` of a message or struct: a truncation gets `name` prepended to its
+/// field path instead, so the innermost field is named last (`pos.x`).
+fn in_named_field(e: CodecError, name: &str, context: std::fmt::Arguments) -> CodecError {
+    match e {
+        CodecError::Truncated { field, needed, available, offset } => {
+            let field = if field.is_empty() { name.to_string() } else { format!("{}.{}", name, field) };
+            CodecError::Truncated { field, needed, available, offset }
+        }
+        e => in_field(e, context),
+    }
+}
+
+/// `e`, raised decoding a sub-slice that starts at byte `base` of the input, with a
+/// [`CodecError::Truncated`] offset counted from the start of the input.
+fn offset_by(e: CodecError, base: usize) -> CodecError {
+    match e {
+        CodecError::Truncated { field, needed, available, offset } => {
+            CodecError::Truncated { field, needed, available, offset: base + offset }
+        }
+        e => e,
+    }
+}
+
+/// Decoded field values paired with the byte range each was read from (see [`Codec::decode_message_with_offsets`]).
+pub type ValuesWithOffsets = HashMap<String, (Value, Range<usize>)>;
+
+/// `(start, end)` byte offsets of each decoded top-level field (see [`Codec::decode_message_spanned`]).
+pub type FieldSpans = HashMap<String, (usize, usize)>;
+
+/// Whether each optional field of a decoded message was present (see [`Codec::decode_message_with_presence`]).
+pub type FieldPresence = HashMap<String, bool>;
+
+/// Field-override callback of [`Codec::decode_message_with`].
+type FieldOverride<'h> = dyn FnMut(&str, &mut Cursor<&[u8]>) -> Option<Result<Value, CodecError>> + 'h;
+
 #[cfg(feature = "codec_decode_profile")]
 fn type_spec_decode_label(spec: &TypeSpec) -> &'static str {
     match spec {
@@ -47,22 +218,162 @@ fn type_spec_decode_label(spec: &TypeSpec) -> &'static str {
         TypeSpec::Padding(_) => "Padding",
         TypeSpec::Bitfield(_) => "Bitfield",
         TypeSpec::SizedInt(_, _) => "SizedInt",
-        TypeSpec::LengthOf(_) => "LengthOf",
-        TypeSpec::CountOf(_) => "CountOf",
-        TypeSpec::PresenceBits(_) => "PresenceBits",
+        TypeSpec::LengthOf(..) => "LengthOf",
+        TypeSpec::CountOf(..) => "CountOf",
+        TypeSpec::DynInt(_) => "DynInt",
+        TypeSpec::Bits(_) => "Bits",
+        TypeSpec::PresenceBits(..) => "PresenceBits",
         TypeSpec::BitmapPresence { .. } => "BitmapPresence",
         TypeSpec::StructRef(_) => "StructRef",
-        TypeSpec::Array(_, _) => "Array",
+        TypeSpec::Enum(_) => "Enum",
+        TypeSpec::Select { .. } => "Select",
+        TypeSpec::Group { .. } => "Group",
+        TypeSpec::Array(..) => "Array",
         TypeSpec::List(_) => "List",
         TypeSpec::RepList(_) => "RepList",
+        TypeSpec::LengthPrefixedList(..) => "LengthPrefixedList",
+        TypeSpec::BytesList(..) => "BytesList",
         TypeSpec::OctetsFx => "OctetsFx",
-        TypeSpec::Optional(_) => "Optional",
+        TypeSpec::Optional(..) => "Optional",
+        TypeSpec::Timestamp { .. } => "Timestamp",
+        TypeSpec::Bcd { .. } => "Bcd",
+        TypeSpec::ReverseBits(_) => "ReverseBits",
+        TypeSpec::Remaining => "Remaining",
+        TypeSpec::Unknown(_) => "Unknown",
+        TypeSpec::CString => "CString",
+        TypeSpec::FixedString(_) => "FixedString",
+        TypeSpec::Checksum { .. } => "Checksum",
+        TypeSpec::Custom(_) => "Custom",
+    }
+}
+
+/// Byte width of a `dyn_int` read from `width_field`; only 1..8 bytes fit a `u64`.
+fn dyn_int_width(width_field: &str, width: u64) -> Result<u64, CodecError> {
+    if (1..=8).contains(&width) {
+        Ok(width)
+    } else {
+        Err(CodecError::Validation(format!("dyn_int({}): width {} is not in 1..8", width_field, width)))
+    }
+}
+
+/// Struct or message a `select<field>` holds, from the value of `field` (None when it was not read).
+pub(crate) fn select_target<'s>(
+    field: &str,
+    cases: &'s [(i64, String)],
+    default: Option<&'s String>,
+    value: Option<i64>,
+) -> Result<&'s str, CodecError> {
+    let value = value.ok_or_else(|| CodecError::UnknownField(field.to_string()))?;
+    select_case(cases, default, value).ok_or_else(|| CodecError::Validation(format!("select<{}>: no case for value {}", field, value)))
+}
+
+/// Packed BCD digits to an integer (`swapped`: low nibble is the first digit of each byte).
+/// None if a nibble is not a decimal digit.
+pub(crate) fn bcd_to_u64(bytes: &[u8], swapped: bool) -> Option<u64> {
+    let mut v = 0u64;
+    for &b in bytes {
+        let (first, second) = if swapped { (b & 0x0F, b >> 4) } else { (b >> 4, b & 0x0F) };
+        if first > 9 || second > 9 {
+            return None;
+        }
+        v = v * 100 + (first as u64) * 10 + second as u64;
+    }
+    Some(v)
+}
+
+/// Integer to `len` bytes of packed BCD; None if it has more than `2 * len` digits.
+pub(crate) fn u64_to_bcd(mut v: u64, len: usize, swapped: bool) -> Option<Vec<u8>> {
+    let mut out = vec![0u8; len];
+    for b in out.iter_mut().rev() {
+        let (second, first) = ((v % 10) as u8, ((v / 10) % 10) as u8);
+        v /= 100;
+        *b = if swapped { (second << 4) | first } else { (first << 4) | second };
+    }
+    if v != 0 {
+        return None;
     }
+    Some(out)
 }
 
 impl Codec {
     pub fn new(resolved: ResolvedProtocol, endianness: Endianness) -> Self {
-        Codec { endianness, resolved }
+        Codec {
+            endianness,
+            strict_fspec: false,
+            encode_options: EncodeOptions::default(),
+            quantum_scaling: false,
+            bit_order: BitOrder::default(),
+            limits: DecodeLimits::default(),
+            resolved,
+            custom_types: CustomTypes::default(),
+        }
+    }
+
+    /// This codec with [`bit_order`](Self::bit_order) set to `order`.
+    pub fn with_bit_order(mut self, order: BitOrder) -> Self {
+        self.bit_order = order;
+        self
+    }
+
+    /// Codec whose decodes stay within `limits` (see [`DecodeLimits`]).
+    pub fn new_with_limits(resolved: ResolvedProtocol, endianness: Endianness, limits: DecodeLimits) -> Self {
+        Codec { limits, ..Codec::new(resolved, endianness) }
+    }
+
+    /// This codec with [`quantum_scaling`](Self::quantum_scaling) set to `on`.
+    pub fn with_quantum_scaling(mut self, on: bool) -> Self {
+        self.quantum_scaling = on;
+        self
+    }
+
+    /// Protocol this codec was built from.
+    pub(crate) fn resolved(&self) -> &ResolvedProtocol {
+        &self.resolved
+    }
+
+    /// Register the codec used for `custom("name")` fields; replaces any earlier registration of `name`.
+    pub fn register_custom(&mut self, name: impl Into<String>, codec: impl FieldCodec + 'static) {
+        self.custom_types.insert(name, codec);
+    }
+
+    /// JSON description of `message_name` for tools that do not parse the DSL: `{"message": .., "fields": [..]}`
+    /// with one object per field, nested struct/message fields following their parent under a dotted `name`
+    /// (e.g. `i048_010.sac`). Each object has `name`, `type` (DSL spelling), `width_bits` and `offset_bits`
+    /// (null when data-dependent; offsets are from the message start), `optional`, `constraint`, `quantum` and `doc`.
+    pub fn message_layout_json(&self, message_name: &str) -> Result<String, CodecError> {
+        crate::layout::message_layout_json(&self.resolved, message_name)
+            .ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))
+    }
+
+    /// Registered custom codecs, e.g. for [`BinaryWalker::with_custom_types`](crate::walk::BinaryWalker::with_custom_types).
+    pub fn custom_types(&self) -> &CustomTypes {
+        &self.custom_types
+    }
+
+    /// Byte order the protocol's `endianness from` directive picks from decoded header (or message) values,
+    /// e.g. to decode the message after a transport holding the version with [`decode_message_as`](Self::decode_message_as).
+    /// None when there is no directive or `values` lacks its field; an error when the value has no case.
+    pub fn endianness_for(&self, values: &HashMap<String, Value>) -> Result<Option<Endianness>, CodecError> {
+        let Some(switch) = &self.resolved.protocol.endianness_switch else { return Ok(None) };
+        match values.get(&switch.field) {
+            Some(v) => self.switched_endianness(&switch.field, v.as_i64()),
+            None => Ok(None),
+        }
+    }
+
+    /// Byte order selected once `field` has the (integer) value `value`: None unless `field` is the
+    /// `endianness from` field.
+    fn switched_endianness(&self, field: &str, value: Option<i64>) -> Result<Option<Endianness>, CodecError> {
+        let Some(switch) = self.resolved.protocol.endianness_switch.as_ref().filter(|s| s.field == field) else {
+            return Ok(None);
+        };
+        let value = value.ok_or_else(|| {
+            CodecError::Validation(format!("endianness from {}: value is not an integer", field))
+        })?;
+        switch
+            .endianness_for(value)
+            .map(Some)
+            .ok_or_else(|| CodecError::Validation(format!("endianness from {}: no byte order for value {}", field, value)))
     }
 
     /// Decode a single message by name from the given bytes.
@@ -75,6 +386,18 @@ impl Codec {
             .1
     }
 
+    /// Decode each slice as one `message_name` record, on the rayon thread pool; results are in slice order.
+    /// Records must be independent (e.g. the ranges from [`split_only`](crate::frame::split_only)).
+    #[cfg(feature = "rayon")]
+    pub fn decode_many_parallel(
+        &self,
+        message_name: &str,
+        record_slices: &[&[u8]],
+    ) -> Vec<Result<HashMap<String, Value>, CodecError>> {
+        use rayon::prelude::*;
+        record_slices.par_iter().map(|bytes| self.decode_message(message_name, bytes)).collect()
+    }
+
     /// Decode a single message and return (bytes_consumed, result). Used by frame decoder to skip non-compliant messages.
     /// Decodes the full message first (to get byte extent), then validates; so on validation error we still return correct consumed.
     pub fn decode_message_with_extent(
@@ -82,22 +405,348 @@ impl Codec {
         message_name: &str,
         bytes: &[u8],
     ) -> (usize, Result<HashMap<String, Value>, CodecError>) {
+        self.decode_message_recording::<Value>(message_name, bytes, None, None, None, None)
+    }
+
+    /// Decode a single message starting in byte order `endianness` instead of the codec's, e.g. the one
+    /// [`endianness_for`](Self::endianness_for) picks from the transport header. An `endianness from` field of
+    /// the message still switches the order for the fields after it.
+    pub fn decode_message_as(
+        &self,
+        message_name: &str,
+        bytes: &[u8],
+        endianness: Endianness,
+    ) -> Result<HashMap<String, Value>, CodecError> {
+        self.decode_message_with_extent_as(message_name, bytes, Some(endianness)).1
+    }
+
+    /// [`decode_message_with_extent`](Self::decode_message_with_extent) starting in `endianness` (None: the codec's).
+    pub(crate) fn decode_message_with_extent_as(
+        &self,
+        message_name: &str,
+        bytes: &[u8],
+        endianness: Option<Endianness>,
+    ) -> (usize, Result<HashMap<String, Value>, CodecError>) {
+        self.decode_message_recording::<Value>(message_name, bytes, None, None, None, endianness)
+    }
+
+    /// Decode a single message, letting `field_override` take over individual message fields (values computed
+    /// from external state, context-dependent layouts). Before each field it is called with the field name and
+    /// the cursor at the field's first byte: `Some(result)` supplies the field (the callback advances the cursor
+    /// past it), `None` leaves the cursor alone and the field is decoded normally. Overridden values are
+    /// validated, referenced by later fields and checked like decoded ones. Fields of nested structs, fields
+    /// skipped by their condition and fields starting inside a bit group are not offered.
+    pub fn decode_message_with<F>(
+        &self,
+        message_name: &str,
+        bytes: &[u8],
+        mut field_override: F,
+    ) -> Result<HashMap<String, Value>, CodecError>
+    where
+        F: FnMut(&str, &mut Cursor<&[u8]>) -> Option<Result<Value, CodecError>>,
+    {
+        self.decode_message_recording::<Value>(message_name, bytes, None, Some(&mut field_override), None, None).1
+    }
+
+    /// Decode a single message without copying its variable-length data: like [`decode_message`](Self::decode_message),
+    /// but `remaining` and `octets_fx` bytes are slices of `bytes` and field names borrow from the protocol.
+    /// [`ValueRef::to_owned`] turns a value into a [`Value`] that outlives the input.
+    pub fn decode_message_ref<'a>(
+        &'a self,
+        message_name: &str,
+        bytes: &'a [u8],
+    ) -> Result<HashMap<&'a str, ValueRef<'a>>, CodecError> {
+        self.decode_message_recording(message_name, bytes, None, None, None, None).1
+    }
+
+    /// Decode a single message read from `reader` (e.g. a socket), pulling bytes as the decoder needs them:
+    /// the message is decoded from the bytes read so far and, while that stops at their end, the missing bytes
+    /// are read and decoding starts over. Only the message's bytes are taken from `reader`, so the next
+    /// message can be read from it; wrap an unbuffered reader in a `BufReader`. A top-level `remaining` field
+    /// reads to the end of the stream. Fails with [`CodecError::Truncated`] when the stream ends inside the message.
+    pub fn decode_message_from<R: Read>(&self, message_name: &str, reader: &mut R) -> Result<HashMap<String, Value>, CodecError> {
+        let mut buf = Vec::new();
+        let to_end = self
+            .resolved
+            .get_message(message_name)
+            .is_some_and(|m| m.fields.iter().any(|f| matches!(f.type_spec, TypeSpec::Remaining)));
+        if to_end {
+            reader.read_to_end(&mut buf)?;
+        }
+        loop {
+            match self.decode_message(message_name, &buf) {
+                Err(CodecError::Truncated { field, needed, available, offset }) if !to_end => {
+                    let start = buf.len();
+                    buf.resize(start + needed.saturating_sub(available).max(1), 0);
+                    if let Err(e) = reader.read_exact(&mut buf[start..]) {
+                        return Err(match e.kind() {
+                            std::io::ErrorKind::UnexpectedEof => CodecError::Truncated { field, needed, available, offset },
+                            _ => CodecError::Io(e),
+                        });
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Decode a self-delimiting record: read `length_field` (a field of the message giving the record's total
+    /// size in bytes, counted from its first byte), then decode the message from exactly that many bytes.
+    /// Returns the values and the record length. Fails with [`CodecError::Truncated`] when `bytes` is shorter
+    /// than the record, and with a validation error when the message needs more or fewer bytes than stated.
+    pub fn decode_length_prefixed_message(
+        &self,
+        message_name: &str,
+        bytes: &[u8],
+        length_field: &str,
+    ) -> Result<(HashMap<String, Value>, usize), CodecError> {
+        let msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
+        let idx = msg
+            .fields
+            .iter()
+            .position(|f| f.name == length_field)
+            .ok_or_else(|| CodecError::UnknownField(format!("{}.{}", message_name, length_field)))?;
+        let mut cursor = Cursor::new(bytes);
+        let prefix = self.decode_message_fields_no_validate::<Value>(&mut cursor, message_name, &msg.fields[..=idx], &mut DecodeContext::default(), None, None)?;
+        let len = prefix.get(length_field).and_then(Value::as_u64).ok_or_else(|| {
+            CodecError::Validation(format!("{}.{}: length is not an unsigned integer", message_name, length_field))
+        })? as usize;
+        if len > bytes.len() {
+            return Err(CodecError::Truncated { field: length_field.to_string(), needed: len, available: bytes.len(), offset: 0 });
+        }
+        let (consumed, values) = self.decode_message_with_extent(message_name, &bytes[..len]);
+        let values = values.map_err(|e| match e {
+            CodecError::Truncated { .. } => CodecError::Validation(format!(
+                "{}: record overruns its {} = {} bytes ({})",
+                message_name, length_field, len, e
+            )),
+            e => e,
+        })?;
+        if consumed != len {
+            return Err(CodecError::Validation(format!(
+                "{}: {} = {} but the record decodes to {} bytes",
+                message_name, length_field, len, consumed
+            )));
+        }
+        Ok((values, len))
+    }
+
+    /// Decode a single message and report, next to the values, whether each of its optional fields was present
+    /// (its presence bit or flag was set). Unlike testing for an empty `Value::List`, this tells an absent
+    /// optional from a present one whose value is an empty list. Optionals of nested structs are not listed.
+    pub fn decode_message_with_presence(
+        &self,
+        message_name: &str,
+        bytes: &[u8],
+    ) -> Result<(HashMap<String, Value>, FieldPresence), CodecError> {
+        let mut presence = HashMap::new();
+        let values = self.decode_message_recording::<Value>(message_name, bytes, None, None, Some(&mut presence), None).1?;
+        Ok((values, presence))
+    }
+
+    /// Decode a single message and pair each decoded field with the byte range it was read from,
+    /// e.g. to link a value tree to a hex view. Returns the values and the bytes consumed; constraints
+    /// are validated as in [`decode_message`](Self::decode_message). A bit-packed field reports the
+    /// range of the byte(s) holding its bits.
+    pub fn decode_message_with_offsets(
+        &self,
+        message_name: &str,
+        bytes: &[u8],
+    ) -> Result<(ValuesWithOffsets, usize), CodecError> {
+        let mut offsets = HashMap::new();
+        let (consumed, values) = self.decode_message_recording::<Value>(message_name, bytes, Some(&mut offsets), None, None, None);
+        let with_offsets = values?
+            .into_iter()
+            .map(|(name, v)| {
+                let range = offsets.remove(&name).unwrap_or(0..0);
+                (name, (v, range))
+            })
+            .collect();
+        Ok((with_offsets, consumed))
+    }
+
+    /// Decode a single message and return, next to the values, the `(start, end)` byte offsets of each
+    /// top-level field, e.g. to highlight fields in a packet view. Same spans as
+    /// [`decode_message_with_offsets`](Self::decode_message_with_offsets), kept in a map of their own; fields
+    /// of nested structs and list elements are covered by the span of their top-level field.
+    pub fn decode_message_spanned(
+        &self,
+        message_name: &str,
+        bytes: &[u8],
+    ) -> Result<(HashMap<String, Value>, FieldSpans), CodecError> {
+        let mut offsets = HashMap::new();
+        let values = self.decode_message_recording::<Value>(message_name, bytes, Some(&mut offsets), None, None, None).1?;
+        let spans = offsets.into_iter().map(|(name, r)| (name, (r.start, r.end))).collect();
+        Ok((values, spans))
+    }
+
+    /// Decode a single message into a JSON object of field name to value, in the tagged form of
+    /// [`Value`]'s serde representation (e.g. `{"id": {"U16": 4660}}`).
+    #[cfg(feature = "serde")]
+    pub fn decode_message_to_json(&self, message_name: &str, bytes: &[u8]) -> Result<serde_json::Value, CodecError> {
+        let values = self.decode_message(message_name, bytes)?;
+        serde_json::to_value(values).map_err(|e| CodecError::Validation(format!("JSON: {}", e)))
+    }
+
+    /// Decode a message and check every constrained field, collecting each failure with its field name
+    /// instead of stopping at the first (e.g. for conformance reports on captured frames). Empty when the
+    /// message is valid. A failed `check { ... }` is reported under the message name, as is an error that
+    /// stops the decode itself (truncation, unknown variant, a constraint inside a nested struct).
+    pub fn validate_all(&self, message_name: &str, bytes: &[u8]) -> Vec<(String, CodecError)> {
+        let mut messages = Vec::new();
+        let mut values = HashMap::new();
+        let mut name = message_name;
+        let mut rest = bytes;
+        let mut endianness = None;
+        loop {
+            let Some(msg) = self.resolved.get_message(name) else {
+                return vec![(message_name.to_string(), CodecError::UnknownStruct(name.to_string()))];
+            };
+            let mut cursor = Cursor::new(rest);
+            let mut ctx = DecodeContext { endianness, ..DecodeContext::default() };
+            match self.decode_message_fields_no_validate::<Value>(&mut cursor, name, msg.fields.as_slice(), &mut ctx, None, None) {
+                Ok(v) => values.extend(v),
+                Err(e) => return vec![(message_name.to_string(), offset_by(e, bytes.len() - rest.len()))],
+            }
+            if let Err(e) = self.check_total_bytes(bytes.len() - rest.len() + cursor.position() as usize) {
+                return vec![(message_name.to_string(), e)];
+            }
+            messages.push(msg);
+            let Some(sel) = &msg.selector else { break };
+            // Body selector: the variant continues the body, as in decode_message.
+            let value_of = |name: &str| values.get(name).and_then(Value::as_i64);
+            let Some(variant) = sel.select(value_of) else {
+                return vec![(message_name.to_string(), CodecError::Validation(sel.no_variant_text(name, value_of)))];
+            };
+            rest = &rest[cursor.position() as usize..];
+            endianness = ctx.endianness;
+            name = variant;
+        }
+        let mut failures = Vec::new();
+        for msg in &messages {
+            for f in &msg.fields {
+                let Some(c) = f.active_constraint(|name| values.get(name).and_then(Value::as_i64)) else { continue };
+                if let Some(Err(e)) = values.get(&f.name).map(|v| self.validate_constraint(v, Some(c))) {
+                    failures.push((f.name.clone(), e));
+                }
+            }
+        }
+        for msg in &messages {
+            if let Some(e) = msg.failed_check(|name| values.get(name).and_then(Decoded::check_value)) {
+                failures.push((msg.name.clone(), CodecError::Validation(e)));
+            }
+        }
+        failures
+    }
+
+    fn decode_message_recording<'a, V: Decoded<'a>>(
+        &'a self,
+        message_name: &str,
+        bytes: &'a [u8],
+        offsets: Option<&mut HashMap<String, Range<usize>>>,
+        field_override: Option<&mut FieldOverride>,
+        presence: Option<&mut HashMap<String, bool>>,
+        endianness: Option<Endianness>,
+    ) -> (usize, Result<HashMap<V::Key, V>, CodecError>) {
+        let (consumed, result) = self.decode_message_body(message_name, message_name, bytes, offsets, field_override, presence, endianness);
+        match self.check_total_bytes(consumed) {
+            Ok(()) => (consumed, result),
+            Err(e) => (consumed, Err(e)),
+        }
+    }
+
+    /// [`DecodeLimits::max_total_bytes`] check on the extent of a decoded message.
+    fn check_total_bytes(&self, extent: usize) -> Result<(), CodecError> {
+        if extent > self.limits.max_total_bytes {
+            return Err(CodecError::Validation(format!(
+                "message of {} bytes exceeds max_total_bytes {}",
+                extent, self.limits.max_total_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    /// [`DecodeLimits::max_list_len`] check on an element count read before allocating the list.
+    fn check_list_len(&self, n: u64) -> Result<(), CodecError> {
+        if n > self.limits.max_list_len {
+            return Err(CodecError::Validation(format!("list length {} exceeds max_list_len {}", n, self.limits.max_list_len)));
+        }
+        Ok(())
+    }
+
+    /// [`decode_message_recording`](Self::decode_message_recording) for `message_name`, decoded as part of
+    /// `top`: field errors read `message {top}: field pos: Plot.rho: ...`, so a body selector variant's
+    /// errors name the record's message.
+    #[allow(clippy::too_many_arguments)]
+    fn decode_message_body<'a, V: Decoded<'a>>(
+        &'a self,
+        top: &str,
+        message_name: &str,
+        bytes: &'a [u8],
+        mut offsets: Option<&mut HashMap<String, Range<usize>>>,
+        mut field_override: Option<&mut FieldOverride>,
+        mut presence: Option<&mut HashMap<String, bool>>,
+        endianness: Option<Endianness>,
+    ) -> (usize, Result<HashMap<V::Key, V>, CodecError>) {
         let msg = match self.resolved.get_message(message_name) {
             Some(m) => m,
+            None if message_name == RAW_RECORD => {
+                if let Some(offsets) = offsets {
+                    offsets.insert(RAW_RECORD_FIELD.to_string(), 0..bytes.len());
+                }
+                let values = HashMap::from([(V::key(RAW_RECORD_FIELD), V::bytes(bytes))]);
+                return (bytes.len(), Ok(values));
+            }
             None => return (0, Err(CodecError::UnknownStruct(message_name.to_string()))),
         };
         let mut cursor = Cursor::new(bytes);
-        let mut ctx = DecodeContext::default();
-        let values = match self.decode_message_fields_no_validate(&mut cursor, message_name, msg.fields.as_slice(), &mut ctx) {
+        let mut ctx = DecodeContext { endianness, presence: presence.is_some().then(HashMap::new), ..DecodeContext::default() };
+        let decoded = self.decode_message_fields_no_validate(&mut cursor, message_name, msg.fields.as_slice(), &mut ctx, offsets.as_deref_mut(), field_override.as_deref_mut());
+        if let (Some(out), Some(seen)) = (presence.as_deref_mut(), ctx.presence.take()) {
+            out.extend(seen);
+        }
+        let mut values = match decoded {
             Ok(v) => v,
+            Err(CodecError::Validation(e)) => return (cursor.position() as usize, Err(CodecError::Validation(format!("message {}: {}", top, e)))),
             Err(e) => return (cursor.position() as usize, Err(e)),
         };
-        let consumed = cursor.position() as usize;
+        let mut consumed = cursor.position() as usize;
+        if let Some(sel) = &msg.selector {
+            // Body selector: the variant message continues the body; its fields join this message's values.
+            let value_of = |name: &str| values.get(name).and_then(V::as_i64);
+            let Some(variant) = sel.select(value_of) else {
+                return (consumed, Err(CodecError::Validation(sel.no_variant_text(message_name, value_of))));
+            };
+            let mut tail_offsets = offsets.is_some().then(HashMap::new);
+            let (n, tail) = self.decode_message_body::<V>(top, variant, &bytes[consumed..], tail_offsets.as_mut(), field_override, presence, ctx.endianness);
+            if let (Some(offsets), Some(tail_offsets)) = (offsets, tail_offsets) {
+                offsets.extend(tail_offsets.into_iter().map(|(name, r)| (name, r.start + consumed..r.end + consumed)));
+            }
+            let base = consumed;
+            consumed += n;
+            match tail {
+                Ok(tail) => values.extend(tail),
+                Err(e) => return (consumed, Err(offset_by(e, base))),
+            }
+        }
         for f in &msg.fields {
-            if let Some(ref c) = f.constraint {
-                if let Some(v) = values.get(&f.name) {
+            if let Some(c) = f.active_constraint(|name| values.get(name).and_then(V::as_i64)) {
+                if let Some(v) = values.get(f.name.as_str()) {
                     if let Err(e) = self.validate_constraint(v, Some(c)) {
-                        return (consumed, Err(e));
+                        return (consumed, Err(in_field(e, format_args!("message {}: field {}", top, f.name))));
+                    }
+                }
+            }
+        }
+        if let Some(e) = msg.failed_check(|name| values.get(name).and_then(V::check_value)) {
+            return (consumed, Err(CodecError::Validation(e)));
+        }
+        if self.quantum_scaling {
+            for f in &msg.fields {
+                let Some(scale) = quantum_scale(&f.type_spec, f.quantum.as_deref()) else { continue };
+                if let Some(v) = values.get_mut(f.name.as_str()) {
+                    if let Some(raw) = v.as_i64() {
+                        *v = V::owned(Value::Double(raw as f64 * scale));
                     }
                 }
             }
@@ -106,21 +755,179 @@ impl Codec {
     }
 
     /// Encode a single message by name. Padding/reserved are written as zero.
+    /// The built-in [`RAW_RECORD`] message writes its raw bytes back verbatim.
     pub fn encode_message(
         &self,
         message_name: &str,
         values: &HashMap<String, Value>,
     ) -> Result<Vec<u8>, CodecError> {
-        let msg = self
-            .resolved
-            .get_message(message_name)
-            .ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
         let mut out = Vec::new();
-        let mut ctx = EncodeContext::from_values(values);
-        self.encode_message_fields(&mut out, msg.fields.as_slice(), &mut ctx)?;
+        self.encode_message_into(message_name, values, &mut out)?;
         Ok(out)
     }
 
+    /// Encode a single message from a JSON object in the form produced by
+    /// [`decode_message_to_json`](Self::decode_message_to_json). JSON that is not an object of tagged
+    /// values is a [`CodecError::Validation`].
+    #[cfg(feature = "serde")]
+    pub fn encode_message_from_json(&self, message_name: &str, json: &serde_json::Value) -> Result<Vec<u8>, CodecError> {
+        let values = <HashMap<String, Value> as serde::Deserialize>::deserialize(json)
+            .map_err(|e| CodecError::Validation(format!("JSON: {}", e)))?;
+        self.encode_message(message_name, &values)
+    }
+
+    /// Like [`encode_message`](Self::encode_message) but appends to `out`, so a caller encoding many records
+    /// can reuse one buffer (`out.clear()` between records) instead of allocating per record.
+    /// On error, `out` may hold a partial record after its original contents.
+    pub fn encode_message_into(
+        &self,
+        message_name: &str,
+        values: &HashMap<String, Value>,
+        out: &mut Vec<u8>,
+    ) -> Result<(), CodecError> {
+        self.encode_message_in(message_name, values, out, None)
+    }
+
+    /// Encode a single message starting in byte order `endianness` instead of the codec's (see
+    /// [`decode_message_as`](Self::decode_message_as)).
+    pub fn encode_message_as(
+        &self,
+        message_name: &str,
+        values: &HashMap<String, Value>,
+        endianness: Endianness,
+    ) -> Result<Vec<u8>, CodecError> {
+        let mut out = Vec::new();
+        self.encode_message_in(message_name, values, &mut out, Some(endianness))?;
+        Ok(out)
+    }
+
+    /// Encode a single message with exactly the optional fields named in `present` present: their presence
+    /// bits (or flags) are set and every other optional is written absent, whatever `values` holds. A present
+    /// field takes its value from `values` (bare or in a one-element list), or the type's default when
+    /// `values` has none. Names that are not optional fields of the message (or of its body variants) are a
+    /// [`CodecError::UnknownField`].
+    pub fn encode_message_present(
+        &self,
+        message_name: &str,
+        values: &HashMap<String, Value>,
+        present: &[&str],
+    ) -> Result<Vec<u8>, CodecError> {
+        let msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
+        let variants = msg
+            .selector
+            .iter()
+            .flat_map(|sel| sel.value_to_message.iter().map(|(_, name, _)| name).chain(sel.default_message.iter()))
+            .filter_map(|name| self.resolved.get_message(name));
+        let optionals: Vec<(&str, &TypeSpec)> = std::iter::once(msg)
+            .chain(variants)
+            .flat_map(|m| m.fields.iter())
+            .filter_map(|f| match &f.type_spec {
+                TypeSpec::Optional(elem, _) => Some((f.name.as_str(), elem.as_ref())),
+                _ => None,
+            })
+            .collect();
+        if let Some(name) = present.iter().find(|name| !optionals.iter().any(|(o, _)| o == *name)) {
+            return Err(CodecError::UnknownField(format!("{}.{}: not an optional field", message_name, name)));
+        }
+        let mut with_presence = values.clone();
+        for (name, elem) in optionals {
+            let v = if !present.contains(&name) {
+                Value::List(vec![])
+            } else {
+                let list_elem = matches!(
+                    elem,
+                    TypeSpec::List(_) | TypeSpec::RepList(_) | TypeSpec::LengthPrefixedList(..) | TypeSpec::BytesList(..) | TypeSpec::Array(..)
+                );
+                match values.get(name) {
+                    Some(Value::List(l)) if l.len() == 1 && (!list_elem || matches!(l[0], Value::List(_))) => Value::List(l.clone()),
+                    Some(Value::List(l)) if l.is_empty() && !list_elem => Value::List(vec![self.default_for_type_spec(elem)]),
+                    Some(v) => Value::List(vec![v.clone()]),
+                    None => Value::List(vec![self.default_for_type_spec(elem)]),
+                }
+            };
+            with_presence.insert(name.to_string(), v);
+        }
+        self.encode_message(message_name, &with_presence)
+    }
+
+    /// Append `message_name` to `out`, starting in `endianness` (None: the codec's).
+    pub(crate) fn encode_message_in(
+        &self,
+        message_name: &str,
+        values: &HashMap<String, Value>,
+        out: &mut Vec<u8>,
+        endianness: Option<Endianness>,
+    ) -> Result<(), CodecError> {
+        let msg = match self.resolved.get_message(message_name) {
+            Some(m) => m,
+            None if message_name == RAW_RECORD => {
+                return match values.get(RAW_RECORD_FIELD) {
+                    Some(Value::Bytes(b)) => {
+                        out.extend_from_slice(b);
+                        Ok(())
+                    }
+                    _ => Err(CodecError::UnknownField(format!("{}.{}", RAW_RECORD, RAW_RECORD_FIELD))),
+                };
+            }
+            None => return Err(CodecError::UnknownStruct(message_name.to_string())),
+        };
+        let raw_values;
+        let values = match self.quantum_scaling.then(|| self.unscaled_values(msg, values)).flatten() {
+            Some(raw) => {
+                raw_values = raw;
+                &raw_values
+            }
+            None => values,
+        };
+        if let Some(e) = msg.failed_check(|name| values.get(name).and_then(Decoded::check_value)) {
+            return Err(CodecError::Validation(e));
+        }
+        let mut ctx = EncodeContext { endianness, ..EncodeContext::from_values(values) };
+        self.encode_message_fields(out, msg.fields.as_slice(), &mut ctx)?;
+        match &msg.selector {
+            Some(sel) => {
+                let value_of = |name: &str| values.get(name).and_then(Value::as_i64);
+                let variant = sel
+                    .select(value_of)
+                    .ok_or_else(|| CodecError::Validation(sel.no_variant_text(message_name, value_of)))?;
+                self.encode_message_in(variant, values, out, ctx.endianness)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// `values` with the physical (`Value::Double` / `Value::Float`) values of `msg`'s quantum fields turned
+    /// back into raw integers (see [`quantum_scaling`](Self::quantum_scaling)); None when there are none.
+    fn unscaled_values(&self, msg: &MessageSection, values: &HashMap<String, Value>) -> Option<HashMap<String, Value>> {
+        let mut raw_values: Option<HashMap<String, Value>> = None;
+        for f in &msg.fields {
+            let Some(scale) = quantum_scale(&f.type_spec, f.quantum.as_deref()) else { continue };
+            let physical = match values.get(&f.name) {
+                Some(Value::Double(x)) => *x,
+                Some(Value::Float(x)) => f64::from(*x),
+                _ => continue,
+            };
+            let raw = (physical / scale).round();
+            let signed = matches!(&f.type_spec, TypeSpec::Base(bt) | TypeSpec::SizedInt(bt, _)
+                if matches!(bt, BaseType::I8 | BaseType::I16 | BaseType::I32 | BaseType::I64));
+            let raw = if signed { Value::I64(raw as i64) } else { Value::U64(raw as u64) };
+            raw_values.get_or_insert_with(|| values.clone()).insert(f.name.clone(), raw);
+        }
+        raw_values
+    }
+
+    /// Encoded size in bytes of one value of `spec`, encoded on its own (a trailing partial bit byte counts).
+    pub(crate) fn encoded_type_len(&self, spec: &TypeSpec, v: &Value) -> Result<usize, CodecError> {
+        let mut out = Vec::new();
+        let empty = HashMap::new();
+        let mut ctx = EncodeContext::from_values(&empty);
+        self.encode_type_spec(&mut out, spec, v, &self.resolved.protocol.structs, &mut ctx)?;
+        if ctx.bit_write.next_bit != 0 {
+            out.push(ctx.bit_write.cur);
+        }
+        Ok(out.len())
+    }
+
     /// Decode transport header (if defined).
     pub fn decode_transport(&self, bytes: &[u8]) -> Result<HashMap<String, Value>, CodecError> {
         let transport = match &self.resolved.protocol.transport {
@@ -137,13 +944,117 @@ impl Codec {
         &self,
         values: &HashMap<String, Value>,
     ) -> Result<Vec<u8>, CodecError> {
+        let mut out = Vec::new();
+        self.encode_transport_into(&mut out, values)?;
+        Ok(out)
+    }
+
+    /// Append the encoded transport header to `out` (padding/reserved zeroed). Returns the header length in bytes (0 if no transport).
+    pub fn encode_transport_into(
+        &self,
+        out: &mut Vec<u8>,
+        values: &HashMap<String, Value>,
+    ) -> Result<usize, CodecError> {
         let transport = match &self.resolved.protocol.transport {
+            Some(t) => t,
+            None => return Ok(0),
+        };
+        let start = out.len();
+        let mut ctx = EncodeContext::from_values(values);
+        self.encode_transport_fields(out, &transport.fields, &mut ctx)?;
+        Ok(out.len() - start)
+    }
+
+    /// Decode the per-record `inner_transport` header (if defined) and return (bytes_consumed, result).
+    /// Without an inner transport, returns (0, Ok(empty map)).
+    pub fn decode_inner_transport_with_extent(
+        &self,
+        bytes: &[u8],
+    ) -> (usize, Result<HashMap<String, Value>, CodecError>) {
+        let inner = match &self.resolved.protocol.inner_transport {
+            Some(t) => t,
+            None => return (0, Ok(HashMap::new())),
+        };
+        let mut cursor = Cursor::new(bytes);
+        let mut ctx = DecodeContext::default();
+        let result = self.decode_transport_fields(&mut cursor, &inner.fields, &mut ctx);
+        (cursor.position() as usize, result)
+    }
+
+    /// Encode the per-record `inner_transport` header (padding/reserved zeroed). Empty if not defined.
+    pub fn encode_inner_transport(
+        &self,
+        values: &HashMap<String, Value>,
+    ) -> Result<Vec<u8>, CodecError> {
+        let inner = match &self.resolved.protocol.inner_transport {
             Some(t) => t,
             None => return Ok(Vec::new()),
         };
         let mut out = Vec::new();
         let mut ctx = EncodeContext::from_values(values);
-        self.encode_transport_fields(&mut out, &transport.fields, &mut ctx)?;
+        self.encode_transport_fields(&mut out, &inner.fields, &mut ctx)?;
+        Ok(out)
+    }
+
+    /// Encode one on-wire record: transport header followed by the message.
+    /// The transport's `length_of` / `count_of` fields ([`TransportSize`]) are set from the encoded record
+    /// (`length_of(record)`: header + message, as in ASTERIX data blocks); values given for them in
+    /// `transport_values` are ignored.
+    /// The message is encoded in the byte order the transport selects through `endianness from`, if any.
+    pub fn encode_record(
+        &self,
+        transport_values: &HashMap<String, Value>,
+        message_name: &str,
+        message_values: &HashMap<String, Value>,
+    ) -> Result<Vec<u8>, CodecError> {
+        let mut out = Vec::new();
+        let header_len = self.encode_transport_into(&mut out, transport_values)?;
+        let order = self.endianness_for(transport_values)?;
+        self.encode_message_in(message_name, message_values, &mut out, order)?;
+        let sized: Vec<_> = self
+            .resolved
+            .protocol
+            .transport
+            .iter()
+            .flat_map(|t| &t.fields)
+            .filter_map(|f| Some((f, f.size_of.as_ref()?)))
+            .collect();
+        if sized.is_empty() {
+            return Ok(out);
+        }
+        // Field lengths and counts are measured as `auto_length` measures a message's own size fields.
+        let msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
+        let size_specs: Vec<_> = sized
+            .iter()
+            .filter_map(|(f, size)| {
+                let TransportTypeSpec::Base(width) = &f.type_spec else { return None };
+                match size {
+                    TransportSize::LengthOf(target) => Some((f.name.as_str(), TypeSpec::LengthOf(target.clone(), width.clone()))),
+                    TransportSize::CountOf(target) => Some((f.name.as_str(), TypeSpec::CountOf(target.clone(), width.clone()))),
+                    TransportSize::Record | TransportSize::Message => None,
+                }
+            })
+            .collect();
+        let mut ctx = EncodeContext::from_values(message_values);
+        let fields = msg.fields.iter().map(|f| (f.name.as_str(), &f.type_spec, f.condition.as_ref()));
+        self.fill_auto_lengths(fields.chain(size_specs.iter().map(|(name, spec)| (*name, spec, None))), &mut ctx)?;
+        let mut values = transport_values.clone();
+        for (f, size) in sized {
+            let TransportTypeSpec::Base(width) = &f.type_spec else { continue };
+            let v = match size {
+                TransportSize::Record => auto_length_value(&f.name, out.len(), width)?,
+                TransportSize::Message => auto_length_value(&f.name, out.len() - header_len, width)?,
+                TransportSize::LengthOf(target) | TransportSize::CountOf(target) => ctx
+                    .computed
+                    .get(&f.name)
+                    .cloned()
+                    .ok_or_else(|| CodecError::Validation(format!("transport {}: no size computed for {}", f.name, target)))?,
+            };
+            values.insert(f.name.clone(), v);
+        }
+        let mut header = Vec::with_capacity(header_len);
+        self.encode_transport_into(&mut header, &values)?;
+        out[..header_len].copy_from_slice(&header);
         Ok(out)
     }
 
@@ -151,12 +1062,15 @@ impl Codec {
         &self,
         r: &mut Cursor<&[u8]>,
         fields: &[TransportField],
-        _ctx: &mut DecodeContext,
+        ctx: &mut DecodeContext,
     ) -> Result<HashMap<String, Value>, CodecError> {
         let mut out = HashMap::new();
         for f in fields {
-            let v = self.decode_transport_type(r, &f.type_spec)?;
+            let v = self.decode_transport_type(r, &f.type_spec, ctx.endianness.unwrap_or(self.endianness))?;
             self.validate_constraint(&v, f.constraint.as_ref())?;
+            if let Some(order) = self.switched_endianness(&f.name, v.as_i64())? {
+                ctx.endianness = Some(order);
+            }
             out.insert(f.name.clone(), v);
         }
         Ok(out)
@@ -166,29 +1080,36 @@ impl Codec {
         &self,
         r: &mut Cursor<&[u8]>,
         spec: &TransportTypeSpec,
+        order: Endianness,
     ) -> Result<Value, CodecError> {
         match spec {
-            TransportTypeSpec::Base(bt) => self.decode_base(r, bt),
-            TransportTypeSpec::SizedInt(bt, n) => self.decode_sized_int(r, bt, *n),
+            TransportTypeSpec::Base(bt) => self.decode_base(r, bt, order),
+            TransportTypeSpec::SizedInt(bt, n) => self.decode_sized_int(r, bt, *n, order),
             TransportTypeSpec::Padding(kind) => {
                 let bytes = match kind {
                     PaddingKind::Bytes(n) => *n as usize,
                     PaddingKind::Bits(n) => ((*n as usize) + 7) / 8,
                 };
                 let mut buf = vec![0u8; bytes];
+                need(r, buf.len())?;
                 r.read_exact(&mut buf)?;
                 Ok(Value::Padding)
             }
             TransportTypeSpec::Bitfield(n) => {
                 let bits = (*n + 7) / 8;
                 let mut buf = vec![0u8; bits as usize];
+                need(r, buf.len())?;
                 r.read_exact(&mut buf)?;
-                let v = self.bytes_to_u64(&buf);
+                let v = self.bytes_to_u64(&buf, order);
                 Ok(Value::U64(v))
             }
             TransportTypeSpec::Magic(expected) => {
                 let mut buf = vec![0u8; expected.len()];
+                need(r, buf.len())?;
                 r.read_exact(&mut buf)?;
+                if buf != *expected {
+                    return Err(CodecError::Validation(format!("magic mismatch: expected {:02X?} got {:02X?}", expected, buf)));
+                }
                 Ok(Value::Bytes(buf))
             }
         }
@@ -201,8 +1122,15 @@ impl Codec {
         ctx: &mut EncodeContext,
     ) -> Result<(), CodecError> {
         for f in fields {
-            let v = ctx.get(&f.name).cloned().unwrap_or(Value::Padding);
-            self.encode_transport_type(w, &f.type_spec, &v)?;
+            let v = match (ctx.get(&f.name), &f.default) {
+                (Some(v), _) => v.clone(),
+                (None, Some(d)) => transport_literal_value(&f.type_spec, d),
+                (None, None) => Value::Padding,
+            };
+            self.encode_transport_type(w, &f.type_spec, &v, ctx.endianness.unwrap_or(self.endianness))?;
+            if let Some(order) = self.switched_endianness(&f.name, v.as_i64())? {
+                ctx.endianness = Some(order);
+            }
         }
         Ok(())
     }
@@ -212,10 +1140,11 @@ impl Codec {
         w: &mut Vec<u8>,
         spec: &TransportTypeSpec,
         v: &Value,
+        order: Endianness,
     ) -> Result<(), CodecError> {
         match spec {
-            TransportTypeSpec::Base(bt) => self.encode_base(w, bt, v),
-            TransportTypeSpec::SizedInt(bt, n) => self.encode_sized_int(w, bt, *n, v),
+            TransportTypeSpec::Base(bt) => self.encode_base(w, bt, v, order),
+            TransportTypeSpec::SizedInt(bt, n) => self.encode_sized_int(w, bt, *n, v, order),
             TransportTypeSpec::Padding(kind) => {
                 let bytes = match kind {
                     PaddingKind::Bytes(n) => *n as usize,
@@ -227,7 +1156,7 @@ impl Codec {
             TransportTypeSpec::Bitfield(n) => {
                 let bits = (*n + 7) / 8;
                 let val = v.as_u64().unwrap_or(0);
-                let buf = self.u64_to_bytes(val, bits as usize);
+                let buf = self.u64_to_bytes(val, bits as usize, order);
                 w.write_all(&buf)?;
                 Ok(())
             }
@@ -246,17 +1175,22 @@ impl Codec {
         }
     }
 
-    fn decode_message_fields_no_validate(
-        &self,
-        r: &mut Cursor<&[u8]>,
+    fn decode_message_fields_no_validate<'a, V: Decoded<'a>>(
+        &'a self,
+        r: &mut Cursor<&'a [u8]>,
         message_name: &str,
-        fields: &[MessageField],
+        fields: &'a [MessageField],
         ctx: &mut DecodeContext,
-    ) -> Result<HashMap<String, Value>, CodecError> {
+        mut offsets: Option<&mut HashMap<String, Range<usize>>>,
+        mut field_override: Option<&mut FieldOverride>,
+    ) -> Result<HashMap<V::Key, V>, CodecError> {
         // Bit packing is local to a message: reset bit cursor for this scope.
         let saved_bits = ctx.bit_read;
         ctx.bit_read = BitReadState::default();
         ctx.current_message_name = Some(message_name.to_string());
+        let saved_start = std::mem::replace(&mut ctx.message_start, r.position() as usize);
+        let saved_field_starts = std::mem::take(&mut ctx.field_starts);
+        let checksum_from = checksum_from_fields(fields);
         let mut out = HashMap::new();
         for f in fields {
             if let Some(ref cond) = f.condition {
@@ -267,15 +1201,37 @@ impl Codec {
                 }
             }
             ctx.current_field_name = Some(f.name.clone());
-            let v = self
-                .decode_type_spec(r, &f.type_spec, &self.resolved.protocol.structs, ctx)
-                .map_err(|e| CodecError::Validation(format!("field {}: {}", f.name, e)))?;
-            ctx.set(f.name.clone(), v.clone());
-            out.insert(f.name.clone(), v);
+            // A partially consumed bit byte was already read: the field starts in that byte.
+            let start = r.position() as usize - usize::from(!ctx.bit_read.is_aligned());
+            if checksum_from.contains(&f.name.as_str()) {
+                ctx.field_starts.insert(f.name.clone(), start);
+            }
+            let overridden = match field_override.as_deref_mut() {
+                Some(hook) if ctx.bit_read.is_aligned() => hook(&f.name, r),
+                _ => None,
+            };
+            let v: V = match overridden {
+                Some(v) => V::owned(v.map_err(|e| in_named_field(e, &f.name, format_args!("field {}", f.name)))?),
+                None => self
+                    .decode_type_spec(r, &f.type_spec, &self.resolved.protocol.structs, ctx)
+                    .map_err(|e| in_named_field(e, &f.name, format_args!("field {}", f.name)))?,
+            };
+            if let Some(offsets) = offsets.as_deref_mut() {
+                offsets.insert(f.name.clone(), start..r.position() as usize);
+            }
+            if let Some(order) = self.switched_endianness(&f.name, v.as_i64())? {
+                ctx.endianness = Some(order);
+            }
+            if let Some(c) = v.context_value() {
+                ctx.set(f.name.clone(), c);
+            }
+            out.insert(V::key(&f.name), v);
         }
         ctx.current_message_name = None;
         ctx.current_field_name = None;
         ctx.bit_read = saved_bits;
+        ctx.message_start = saved_start;
+        ctx.field_starts = saved_field_starts;
         Ok(out)
     }
 
@@ -285,9 +1241,19 @@ impl Codec {
         fields: &[MessageField],
         ctx: &mut EncodeContext,
     ) -> Result<(), CodecError> {
+        for f in fields {
+            ctx.fill_default(&f.name, &f.type_spec, f.default.as_ref());
+            ctx.fill_dyn_int_width(&f.name, &f.type_spec);
+        }
+        if self.encode_options.auto_length {
+            self.fill_auto_lengths(fields.iter().map(|f| (f.name.as_str(), &f.type_spec, f.condition.as_ref())), ctx)?;
+        }
         // Bit packing is local to a message: reset bit cursor for this scope.
         let saved_bits = ctx.bit_write;
         ctx.bit_write = BitWriteState::default();
+        ctx.message_start = w.len();
+        ctx.field_starts.clear();
+        let checksum_from = checksum_from_fields(fields);
         let structs = &self.resolved.protocol.structs;
         let mut skip_count = 0usize;
         let mut i = 0;
@@ -306,15 +1272,15 @@ impl Codec {
                     continue;
                 }
             }
-            if let TypeSpec::PresenceBits(n) = &f.type_spec {
+            if let TypeSpec::PresenceBits(n, stop) = &f.type_spec {
                 let optional_indices = self.collect_following_optionals_message(fields, i + 1, ctx);
                 let bitmap = self.build_presence_bitmap_message(fields, &optional_indices, ctx);
-                self.write_bitmap_n(w, *n, bitmap)?;
+                self.write_bitmap_n(w, *n, presence_bits_wire(bitmap, *stop), ctx.endianness.unwrap_or(self.endianness))?;
                 for (bit_j, &idx) in optional_indices.iter().enumerate() {
                     if (bitmap >> bit_j) & 1 != 0 {
                         let o = &fields[idx];
                         let v = ctx.get(&o.name).cloned().unwrap_or_else(|| self.default_for_type_spec(&o.type_spec));
-                        if let TypeSpec::Optional(elem) = &o.type_spec {
+                        if let TypeSpec::Optional(elem, _) = &o.type_spec {
                             let inner = v.as_list().and_then(|l| l.first().cloned()).unwrap_or_else(|| self.default_for_type_spec(elem));
                             self.encode_type_spec(w, elem, &inner, structs, ctx)?;
                         }
@@ -340,7 +1306,7 @@ impl Codec {
                         let byte_idx = bit_j / 8;
                         let bit_in_byte = 7 - (bit_j % 8);
                         let bit = bp_bytes.get(byte_idx).map(|&b| (b >> bit_in_byte) & 1).unwrap_or(0);
-                        self.write_bits(w, ctx, 1, bit as u64)?;
+                        self.write_bits_in(w, ctx, 1, bit as u64, BitOrder::Lsb0)?;
                     }
                 } else {
                     let block_bits = presence_per_block + 1;
@@ -352,7 +1318,7 @@ impl Codec {
                             for j in 0..*presence_per_block as usize {
                                 value |= (((*byte >> (7 - j)) & 1) as u64) << (j + 1);
                             }
-                            self.write_bits(w, ctx, block_bits as u64, value)?;
+                            self.write_bits_in(w, ctx, block_bits as u64, value, BitOrder::Lsb0)?;
                         }
                         if ctx.bit_write.next_bit != 0 {
                             w.write_all(&[ctx.bit_write.cur])?;
@@ -366,7 +1332,7 @@ impl Codec {
                     if bp_bytes.get(bit_j / bits_per_block).map(|&b| (b >> bit_in_byte) & 1).unwrap_or(0) != 0 {
                         let o = &fields[idx];
                         let v = ctx.get(&o.name).cloned().unwrap_or_else(|| self.default_for_type_spec(&o.type_spec));
-                        if let TypeSpec::Optional(elem) = &o.type_spec {
+                        if let TypeSpec::Optional(elem, _) = &o.type_spec {
                             let inner = v.as_list().and_then(|l| l.first().cloned()).unwrap_or_else(|| self.default_for_type_spec(elem));
                             self.encode_type_spec(w, elem, &inner, structs, ctx)?;
                         }
@@ -376,14 +1342,72 @@ impl Codec {
                 i += 1;
                 continue;
             }
+            if checksum_from.contains(&f.name.as_str()) {
+                ctx.field_starts.insert(f.name.clone(), w.len());
+            }
             let v = ctx.get(&f.name).cloned().unwrap_or_else(|| self.default_for_type_spec(&f.type_spec));
             self.encode_type_spec(w, &f.type_spec, &v, structs, ctx)?;
+            if let Some(order) = self.switched_endianness(&f.name, v.as_i64())? {
+                ctx.endianness = Some(order);
+            }
             i += 1;
         }
         ctx.bit_write = saved_bits;
         Ok(())
     }
 
+    /// Sets the `count_of` fields of one scope (`(name, type, condition)` of its fields), then its `length_of`
+    /// fields, from the fields they describe (see [`EncodeOptions::auto_length`]). A described field whose
+    /// condition does not hold, or an absent optional, counts 0.
+    fn fill_auto_lengths<'f>(
+        &self,
+        fields: impl Iterator<Item = (&'f str, &'f TypeSpec, Option<&'f Condition>)> + Clone,
+        ctx: &mut EncodeContext,
+    ) -> Result<(), CodecError> {
+        let written = |ctx: &EncodeContext, target: &str| {
+            fields.clone().find(|(name, ..)| *name == target).filter(|(_, _, cond)| match cond {
+                Some(c) => ctx.get(&c.field).and_then(Value::as_i64) == c.value.as_i64(),
+                None => true,
+            })
+        };
+        for (name, spec, _) in fields.clone() {
+            if let TypeSpec::CountOf(target, width) = spec {
+                let n = match written(ctx, target).and(ctx.get(target)) {
+                    Some(Value::List(l)) => l.len(),
+                    Some(Value::Bytes(b)) => b.len(),
+                    _ => 0,
+                };
+                ctx.computed.insert(name.to_string(), auto_length_value(name, n, width)?);
+            }
+        }
+        for (name, spec, _) in fields.clone() {
+            let TypeSpec::LengthOf(target, width) = spec else { continue };
+            let len = match written(ctx, target) {
+                Some((_, target_spec, _)) => {
+                    let v = ctx.get(target).cloned().unwrap_or_else(|| self.default_for_type_spec(target_spec));
+                    // A present optional is measured without its presence flag or bit; an absent one is 0.
+                    let (spec, v) = match (target_spec, v) {
+                        (TypeSpec::Optional(elem, _), Value::List(l)) => match l.into_iter().next() {
+                            Some(inner) => (elem.as_ref(), inner),
+                            None => {
+                                ctx.computed.insert(name.to_string(), auto_length_value(name, 0, width)?);
+                                continue;
+                            }
+                        },
+                        (spec, v) => (spec, v),
+                    };
+                    let mut scratch = Vec::new();
+                    let mut sub = ctx.scratch();
+                    self.encode_type_spec(&mut scratch, spec, &v, &self.resolved.protocol.structs, &mut sub)?;
+                    scratch.len() + usize::from(sub.bit_write.next_bit != 0)
+                }
+                None => 0,
+            };
+            ctx.computed.insert(name.to_string(), auto_length_value(name, len, width)?);
+        }
+        Ok(())
+    }
+
     fn collect_following_optionals_message(&self, fields: &[MessageField], start: usize, ctx: &EncodeContext) -> Vec<usize> {
         let mut out = Vec::new();
         for j in start..fields.len() {
@@ -395,7 +1419,7 @@ impl Codec {
                     continue;
                 }
             }
-            if matches!(&f.type_spec, TypeSpec::Optional(_)) {
+            if matches!(&f.type_spec, TypeSpec::Optional(..)) {
                 out.push(j);
             } else {
                 break;
@@ -447,14 +1471,14 @@ impl Codec {
         out
     }
 
-    fn write_bitmap_n(&self, w: &mut Vec<u8>, n: u64, bitmap: u64) -> Result<(), CodecError> {
+    fn write_bitmap_n(&self, w: &mut Vec<u8>, n: u64, bitmap: u64, order: Endianness) -> Result<(), CodecError> {
         let len = match n {
             1 => 1,
             2 => 2,
             4 => 4,
             _ => return Err(CodecError::Validation("presence_bits(n): n must be 1, 2, or 4".to_string())),
         };
-        let buf = self.u64_to_bytes(bitmap, len);
+        let buf = self.u64_to_bytes(bitmap, len, order);
         w.write_all(&buf)?;
         Ok(())
     }
@@ -470,18 +1494,27 @@ impl Codec {
     }
 
     fn read_bits(&self, r: &mut Cursor<&[u8]>, ctx: &mut DecodeContext, n: u64) -> Result<u64, CodecError> {
+        self.read_bits_in(r, ctx, n, self.bit_order)
+    }
+
+    /// [`read_bits`](Self::read_bits) in a given bit order (presence bitmaps are always [`BitOrder::Lsb0`]).
+    fn read_bits_in(&self, r: &mut Cursor<&[u8]>, ctx: &mut DecodeContext, n: u64, bit_order: BitOrder) -> Result<u64, CodecError> {
         if n > 64 {
             return Err(CodecError::Validation(format!("bitfield({}): too many bits (max 64)", n)));
         }
         let mut out = 0u64;
         for i in 0..(n as u8) {
             if ctx.bit_read.next_bit == 8 {
-                ctx.bit_read.cur = r.read_u8()?;
+                ctx.bit_read.cur = self.read_u8(r)?;
                 ctx.bit_read.next_bit = 0;
             }
-            let bit = (ctx.bit_read.cur >> ctx.bit_read.next_bit) & 1;
-            if bit != 0 {
-                out |= 1u64 << i;
+            match bit_order {
+                BitOrder::Lsb0 => {
+                    if (ctx.bit_read.cur >> ctx.bit_read.next_bit) & 1 != 0 {
+                        out |= 1u64 << i;
+                    }
+                }
+                BitOrder::Msb0 => out = (out << 1) | ((ctx.bit_read.cur >> (7 - ctx.bit_read.next_bit)) & 1) as u64,
             }
             ctx.bit_read.next_bit += 1;
             if ctx.bit_read.next_bit == 8 {
@@ -491,15 +1524,22 @@ impl Codec {
         Ok(out)
     }
 
-    fn write_bits(&self, w: &mut Vec<u8>, ctx: &mut EncodeContext, n: u64, mut value: u64) -> Result<(), CodecError> {
+    fn write_bits(&self, w: &mut Vec<u8>, ctx: &mut EncodeContext, n: u64, value: u64) -> Result<(), CodecError> {
+        self.write_bits_in(w, ctx, n, value, self.bit_order)
+    }
+
+    /// [`write_bits`](Self::write_bits) in a given bit order (presence bitmaps are always [`BitOrder::Lsb0`]).
+    fn write_bits_in(&self, w: &mut Vec<u8>, ctx: &mut EncodeContext, n: u64, value: u64, bit_order: BitOrder) -> Result<(), CodecError> {
         if n > 64 {
             return Err(CodecError::Validation(format!("bitfield({}): too many bits (max 64)", n)));
         }
-        for _ in 0..n {
-            let bit = (value & 1) as u8;
-            value >>= 1;
+        for i in 0..n {
+            let (bit, shift) = match bit_order {
+                BitOrder::Lsb0 => ((value >> i) & 1, ctx.bit_write.next_bit),
+                BitOrder::Msb0 => ((value >> (n - 1 - i)) & 1, 7 - ctx.bit_write.next_bit),
+            };
             if bit != 0 {
-                ctx.bit_write.cur |= 1u8 << ctx.bit_write.next_bit;
+                ctx.bit_write.cur |= 1u8 << shift;
             }
             ctx.bit_write.next_bit += 1;
             if ctx.bit_write.next_bit == 8 {
@@ -511,35 +1551,37 @@ impl Codec {
         Ok(())
     }
 
-    fn decode_type_spec(
-        &self,
-        r: &mut Cursor<&[u8]>,
-        spec: &TypeSpec,
-        structs: &[StructSection],
+    fn decode_type_spec<'a, V: Decoded<'a>>(
+        &'a self,
+        r: &mut Cursor<&'a [u8]>,
+        spec: &'a TypeSpec,
+        structs: &'a [StructSection],
         ctx: &mut DecodeContext,
-    ) -> Result<Value, CodecError> {
+    ) -> Result<V, CodecError> {
         #[cfg(feature = "codec_decode_profile")]
         let _guard = DecodeProfileGuard::new(type_spec_decode_label(spec));
+        let order = ctx.endianness.unwrap_or(self.endianness);
         match spec {
             TypeSpec::Base(bt) => {
                 self.ensure_decode_bit_aligned(ctx)?;
-                self.decode_base(r, bt)
+                self.decode_base(r, bt, order).map(V::owned)
             }
             TypeSpec::Padding(kind) => match kind {
                 PaddingKind::Bytes(n) => {
                     self.ensure_decode_bit_aligned(ctx)?;
                     let mut buf = vec![0u8; *n as usize];
+                    need(r, buf.len())?;
                     r.read_exact(&mut buf)?;
-                    Ok(Value::Padding)
+                    Ok(V::owned(Value::Padding))
                 }
                 PaddingKind::Bits(n) => {
                     let _ = self.read_bits(r, ctx, *n)?;
-                    Ok(Value::Padding)
+                    Ok(V::owned(Value::Padding))
                 }
             }
             TypeSpec::Bitfield(n) => {
                 let v = self.read_bits(r, ctx, *n)?;
-                Ok(Value::U64(v))
+                Ok(V::owned(Value::U64(v)))
             }
             TypeSpec::SizedInt(bt, n) => {
                 // Sub-byte sizes (e.g. 6-bit chars) must use read_bits so they pack; byte-aligned full bytes use decode_sized_int.
@@ -559,7 +1601,7 @@ impl Codec {
                         }
                         _ => raw as i64,
                     };
-                    Ok(match bt {
+                    Ok(V::owned(match bt {
                         BaseType::U8 => Value::U8(val as u8),
                         BaseType::U16 => Value::U16(val as u16),
                         BaseType::U32 => Value::U32(val as u32),
@@ -569,33 +1611,26 @@ impl Codec {
                         BaseType::I32 => Value::I32(val as i32),
                         BaseType::I64 => Value::I64(val),
                         _ => Value::U64(raw),
-                    })
+                    }))
                 } else {
-                    self.decode_sized_int(r, bt, *n)
+                    self.decode_sized_int(r, bt, *n, order).map(V::owned)
                 }
             }
-            TypeSpec::LengthOf(_) => {
+            TypeSpec::LengthOf(_, width) | TypeSpec::CountOf(_, width) => {
                 self.ensure_decode_bit_aligned(ctx)?;
-                // Length fields are typically u16/u32 - decode as u32 for generality
-                let v = self.read_u32(r)?;
-                Ok(Value::U32(v))
+                self.decode_base(r, width, order).map(V::owned)
             }
-            TypeSpec::CountOf(_) => {
-                self.ensure_decode_bit_aligned(ctx)?;
-                let v = self.read_u32(r)?;
-                Ok(Value::U32(v))
-            }
-            TypeSpec::PresenceBits(n) => {
+            TypeSpec::PresenceBits(n, stop) => {
                 self.ensure_decode_bit_aligned(ctx)?;
                 let bytes = *n as usize;
                 let bitmap = match bytes {
-                    1 => r.read_u8()? as u64,
-                    2 => self.read_u16(r)? as u64,
-                    4 => self.read_u32(r)? as u64,
+                    1 => self.read_u8(r)? as u64,
+                    2 => self.read_u16(r, order)? as u64,
+                    4 => self.read_u32(r, order)? as u64,
                     _ => return Err(CodecError::Validation("presence_bits(n): n must be 1, 2, or 4".to_string())),
                 };
-                ctx.presence_stack.push(PresenceState::Bitmap { value: bitmap, bit_index: 0 });
-                Ok(Value::U64(bitmap))
+                ctx.presence_stack.push(PresenceState::Bitmap { value: presence_bits_logical(bitmap, *stop), bit_index: 0 });
+                Ok(V::owned(Value::U64(bitmap)))
             }
             TypeSpec::BitmapPresence { total_bits, presence_per_block, .. } => {
                 let max_encoded_bits = if *presence_per_block == 0 { *total_bits } else { ((*total_bits + presence_per_block - 1) / presence_per_block) * (presence_per_block + 1) };
@@ -603,7 +1638,7 @@ impl Codec {
                 let bytes = if *presence_per_block == 0 && *total_bits == 1 {
                     // Single presence bit in same byte as preceding bitfields: LSB (bit 0) of current byte (e.g. EUROCONTROL I048/170 FX).
                     if ctx.bit_read.next_bit == 8 {
-                        ctx.bit_read.cur = r.read_u8()?;
+                        ctx.bit_read.cur = self.read_u8(r)?;
                         ctx.bit_read.next_bit = 0;
                     }
                     let bit = (ctx.bit_read.cur & 1) as u64;
@@ -617,7 +1652,7 @@ impl Codec {
                     // Consecutive presence bits (no FX); consume bit-by-bit from the current bit stream.
                     let mut b = vec![0u8; max_bytes];
                     for i in 0..*total_bits as usize {
-                        let bit = self.read_bits(r, ctx, 1)?;
+                        let bit = self.read_bits_in(r, ctx, 1, BitOrder::Lsb0)?;
                         let byte_idx = i / 8;
                         let bit_idx = i % 8;
                         if bit != 0 {
@@ -633,9 +1668,9 @@ impl Codec {
                     let k = *presence_per_block as usize;
                     for _ in 0..max_blocks {
                         let b = if block_bits >= 8 {
-                            r.read_u8()?
+                            self.read_u8(r)?
                         } else {
-                            let b = self.read_bits(r, ctx, block_bits)? as u8;
+                            let b = self.read_bits_in(r, ctx, block_bits, BitOrder::Lsb0)? as u8;
                             let mut stored = b & 1;
                             for j in 0..k {
                                 stored |= ((b >> (j + 1)) & 1) << (7 - j);
@@ -654,15 +1689,22 @@ impl Codec {
                             ));
                         }
                     }
+                    if self.strict_fspec && bytes.len() > 1 && bytes.last().is_some_and(|&b| b & 0xFE == 0) {
+                        return Err(CodecError::Validation(format!(
+                            "bitmap presence: non-minimal FSPEC ({} bytes, last block has no presence bit set)",
+                            bytes.len()
+                        )));
+                    }
                     bytes
                 };
                 ctx.presence_stack.push(PresenceState::BitmapPresence { bytes: bytes.clone(), bit_index: 0, presence_per_block: *presence_per_block });
-                Ok(Value::Bytes(bytes))
+                Ok(V::owned(Value::Bytes(bytes)))
             }
             TypeSpec::StructRef(name) => {
                 self.ensure_decode_bit_aligned(ctx)?;
                 if let Some(enum_sec) = self.resolved.get_enum(name) {
-                    let raw = r.read_u8()? as i64;
+                    let v = self.decode_base(r, &enum_sec.base, order)?;
+                    let raw = v.as_i64().unwrap_or(0);
                     let ok = enum_sec.variants.iter().any(|(_, lit)| lit.as_i64() == Some(raw));
                     if !ok {
                         return Err(CodecError::Validation(format!(
@@ -670,32 +1712,66 @@ impl Codec {
                             name, raw
                         )));
                     }
-                    Ok(Value::U8(raw as u8))
+                    Ok(V::owned(v))
                 } else {
-                    let s = self.resolved.get_struct(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
-                    self.decode_struct(r, s, structs, ctx)
+                    self.decode_nested(r, name, structs, ctx)
                 }
             }
-            TypeSpec::Array(elem, len) => {
+            TypeSpec::Enum(name) => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let e = self.resolved.get_enum(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
+                let raw = self.decode_base(r, &e.base, order)?.as_i64().unwrap_or(0);
+                let variant = self.resolved.enum_variant_name_for_type_and_value(spec, raw);
+                Ok(V::owned(Value::Enum { name: name.clone(), variant, raw }))
+            }
+            TypeSpec::Select { field, cases, default } => {
+                let target = select_target(field, cases, default.as_ref(), ctx.get(field).and_then(Value::as_i64))?;
+                self.ensure_decode_bit_aligned(ctx)?;
+                self.decode_nested(r, target, structs, ctx)
+            }
+            TypeSpec::Array(elem, len, stride) => {
                 self.ensure_decode_bit_aligned(ctx)?;
                 let n = match len {
                     ArrayLen::Constant(k) => *k,
                     ArrayLen::FieldRef(field) => ctx.get(field).and_then(Value::as_u64).ok_or_else(|| CodecError::UnknownField(field.clone()))?,
                 };
+                self.check_list_len(n)?;
                 let mut list = Vec::with_capacity(n as usize);
                 for _ in 0..n {
+                    let start = r.position() as usize;
                     list.push(self.decode_type_spec(r, elem, structs, ctx)?);
+                    if let Some(stride) = stride {
+                        let gap = stride_gap(*stride, r.position() as usize - start)?;
+                        need(r, gap)?;
+                        r.set_position(r.position() + gap as u64);
+                    }
                 }
-                Ok(Value::List(list))
+                Ok(V::list(list))
             }
-            TypeSpec::List(elem) => {
+            TypeSpec::Group { count, fields } => {
                 self.ensure_decode_bit_aligned(ctx)?;
-                let n = self.read_u32(r)?;
+                let n = match count {
+                    ArrayLen::Constant(k) => *k,
+                    ArrayLen::FieldRef(field) => ctx.get(field).and_then(Value::as_u64).ok_or_else(|| CodecError::UnknownField(field.clone()))?,
+                };
+                self.check_list_len(n)?;
                 let mut list = Vec::with_capacity(n as usize);
+                for _ in 0..n {
+                    list.push(self.decode_struct_fields(r, "repeat", fields.iter(), false, structs, ctx)?);
+                }
+                Ok(V::list(list))
+            }
+            TypeSpec::List(elem) => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let n = self.read_u32(r, order)?;
+                self.check_list_len(n as u64)?;
+                // Capacity hint only: bounded by the bytes left so a corrupt count cannot reserve gigabytes.
+                let remaining = r.get_ref().len().saturating_sub(r.position() as usize);
+                let mut list = Vec::with_capacity((n as usize).min(remaining));
                 for _ in 0..n {
                     list.push(self.decode_type_spec(r, elem, structs, ctx)?);
                 }
-                Ok(Value::List(list))
+                Ok(V::list(list))
             }
             TypeSpec::RepList(elem) => {
                 self.ensure_decode_bit_aligned(ctx)?;
@@ -713,28 +1789,89 @@ impl Codec {
                 } else {
                     n_raw
                 };
+                self.check_list_len(n)?;
                 let mut list = Vec::with_capacity(n as usize);
                 for i in 0..n {
                     let v = self
                         .decode_type_spec(r, elem, structs, ctx)
-                        .map_err(|e| CodecError::Validation(format!("rep_list item {}/{}: {}", i + 1, n, e)))?;
+                        .map_err(|e| in_field(e, format_args!("rep_list item {}/{}", i + 1, n)))?;
+                    list.push(v);
+                }
+                Ok(V::list(list))
+            }
+            TypeSpec::LengthPrefixedList(elem, len_type) => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let n = self.read_u32(r, order)?;
+                self.check_list_len(n as u64)?;
+                let data: &'a [u8] = r.get_ref();
+                let remaining = data.len().saturating_sub(r.position() as usize);
+                let mut list = Vec::with_capacity((n as usize).min(remaining));
+                for i in 0..n {
+                    let item = format_args!("length_prefixed_list item {}/{}", i + 1, n);
+                    let len = self.decode_base(r, len_type, order)?.as_u64().unwrap_or(0) as usize;
+                    need(r, len)?;
+                    let start = r.position() as usize;
+                    let mut sub = Cursor::new(&data[start..start + len]);
+                    let v = self.decode_type_spec(&mut sub, elem, structs, ctx).map_err(|e| match e {
+                        CodecError::Truncated { .. } => {
+                            let e = offset_by(e, start);
+                            CodecError::Validation(format!("{}: element overruns its length {} ({})", item, len, e))
+                        }
+                        e => in_field(e, item),
+                    })?;
+                    if sub.position() as usize != len {
+                        return Err(CodecError::Validation(format!(
+                            "{}: length is {} but the element decodes to {} bytes",
+                            item,
+                            len,
+                            sub.position()
+                        )));
+                    }
+                    r.set_position((start + len) as u64);
+                    list.push(v);
+                }
+                Ok(V::list(list))
+            }
+            TypeSpec::BytesList(elem, len_field) => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let len = ctx.get(len_field).and_then(Value::as_u64).ok_or_else(|| CodecError::UnknownField(len_field.clone()))? as usize;
+                need(r, len)?;
+                let data: &'a [u8] = r.get_ref();
+                let start = r.position() as usize;
+                let mut sub = Cursor::new(&data[start..start + len]);
+                let mut list = Vec::new();
+                while (sub.position() as usize) < len {
+                    let item = format_args!("bytes_list({}) item {}", len_field, list.len() + 1);
+                    let before = sub.position();
+                    let v = self.decode_type_spec(&mut sub, elem, structs, ctx).map_err(|e| match e {
+                        CodecError::Truncated { .. } => {
+                            let e = offset_by(e, start);
+                            CodecError::Validation(format!("{}: element overruns the byte length {} ({})", item, len, e))
+                        }
+                        e => in_field(e, item),
+                    })?;
+                    if sub.position() == before {
+                        return Err(CodecError::Validation(format!("{}: element takes no bytes", item)));
+                    }
                     list.push(v);
+                    self.check_list_len(list.len() as u64)?;
                 }
-                Ok(Value::List(list))
+                r.set_position((start + len) as u64);
+                Ok(V::list(list))
             }
             TypeSpec::OctetsFx => {
                 self.ensure_decode_bit_aligned(ctx)?;
-                let mut bytes = Vec::new();
+                let start = r.position() as usize;
                 loop {
-                    let b = r.read_u8()?;
-                    bytes.push(b);
+                    let b = self.read_u8(r)?;
                     if b & 0x80 == 0 {
                         break;
                     }
                 }
-                Ok(Value::Bytes(bytes))
+                let data: &'a [u8] = r.get_ref();
+                Ok(V::bytes(&data[start..r.position() as usize]))
             }
-            TypeSpec::Optional(elem) => {
+            TypeSpec::Optional(elem, flag) => {
                 self.ensure_decode_bit_aligned(ctx)?;
                 let presence_stack_len = ctx.presence_stack.len();
                 let msg_name_opt = ctx.current_message_name.clone();
@@ -789,14 +1926,121 @@ impl Codec {
                         }
                     }
                 } else {
-                    let n = self.read_u8(r)?;
-                    n != 0
+                    self.decode_base(r, flag, order)?.as_u64() != Some(0)
                 };
+                // Only the message's own optionals, not those of the structs it embeds.
+                if let (Some(seen), Some(field), 0) = (ctx.presence.as_mut(), field_name_opt, ctx.depth) {
+                    seen.insert(field, present);
+                }
                 if present {
                     self.decode_type_spec(r, elem, structs, ctx)
                 } else {
-                    Ok(Value::List(vec![]))
+                    Ok(V::list(vec![]))
+                }
+            }
+            TypeSpec::Timestamp { base, scale, .. } => {
+                let raw = self.decode_type_spec::<V>(r, base, structs, ctx)?.as_i64().unwrap_or(0);
+                Ok(V::owned(Value::Timestamp { raw, seconds: raw as f64 * scale }))
+            }
+            TypeSpec::Bits(members) => {
+                if !ctx.bit_read.is_aligned() {
+                    return Err(CodecError::Validation("bits { ... }: must start on a byte boundary".to_string()));
+                }
+                let mut out = HashMap::with_capacity(members.len());
+                for (name, n) in members {
+                    out.insert(V::key(name), V::owned(Value::U64(self.read_bits(r, ctx, *n)?)));
+                }
+                Ok(V::record(out))
+            }
+            TypeSpec::DynInt(width_field) => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let width = ctx.get(width_field).and_then(Value::as_u64).ok_or_else(|| CodecError::UnknownField(width_field.clone()))?;
+                self.decode_sized_int(r, &BaseType::U64, dyn_int_width(width_field, width)? * 8, order).map(V::owned)
+            }
+            TypeSpec::Bcd { bytes, swapped } => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let mut buf = vec![0u8; *bytes as usize];
+                need(r, buf.len())?;
+                r.read_exact(&mut buf)?;
+                let v = bcd_to_u64(&buf, *swapped)
+                    .ok_or_else(|| CodecError::Validation(format!("bcd: invalid digit in {:02X?}", buf)))?;
+                Ok(V::owned(Value::U64(v)))
+            }
+            TypeSpec::ReverseBits(inner) => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let n = type_spec_fixed_bytes(inner)
+                    .ok_or_else(|| CodecError::Validation("reverse_bits<T>: T must be fixed-size".to_string()))?;
+                let mut buf = vec![0u8; n];
+                need(r, buf.len())?;
+                r.read_exact(&mut buf)?;
+                for b in buf.iter_mut() {
+                    *b = b.reverse_bits();
                 }
+                let mut sub = Cursor::new(buf.as_slice());
+                let mut sub_ctx = DecodeContext { endianness: ctx.endianness, ..DecodeContext::default() };
+                self.decode_type_spec::<Value>(&mut sub, inner, structs, &mut sub_ctx).map(V::owned)
+            }
+            TypeSpec::Remaining => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let data: &'a [u8] = r.get_ref();
+                let start = (r.position() as usize).min(data.len());
+                r.set_position(data.len() as u64);
+                Ok(V::bytes(&data[start..]))
+            }
+            TypeSpec::Unknown(n) => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let n = *n as usize;
+                need(r, n)?;
+                let data: &'a [u8] = r.get_ref();
+                let start = r.position() as usize;
+                r.set_position((start + n) as u64);
+                Ok(V::bytes(&data[start..start + n]))
+            }
+            TypeSpec::Checksum { algorithm, from } => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let data: &'a [u8] = r.get_ref();
+                let start = ctx.checksum_start(from.as_deref())?;
+                let expected = algorithm.compute(&data[start..r.position() as usize]);
+                let v = self.decode_base(r, &algorithm.base_type(), order)?;
+                if v.as_u64() != Some(u64::from(expected)) {
+                    return Err(CodecError::Validation(format!(
+                        "checksum({}): field holds {:#x}, bytes give {:#x}",
+                        algorithm.name(),
+                        v.as_u64().unwrap_or(0),
+                        expected
+                    )));
+                }
+                Ok(V::owned(v))
+            }
+            TypeSpec::CString => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let data: &'a [u8] = r.get_ref();
+                let start = (r.position() as usize).min(data.len());
+                let Some(len) = data[start..].iter().position(|&b| b == 0) else {
+                    return Err(CodecError::Truncated {
+                        field: String::new(),
+                        needed: data.len() - start + 1,
+                        available: data.len() - start,
+                        offset: start,
+                    });
+                };
+                r.set_position((start + len + 1) as u64);
+                Ok(V::bytes(&data[start..start + len]))
+            }
+            TypeSpec::FixedString(n) => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                let n = *n as usize;
+                need(r, n)?;
+                let data: &'a [u8] = r.get_ref();
+                let start = r.position() as usize;
+                r.set_position((start + n) as u64);
+                let text = &data[start..start + n];
+                let len = text.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+                Ok(V::bytes(&text[..len]))
+            }
+            TypeSpec::Custom(name) => {
+                self.ensure_decode_bit_aligned(ctx)?;
+                self.custom_types.get(name)?.decode(r, order).map(V::owned)
             }
         }
     }
@@ -809,10 +2053,11 @@ impl Codec {
         structs: &[StructSection],
         ctx: &mut EncodeContext,
     ) -> Result<(), CodecError> {
+        let order = ctx.endianness.unwrap_or(self.endianness);
         match spec {
             TypeSpec::Base(bt) => {
                 self.ensure_encode_bit_aligned(ctx)?;
-                self.encode_base(w, bt, v)
+                self.encode_base(w, bt, v, order)
             }
             TypeSpec::Padding(kind) => match kind {
                 PaddingKind::Bytes(n) => {
@@ -829,8 +2074,9 @@ impl Codec {
                 self.write_bits(w, ctx, *n, val)
             }
             TypeSpec::SizedInt(bt, n) => {
-                if ctx.bit_write.is_aligned() {
-                    self.encode_sized_int(w, bt, *n, v)
+                // As on decode: sub-byte sizes pack with the bit fields around them, even on a byte boundary.
+                if *n >= 8 && ctx.bit_write.is_aligned() {
+                    self.encode_sized_int(w, bt, *n, v, order)
                 } else {
                     let mask = if *n >= 64 { u64::MAX } else { (1u64 << *n) - 1 };
                     let raw = match bt {
@@ -842,70 +2088,151 @@ impl Codec {
                     self.write_bits(w, ctx, *n, raw)
                 }
             }
-            TypeSpec::LengthOf(_) => {
+            TypeSpec::LengthOf(_, width) | TypeSpec::CountOf(_, width) => {
                 self.ensure_encode_bit_aligned(ctx)?;
-                let val = v.as_u64().unwrap_or(0);
-                self.write_u32(w, val as u32)?;
+                self.encode_base(w, width, &Value::U64(v.as_u64().unwrap_or(0)), order)
+            }
+            TypeSpec::PresenceBits(..) | TypeSpec::BitmapPresence { .. } => {
+                // Written by encode_message_fields / encode_struct when they see this field and look ahead.
                 Ok(())
             }
-            TypeSpec::CountOf(_) => {
+            TypeSpec::Enum(name) => {
                 self.ensure_encode_bit_aligned(ctx)?;
-                let val = v.as_u64().unwrap_or(0);
-                self.write_u32(w, val as u32)?;
-                Ok(())
+                let e = self.resolved.get_enum(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
+                // A variant name (as decoded strings are, in `Value::Bytes`), else the raw integer.
+                let raw = match v {
+                    Value::Bytes(b) => e
+                        .variants
+                        .iter()
+                        .find(|(variant, _)| variant.as_bytes() == b.as_slice())
+                        .and_then(|(_, lit)| lit.as_i64())
+                        .ok_or_else(|| {
+                            CodecError::Validation(format!("enum {}: no variant named {}", name, String::from_utf8_lossy(b)))
+                        })?,
+                    other => other.as_i64().ok_or_else(|| {
+                        CodecError::Validation(format!("enum {}: expected a variant name or an integer, got {:?}", name, other))
+                    })?,
+                };
+                match type_spec_integer_range(&TypeSpec::Base(e.base.clone())) {
+                    Some((min, max)) if raw < min || raw > max => {
+                        Err(CodecError::Validation(format!("enum {}: value {} does not fit in {:?}", name, raw, e.base)))
+                    }
+                    _ => self.encode_base(w, &e.base, &Value::U64(raw as u64), order),
+                }
             }
-            TypeSpec::PresenceBits(_) | TypeSpec::BitmapPresence { .. } => {
-                // Written by encode_message_fields / encode_struct when they see this field and look ahead.
-                Ok(())
+            TypeSpec::Select { field, cases, default } => {
+                let target = select_target(field, cases, default.as_ref(), ctx.get(field).and_then(Value::as_i64))?;
+                self.encode_type_spec(w, &TypeSpec::StructRef(target.to_string()), v, structs, ctx)
             }
             TypeSpec::StructRef(name) => {
                 self.ensure_encode_bit_aligned(ctx)?;
                 if let Some(enum_sec) = self.resolved.get_enum(name) {
-                    let raw = v.as_u64().unwrap_or(0) as u8;
-                    let ok = enum_sec.variants.iter().any(|(_, lit)| lit.as_i64() == Some(raw as i64));
+                    let raw = v.as_i64().unwrap_or(0);
+                    let ok = enum_sec.variants.iter().any(|(_, lit)| lit.as_i64() == Some(raw));
                     if !ok {
                         return Err(CodecError::Validation(format!(
                             "enum {}: value {} not in allowed set",
                             name, raw
                         )));
                     }
-                    w.write_all(&[raw])?;
+                    self.encode_base(w, &enum_sec.base, v, order)?;
                     Ok(())
-                } else {
-                    let s = self.resolved.get_struct(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
-                    let m = v.as_struct().cloned().unwrap_or_default();
-                    let mut sub = EncodeContext::from_values(&m);
+                } else if let Some(s) = self.resolved.get_struct(name) {
+                    let empty = HashMap::new();
+                    let mut sub = ctx.nested(v.as_struct().unwrap_or(&empty));
                     self.encode_struct(w, s, structs, &mut sub)?;
                     Ok(())
+                } else {
+                    let msg = self.resolved.get_message(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
+                    let empty = HashMap::new();
+                    let values = v.as_struct().unwrap_or(&empty);
+                    if let Some(e) = msg.failed_check(|name| values.get(name).and_then(Decoded::check_value)) {
+                        return Err(CodecError::Validation(e));
+                    }
+                    let mut sub = ctx.nested(values);
+                    self.encode_message_fields(w, msg.fields.as_slice(), &mut sub)
                 }
             }
-            TypeSpec::Array(elem, _len) => {
+            TypeSpec::Array(elem, _len, stride) => {
                 self.ensure_encode_bit_aligned(ctx)?;
-                let list = v.as_list().map(|s| s.to_vec()).unwrap_or_default();
-                for item in list {
-                    self.encode_type_spec(w, elem, &item, structs, ctx)?;
+                for item in v.as_list().unwrap_or_default() {
+                    let start = w.len();
+                    self.encode_type_spec(w, elem, item, structs, ctx)?;
+                    if let Some(stride) = stride {
+                        let gap = stride_gap(*stride, w.len() - start)?;
+                        w.resize(w.len() + gap, 0);
+                    }
+                }
+                Ok(())
+            }
+            TypeSpec::Group { fields, .. } => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                let empty = HashMap::new();
+                for item in v.as_list().unwrap_or_default() {
+                    let mut sub = ctx.nested(item.as_struct().unwrap_or(&empty));
+                    self.encode_struct_fields(w, "repeat", fields, false, structs, &mut sub)?;
                 }
                 Ok(())
             }
             TypeSpec::List(elem) => {
                 self.ensure_encode_bit_aligned(ctx)?;
-                let list = v.as_list().map(|s| s.to_vec()).unwrap_or_default();
-                self.write_u32(w, list.len() as u32)?;
+                let list = v.as_list().unwrap_or_default();
+                self.write_u32(w, list.len() as u32, order)?;
                 for item in list {
-                    self.encode_type_spec(w, elem, &item, structs, ctx)?;
+                    self.encode_type_spec(w, elem, item, structs, ctx)?;
                 }
                 Ok(())
             }
             TypeSpec::RepList(elem) => {
                 self.ensure_encode_bit_aligned(ctx)?;
-                let list = v.as_list().map(|s| s.to_vec()).unwrap_or_default();
+                let list = v.as_list().unwrap_or_default();
                 let n = list.len().min(255);
                 self.write_u8(w, n as u8)?;
-                for item in list.into_iter().take(n) {
-                    self.encode_type_spec(w, elem, &item, structs, ctx)?;
+                for item in list.iter().take(n) {
+                    self.encode_type_spec(w, elem, item, structs, ctx)?;
+                }
+                Ok(())
+            }
+            TypeSpec::LengthPrefixedList(elem, len_type) => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                let list = v.as_list().unwrap_or_default();
+                self.write_u32(w, list.len() as u32, order)?;
+                let max_len = type_spec_integer_range(&TypeSpec::Base(len_type.clone())).map_or(i64::MAX, |(_, max)| max);
+                let mut buf = Vec::new();
+                for item in list {
+                    buf.clear();
+                    self.encode_type_spec(&mut buf, elem, item, structs, ctx)?;
+                    if buf.len() as i64 > max_len {
+                        return Err(CodecError::Validation(format!(
+                            "length_prefixed_list: element of {} bytes does not fit a {:?} length",
+                            buf.len(),
+                            len_type
+                        )));
+                    }
+                    self.encode_base(w, len_type, &Value::U64(buf.len() as u64), order)?;
+                    w.extend_from_slice(&buf);
                 }
                 Ok(())
             }
+            TypeSpec::BytesList(elem, len_field) => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                let start = w.len();
+                for item in v.as_list().unwrap_or_default() {
+                    self.encode_type_spec(w, elem, item, structs, ctx)?;
+                }
+                let written = (w.len() - start) as u64;
+                match ctx.get(len_field).and_then(Value::as_u64) {
+                    _ if ctx.measuring => Ok(()),
+                    Some(len) if len == written => Ok(()),
+                    len => Err(CodecError::Validation(format!(
+                        "bytes_list({}): elements take {} bytes but {} is {}",
+                        len_field,
+                        written,
+                        len_field,
+                        len.map_or("missing".to_string(), |n| n.to_string())
+                    ))),
+                }
+            }
             TypeSpec::OctetsFx => {
                 self.ensure_encode_bit_aligned(ctx)?;
                 let bytes = match v {
@@ -921,69 +2248,261 @@ impl Codec {
                 }
                 Ok(())
             }
-            TypeSpec::Optional(elem) => {
+            TypeSpec::Optional(elem, flag) => {
                 self.ensure_encode_bit_aligned(ctx)?;
-                if v.as_list().map(|s| s.is_empty()).unwrap_or(true) {
-                    self.write_u8(w, 0)?;
-                } else {
-                    self.write_u8(w, 1)?;
-                    self.encode_type_spec(w, elem, v, structs, ctx)?;
+                // Present as a one-element list or, as decoded, the bare value; absent as an empty list.
+                let inner = match v {
+                    Value::List(items) if !matches!(elem.as_ref(), TypeSpec::List(_) | TypeSpec::RepList(_) | TypeSpec::LengthPrefixedList(..) | TypeSpec::BytesList(..) | TypeSpec::Array(..)) => {
+                        items.first()
+                    }
+                    Value::List(items) if items.is_empty() => None,
+                    other => Some(other),
+                };
+                match inner {
+                    None => self.encode_base(w, flag, &Value::U64(0), order)?,
+                    Some(inner) => {
+                        self.encode_base(w, flag, &Value::U64(1), order)?;
+                        self.encode_type_spec(w, elem, inner, structs, ctx)?;
+                    }
+                }
+                Ok(())
+            }
+            TypeSpec::Timestamp { base, scale, .. } => {
+                // Seconds (Timestamp or float) are converted to ticks; plain integers are taken as raw ticks.
+                let raw = match v {
+                    Value::Timestamp { seconds, .. } | Value::Double(seconds) => (seconds / scale).round() as i64,
+                    Value::Float(seconds) => (*seconds as f64 / scale).round() as i64,
+                    _ => v.as_i64().unwrap_or(0),
+                };
+                let raw_value = if raw >= 0 { Value::U64(raw as u64) } else { Value::I64(raw) };
+                self.encode_type_spec(w, base, &raw_value, structs, ctx)
+            }
+            TypeSpec::Bits(members) => {
+                if !ctx.bit_write.is_aligned() {
+                    return Err(CodecError::Validation("bits { ... }: must start on a byte boundary".to_string()));
+                }
+                let fields = v.as_struct();
+                for (name, n) in members {
+                    let val = fields.and_then(|m| m.get(name)).and_then(Value::as_u64).unwrap_or(0);
+                    self.write_bits(w, ctx, *n, val)?;
+                }
+                Ok(())
+            }
+            TypeSpec::DynInt(width_field) => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                let width = ctx.get(width_field).and_then(Value::as_u64).ok_or_else(|| CodecError::UnknownField(width_field.clone()))?;
+                let width = dyn_int_width(width_field, width)?;
+                let val = v.as_u64().unwrap_or(0);
+                if width < 8 && val >> (width * 8) != 0 {
+                    return Err(CodecError::Validation(format!("dyn_int({}): {} does not fit in {} bytes", width_field, val, width)));
+                }
+                self.encode_sized_int(w, &BaseType::U64, width * 8, v, order)
+            }
+            TypeSpec::Bcd { bytes, swapped } => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                let val = v.as_u64().unwrap_or(0);
+                let buf = u64_to_bcd(val, *bytes as usize, *swapped)
+                    .ok_or_else(|| CodecError::Validation(format!("bcd({}): {} has too many digits", bytes, val)))?;
+                w.write_all(&buf)?;
+                Ok(())
+            }
+            TypeSpec::ReverseBits(inner) => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                let mut buf = Vec::new();
+                let empty = HashMap::new();
+                let mut sub = ctx.nested(&empty);
+                self.encode_type_spec(&mut buf, inner, v, structs, &mut sub)?;
+                if sub.bit_write.next_bit != 0 {
+                    buf.push(sub.bit_write.cur);
+                }
+                w.extend(buf.iter().map(|b| b.reverse_bits()));
+                Ok(())
+            }
+            TypeSpec::Remaining => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                if let Value::Bytes(b) = v {
+                    w.write_all(b)?;
+                }
+                Ok(())
+            }
+            TypeSpec::Unknown(n) => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                match v {
+                    Value::Bytes(b) if b.len() as u64 == *n => w.write_all(b)?,
+                    Value::Bytes(b) => {
+                        return Err(CodecError::Validation(format!("unknown({}): expected {} bytes, got {}", n, n, b.len())))
+                    }
+                    _ => return Err(CodecError::Validation(format!("unknown({}): expected bytes", n))),
+                }
+                Ok(())
+            }
+            TypeSpec::Checksum { algorithm, from } => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                let start = ctx.checksum_start(from.as_deref())?;
+                // A field measured on its own (auto_length) has no message bytes before it.
+                let sum = algorithm.compute(w.get(start..).unwrap_or_default());
+                self.encode_base(w, &algorithm.base_type(), &Value::U32(sum), order)
+            }
+            TypeSpec::CString => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                let Value::Bytes(b) = v else {
+                    return Err(CodecError::Validation("cstring: expected bytes".to_string()));
+                };
+                if let Some(i) = b.iter().position(|&c| c == 0) {
+                    return Err(CodecError::Validation(format!("cstring: zero byte at {} would end the string", i)));
+                }
+                w.write_all(b)?;
+                w.write_all(&[0])?;
+                Ok(())
+            }
+            TypeSpec::FixedString(n) => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                let Value::Bytes(b) = v else {
+                    return Err(CodecError::Validation(format!("string({}): expected bytes", n)));
+                };
+                if b.len() as u64 > *n {
+                    return Err(CodecError::Validation(format!("string({}): {} bytes do not fit", n, b.len())));
                 }
+                w.write_all(b)?;
+                w.resize(w.len() + (*n as usize - b.len()), 0);
                 Ok(())
             }
+            TypeSpec::Custom(name) => {
+                self.ensure_encode_bit_aligned(ctx)?;
+                self.custom_types.get(name)?.encode(w, v)
+            }
         }
     }
 
-    fn decode_struct(
-        &self,
-        r: &mut Cursor<&[u8]>,
-        s: &StructSection,
-        structs: &[StructSection],
+    fn decode_struct<'a, V: Decoded<'a>>(
+        &'a self,
+        r: &mut Cursor<&'a [u8]>,
+        s: &'a StructSection,
+        structs: &'a [StructSection],
         ctx: &mut DecodeContext,
-    ) -> Result<Value, CodecError> {
+    ) -> Result<V, CodecError> {
+        self.decode_struct_fields(r, &s.name, s.wire_fields(), s.reversed, structs, ctx)
+    }
+
+    /// Struct body (`fields` in wire order); also one repetition of a `repeat(n) { ... }` group, named `name` in errors.
+    fn decode_struct_fields<'a, V: Decoded<'a>>(
+        &'a self,
+        r: &mut Cursor<&'a [u8]>,
+        name: &str,
+        fields: impl Iterator<Item = &'a StructField>,
+        reversed: bool,
+        structs: &'a [StructSection],
+        ctx: &mut DecodeContext,
+    ) -> Result<V, CodecError> {
         // Bit packing is local to a struct; presence uses a stack: any bitmap_presence/presence_bits decoded inside this struct push, we pop on exit.
         let saved_bits = ctx.bit_read;
         let presence_stack_len = ctx.presence_stack.len();
         ctx.bit_read = BitReadState::default();
         let mut out = HashMap::new();
-        for f in &s.fields {
+        for f in fields {
             if let Some(ref cond) = f.condition {
                 let cond_val = ctx.get(cond.field.as_str()).and_then(Value::as_i64);
                 let expected = cond.value.as_i64();
                 if cond_val != expected {
                     // Optional with condition: treat as absent, do not read from stream.
-                    if matches!(f.type_spec, TypeSpec::Optional(_)) {
+                    if matches!(f.type_spec, TypeSpec::Optional(..)) {
                         ctx.set(f.name.clone(), Value::List(vec![]));
-                        out.insert(f.name.clone(), Value::List(vec![]));
+                        out.insert(V::key(&f.name), V::list(vec![]));
                     }
                     continue;
                 }
             }
             // Optional with condition that matched: decode inner type directly (no bitmap presence read).
             let v = if let Some(ref _cond) = f.condition {
-                if let TypeSpec::Optional(elem) = &f.type_spec {
+                if let TypeSpec::Optional(elem, _) = &f.type_spec {
                     let inner = self
                         .decode_type_spec(r, elem, structs, ctx)
-                        .map_err(|e| CodecError::Validation(format!("{}.{}: {}", s.name, f.name, e)))?;
-                    Value::List(vec![inner])
+                        .map_err(|e| in_named_field(e, &f.name, format_args!("{}.{}", name, f.name)))?;
+                    V::list(vec![inner])
                 } else {
                     self.decode_type_spec(r, &f.type_spec, structs, ctx)
-                        .map_err(|e| CodecError::Validation(format!("{}.{}: {}", s.name, f.name, e)))?
+                        .map_err(|e| in_named_field(e, &f.name, format_args!("{}.{}", name, f.name)))?
                 }
             } else {
                 self.decode_type_spec(r, &f.type_spec, structs, ctx)
-                    .map_err(|e| CodecError::Validation(format!("{}.{}: {}", s.name, f.name, e)))?
+                    .map_err(|e| in_named_field(e, &f.name, format_args!("{}.{}", name, f.name)))?
             };
-            self.validate_constraint(&v, f.constraint.as_ref())?;
-            ctx.set(f.name.clone(), v.clone());
-            out.insert(f.name.clone(), v);
+            let c = f.active_constraint(|name| ctx.get(name).and_then(Value::as_i64));
+            self.validate_constraint(&v, c).map_err(|e| in_field(e, format_args!("{}.{}", name, f.name)))?;
+            if let Some(c) = v.context_value() {
+                ctx.set(f.name.clone(), c);
+            }
+            out.insert(V::key(&f.name), v);
+        }
+        if reversed && !ctx.bit_read.is_aligned() {
+            return Err(CodecError::Validation(format!("struct {}: reversed fields end inside a bit group", name)));
         }
         ctx.bit_read = saved_bits;
         // Pop any presence state pushed while decoding this struct (e.g. nested bitmap_presence).
         while ctx.presence_stack.len() > presence_stack_len {
             ctx.presence_stack.pop();
         }
-        Ok(Value::Struct(out))
+        Ok(V::record(out))
+    }
+
+    /// Struct, or message used as a field type, named `name`, one nesting level deeper.
+    fn decode_nested<'a, V: Decoded<'a>>(
+        &'a self,
+        r: &mut Cursor<&'a [u8]>,
+        name: &'a str,
+        structs: &'a [StructSection],
+        ctx: &mut DecodeContext,
+    ) -> Result<V, CodecError> {
+        if ctx.depth >= self.limits.max_recursion_depth {
+            return Err(CodecError::Validation(format!(
+                "{}: nesting exceeds max_recursion_depth {}",
+                name, self.limits.max_recursion_depth
+            )));
+        }
+        ctx.depth += 1;
+        let v = if let Some(s) = self.resolved.get_struct(name) {
+            self.decode_struct(r, s, structs, ctx)
+        } else {
+            let m = self.resolved.get_message(name).ok_or_else(|| CodecError::UnknownStruct(name.to_string()));
+            m.and_then(|m| self.decode_embedded_message(r, m, ctx))
+        };
+        ctx.depth -= 1;
+        v
+    }
+
+    /// Message used as a field type (e.g. `list<Inner>`): decoded like a struct into `Value::Struct`,
+    /// with the message's constraints checked.
+    fn decode_embedded_message<'a, V: Decoded<'a>>(
+        &'a self,
+        r: &mut Cursor<&'a [u8]>,
+        m: &'a MessageSection,
+        ctx: &mut DecodeContext,
+    ) -> Result<V, CodecError> {
+        let saved_message = ctx.current_message_name.take();
+        let saved_field = ctx.current_field_name.take();
+        let presence_stack_len = ctx.presence_stack.len();
+        // An `endianness from` switch inside the embedded message ends with it, as on encode.
+        let saved_endianness = ctx.endianness;
+        let values = self.decode_message_fields_no_validate(r, &m.name, m.fields.as_slice(), ctx, None, None);
+        ctx.current_message_name = saved_message;
+        ctx.current_field_name = saved_field;
+        ctx.endianness = saved_endianness;
+        while ctx.presence_stack.len() > presence_stack_len {
+            ctx.presence_stack.pop();
+        }
+        let values = values?;
+        for f in &m.fields {
+            if let Some(c) = f.active_constraint(|name| values.get(name).and_then(V::as_i64)) {
+                if let Some(v) = values.get(f.name.as_str()) {
+                    self.validate_constraint(v, Some(c))
+                        .map_err(|e| in_field(e, format_args!("{}.{}", m.name, f.name)))?;
+                }
+            }
+        }
+        if let Some(e) = m.failed_check(|name| values.get(name).and_then(V::check_value)) {
+            return Err(CodecError::Validation(e));
+        }
+        Ok(V::record(values))
     }
 
     fn encode_struct(
@@ -992,6 +2511,21 @@ impl Codec {
         s: &StructSection,
         structs: &[StructSection],
         ctx: &mut EncodeContext,
+    ) -> Result<(), CodecError> {
+        let fields: Cow<[StructField]> =
+            if s.reversed { Cow::Owned(s.wire_fields().cloned().collect()) } else { Cow::Borrowed(&s.fields) };
+        self.encode_struct_fields(w, &s.name, &fields, s.reversed, structs, ctx)
+    }
+
+    /// Struct body (`fields` in wire order); also one repetition of a `repeat(n) { ... }` group, named `name` in errors.
+    fn encode_struct_fields(
+        &self,
+        w: &mut Vec<u8>,
+        name: &str,
+        fields: &[StructField],
+        reversed: bool,
+        structs: &[StructSection],
+        ctx: &mut EncodeContext,
     ) -> Result<(), CodecError> {
         // Flush current byte so nested struct (e.g. optional extension) does not lose it.
         if ctx.bit_write.next_bit != 0 {
@@ -1000,15 +2534,22 @@ impl Codec {
         // Bit packing is local to a struct: reset bit cursor for this scope.
         let saved_bits = ctx.bit_write;
         ctx.bit_write = BitWriteState::default();
+        for f in fields {
+            ctx.fill_default(&f.name, &f.type_spec, f.default.as_ref());
+            ctx.fill_dyn_int_width(&f.name, &f.type_spec);
+        }
+        if self.encode_options.auto_length {
+            self.fill_auto_lengths(fields.iter().map(|f| (f.name.as_str(), &f.type_spec, f.condition.as_ref())), ctx)?;
+        }
         let mut skip_count = 0usize;
         let mut i = 0;
-        while i < s.fields.len() {
+        while i < fields.len() {
             if skip_count > 0 {
                 skip_count -= 1;
                 i += 1;
                 continue;
             }
-            let f = &s.fields[i];
+            let f = &fields[i];
             if let Some(ref cond) = f.condition {
                 let cond_val = ctx.get(cond.field.as_str()).and_then(Value::as_i64);
                 let expected = cond.value.as_i64();
@@ -1019,7 +2560,7 @@ impl Codec {
             }
             // Optional with condition that matched: encode inner type only (no presence byte).
             if f.condition.is_some() {
-                if let TypeSpec::Optional(elem) = &f.type_spec {
+                if let TypeSpec::Optional(elem, _) = &f.type_spec {
                     let v = ctx.get(&f.name).cloned().unwrap_or_else(|| self.default_for_type_spec(&f.type_spec));
                     let inner = v.as_list().and_then(|l| l.first().cloned()).unwrap_or_else(|| self.default_for_type_spec(elem));
                     self.encode_type_spec(w, elem, &inner, structs, ctx)?;
@@ -1027,15 +2568,15 @@ impl Codec {
                     continue;
                 }
             }
-            if let TypeSpec::PresenceBits(n) = &f.type_spec {
-                let optional_indices = self.collect_following_optionals_struct(&s.fields, i + 1, ctx);
-                let bitmap = self.build_presence_bitmap_struct(&s.fields, &optional_indices, ctx);
-                self.write_bitmap_n(w, *n, bitmap)?;
+            if let TypeSpec::PresenceBits(n, stop) = &f.type_spec {
+                let optional_indices = self.collect_following_optionals_struct(fields, i + 1, ctx);
+                let bitmap = self.build_presence_bitmap_struct(fields, &optional_indices, ctx);
+                self.write_bitmap_n(w, *n, presence_bits_wire(bitmap, *stop), ctx.endianness.unwrap_or(self.endianness))?;
                 for (bit_j, &idx) in optional_indices.iter().enumerate() {
                     if (bitmap >> bit_j) & 1 != 0 {
-                        let o = &s.fields[idx];
+                        let o = &fields[idx];
                         let v = ctx.get(&o.name).cloned().unwrap_or_else(|| self.default_for_type_spec(&o.type_spec));
-                        if let TypeSpec::Optional(elem) = &o.type_spec {
+                        if let TypeSpec::Optional(elem, _) = &o.type_spec {
                             let inner = v.as_list().and_then(|l| l.first().cloned()).unwrap_or_else(|| self.default_for_type_spec(elem));
                             self.encode_type_spec(w, elem, &inner, structs, ctx)?;
                         }
@@ -1046,8 +2587,8 @@ impl Codec {
                 continue;
             }
             if let TypeSpec::BitmapPresence { total_bits, presence_per_block, .. } = &f.type_spec {
-                let optional_indices = self.collect_following_optionals_struct(&s.fields, i + 1, ctx);
-                let mut bp_bytes = self.build_bitmap_presence_bytes_struct(&s.fields, &optional_indices, ctx, *presence_per_block);
+                let optional_indices = self.collect_following_optionals_struct(fields, i + 1, ctx);
+                let mut bp_bytes = self.build_bitmap_presence_bytes_struct(fields, &optional_indices, ctx, *presence_per_block);
                 let max_encoded_bits = if *presence_per_block == 0 { *total_bits } else { ((*total_bits + presence_per_block - 1) / presence_per_block) * (presence_per_block + 1) };
                 let max_bytes = ((max_encoded_bits + 7) / 8) as usize;
                 bp_bytes.truncate(max_bytes);
@@ -1066,7 +2607,7 @@ impl Codec {
                         let byte_idx = bit_j / 8;
                         let bit_in_byte = 7 - (bit_j % 8);
                         let bit = bp_bytes.get(byte_idx).map(|&b| (b >> bit_in_byte) & 1).unwrap_or(0);
-                        self.write_bits(w, ctx, 1, bit as u64)?;
+                        self.write_bits_in(w, ctx, 1, bit as u64, BitOrder::Lsb0)?;
                     }
                 } else {
                     let block_bits = presence_per_block + 1;
@@ -1078,7 +2619,7 @@ impl Codec {
                             for j in 0..*presence_per_block as usize {
                                 value |= (((*byte >> (7 - j)) & 1) as u64) << (j + 1);
                             }
-                            self.write_bits(w, ctx, block_bits as u64, value)?;
+                            self.write_bits_in(w, ctx, block_bits as u64, value, BitOrder::Lsb0)?;
                         }
                         if ctx.bit_write.next_bit != 0 {
                             w.write_all(&[ctx.bit_write.cur])?;
@@ -1090,9 +2631,9 @@ impl Codec {
                 for (bit_j, &idx) in optional_indices.iter().enumerate() {
                     let bit_in_byte = 7 - (bit_j % bits_per_block);
                     if bp_bytes.get(bit_j / bits_per_block).map(|&b| (b >> bit_in_byte) & 1).unwrap_or(0) != 0 {
-                        let o = &s.fields[idx];
+                        let o = &fields[idx];
                         let v = ctx.get(&o.name).cloned().unwrap_or_else(|| self.default_for_type_spec(&o.type_spec));
-                        if let TypeSpec::Optional(elem) = &o.type_spec {
+                        if let TypeSpec::Optional(elem, _) = &o.type_spec {
                             let inner = v.as_list().and_then(|l| l.first().cloned()).unwrap_or_else(|| self.default_for_type_spec(elem));
                             self.encode_type_spec(w, elem, &inner, structs, ctx)?;
                         }
@@ -1106,6 +2647,9 @@ impl Codec {
             self.encode_type_spec(w, &f.type_spec, &v, structs, ctx)?;
             i += 1;
         }
+        if reversed && !ctx.bit_write.is_aligned() {
+            return Err(CodecError::Validation(format!("struct {}: reversed fields end inside a bit group", name)));
+        }
         ctx.bit_write = saved_bits;
         Ok(())
     }
@@ -1121,7 +2665,7 @@ impl Codec {
                     continue;
                 }
             }
-            if matches!(&f.type_spec, TypeSpec::Optional(_)) {
+            if matches!(&f.type_spec, TypeSpec::Optional(..)) {
                 out.push(j);
             } else {
                 break;
@@ -1179,11 +2723,14 @@ impl Codec {
             TypeSpec::Base(BaseType::Double) => Value::Double(0.0),
             TypeSpec::Base(_) => Value::U64(0),
             TypeSpec::Padding(_) => Value::Padding,
-            TypeSpec::List(_) => Value::List(vec![]),
-            TypeSpec::OctetsFx => Value::Bytes(vec![]),
+            TypeSpec::List(_) | TypeSpec::LengthPrefixedList(..) | TypeSpec::BytesList(..) => Value::List(vec![]),
+            TypeSpec::OctetsFx | TypeSpec::Remaining => Value::Bytes(vec![]),
+            TypeSpec::Unknown(n) => Value::Bytes(vec![0; *n as usize]),
+            TypeSpec::CString | TypeSpec::FixedString(_) => Value::Bytes(vec![]),
+            TypeSpec::Checksum { .. } => Value::U32(0),
             TypeSpec::StructRef(name) => {
-                if self.resolved.get_enum(name).is_some() {
-                    Value::U8(0)
+                if let Some(enum_sec) = self.resolved.get_enum(name) {
+                    self.default_for_type_spec(&TypeSpec::Base(enum_sec.base.clone()))
                 } else {
                     Value::Struct(HashMap::new())
                 }
@@ -1193,14 +2740,19 @@ impl Codec {
         }
     }
 
-    fn validate_constraint(&self, v: &Value, c: Option<&Constraint>) -> Result<(), CodecError> {
+    fn validate_constraint<'a, V: Decoded<'a>>(&self, v: &V, c: Option<&Constraint>) -> Result<(), CodecError> {
         let c = match c {
             Some(x) => x,
             None => return Ok(()),
         };
+        let n = v.as_i64();
+        let tolerant = matches!(c, Constraint::EnumTolerance(..));
+        if let Some(x) = v.as_f64().or(n.filter(|_| tolerant).map(|n| n as f64)) {
+            return check_float_constraint(x, c).map_err(CodecError::Validation);
+        }
         match c {
             Constraint::Range(intervals) => {
-                let n = match v.as_i64() {
+                let n = match n {
                     Some(x) => x,
                     None => return Ok(()), // non-numeric (Bytes, List, Struct): skip range check
                 };
@@ -1214,58 +2766,63 @@ impl Codec {
                 }
             }
             Constraint::Enum(allowed) => {
-                let n = v.as_i64();
-                if n.is_none() {
+                let Some(n) = n else {
                     return Ok(()); // non-numeric: skip enum check
-                }
-                let ok = allowed.iter().any(|l| l.as_i64() == n);
+                };
+                let ok = allowed.iter().any(|l| l.as_i64() == Some(n));
                 if !ok {
-                    return Err(CodecError::Validation("value not in allowed enum".to_string()));
+                    return Err(CodecError::Validation(enum_violation(allowed, n)));
                 }
             }
+            Constraint::EnumTolerance(..) => {} // non-numeric: skip (numbers were checked as floats)
         }
         Ok(())
     }
 
-    fn decode_base(&self, r: &mut Cursor<&[u8]>, bt: &BaseType) -> Result<Value, CodecError> {
+    fn decode_base(&self, r: &mut Cursor<&[u8]>, bt: &BaseType, order: Endianness) -> Result<Value, CodecError> {
         Ok(match bt {
-            BaseType::U8 => Value::U8(r.read_u8()?),
-            BaseType::U16 => Value::U16(self.read_u16(r)?),
-            BaseType::U32 => Value::U32(self.read_u32(r)?),
-            BaseType::U64 => Value::U64(self.read_u64(r)?),
-            BaseType::I8 => Value::I8(r.read_i8()?),
-            BaseType::I16 => Value::I16(self.read_i16(r)?),
-            BaseType::I32 => Value::I32(self.read_i32(r)?),
-            BaseType::I64 => Value::I64(self.read_i64(r)?),
-            BaseType::Bool => Value::Bool(r.read_u8()? != 0),
-            BaseType::Float => Value::Float(self.read_f32(r)?),
-            BaseType::Double => Value::Double(self.read_f64(r)?),
+            BaseType::U8 => Value::U8(self.read_u8(r)?),
+            BaseType::U16 => Value::U16(self.read_u16(r, order)?),
+            BaseType::U32 => Value::U32(self.read_u32(r, order)?),
+            BaseType::U64 => Value::U64(self.read_u64(r, order)?),
+            BaseType::U24 => Value::U32(self.read_uint(r, 3, order)? as u32),
+            BaseType::U48 => Value::U64(self.read_uint(r, 6, order)?),
+            BaseType::I8 => Value::I8(self.read_u8(r)? as i8),
+            BaseType::I16 => Value::I16(self.read_i16(r, order)?),
+            BaseType::I32 => Value::I32(self.read_i32(r, order)?),
+            BaseType::I64 => Value::I64(self.read_i64(r, order)?),
+            BaseType::Bool => Value::Bool(self.read_u8(r)? != 0),
+            BaseType::Float => Value::Float(self.read_f32(r, order)?),
+            BaseType::Double => Value::Double(self.read_f64(r, order)?),
         })
     }
 
-    fn encode_base(&self, w: &mut Vec<u8>, bt: &BaseType, v: &Value) -> Result<(), CodecError> {
+    fn encode_base(&self, w: &mut Vec<u8>, bt: &BaseType, v: &Value, order: Endianness) -> Result<(), CodecError> {
         match bt {
             BaseType::U8 => w.write_u8(v.as_u64().unwrap_or(0) as u8)?,
-            BaseType::U16 => self.write_u16(w, v.as_u64().unwrap_or(0) as u16)?,
-            BaseType::U32 => self.write_u32(w, v.as_u64().unwrap_or(0) as u32)?,
-            BaseType::U64 => self.write_u64(w, v.as_u64().unwrap_or(0))?,
+            BaseType::U16 => self.write_u16(w, v.as_u64().unwrap_or(0) as u16, order)?,
+            BaseType::U32 => self.write_u32(w, v.as_u64().unwrap_or(0) as u32, order)?,
+            BaseType::U64 => self.write_u64(w, v.as_u64().unwrap_or(0), order)?,
+            BaseType::U24 => w.write_all(&self.u64_to_bytes(v.as_u64().unwrap_or(0) & 0xFF_FFFF, 3, order))?,
+            BaseType::U48 => w.write_all(&self.u64_to_bytes(v.as_u64().unwrap_or(0) & 0xFFFF_FFFF_FFFF, 6, order))?,
             BaseType::I8 => w.write_i8(v.as_i64().unwrap_or(0) as i8)?,
-            BaseType::I16 => self.write_i16(w, v.as_i64().unwrap_or(0) as i16)?,
-            BaseType::I32 => self.write_i32(w, v.as_i64().unwrap_or(0) as i32)?,
-            BaseType::I64 => self.write_i64(w, v.as_i64().unwrap_or(0))?,
+            BaseType::I16 => self.write_i16(w, v.as_i64().unwrap_or(0) as i16, order)?,
+            BaseType::I32 => self.write_i32(w, v.as_i64().unwrap_or(0) as i32, order)?,
+            BaseType::I64 => self.write_i64(w, v.as_i64().unwrap_or(0), order)?,
             BaseType::Bool => w.write_u8(if v.as_u64().unwrap_or(0) != 0 { 1 } else { 0 })?,
-            BaseType::Float => self.write_f32(w, v.as_f32().unwrap_or(0.0)),
-            BaseType::Double => self.write_f64(w, v.as_f64().unwrap_or(0.0)),
+            BaseType::Float => self.write_f32(w, v.as_f32().unwrap_or(0.0), order),
+            BaseType::Double => self.write_f64(w, v.as_f64().unwrap_or(0.0), order),
         }
         Ok(())
     }
 
-    fn decode_sized_int(&self, r: &mut Cursor<&[u8]>, bt: &BaseType, n: u64) -> Result<Value, CodecError> {
+    fn decode_sized_int(&self, r: &mut Cursor<&[u8]>, bt: &BaseType, n: u64, order: Endianness) -> Result<Value, CodecError> {
         let bytes = ((n + 7) / 8) as usize;
         let mut buf = vec![0u8; bytes];
+        need(r, buf.len())?;
         r.read_exact(&mut buf)?;
         let mask = if n >= 64 { u64::MAX } else { (1u64 << n) - 1 };
-        let raw = self.bytes_to_u64(&buf) & mask;
+        let raw = self.bytes_to_u64(&buf, order) & mask;
         let val: i64 = match bt {
             BaseType::I8 | BaseType::I16 | BaseType::I32 | BaseType::I64 if n > 0 => {
                 let sign_bit = 1i64 << (n as i64 - 1);
@@ -1290,7 +2847,7 @@ impl Codec {
         })
     }
 
-    fn encode_sized_int(&self, w: &mut Vec<u8>, bt: &BaseType, n: u64, v: &Value) -> Result<(), CodecError> {
+    fn encode_sized_int(&self, w: &mut Vec<u8>, bt: &BaseType, n: u64, v: &Value, order: Endianness) -> Result<(), CodecError> {
         let bytes = ((n + 7) / 8) as usize;
         let mask = if n >= 64 { u64::MAX } else { (1u64 << n) - 1 };
         let raw = match bt {
@@ -1300,58 +2857,74 @@ impl Codec {
             }
             _ => v.as_u64().unwrap_or(0) & mask,
         };
-        let buf = self.u64_to_bytes(raw, bytes);
+        let buf = self.u64_to_bytes(raw, bytes, order);
         w.write_all(&buf)?;
         Ok(())
     }
 
     fn read_u8(&self, r: &mut Cursor<&[u8]>) -> Result<u8, CodecError> {
+        need(r, 1)?;
         Ok(r.read_u8()?)
     }
-    fn read_u16(&self, r: &mut Cursor<&[u8]>) -> Result<u16, CodecError> {
-        Ok(match self.endianness {
+    fn read_u16(&self, r: &mut Cursor<&[u8]>, order: Endianness) -> Result<u16, CodecError> {
+        need(r, 2)?;
+        Ok(match order {
             Endianness::Big => r.read_u16::<BigEndian>()?,
             Endianness::Little => r.read_u16::<LittleEndian>()?,
         })
     }
-    fn read_u32(&self, r: &mut Cursor<&[u8]>) -> Result<u32, CodecError> {
-        Ok(match self.endianness {
+    fn read_u32(&self, r: &mut Cursor<&[u8]>, order: Endianness) -> Result<u32, CodecError> {
+        need(r, 4)?;
+        Ok(match order {
             Endianness::Big => r.read_u32::<BigEndian>()?,
             Endianness::Little => r.read_u32::<LittleEndian>()?,
         })
     }
-    fn read_u64(&self, r: &mut Cursor<&[u8]>) -> Result<u64, CodecError> {
-        Ok(match self.endianness {
+    fn read_u64(&self, r: &mut Cursor<&[u8]>, order: Endianness) -> Result<u64, CodecError> {
+        need(r, 8)?;
+        Ok(match order {
             Endianness::Big => r.read_u64::<BigEndian>()?,
             Endianness::Little => r.read_u64::<LittleEndian>()?,
         })
     }
-    fn read_i16(&self, r: &mut Cursor<&[u8]>) -> Result<i16, CodecError> {
-        Ok(match self.endianness {
+    /// Unsigned integer of `len` bytes (`u24`, `u48`).
+    fn read_uint(&self, r: &mut Cursor<&[u8]>, len: usize, order: Endianness) -> Result<u64, CodecError> {
+        need(r, len)?;
+        let start = r.position() as usize;
+        r.set_position((start + len) as u64);
+        Ok(self.bytes_to_u64(&r.get_ref()[start..start + len], order))
+    }
+    fn read_i16(&self, r: &mut Cursor<&[u8]>, order: Endianness) -> Result<i16, CodecError> {
+        need(r, 2)?;
+        Ok(match order {
             Endianness::Big => r.read_i16::<BigEndian>()?,
             Endianness::Little => r.read_i16::<LittleEndian>()?,
         })
     }
-    fn read_i32(&self, r: &mut Cursor<&[u8]>) -> Result<i32, CodecError> {
-        Ok(match self.endianness {
+    fn read_i32(&self, r: &mut Cursor<&[u8]>, order: Endianness) -> Result<i32, CodecError> {
+        need(r, 4)?;
+        Ok(match order {
             Endianness::Big => r.read_i32::<BigEndian>()?,
             Endianness::Little => r.read_i32::<LittleEndian>()?,
         })
     }
-    fn read_i64(&self, r: &mut Cursor<&[u8]>) -> Result<i64, CodecError> {
-        Ok(match self.endianness {
+    fn read_i64(&self, r: &mut Cursor<&[u8]>, order: Endianness) -> Result<i64, CodecError> {
+        need(r, 8)?;
+        Ok(match order {
             Endianness::Big => r.read_i64::<BigEndian>()?,
             Endianness::Little => r.read_i64::<LittleEndian>()?,
         })
     }
-    fn read_f32(&self, r: &mut Cursor<&[u8]>) -> Result<f32, CodecError> {
-        Ok(match self.endianness {
+    fn read_f32(&self, r: &mut Cursor<&[u8]>, order: Endianness) -> Result<f32, CodecError> {
+        need(r, 4)?;
+        Ok(match order {
             Endianness::Big => r.read_f32::<BigEndian>()?,
             Endianness::Little => r.read_f32::<LittleEndian>()?,
         })
     }
-    fn read_f64(&self, r: &mut Cursor<&[u8]>) -> Result<f64, CodecError> {
-        Ok(match self.endianness {
+    fn read_f64(&self, r: &mut Cursor<&[u8]>, order: Endianness) -> Result<f64, CodecError> {
+        need(r, 8)?;
+        Ok(match order {
             Endianness::Big => r.read_f64::<BigEndian>()?,
             Endianness::Little => r.read_f64::<LittleEndian>()?,
         })
@@ -1361,63 +2934,63 @@ impl Codec {
         w.write_u8(v)?;
         Ok(())
     }
-    fn write_u16(&self, w: &mut Vec<u8>, v: u16) -> Result<(), CodecError> {
-        match self.endianness {
+    fn write_u16(&self, w: &mut Vec<u8>, v: u16, order: Endianness) -> Result<(), CodecError> {
+        match order {
             Endianness::Big => w.write_u16::<BigEndian>(v)?,
             Endianness::Little => w.write_u16::<LittleEndian>(v)?,
         }
         Ok(())
     }
-    fn write_u32(&self, w: &mut Vec<u8>, v: u32) -> Result<(), CodecError> {
-        match self.endianness {
+    fn write_u32(&self, w: &mut Vec<u8>, v: u32, order: Endianness) -> Result<(), CodecError> {
+        match order {
             Endianness::Big => w.write_u32::<BigEndian>(v)?,
             Endianness::Little => w.write_u32::<LittleEndian>(v)?,
         }
         Ok(())
     }
-    fn write_u64(&self, w: &mut Vec<u8>, v: u64) -> Result<(), CodecError> {
-        match self.endianness {
+    fn write_u64(&self, w: &mut Vec<u8>, v: u64, order: Endianness) -> Result<(), CodecError> {
+        match order {
             Endianness::Big => w.write_u64::<BigEndian>(v)?,
             Endianness::Little => w.write_u64::<LittleEndian>(v)?,
         }
         Ok(())
     }
-    fn write_i16(&self, w: &mut Vec<u8>, v: i16) -> Result<(), CodecError> {
-        match self.endianness {
+    fn write_i16(&self, w: &mut Vec<u8>, v: i16, order: Endianness) -> Result<(), CodecError> {
+        match order {
             Endianness::Big => w.write_i16::<BigEndian>(v)?,
             Endianness::Little => w.write_i16::<LittleEndian>(v)?,
         }
         Ok(())
     }
-    fn write_i32(&self, w: &mut Vec<u8>, v: i32) -> Result<(), CodecError> {
-        match self.endianness {
+    fn write_i32(&self, w: &mut Vec<u8>, v: i32, order: Endianness) -> Result<(), CodecError> {
+        match order {
             Endianness::Big => w.write_i32::<BigEndian>(v)?,
             Endianness::Little => w.write_i32::<LittleEndian>(v)?,
         }
         Ok(())
     }
-    fn write_i64(&self, w: &mut Vec<u8>, v: i64) -> Result<(), CodecError> {
-        match self.endianness {
+    fn write_i64(&self, w: &mut Vec<u8>, v: i64, order: Endianness) -> Result<(), CodecError> {
+        match order {
             Endianness::Big => w.write_i64::<BigEndian>(v)?,
             Endianness::Little => w.write_i64::<LittleEndian>(v)?,
         }
         Ok(())
     }
-    fn write_f32(&self, w: &mut Vec<u8>, v: f32) {
-        let _ = match self.endianness {
+    fn write_f32(&self, w: &mut Vec<u8>, v: f32, order: Endianness) {
+        let _ = match order {
             Endianness::Big => w.write_f32::<BigEndian>(v),
             Endianness::Little => w.write_f32::<LittleEndian>(v),
         };
     }
-    fn write_f64(&self, w: &mut Vec<u8>, v: f64) {
-        let _ = match self.endianness {
+    fn write_f64(&self, w: &mut Vec<u8>, v: f64, order: Endianness) {
+        let _ = match order {
             Endianness::Big => w.write_f64::<BigEndian>(v),
             Endianness::Little => w.write_f64::<LittleEndian>(v),
         };
     }
 
-    fn bytes_to_u64(&self, buf: &[u8]) -> u64 {
-        match self.endianness {
+    fn bytes_to_u64(&self, buf: &[u8], order: Endianness) -> u64 {
+        match order {
             Endianness::Big => match buf.len() {
                 1 => buf[0] as u64,
                 2 => BigEndian::read_u16(buf) as u64,
@@ -1444,9 +3017,9 @@ impl Codec {
         }
     }
 
-    fn u64_to_bytes(&self, v: u64, len: usize) -> Vec<u8> {
+    fn u64_to_bytes(&self, v: u64, len: usize, order: Endianness) -> Vec<u8> {
         let mut buf = vec![0u8; len];
-        match self.endianness {
+        match order {
             Endianness::Big => {
                 match len {
                     1 => buf[0] = v as u8,
@@ -1536,28 +3109,317 @@ struct DecodeContext {
     /// When decoding message fields: set so Optional can read the correct bit by field name (message-level mapping only).
     current_message_name: Option<String>,
     current_field_name: Option<String>,
+    /// Byte order picked by an `endianness from` field decoded so far; None: the codec's.
+    endianness: Option<Endianness>,
+    /// Offset of the message being decoded, and of its fields named by a `checksum(alg, from: field)`.
+    message_start: usize,
+    field_starts: HashMap<String, usize>,
+    /// Struct and embedded message references entered so far (see [`DecodeLimits::max_recursion_depth`]).
+    depth: usize,
+    /// With [`Codec::decode_message_with_presence`]: whether each optional field of the message was present.
+    presence: Option<HashMap<String, bool>>,
 }
 
 impl DecodeContext {
     fn get(&self, k: &str) -> Option<&Value> {
         self.values.get(k)
     }
+    /// First byte a checksum covers: the start of field `from`, else of the message.
+    fn checksum_start(&self, from: Option<&str>) -> Result<usize, CodecError> {
+        checksum_start(self.message_start, &self.field_starts, from)
+    }
     fn set(&mut self, k: String, v: Value) {
         self.values.insert(k, v);
     }
 }
 
-struct EncodeContext {
-    values: HashMap<String, Value>,
+/// What the decoder builds: the owning [`Value`] or the borrowing [`ValueRef`]. `'a` covers both the
+/// input bytes and the protocol (field names).
+trait Decoded<'a>: Sized {
+    type Key: Eq + std::hash::Hash + std::borrow::Borrow<str>;
+    fn key(name: &'a str) -> Self::Key;
+    /// Scalar, or a value rebuilt while decoding.
+    fn owned(v: Value) -> Self;
+    /// Bytes read verbatim from the input.
+    fn bytes(b: &'a [u8]) -> Self;
+    fn list(items: Vec<Self>) -> Self;
+    fn record(fields: HashMap<Self::Key, Self>) -> Self;
+    fn as_i64(&self) -> Option<i64>;
+    /// `float`/`double` value (constraints on floats compare as floats).
+    fn as_f64(&self) -> Option<f64>;
+    fn as_bool(&self) -> Option<bool>;
+    /// Operand of a message `check { ... }`.
+    fn check_value(&self) -> Option<CheckValue> {
+        self.as_i64()
+            .map(CheckValue::Int)
+            .or_else(|| self.as_f64().map(CheckValue::Float))
+            .or_else(|| self.as_bool().map(CheckValue::Bool))
+    }
+    /// Copy kept in [`DecodeContext`] for later `if` conditions, array lengths and widths (only numbers are read).
+    fn context_value(&self) -> Option<Value>;
+}
+
+impl<'a> Decoded<'a> for Value {
+    type Key = String;
+    fn key(name: &'a str) -> String {
+        name.to_string()
+    }
+    fn owned(v: Value) -> Self {
+        v
+    }
+    fn bytes(b: &'a [u8]) -> Self {
+        Value::Bytes(b.to_vec())
+    }
+    fn list(items: Vec<Self>) -> Self {
+        Value::List(items)
+    }
+    fn record(fields: HashMap<String, Self>) -> Self {
+        Value::Struct(fields)
+    }
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Float(_) | Value::Double(_) => None,
+            _ => Value::as_i64(self),
+        }
+    }
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Float(x) => Some(*x as f64),
+            Value::Double(x) => Some(*x),
+            _ => None,
+        }
+    }
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+    fn context_value(&self) -> Option<Value> {
+        Some(self.clone())
+    }
+}
+
+impl<'a> Decoded<'a> for ValueRef<'a> {
+    type Key = &'a str;
+    fn key(name: &'a str) -> &'a str {
+        name
+    }
+    fn owned(v: Value) -> Self {
+        ValueRef::from_owned(v)
+    }
+    fn bytes(b: &'a [u8]) -> Self {
+        ValueRef::Bytes(b)
+    }
+    fn list(items: Vec<Self>) -> Self {
+        ValueRef::List(items)
+    }
+    fn record(fields: HashMap<&'a str, Self>) -> Self {
+        ValueRef::Struct(fields)
+    }
+    fn as_i64(&self) -> Option<i64> {
+        ValueRef::as_i64(self)
+    }
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            ValueRef::Float(x) => Some(*x as f64),
+            ValueRef::Double(x) => Some(*x),
+            _ => None,
+        }
+    }
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            ValueRef::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+    fn context_value(&self) -> Option<Value> {
+        self.as_i64().map(|_| self.to_owned())
+    }
+}
+
+/// Borrows the caller's value map; only field defaults filled in during encode are owned.
+struct EncodeContext<'a> {
+    values: &'a HashMap<String, Value>,
+    defaults: HashMap<String, Value>,
+    /// `length_of` / `count_of` values computed with [`EncodeOptions::auto_length`]; they take precedence
+    /// over `values`.
+    computed: HashMap<String, Value>,
     bit_write: BitWriteState,
+    /// Byte order picked by an `endianness from` field encoded so far; None: the codec's.
+    endianness: Option<Endianness>,
+    /// Offset in the output of the message being encoded, and of its fields named by a `checksum(alg, from: field)`.
+    message_start: usize,
+    field_starts: HashMap<String, usize>,
+    /// Encoding a field on its own to measure it ([`scratch`](Self::scratch)): a `bytes_list` skips the check
+    /// against its length field, which may be the value being computed.
+    measuring: bool,
 }
 
-impl EncodeContext {
-    fn from_values(m: &HashMap<String, Value>) -> Self {
-        EncodeContext { values: m.clone(), bit_write: BitWriteState::default() }
+impl<'a> EncodeContext<'a> {
+    fn from_values(m: &'a HashMap<String, Value>) -> Self {
+        EncodeContext {
+            values: m,
+            defaults: HashMap::new(),
+            computed: HashMap::new(),
+            bit_write: BitWriteState::default(),
+            endianness: None,
+            message_start: 0,
+            field_starts: HashMap::new(),
+            measuring: false,
+        }
+    }
+    /// Copy of this scope for encoding one field on its own (to measure it), starting a fresh byte.
+    fn scratch(&self) -> Self {
+        EncodeContext {
+            values: self.values,
+            defaults: self.defaults.clone(),
+            computed: self.computed.clone(),
+            bit_write: BitWriteState::default(),
+            endianness: self.endianness,
+            message_start: self.message_start,
+            field_starts: self.field_starts.clone(),
+            measuring: true,
+        }
+    }
+    /// Nested scope (struct, embedded message) keeping the byte order of the enclosing one.
+    fn nested(&self, m: &'a HashMap<String, Value>) -> Self {
+        EncodeContext {
+            endianness: self.endianness,
+            message_start: self.message_start,
+            field_starts: self.field_starts.clone(),
+            measuring: self.measuring,
+            ..EncodeContext::from_values(m)
+        }
+    }
+    /// First byte a checksum covers: the start of field `from`, else of the message.
+    fn checksum_start(&self, from: Option<&str>) -> Result<usize, CodecError> {
+        checksum_start(self.message_start, &self.field_starts, from)
     }
     fn get(&self, k: &str) -> Option<&Value> {
-        self.values.get(k)
+        if !self.computed.is_empty() {
+            if let Some(v) = self.computed.get(k) {
+                return Some(v);
+            }
+        }
+        self.values.get(k).or_else(|| self.defaults.get(k))
+    }
+    /// Width field of a `dyn_int` the caller did not supply: the fewest bytes (at least 1) that hold the value.
+    fn fill_dyn_int_width(&mut self, name: &str, spec: &TypeSpec) {
+        if let TypeSpec::DynInt(width_field) = spec {
+            if self.get(width_field).is_none() {
+                let val = self.get(name).and_then(Value::as_u64).unwrap_or(0);
+                let width = (64 - val.leading_zeros()).div_ceil(8).max(1);
+                self.defaults.insert(width_field.clone(), Value::U64(width as u64));
+            }
+        }
+    }
+    /// Use a field's `= ...` default when the caller did not supply the field.
+    fn fill_default(&mut self, name: &str, spec: &TypeSpec, default: Option<&Literal>) {
+        if let Some(d) = default {
+            if self.get(name).is_none() {
+                self.defaults.insert(name.to_string(), literal_value(spec, d));
+            }
+        }
+    }
+}
+
+/// Scale of an integer field's `quantum` (raw value times scale = physical value); None for other fields.
+fn quantum_scale(spec: &TypeSpec, quantum: Option<&str>) -> Option<f64> {
+    match spec {
+        TypeSpec::Base(bt) if !matches!(bt, BaseType::Bool | BaseType::Float | BaseType::Double) => {}
+        TypeSpec::SizedInt(..) | TypeSpec::Bitfield(_) => {}
+        _ => return None,
+    }
+    parse_quantum(quantum?).map(|(scale, _)| scale)
+}
+
+/// Fields whose start a `checksum(alg, from: field)` of `fields` needs (usually none).
+fn checksum_from_fields(fields: &[MessageField]) -> Vec<&str> {
+    fields
+        .iter()
+        .filter_map(|f| match &f.type_spec {
+            TypeSpec::Checksum { from: Some(name), .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn checksum_start(message_start: usize, field_starts: &HashMap<String, usize>, from: Option<&str>) -> Result<usize, CodecError> {
+    match from {
+        None => Ok(message_start),
+        Some(name) => field_starts
+            .get(name)
+            .copied()
+            .ok_or_else(|| CodecError::Validation(format!("checksum from {}: no such field before the checksum", name))),
+    }
+}
+
+/// `length_of` / `count_of` value `n` of field `name`, as the value its `width` decodes to; it must fit.
+fn auto_length_value(name: &str, n: usize, width: &BaseType) -> Result<Value, CodecError> {
+    let max = type_spec_integer_range(&TypeSpec::Base(width.clone())).map_or(i64::MAX, |(_, max)| max);
+    if n as u64 > max as u64 {
+        return Err(CodecError::Validation(format!("{}: length {} does not fit in {:?}", name, n, width)));
+    }
+    Ok(match width {
+        BaseType::U8 => Value::U8(n as u8),
+        BaseType::U16 => Value::U16(n as u16),
+        BaseType::U24 | BaseType::U32 => Value::U32(n as u32),
+        _ => Value::U64(n as u64),
+    })
+}
+
+/// Value of a transport field default, in the field's type as for [`literal_value`].
+fn transport_literal_value(spec: &TransportTypeSpec, lit: &Literal) -> Value {
+    match spec {
+        TransportTypeSpec::Base(bt) => literal_value(&TypeSpec::Base(bt.clone()), lit),
+        TransportTypeSpec::SizedInt(bt, n) => literal_value(&TypeSpec::SizedInt(bt.clone(), *n), lit),
+        TransportTypeSpec::Bitfield(n) => literal_value(&TypeSpec::Bitfield(*n), lit),
+        TransportTypeSpec::Padding(_) | TransportTypeSpec::Magic(_) => literal_value(&TypeSpec::Remaining, lit),
+    }
+}
+
+/// Value of a field default for `spec`: a `[..]` literal becomes the list elements, an optional's default
+/// its present inner value, and an integer the value its base type decodes to (`version: u8 = 1` gives
+/// `Value::U8(1)`).
+fn literal_value(spec: &TypeSpec, lit: &Literal) -> Value {
+    match (spec, lit) {
+        (
+            TypeSpec::List(elem)
+            | TypeSpec::RepList(elem)
+            | TypeSpec::LengthPrefixedList(elem, _)
+            | TypeSpec::BytesList(elem, _)
+            | TypeSpec::Array(elem, ..),
+            Literal::List(items),
+        ) => {
+            Value::List(items.iter().map(|l| literal_value(elem, l)).collect())
+        }
+        (TypeSpec::Optional(elem, _), l) => Value::List(vec![literal_value(elem, l)]),
+        (TypeSpec::Base(BaseType::Float), l) if l.as_f64().is_some() => Value::Float(l.as_f64().unwrap_or(0.0) as f32),
+        (TypeSpec::Base(BaseType::Double), l) if l.as_f64().is_some() => Value::Double(l.as_f64().unwrap_or(0.0)),
+        // In range only: a literal the type cannot hold is left for the encoder to reject.
+        (TypeSpec::Base(bt) | TypeSpec::SizedInt(bt, _), Literal::Int(_) | Literal::Hex(_))
+            if !matches!(bt, BaseType::Bool) && lit.as_i64().zip(type_spec_integer_range(spec)).is_some_and(|(v, (min, max))| v >= min && v <= max) =>
+        {
+            let v = lit.as_i64().unwrap_or(0);
+            match bt {
+                BaseType::U8 => Value::U8(v as u8),
+                BaseType::U16 => Value::U16(v as u16),
+                BaseType::U24 | BaseType::U32 => Value::U32(v as u32),
+                BaseType::I8 => Value::I8(v as i8),
+                BaseType::I16 => Value::I16(v as i16),
+                BaseType::I32 => Value::I32(v as i32),
+                BaseType::I64 => Value::I64(v),
+                _ => Value::U64(v as u64),
+            }
+        }
+        (_, Literal::Int(i)) if *i < 0 => Value::I64(*i),
+        (_, Literal::Int(i)) => Value::U64(*i as u64),
+        (_, Literal::Hex(h)) => Value::U64(*h),
+        (_, Literal::Float(x)) => Value::Double(*x),
+        (_, Literal::Bool(b)) => Value::Bool(*b),
+        (_, Literal::String(s)) => Value::Bytes(s.as_bytes().to_vec()),
+        (_, Literal::List(items)) => Value::List(items.iter().map(|l| literal_value(spec, l)).collect()),
     }
 }
 
@@ -0,0 +1,503 @@
+//! Export of a resolved protocol to other schema languages.
+//!
+//! [`to_kaitai`] writes a [Kaitai Struct](https://kaitai.io) `.ksy` schema, so captures can be opened in the
+//! Kaitai viewers and parsed by its generated code. Messages and structs become `types`, enums become
+//! `enums`, and the transport and payload selector form the top-level `seq`. Constructs Kaitai cannot express
+//! (custom codecs, `dyn_int` widths, inner transport headers, ...) are written as `#` comments in place of the
+//! field, so the export never fails but the schema is incomplete where such a comment appears.
+
+use crate::ast::*;
+
+/// Kaitai Struct (`.ksy`) schema for `resolved`.
+///
+/// Names are converted to Kaitai's lower snake case (`Cat048Record` -> `cat048_record`). Multi-byte values are
+/// big-endian (`meta/endian: be`); bit fields use `bit-endian: le`, the codec's LSB-first packing. `list<T>`
+/// and `rep_list<T>` become a count attribute (`<name>_count`, `<name>_rep`) followed by a repeated one,
+/// `optional<T>` an `if:` on its presence bit (FSPEC bitmaps with 7 presence bits + FX per byte, or
+/// `bitmap(n, 0)` bits) or on a leading `<name>_present` flag, and an `if field == value` condition an `if:`. A repeated
+/// payload is read until the end of the stream: open one data block at a time.
+pub fn to_kaitai(resolved: &ResolvedProtocol) -> String {
+    let protocol = &resolved.protocol;
+    let mut out = String::new();
+    out.push_str("# Kaitai Struct schema exported by aiprotodsl::codegen::to_kaitai.\n");
+    out.push_str("meta:\n  id: protocol\n  endian: be\n  bit-endian: le\n");
+    if let Some(switch) = &protocol.endianness_switch {
+        out.push_str(&format!("  # endianness from {}: not exported, every field is big-endian\n", switch.field));
+    }
+
+    let mut top = Vec::new();
+    if let Some(transport) = &protocol.transport {
+        top.extend(transport.fields.iter().map(transport_item));
+    }
+    if protocol.inner_transport.is_some() {
+        top.push(Item::Comment("inner_transport: per-record headers are not exported".to_string()));
+    }
+    if let Some(payload) = &protocol.payload {
+        top.extend(payload_items(payload));
+    }
+    if !top.is_empty() {
+        out.push_str("seq:\n");
+        write_items(&mut out, &top, 2);
+    }
+
+    let mut types = Vec::new();
+    for msg in &protocol.messages {
+        let mut fields = FieldList::new(resolved, resolved.bitmap_presence_mapping_message(&msg.name));
+        for f in &msg.fields {
+            fields.push(&f.name, &f.type_spec, f.condition.as_ref(), doc_text(f.doc.as_deref(), f.quantum.as_deref()));
+        }
+        if let Some(sel) = &msg.selector {
+            fields.items.push(body_selector_item(sel));
+        }
+        types.push((snake(&msg.name), fields));
+    }
+    for s in &protocol.structs {
+        let mut fields = FieldList::new(resolved, resolved.bitmap_presence_mapping_struct(&s.name));
+        for f in s.wire_fields() {
+            fields.push(&f.name, &f.type_spec, f.condition.as_ref(), doc_text(None, f.quantum.as_deref()));
+        }
+        types.push((snake(&s.name), fields));
+    }
+    if !types.is_empty() {
+        out.push_str("types:\n");
+        for (name, fields) in &types {
+            write_type(&mut out, name, fields, 2);
+        }
+    }
+
+    if !protocol.enum_defs.is_empty() {
+        out.push_str("enums:\n");
+        for e in &protocol.enum_defs {
+            out.push_str(&format!("  {}:\n", snake(&e.name)));
+            for (variant, value) in &e.variants {
+                match value.as_i64() {
+                    Some(v) => out.push_str(&format!("    {}: {}\n", v, snake(variant))),
+                    None => out.push_str(&format!("    # {}: not an integer value\n", variant)),
+                }
+            }
+        }
+    }
+    out
+}
+
+/// An entry of a Kaitai `seq`: an attribute (`id`, its properties as one `key: value` line each with nested
+/// lines indented by two spaces, and `doc` parts joined into its `doc:`) or a comment for a field that is
+/// not exported.
+enum Item {
+    Attr { id: String, lines: Vec<String>, doc: Vec<String> },
+    Comment(String),
+}
+
+impl Item {
+    fn attr(id: impl Into<String>, lines: Vec<String>) -> Self {
+        Item::Attr { id: id.into(), lines, doc: Vec::new() }
+    }
+
+    fn unsupported(name: &str, what: impl std::fmt::Display) -> Self {
+        Item::Comment(format!("{}: {} is not supported by the Kaitai export", name, what))
+    }
+
+    /// Adds `if: cond`, combined with an existing condition.
+    fn with_if(mut self, cond: &str) -> Self {
+        if let Item::Attr { lines, .. } = &mut self {
+            match lines.iter_mut().find(|l| l.starts_with("if: ")) {
+                Some(l) => *l = format!("if: ({}) and ({})", cond, &l[4..]),
+                None => lines.push(format!("if: {}", cond)),
+            }
+        }
+        self
+    }
+
+    fn with_doc(mut self, text: Option<&str>) -> Self {
+        if let (Item::Attr { doc, .. }, Some(text)) = (&mut self, text) {
+            doc.push(text.to_string());
+        }
+        self
+    }
+}
+
+/// Where the optionals of a message or struct take their presence from.
+enum Presence<'a> {
+    /// No bitmap so far: each optional has its own leading flag.
+    Flag,
+    /// FSPEC bytes (`bitmap(n, 7)`): logical bit `i` is bit `7 - i % 7` of byte `i / 7`, bit 0 is FX.
+    FspecBytes { id: String, mapping: Option<&'a BitmapPresenceMapping>, next: u32 },
+    /// `bitmap(n, 0)`: logical bit `i` is the `i`-th bit read.
+    FspecBits { id: String, mapping: Option<&'a BitmapPresenceMapping>, next: u32 },
+    /// A presence layout that is not exported; the optionals after it are left as comments.
+    Unsupported,
+}
+
+/// The `seq` of one message or struct, with the nested types its `repeat(n) { ... }` groups need.
+struct FieldList<'a> {
+    resolved: &'a ResolvedProtocol,
+    presence: Presence<'a>,
+    mapping: Option<&'a BitmapPresenceMapping>,
+    /// Bits already read from the current byte by bit fields (0: byte aligned).
+    bit_pos: u64,
+    items: Vec<Item>,
+    nested: Vec<(String, FieldList<'a>)>,
+}
+
+impl<'a> FieldList<'a> {
+    fn new(resolved: &'a ResolvedProtocol, mapping: Option<&'a BitmapPresenceMapping>) -> Self {
+        FieldList { resolved, presence: Presence::Flag, mapping, bit_pos: 0, items: Vec::new(), nested: Vec::new() }
+    }
+
+    fn push(&mut self, name: &str, spec: &'a TypeSpec, condition: Option<&Condition>, doc: Option<String>) {
+        let items = self.field_items(name, spec);
+        let cond = match condition.map(|c| (c, c.value.as_i64())) {
+            Some((c, Some(v))) => Some(format!("{} == {}", snake(&c.field), v)),
+            Some((_, None)) => return self.items.push(Item::unsupported(name, "an `if` condition on a non-integer value")),
+            None => None,
+        };
+        // The doc goes on the field's value; a list's count attribute comes before it.
+        let last = items.len().saturating_sub(1);
+        for (i, item) in items.into_iter().enumerate() {
+            let item = match &cond {
+                Some(cond) => item.with_if(cond),
+                None => item,
+            };
+            self.items.push(if i == last { item.with_doc(doc.as_deref()) } else { item });
+        }
+    }
+
+    /// Items for field `name` of type `spec`; most types need one attribute, lists two.
+    fn field_items(&mut self, name: &str, spec: &'a TypeSpec) -> Vec<Item> {
+        let id = snake(name);
+        // Byte reads skip the rest of a partly read byte, as Kaitai does; bit reads continue it.
+        let pos = std::mem::take(&mut self.bit_pos);
+        let mut bits = |n: u64| self.bit_pos = (pos + n) % 8;
+        match spec {
+            TypeSpec::Bitfield(n) | TypeSpec::Padding(PaddingKind::Bits(n)) => {
+                bits(*n);
+                vec![Item::attr(id, vec![format!("type: b{}", n)])]
+            }
+            TypeSpec::SizedInt(bt, n) if *n < 8 || pos != 0 => {
+                bits(*n);
+                vec![Item::attr(id, vec![format!("type: b{}", n)]).with_doc(is_signed(bt).then_some("signed (two's complement)"))]
+            }
+            TypeSpec::SizedInt(bt, n) => {
+                let ty = sized_int_type(bt, *n);
+                let low = (n % 8 != 0).then(|| format!("low {} bits", n));
+                let signed = (ty.starts_with('b') && is_signed(bt)).then(|| "signed (two's complement)".to_string());
+                let doc = low.into_iter().chain(signed).collect::<Vec<_>>().join(", ");
+                let doc = (!doc.is_empty()).then_some(doc);
+                vec![Item::attr(id, vec![format!("type: {}", ty)]).with_doc(doc.as_deref())]
+            }
+            TypeSpec::Padding(PaddingKind::Bytes(n)) | TypeSpec::Unknown(n) => vec![Item::attr(id, vec![format!("size: {}", n)])],
+            TypeSpec::Bcd { bytes, .. } => vec![Item::attr(id, vec![format!("size: {}", bytes)]).with_doc(Some("packed BCD"))],
+            TypeSpec::Remaining => vec![Item::attr(id, vec!["size-eos: true".to_string()])],
+            TypeSpec::LengthOf(_, bt) | TypeSpec::CountOf(_, bt) => {
+                vec![Item::attr(id, vec![format!("type: {}", base_type(bt))])]
+            }
+            TypeSpec::BitmapPresence { total_bits, presence_per_block: 7, .. } => {
+                self.presence = Presence::FspecBytes { id: id.clone(), mapping: self.mapping, next: 0 };
+                let max_bytes = total_bits.div_ceil(7);
+                let lines = vec!["type: u1".to_string(), "repeat: until".to_string(), "repeat-until: (_ & 1) == 0".to_string()];
+                vec![Item::attr(id, lines).with_doc(Some(&format!("FSPEC, at most {} bytes", max_bytes)))]
+            }
+            // A single presence bit is bit 0 of the current byte, and the rest of the byte is skipped: only
+            // expressible when the byte is not partly read yet.
+            TypeSpec::BitmapPresence { total_bits: 1, presence_per_block: 0, .. } if pos == 0 => {
+                self.presence = Presence::FspecBits { id: id.clone(), mapping: self.mapping, next: 0 };
+                vec![Item::attr(id, vec!["type: b8".to_string()])]
+            }
+            TypeSpec::BitmapPresence { total_bits, presence_per_block: 0, .. } if *total_bits > 1 => {
+                bits(u64::from(*total_bits));
+                self.presence = Presence::FspecBits { id: id.clone(), mapping: self.mapping, next: 0 };
+                vec![Item::attr(id, vec![format!("type: b{}", total_bits)])]
+            }
+            TypeSpec::BitmapPresence { total_bits: 1, presence_per_block: 0, .. } => {
+                self.presence = Presence::Unsupported;
+                vec![Item::unsupported(name, "bitmap(1, 0) in a partly read byte")]
+            }
+            TypeSpec::BitmapPresence { total_bits, presence_per_block, .. } => {
+                self.presence = Presence::Unsupported;
+                vec![Item::unsupported(name, format_args!("bitmap({}, {})", total_bits, presence_per_block))]
+            }
+            TypeSpec::PresenceBits(n, _) => {
+                self.presence = Presence::Unsupported;
+                vec![Item::unsupported(name, format_args!("presence_bits({})", n))]
+            }
+            TypeSpec::Bits(members) => {
+                bits(members.iter().map(|(_, w)| w).sum());
+                members.iter().map(|(m, w)| Item::attr(format!("{}_{}", id, snake(m)), vec![format!("type: b{}", w)])).collect()
+            }
+            TypeSpec::Group { count, fields } => {
+                let mut group = FieldList::new(self.resolved, None);
+                for f in fields {
+                    group.push(&f.name, &f.type_spec, f.condition.as_ref(), doc_text(None, f.quantum.as_deref()));
+                }
+                self.nested.push((id.clone(), group));
+                vec![Item::attr(id.clone(), vec![format!("type: {}", id), "repeat: expr".to_string(), format!("repeat-expr: {}", array_len(count))])]
+            }
+            TypeSpec::Array(elem, len, stride) => {
+                let Some(mut lines) = self.element_lines(elem) else {
+                    return vec![Item::unsupported(name, "an array of this element type")];
+                };
+                if let Some(stride) = stride {
+                    // Kaitai sizes substreams of user types only.
+                    if !matches!(elem.as_ref(), TypeSpec::StructRef(s) if self.resolved.get_enum(s).is_none()) {
+                        return vec![Item::unsupported(name, "stride(m) on a non-struct element")];
+                    }
+                    lines.push(format!("size: {}", stride));
+                }
+                lines.extend(["repeat: expr".to_string(), format!("repeat-expr: {}", array_len(len))]);
+                vec![Item::attr(id, lines)]
+            }
+            TypeSpec::List(elem) | TypeSpec::RepList(elem) => {
+                let Some(mut lines) = self.element_lines(elem) else {
+                    return vec![Item::unsupported(name, "a list of this element type")];
+                };
+                let (count, ty) = if matches!(spec, TypeSpec::List(_)) { (format!("{}_count", id), "u4") } else { (format!("{}_rep", id), "u1") };
+                lines.extend(["repeat: expr".to_string(), format!("repeat-expr: {}", count)]);
+                vec![Item::attr(count, vec![format!("type: {}", ty)]), Item::attr(id, lines)]
+            }
+            TypeSpec::OctetsFx => vec![Item::attr(
+                id,
+                vec!["type: u1".to_string(), "repeat: until".to_string(), "repeat-until: (_ & 0x80) == 0".to_string()],
+            )],
+            TypeSpec::Optional(elem, flag) => {
+                self.bit_pos = pos;
+                self.optional_items(name, elem, flag)
+            }
+            TypeSpec::Timestamp { base, quantum, .. } => {
+                self.bit_pos = pos;
+                self.field_items(name, base).into_iter().map(|i| i.with_doc(Some(&format!("timestamp, {} per tick", quantum)))).collect()
+            }
+            TypeSpec::LengthPrefixedList(..) => vec![Item::unsupported(name, "length_prefixed_list")],
+            TypeSpec::BytesList(_, len) => vec![Item::unsupported(name, format_args!("bytes_list({})", len))],
+            TypeSpec::DynInt(field) => vec![Item::unsupported(name, format_args!("dyn_int({})", field))],
+            TypeSpec::ReverseBits(_) => vec![Item::unsupported(name, "reverse_bits")],
+            TypeSpec::Custom(codec) => vec![Item::unsupported(name, format_args!("custom(\"{}\")", codec))],
+            TypeSpec::Select { field, cases, default } => {
+                let mut lines = vec!["type:".to_string(), format!("  switch-on: {}", snake(field)), "  cases:".to_string()];
+                lines.extend(cases.iter().map(|(k, target)| format!("    {}: {}", k, snake(target))));
+                lines.extend(default.iter().map(|target| format!("    _: {}", snake(target))));
+                vec![Item::attr(id, lines)]
+            }
+            _ => match self.element_lines(spec) {
+                Some(lines) => vec![Item::attr(id, lines)],
+                None => vec![Item::unsupported(name, "this type")],
+            },
+        }
+    }
+
+    /// Items for `name: optional<elem>`: `elem` under an `if:` on its presence.
+    fn optional_items(&mut self, name: &str, elem: &'a TypeSpec, flag: &BaseType) -> Vec<Item> {
+        let id = snake(name);
+        let (flag_item, cond) = match &mut self.presence {
+            Presence::Flag => {
+                let flag_id = format!("{}_present", id);
+                let cond = format!("{} != 0", flag_id);
+                (Some(Item::attr(flag_id, vec![format!("type: {}", base_type(flag))])), cond)
+            }
+            Presence::FspecBytes { id: fspec, mapping, next } => {
+                let bit = presence_bit(*mapping, name, next);
+                let (byte, mask) = (bit / 7, 0x80u32 >> (bit % 7));
+                let test = format!("({}[{}] & 0x{:02x}) != 0", fspec, byte, mask);
+                (None, if byte == 0 { test } else { format!("{}.size > {} and {}", fspec, byte, test) })
+            }
+            Presence::FspecBits { id: fspec, mapping, next } => {
+                let bit = presence_bit(*mapping, name, next);
+                (None, format!("(({} >> {}) & 1) != 0", fspec, bit))
+            }
+            Presence::Unsupported => return vec![Item::unsupported(name, "presence from this bitmap")],
+        };
+        if flag_item.is_some() {
+            self.bit_pos = 0;
+        }
+        let inner = self.field_items(name, elem);
+        flag_item.into_iter().chain(inner.into_iter().map(|i| i.with_if(&cond))).collect()
+    }
+
+    /// `type:` (plus `enum:`) lines of a type that is read as a single value, for attributes and repeated
+    /// elements. None for types that need more than one attribute or are not exported. A sized integer
+    /// here starts on a byte boundary.
+    fn element_lines(&self, spec: &TypeSpec) -> Option<Vec<String>> {
+        Some(match spec {
+            TypeSpec::Base(b) => vec![format!("type: {}", base_type(b))],
+            TypeSpec::SizedInt(bt, n) => vec![format!("type: {}", sized_int_type(bt, *n))],
+            TypeSpec::Bitfield(n) | TypeSpec::Padding(PaddingKind::Bits(n)) => vec![format!("type: b{}", n)],
+            TypeSpec::StructRef(name) | TypeSpec::Enum(name) => match self.resolved.get_enum(name) {
+                Some(e) => vec![format!("type: {}", base_type(&e.base)), format!("enum: {}", snake(name))],
+                None => vec![format!("type: {}", snake(name))],
+            },
+            TypeSpec::CString => vec!["terminator: 0".to_string()],
+            TypeSpec::FixedString(n) => vec![format!("size: {}", n), "pad-right: 0".to_string()],
+            TypeSpec::Checksum { algorithm, .. } => vec![format!("type: {}", base_type(&algorithm.base_type()))],
+            _ => return None,
+        })
+    }
+}
+
+/// Logical presence bit of optional `name`: its mapped bit, else the next unmapped one.
+fn presence_bit(mapping: Option<&BitmapPresenceMapping>, name: &str, next: &mut u32) -> u32 {
+    mapping.and_then(|m| m.bit_for_field(name)).unwrap_or_else(|| {
+        *next += 1;
+        *next - 1
+    })
+}
+
+fn transport_item(f: &TransportField) -> Item {
+    let id = snake(&f.name);
+    // Transport bit fields and sized integers take whole bytes.
+    let lines = match &f.type_spec {
+        TransportTypeSpec::Base(b) => vec![format!("type: {}", base_type(b))],
+        TransportTypeSpec::SizedInt(bt, n) => vec![format!("type: {}", sized_int_type(bt, *n))],
+        TransportTypeSpec::Bitfield(n) => vec![format!("type: {}", sized_int_type(&BaseType::U64, *n))],
+        TransportTypeSpec::Padding(PaddingKind::Bits(n)) => vec![format!("size: {}", n.div_ceil(8))],
+        TransportTypeSpec::Padding(PaddingKind::Bytes(n)) => vec![format!("size: {}", n)],
+        TransportTypeSpec::Magic(bytes) => {
+            vec![format!("contents: [{}]", bytes.iter().map(|b| format!("0x{:02x}", b)).collect::<Vec<_>>().join(", "))]
+        }
+    };
+    Item::attr(id, lines).with_doc(doc_text(None, f.quantum.as_deref()).as_deref())
+}
+
+/// The `records` attribute: the selected message type, repeated to the end of the stream for a list payload.
+fn payload_items(payload: &PayloadSection) -> Vec<Item> {
+    let repeated = payload.repeated || payload.selector.as_ref().is_some_and(|s| s.value_to_message.iter().any(|(_, _, list)| *list));
+    let mut lines = match &payload.selector {
+        Some(sel) if !sel.extra_fields.is_empty() => return vec![Item::unsupported("records", "a tuple selector")],
+        Some(sel) => switch_lines(sel),
+        None if payload.messages.len() == 1 => vec![format!("type: {}", snake(&payload.messages[0]))],
+        None => return vec![Item::unsupported("records", "a payload without selector")],
+    };
+    if repeated {
+        lines.push("repeat: eos".to_string());
+    }
+    let mut items = vec![Item::attr("records", lines)];
+    if let Some(default) = payload.selector.as_ref().and_then(|s| s.default_message.as_ref()) {
+        items.push(Item::Comment(format!("records: default {} is not exported; other values fail to parse", default)));
+    }
+    items
+}
+
+/// The `body` attribute of a message with a body selector.
+fn body_selector_item(sel: &PayloadSelector) -> Item {
+    if !sel.extra_fields.is_empty() {
+        return Item::unsupported("body", "a tuple selector");
+    }
+    Item::attr("body", switch_lines(sel))
+}
+
+/// `type: switch-on:` lines mapping each selector value to its message type.
+fn switch_lines(sel: &PayloadSelector) -> Vec<String> {
+    let mut lines = vec!["type:".to_string(), format!("  switch-on: {}", snake(&sel.transport_field)), "  cases:".to_string()];
+    for (value, msg, _) in &sel.value_to_message {
+        if let Some(v) = value.as_i64() {
+            lines.push(format!("    {}: {}", v, snake(msg)));
+        }
+    }
+    lines
+}
+
+fn write_type(out: &mut String, name: &str, fields: &FieldList, indent: usize) {
+    let pad = " ".repeat(indent);
+    out.push_str(&format!("{}{}:\n", pad, name));
+    if fields.items.is_empty() {
+        out.push_str(&format!("{}  seq: []\n", pad));
+    } else {
+        out.push_str(&format!("{}  seq:\n", pad));
+        write_items(out, &fields.items, indent + 4);
+    }
+    if !fields.nested.is_empty() {
+        out.push_str(&format!("{}  types:\n", pad));
+        for (nested_name, nested) in &fields.nested {
+            write_type(out, nested_name, nested, indent + 4);
+        }
+    }
+}
+
+fn write_items(out: &mut String, items: &[Item], indent: usize) {
+    let pad = " ".repeat(indent);
+    for item in items {
+        match item {
+            Item::Attr { id, lines, doc } => {
+                out.push_str(&format!("{}- id: {}\n", pad, id));
+                for line in lines {
+                    out.push_str(&format!("{}  {}\n", pad, line));
+                }
+                if !doc.is_empty() {
+                    out.push_str(&format!("{}  doc: {}\n", pad, yaml_str(&doc.join("; "))));
+                }
+            }
+            Item::Comment(text) => out.push_str(&format!("{}# {}\n", pad, text)),
+        }
+    }
+}
+
+/// Kaitai type of a byte-aligned base type. `u24` / `u48` are read as big-endian bit fields.
+fn base_type(b: &BaseType) -> &'static str {
+    match b {
+        BaseType::U8 | BaseType::Bool => "u1",
+        BaseType::U16 => "u2",
+        BaseType::U32 => "u4",
+        BaseType::U64 => "u8",
+        BaseType::U24 => "b24be",
+        BaseType::U48 => "b48be",
+        BaseType::I8 => "s1",
+        BaseType::I16 => "s2",
+        BaseType::I32 => "s4",
+        BaseType::I64 => "s8",
+        BaseType::Float => "f4",
+        BaseType::Double => "f8",
+    }
+}
+
+/// Kaitai type of a byte-aligned `u16(n)`-style integer: its whole bytes, big-endian. The value is the low
+/// `n` bits; Kaitai bit types are unsigned, so a signed value that is not a whole `s1`..`s8` reads unsigned.
+fn sized_int_type(bt: &BaseType, n: u64) -> String {
+    let bytes = n.div_ceil(8);
+    match (n % 8, bytes) {
+        (0, 1 | 2 | 4 | 8) => format!("{}{}", if is_signed(bt) { "s" } else { "u" }, bytes),
+        _ => format!("b{}be", bytes * 8),
+    }
+}
+
+fn is_signed(bt: &BaseType) -> bool {
+    matches!(bt, BaseType::I8 | BaseType::I16 | BaseType::I32 | BaseType::I64)
+}
+
+fn array_len(len: &ArrayLen) -> String {
+    match len {
+        ArrayLen::Constant(n) => n.to_string(),
+        ArrayLen::FieldRef(field) => snake(field),
+    }
+}
+
+/// `doc:` text of a field: its `@doc` and its quantum.
+fn doc_text(doc: Option<&str>, quantum: Option<&str>) -> Option<String> {
+    match (doc, quantum) {
+        (Some(d), Some(q)) => Some(format!("{} (quantum {})", d, q)),
+        (Some(d), None) => Some(d.to_string()),
+        (None, Some(q)) => Some(format!("quantum {}", q)),
+        (None, None) => None,
+    }
+}
+
+/// Double-quoted YAML scalar.
+fn yaml_str(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Kaitai identifier for a DSL name: lower snake case (`Mode3ACode` -> `mode3_a_code`).
+fn snake(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() {
+            let prev = i.checked_sub(1).map(|j| chars[j]);
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if prev.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit() || (p.is_ascii_uppercase() && next_lower)) {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
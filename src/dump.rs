@@ -1,48 +1,22 @@
 //! Format decoded values for display (dump text, tree view). Uses resolved protocol for quantum/units and enum names.
 
 use crate::ast::ResolvedProtocol;
+pub use crate::quantum::parse_quantum;
+use crate::quantum::apply_quantum;
 use crate::value::Value;
 
-/// Parse quantum string (e.g. "1/256 NM", "360/65536 °") into (scale, unit).
-pub fn parse_quantum(quantum_str: &str) -> Option<(f64, String)> {
-    let s = quantum_str.trim();
-    let (scale_str, unit) = match s.find(' ') {
-        Some(i) => (s[..i].trim(), s[i + 1..].trim().to_string()),
-        None => (s, String::new()),
+/// Seconds per raw tick for a time quantum (e.g. "1/128 s" → 0.0078125, "1 ms" → 0.001).
+/// Units: `s`/`sec` (or none), `ms`, `us`, `ns`. Returns None for other units.
+pub fn quantum_seconds_per_tick(quantum_str: &str) -> Option<f64> {
+    let (scale, unit) = parse_quantum(quantum_str)?;
+    let unit_seconds = match unit.as_str() {
+        "" | "s" | "sec" => 1.0,
+        "ms" => 1e-3,
+        "us" | "µs" => 1e-6,
+        "ns" => 1e-9,
+        _ => return None,
     };
-    let scale = parse_scale_expr(scale_str)?;
-    Some((scale, unit))
-}
-
-fn parse_scale_expr(s: &str) -> Option<f64> {
-    let s = s.trim();
-    if let Some(slash) = s.find('/') {
-        let num_str = s[..slash].trim();
-        let denom_str = s[slash + 1..].trim();
-        let num: f64 = num_str.parse().ok()?;
-        let denom: f64 = if let Some(exp_str) = denom_str.strip_prefix("2^") {
-            let exp_str = exp_str.trim_matches(|c| c == '(' || c == ')');
-            let exp: i32 = exp_str.parse().ok()?;
-            if exp >= 0 {
-                (1u64 << exp) as f64
-            } else {
-                1.0 / (1u64 << (-exp) as u32) as f64
-            }
-        } else {
-            denom_str.parse().ok()?
-        };
-        return Some(num / denom);
-    }
-    if let Some(exp_str) = s.strip_prefix("2^") {
-        let exp_str = exp_str.trim_matches(|c| c == '(' || c == ')');
-        let exp: i32 = exp_str.parse().ok()?;
-        return Some(if exp >= 0 {
-            (1u64 << exp) as f64
-        } else {
-            1.0 / (1u64 << (-exp) as u32) as f64
-        });
-    }
-    s.parse::<f64>().ok()
+    Some(scale * unit_seconds)
 }
 
 /// Format seconds since midnight as HH:MM:SS.
@@ -65,24 +39,9 @@ pub fn format_seconds_as_tod(seconds: f64) -> String {
 
 /// Format a scalar with optional quantum; TOD (seconds >= 3600) as HH:MM:SS.
 pub fn format_scalar_with_quantum(v: &Value, quantum: Option<&str>) -> String {
-    let (scale, unit) = match quantum.and_then(parse_quantum) {
-        Some((s, u)) => (s, u),
-        None => return format_scalar_raw(v),
-    };
-    let raw = match v {
-        Value::U8(x) => *x as f64,
-        Value::U16(x) => *x as f64,
-        Value::U32(x) => *x as f64,
-        Value::U64(x) => *x as f64,
-        Value::I8(x) => *x as f64,
-        Value::I16(x) => *x as f64,
-        Value::I32(x) => *x as f64,
-        Value::I64(x) => *x as f64,
-        Value::Float(x) => *x as f64,
-        Value::Double(x) => *x,
-        _ => return format_scalar_raw(v),
+    let (Some((_, unit)), Some(physical)) = (quantum.and_then(parse_quantum), quantum.and_then(|q| apply_quantum(v, q))) else {
+        return format_scalar_raw(v);
     };
-    let physical = raw * scale;
     let is_tod_seconds = (unit.eq_ignore_ascii_case("s") || unit.eq_ignore_ascii_case("sec"))
         && physical >= 3600.0
         && physical < 86400.0 * 2.0;
@@ -142,7 +101,7 @@ pub fn value_to_dump(
                 }
                 if let Some(ts) = resolved.field_type_spec(container_name, field_name) {
                     let ts_for_enum = match ts {
-                        crate::TypeSpec::Optional(inner) => inner.as_ref(),
+                        crate::TypeSpec::Optional(inner, _) => inner.as_ref(),
                         _ => ts,
                     };
                     if let Some(name) = resolved.enum_variant_name_for_type_and_value(ts_for_enum, n) {
@@ -158,6 +117,15 @@ pub fn value_to_dump(
             let (quantum, _) = resolved.field_quantum_and_child(container_name, field_name);
             format!("{}{}", pad, format_scalar_with_quantum(v, quantum))
         }
+        Value::Timestamp { raw, seconds } => {
+            if (0.0..86400.0 * 2.0).contains(seconds) {
+                format!("{}{} ({})", pad, format_seconds_as_tod(*seconds), raw)
+            } else {
+                format!("{}{} s ({})", pad, seconds, raw)
+            }
+        }
+        Value::Enum { variant: Some(name), .. } => format!("{}{}", pad, name),
+        Value::Enum { raw, .. } => format!("{}{}", pad, raw),
         Value::Bytes(b) => format!("{}hex({})", pad, hex_string(b)),
         Value::Struct(m) => {
             let (_, child_container) = resolved.field_quantum_and_child(container_name, field_name);
@@ -3,9 +3,13 @@
 //! When a message is non-compliant (validation failure) but decodable, it is removed
 //! and length/count fields in the frame are updated accordingly.
 
-use crate::codec::{Codec, CodecError};
+use crate::ast::{type_spec_fixed_bytes, PaddingKind, ResolvedProtocol, TransportTypeSpec, TypeSpec};
+use crate::codec::{Codec, CodecError, Endianness};
 use crate::value::Value;
+use crate::walk::BinaryWalker;
 use std::collections::HashMap;
+#[cfg(feature = "frame_profile")]
+use std::time::Instant;
 
 /// Result of decoding a frame: valid messages and optional raw bytes for messages that failed validation.
 #[derive(Debug)]
@@ -14,12 +18,35 @@ pub struct FrameDecodeResult {
     pub messages: Vec<DecodedMessage>,
     /// Indices/offsets of messages that were removed (non-compliant).
     pub removed: Vec<RemovedMessage>,
+    /// With [`decode_frame_selected`]: records whose transport values the payload selector maps to no message.
+    pub unknown: Vec<UnknownRecord>,
+    /// Byte range after the last record whose bytes were all present, up to the end of the input: the start
+    /// of a record cut short by the end of the frame (also listed in `removed` as
+    /// [`RemovalReason::Truncated`]), e.g. a fragment of the next frame on a stream. Empty when every byte
+    /// belongs to a complete record.
+    pub trailing_range: (usize, usize),
+    /// With the `frame_profile` feature: nanoseconds spent decoding records (inner header and message,
+    /// removed records included), per message name.
+    #[cfg(feature = "frame_profile")]
+    pub decode_ns: HashMap<String, u64>,
 }
 
 #[derive(Debug)]
 pub struct DecodedMessage {
     pub name: String,
     pub values: HashMap<String, Value>,
+    /// Per-record `inner_transport` header values (empty when the protocol has no inner transport).
+    pub header: HashMap<String, Value>,
+    /// Byte range of the record, including its inner header.
+    pub byte_range: (usize, usize),
+}
+
+/// A record of [`decode_frame_selected`] whose message type the payload selector does not give. Its extent is
+/// unknown, so it runs to the end of the frame and decoding stops there.
+#[derive(Debug)]
+pub struct UnknownRecord {
+    /// Record's `inner_transport` header values (empty when the protocol has no inner transport).
+    pub header: HashMap<String, Value>,
     pub byte_range: (usize, usize),
 }
 
@@ -27,16 +54,102 @@ pub struct DecodedMessage {
 pub struct RemovedMessage {
     pub name: String,
     pub byte_range: (usize, usize),
+    /// Human-readable error.
     pub reason: String,
+    /// Class of the error, for handling removals by cause (retry, discard, alert).
+    pub cause: RemovalReason,
+}
+
+/// Why a record was removed from a frame (see [`RemovedMessage::cause`]).
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemovalReason {
+    /// A message field holds a value outside its constraint (`value` is the raw value read).
+    ConstraintFailed { field: String, value: i64 },
+    /// The record or its inner header runs past the end of the frame.
+    Truncated { needed: usize, available: usize },
+    /// The message, or a struct it uses, is not defined.
+    UnknownType(String),
+    /// Any other decoding failure (e.g. a value outside an enum, an invalid BCD digit, a failed
+    /// constraint inside a struct).
+    DecodeError,
+}
+
+impl RemovalReason {
+    /// Short label of the cause, e.g. for aggregating removals.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RemovalReason::ConstraintFailed { .. } => "constraint failed",
+            RemovalReason::Truncated { .. } => "truncated",
+            RemovalReason::UnknownType(_) => "unknown type",
+            RemovalReason::DecodeError => "decode error",
+        }
+    }
+
+    /// Classify `e`, raised decoding `message_name` from `record` (in byte order `order`, when its inner header
+    /// set one). The codec reports nested failures as validation errors, so those are classified by walking
+    /// the record: the message field whose constraint failed, or the undefined type the walk stopped at.
+    fn classify(codec: &Codec, message_name: &str, record: &[u8], order: Option<Endianness>, e: &CodecError) -> Self {
+        match e {
+            CodecError::Truncated { needed, available, .. } => RemovalReason::Truncated { needed: *needed, available: *available },
+            CodecError::UnknownStruct(name) => RemovalReason::UnknownType(name.clone()),
+            CodecError::Validation(_) => {
+                let endianness = order.unwrap_or(codec.endianness).into();
+                let mut walker = BinaryWalker::new(record, codec.resolved(), endianness).with_custom_types(codec.custom_types());
+                match walker.first_violation(message_name) {
+                    Ok(Some(v)) => RemovalReason::ConstraintFailed { field: v.field, value: v.value },
+                    Err(CodecError::UnknownStruct(name)) => RemovalReason::UnknownType(name),
+                    _ => RemovalReason::DecodeError,
+                }
+            }
+            _ => RemovalReason::DecodeError,
+        }
+    }
 }
 
 /// Decode a binary frame: optionally parse transport header, then one or more messages.
+/// When the protocol declares an `inner_transport`, each message is preceded by that header.
 /// If a message fails validation, it is removed (bytes still consumed so we can continue).
 pub fn decode_frame(
     codec: &Codec,
     message_name: &str,
     bytes: &[u8],
     transport_len: Option<usize>,
+) -> Result<FrameDecodeResult, CodecError> {
+    decode_records(codec, bytes, transport_len, |_| Some(message_name))
+}
+
+/// Decode a frame whose records may be of different message types, as [`decode_frame`] does for one type: the
+/// message of each record is the one `resolved`'s payload selector gives for the transport values (decoded
+/// from the first `transport_len` bytes) together with the record's `inner_transport` header, which takes
+/// precedence. A record the selector maps to no message goes to [`FrameDecodeResult::unknown`] and ends the
+/// decoding, since its extent is unknown.
+pub fn decode_frame_selected(
+    codec: &Codec,
+    resolved: &ResolvedProtocol,
+    bytes: &[u8],
+    transport_len: Option<usize>,
+) -> Result<FrameDecodeResult, CodecError> {
+    let transport = match transport_len {
+        Some(n) if bytes.len() >= n => codec.decode_transport(&bytes[..n])?,
+        _ => HashMap::new(),
+    };
+    decode_records(codec, bytes, transport_len, |header| {
+        if header.is_empty() {
+            return resolved.message_for_transport_values(&transport);
+        }
+        let mut values = transport.clone();
+        values.extend(header.iter().map(|(k, v)| (k.clone(), v.clone())));
+        resolved.message_for_transport_values(&values)
+    })
+}
+
+/// Record loop of [`decode_frame`] and [`decode_frame_selected`]: `select` gives the message of a record from
+/// its inner header values.
+fn decode_records<'a>(
+    codec: &Codec,
+    bytes: &[u8],
+    transport_len: Option<usize>,
+    select: impl Fn(&HashMap<String, Value>) -> Option<&'a str>,
 ) -> Result<FrameDecodeResult, CodecError> {
     let body_bytes = if let Some(n) = transport_len {
         if bytes.len() < n {
@@ -49,34 +162,259 @@ pub fn decode_frame(
 
     let mut messages = Vec::new();
     let mut removed = Vec::new();
+    let mut unknown = Vec::new();
     let mut offset = 0;
+    let mut complete_end = 0;
     let base = transport_len.unwrap_or(0);
+    #[cfg(feature = "frame_profile")]
+    let mut decode_ns: HashMap<String, u64> = HashMap::new();
 
     while offset < body_bytes.len() {
-        let (consumed, result) = codec.decode_message_with_extent(message_name, &body_bytes[offset..]);
-        if consumed == 0 {
+        #[cfg(feature = "frame_profile")]
+        let start = Instant::now();
+        let (header_len, header) = codec.decode_inner_transport_with_extent(&body_bytes[offset..]);
+        // An `endianness from` field in the inner header sets the byte order of its record.
+        let header = header.and_then(|h| codec.endianness_for(&h).map(|order| (h, order)));
+        let (header, order) = match header {
+            Ok(h) => h,
+            Err(e) => {
+                // Without a complete inner header the record boundary is unknown: drop the rest of the frame.
+                let cause = match e {
+                    CodecError::Truncated { needed, available, .. } => RemovalReason::Truncated { needed, available },
+                    _ => RemovalReason::DecodeError,
+                };
+                removed.push(RemovedMessage {
+                    name: select(&HashMap::new()).unwrap_or_default().to_string(),
+                    byte_range: (base + offset, base + body_bytes.len()),
+                    reason: format!("inner transport: {}", e),
+                    cause,
+                });
+                break;
+            }
+        };
+        let Some(message_name) = select(&header) else {
+            unknown.push(UnknownRecord { header, byte_range: (base + offset, base + body_bytes.len()) });
             break;
+        };
+        let (message_len, result) = codec.decode_message_with_extent_as(message_name, &body_bytes[offset + header_len..], order);
+        #[cfg(feature = "frame_profile")]
+        {
+            *decode_ns.entry(message_name.to_string()).or_insert(0) += start.elapsed().as_nanos() as u64;
         }
+        if message_len == 0 {
+            break;
+        }
+        let consumed = header_len + message_len;
         match result {
             Ok(values) => {
                 messages.push(DecodedMessage {
                     name: message_name.to_string(),
                     values,
+                    header,
                     byte_range: (base + offset, base + offset + consumed),
                 });
+                complete_end = offset + consumed;
             }
             Err(e) => {
+                let record = &body_bytes[offset + header_len..offset + consumed];
+                let cause = RemovalReason::classify(codec, message_name, record, order, &e);
+                if !matches!(cause, RemovalReason::Truncated { .. }) {
+                    complete_end = offset + consumed;
+                }
                 removed.push(RemovedMessage {
                     name: message_name.to_string(),
                     byte_range: (base + offset, base + offset + consumed),
                     reason: e.to_string(),
+                    cause,
                 });
             }
         }
         offset += consumed;
     }
 
-    Ok(FrameDecodeResult { messages, removed })
+    Ok(FrameDecodeResult {
+        messages,
+        removed,
+        unknown,
+        trailing_range: (base + complete_end, base + body_bytes.len()),
+        #[cfg(feature = "frame_profile")]
+        decode_ns,
+    })
+}
+
+/// Split a frame into its records without decoding them: the byte range of each record's message (after
+/// its inner header, if any), found by walking the layout as [`decode_frame`] would. Records are independent,
+/// so the slices can be decoded in any order, e.g. with [`Codec::decode_many_parallel`] (feature `rayon`).
+/// Constraints are not checked. Fails when a record boundary cannot be found (truncated record or inner
+/// header, unknown type).
+pub fn split_only(
+    codec: &Codec,
+    message_name: &str,
+    bytes: &[u8],
+    transport_len: Option<usize>,
+) -> Result<Vec<(usize, usize)>, CodecError> {
+    let base = transport_len.unwrap_or(0);
+    if bytes.len() < base {
+        return Err(CodecError::Validation("Frame shorter than transport header".to_string()));
+    }
+    let mut ranges = Vec::new();
+    let mut offset = base;
+    while offset < bytes.len() {
+        let (header_len, header) = codec.decode_inner_transport_with_extent(&bytes[offset..]);
+        let order = codec.endianness_for(&header?)?;
+        let start = offset + header_len;
+        let mut walker = BinaryWalker::new(&bytes[start..], codec.resolved(), order.unwrap_or(codec.endianness).into())
+            .with_custom_types(codec.custom_types());
+        let len = walker.skip_message(message_name)?;
+        if header_len + len == 0 {
+            break;
+        }
+        ranges.push((start, start + len));
+        offset = start + len;
+    }
+    Ok(ranges)
+}
+
+/// Incremental decoder for stream transports (e.g. TCP), where a record may span two reads: bytes are
+/// accumulated across [`push`](Self::push) calls and each complete record is decoded as by [`decode_frame`]
+/// (inner header, then message). A record whose extent runs past the buffered bytes
+/// ([`CodecError::Truncated`]) stays buffered until more bytes arrive. Byte ranges are stream offsets.
+pub struct StreamDecoder<'c> {
+    codec: &'c Codec,
+    message_name: String,
+    buffer: Vec<u8>,
+    /// Stream offset of `buffer[0]`.
+    offset: usize,
+}
+
+impl<'c> StreamDecoder<'c> {
+    pub fn new(codec: &'c Codec, message_name: &str) -> Self {
+        Self { codec, message_name: message_name.to_string(), buffer: Vec::new(), offset: 0 }
+    }
+
+    /// Append `bytes` and decode every record now complete. Records failing validation are removed, as in
+    /// [`decode_frame`]. Fails when a record boundary cannot be found for another reason than missing bytes
+    /// (e.g. an unknown type); the buffered bytes are then kept, so the caller may resynchronize. Records
+    /// completed before such a failure are returned first, and the next push reports it.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<FrameDecodeResult, CodecError> {
+        self.buffer.extend_from_slice(bytes);
+        let codec = self.codec;
+        let name = self.message_name.as_str();
+        let mut messages = Vec::new();
+        let mut removed = Vec::new();
+        #[cfg(feature = "frame_profile")]
+        let mut decode_ns: HashMap<String, u64> = HashMap::new();
+        let mut used = 0;
+        let mut failure = None;
+        while used < self.buffer.len() {
+            let rest = &self.buffer[used..];
+            #[cfg(feature = "frame_profile")]
+            let start = Instant::now();
+            let (header_len, header) = codec.decode_inner_transport_with_extent(rest);
+            let header = header.and_then(|h| codec.endianness_for(&h).map(|order| (h, order)));
+            let extent = header.and_then(|(h, order)| {
+                let endianness = order.unwrap_or(codec.endianness).into();
+                let mut walker = BinaryWalker::new(&rest[header_len..], codec.resolved(), endianness)
+                    .with_custom_types(codec.custom_types());
+                walker.skip_message(name).map(|len| (h, order, len))
+            });
+            let (header, order, message_len) = match extent {
+                Ok(e) => e,
+                Err(CodecError::Truncated { .. }) => break,
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            };
+            let consumed = header_len + message_len;
+            if consumed == 0 {
+                break;
+            }
+            let record = &rest[header_len..consumed];
+            let byte_range = (self.offset + used, self.offset + used + consumed);
+            let (_, result) = codec.decode_message_with_extent_as(name, record, order);
+            #[cfg(feature = "frame_profile")]
+            {
+                *decode_ns.entry(name.to_string()).or_insert(0) += start.elapsed().as_nanos() as u64;
+            }
+            match result {
+                Ok(values) => messages.push(DecodedMessage { name: name.to_string(), values, header, byte_range }),
+                Err(e) => removed.push(RemovedMessage {
+                    name: name.to_string(),
+                    byte_range,
+                    reason: e.to_string(),
+                    cause: RemovalReason::classify(codec, name, record, order, &e),
+                }),
+            }
+            used += consumed;
+        }
+        self.buffer.drain(..used);
+        self.offset += used;
+        if let Some(e) = failure {
+            if messages.is_empty() && removed.is_empty() {
+                return Err(e);
+            }
+        }
+        Ok(FrameDecodeResult {
+            messages,
+            removed,
+            unknown: Vec::new(),
+            trailing_range: (self.offset, self.offset + self.buffer.len()),
+            #[cfg(feature = "frame_profile")]
+            decode_ns,
+        })
+    }
+
+    /// Bytes received but not yet part of a complete record.
+    pub fn pending(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Stream offset of the first pending byte (total bytes consumed by complete records).
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+/// Offset of the next plausible frame at or after `start`, for resynchronizing a stream that lost framing
+/// (corrupted or dropped bytes): scans byte by byte for the transport's `magic` pattern and returns the
+/// offset where a transport header holding it starts (the magic may follow other header fields) and decodes
+/// with its constraints met. None when the transport has no `magic` field or no such frame follows.
+pub fn resync_scan(codec: &Codec, bytes: &[u8], start: usize) -> Option<usize> {
+    let transport = codec.resolved().protocol.transport.as_ref()?;
+    let mut magic_offset = 0;
+    let mut magic = None;
+    for f in &transport.fields {
+        match &f.type_spec {
+            TransportTypeSpec::Magic(pattern) => {
+                magic = Some(pattern.as_slice());
+                break;
+            }
+            TransportTypeSpec::Base(bt) => magic_offset += type_spec_fixed_bytes(&TypeSpec::Base(bt.clone()))?,
+            TransportTypeSpec::SizedInt(_, n) | TransportTypeSpec::Bitfield(n) => magic_offset += n.div_ceil(8) as usize,
+            TransportTypeSpec::Padding(PaddingKind::Bytes(n)) => magic_offset += *n as usize,
+            TransportTypeSpec::Padding(PaddingKind::Bits(n)) => magic_offset += n.div_ceil(8) as usize,
+        }
+    }
+    let magic = magic?;
+    let mut from = start.checked_add(magic_offset)?;
+    while let Some(found) = resync_scan_for(bytes, from, magic) {
+        let frame_start = found - magic_offset;
+        if codec.decode_transport(&bytes[frame_start..]).is_ok() {
+            return Some(frame_start);
+        }
+        from = found + 1;
+    }
+    None
+}
+
+/// Offset of the next occurrence of `pattern` at or after `start` (a user-supplied sync word, for
+/// transports without a `magic` field). None if it does not occur or `pattern` is empty.
+pub fn resync_scan_for(bytes: &[u8], start: usize, pattern: &[u8]) -> Option<usize> {
+    if pattern.is_empty() || start >= bytes.len() {
+        return None;
+    }
+    bytes[start..].windows(pattern.len()).position(|w| w == pattern).map(|i| start + i)
 }
 
 /// Re-encode a frame with only compliant messages, updating transport length and any length/count fields.
@@ -99,8 +437,8 @@ pub fn encode_frame_with_compliant_only(
     }
 
     for msg in &result.messages {
-        let encoded = codec.encode_message(message_name, &msg.values)?;
-        out.extend(encoded);
+        out.extend(codec.encode_inner_transport(&msg.header)?);
+        codec.encode_message_in(message_name, &msg.values, &mut out, codec.endianness_for(&msg.header)?)?;
     }
 
     Ok(out)
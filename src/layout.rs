@@ -0,0 +1,497 @@
+//! Machine-readable field layout of a message (see [`Codec::message_layout_json`](crate::codec::Codec::message_layout_json)).
+
+use crate::ast::*;
+use std::fmt::Write;
+
+/// The parts of a message or struct field that the layout describes.
+struct LayoutField<'a> {
+    name: &'a str,
+    type_spec: &'a TypeSpec,
+    constraint: Option<&'a Constraint>,
+    conditional_constraints: &'a [(Option<Condition>, Constraint)],
+    quantum: Option<&'a str>,
+    doc: Option<&'a str>,
+    conditional: bool,
+    saturating: bool,
+}
+
+impl<'a> From<&'a MessageField> for LayoutField<'a> {
+    fn from(f: &'a MessageField) -> Self {
+        LayoutField {
+            name: &f.name,
+            type_spec: &f.type_spec,
+            constraint: f.constraint.as_ref(),
+            conditional_constraints: &f.conditional_constraints,
+            quantum: f.quantum.as_deref(),
+            doc: f.doc.as_deref(),
+            conditional: f.condition.is_some(),
+            saturating: f.saturating,
+        }
+    }
+}
+
+impl<'a> From<&'a StructField> for LayoutField<'a> {
+    fn from(f: &'a StructField) -> Self {
+        LayoutField {
+            name: &f.name,
+            type_spec: &f.type_spec,
+            constraint: f.constraint.as_ref(),
+            conditional_constraints: &f.conditional_constraints,
+            quantum: f.quantum.as_deref(),
+            doc: None,
+            conditional: f.condition.is_some(),
+            saturating: false,
+        }
+    }
+}
+
+/// JSON layout of `message_name`, or None if there is no such message.
+pub(crate) fn message_layout_json(resolved: &ResolvedProtocol, message_name: &str) -> Option<String> {
+    let msg = resolved.get_message(message_name)?;
+    let fields: Vec<LayoutField> = msg.fields.iter().map(LayoutField::from).collect();
+    let mut entries = Vec::new();
+    let mut stack = vec![message_name];
+    push_fields(resolved, &fields, "", Some(0), false, &mut stack, &mut entries);
+    Some(format!("{{\"message\":{},\"fields\":[{}]}}", json_str(message_name), entries.join(",")))
+}
+
+/// Appends one JSON object per field (and, for struct/message types, per nested field with a dotted path).
+/// `offset` is the bit offset of the first field when everything before it has a fixed width.
+fn push_fields<'a>(
+    resolved: &'a ResolvedProtocol,
+    fields: &[LayoutField<'a>],
+    prefix: &str,
+    mut offset: Option<u64>,
+    optional: bool,
+    stack: &mut Vec<&'a str>,
+    out: &mut Vec<String>,
+) {
+    for f in fields {
+        let path = format!("{}{}", prefix, f.name);
+        let is_optional = optional || f.conditional || matches!(f.type_spec, TypeSpec::Optional(..));
+        let field_offset = if f.conditional || matches!(f.type_spec, TypeSpec::Optional(..)) { None } else { offset };
+        let width = fixed_bits(resolved, f.type_spec);
+        let mut obj = String::new();
+        let _ = write!(
+            obj,
+            "{{\"name\":{},\"type\":{},\"width_bits\":{},\"offset_bits\":{},\"optional\":{},\"constraint\":{},\"quantum\":{},\"doc\":{}}}",
+            json_str(&path),
+            json_str(&type_spec_text(f.type_spec)),
+            json_opt_num(width),
+            json_opt_num(field_offset),
+            is_optional,
+            constraint_text(f.constraint, f.conditional_constraints).as_deref().map_or("null".to_string(), json_str),
+            f.quantum.map_or("null".to_string(), json_str),
+            f.doc.map_or("null".to_string(), json_str),
+        );
+        out.push(obj);
+        if let TypeSpec::Group { fields: group, .. } = f.type_spec {
+            let nested: Vec<LayoutField> = group.iter().map(LayoutField::from).collect();
+            push_fields(resolved, &nested, &format!("{}.", path), None, is_optional, stack, out);
+        }
+        if let TypeSpec::Bits(members) = f.type_spec {
+            let mut member_offset = field_offset;
+            for (name, w) in members {
+                out.push(format!(
+                    "{{\"name\":{},\"type\":{},\"width_bits\":{},\"offset_bits\":{},\"optional\":{},\"constraint\":null,\"quantum\":null,\"doc\":null}}",
+                    json_str(&format!("{}.{}", path, name)),
+                    json_str(&format!("bitfield({})", w)),
+                    w,
+                    json_opt_num(member_offset),
+                    is_optional,
+                ));
+                member_offset = member_offset.map(|o| o + w);
+            }
+        }
+        let (child, child_offset, child_optional) = match f.type_spec {
+            TypeSpec::StructRef(name) => (Some(name.as_str()), field_offset, is_optional),
+            TypeSpec::Optional(inner, _) => (struct_ref_name(inner), None, true),
+            TypeSpec::List(inner)
+            | TypeSpec::RepList(inner)
+            | TypeSpec::LengthPrefixedList(inner, _)
+            | TypeSpec::BytesList(inner, _)
+            | TypeSpec::Array(inner, ..) => (struct_ref_name(inner), None, is_optional),
+            _ => (None, None, is_optional),
+        };
+        if let Some(name) = child.filter(|n| !stack.contains(n)) {
+            let nested: Option<Vec<LayoutField>> = if let Some(s) = resolved.get_struct(name) {
+                Some(s.wire_fields().map(LayoutField::from).collect())
+            } else {
+                resolved.get_message(name).map(|m| m.fields.iter().map(LayoutField::from).collect())
+            };
+            if let Some(nested) = nested {
+                stack.push(name);
+                push_fields(resolved, &nested, &format!("{}.", path), child_offset, child_optional, stack, out);
+                stack.pop();
+            }
+        }
+        offset = match (field_offset, width) {
+            (Some(o), Some(w)) => Some(o + w),
+            _ => None,
+        };
+    }
+}
+
+fn struct_ref_name(spec: &TypeSpec) -> Option<&str> {
+    match spec {
+        TypeSpec::StructRef(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// Encoded width in bits when it does not depend on the data.
+fn fixed_bits(resolved: &ResolvedProtocol, spec: &TypeSpec) -> Option<u64> {
+    match spec {
+        TypeSpec::SizedInt(_, n) | TypeSpec::Bitfield(n) => Some(*n),
+        TypeSpec::Bits(members) => Some(members.iter().map(|(_, w)| w).sum()),
+        TypeSpec::Padding(PaddingKind::Bytes(n)) => Some(n * 8),
+        TypeSpec::Padding(PaddingKind::Bits(n)) => Some(*n),
+        TypeSpec::LengthOf(_, bt) | TypeSpec::CountOf(_, bt) => fixed_bits(resolved, &TypeSpec::Base(bt.clone())),
+        TypeSpec::PresenceBits(n, _) => Some(n * 8),
+        TypeSpec::Timestamp { base, .. } => fixed_bits(resolved, base),
+        TypeSpec::Array(_, ArrayLen::Constant(n), Some(stride)) => Some(stride * 8 * n),
+        TypeSpec::Array(elem, ArrayLen::Constant(n), None) => fixed_bits(resolved, elem).map(|w| w * n),
+        TypeSpec::Group { count: ArrayLen::Constant(n), fields } => {
+            fixed_field_bits(resolved, fields.iter().map(|f| (&f.type_spec, f.condition.is_some()))).map(|w| w * n)
+        }
+        TypeSpec::StructRef(name) | TypeSpec::Enum(name) => {
+            if let Some(e) = resolved.get_enum(name) {
+                type_spec_fixed_bytes(&TypeSpec::Base(e.base.clone())).map(|b| b as u64 * 8)
+            } else if let Some(s) = resolved.get_struct(name) {
+                fixed_field_bits(resolved, s.fields.iter().map(|f| (&f.type_spec, f.condition.is_some())))
+            } else {
+                None
+            }
+        }
+        _ => type_spec_fixed_bytes(spec).map(|b| b as u64 * 8),
+    }
+}
+
+fn fixed_field_bits<'a>(resolved: &ResolvedProtocol, fields: impl Iterator<Item = (&'a TypeSpec, bool)>) -> Option<u64> {
+    let mut total = 0;
+    for (spec, conditional) in fields {
+        if conditional {
+            return None;
+        }
+        total += fixed_bits(resolved, spec)?;
+    }
+    Some(total)
+}
+
+/// Worst-case encoded size of `message_name` in bits (see [`ResolvedProtocol::max_size`]): every optional and
+/// conditional field present, every count at the largest value its constraint (or, for array counts, its
+/// integer width) admits, the largest body-selector variant. None when some part has no bound.
+pub(crate) fn message_max_bits(resolved: &ResolvedProtocol, message_name: &str) -> Option<u64> {
+    let mut stack = Vec::new();
+    message_max_bits_inner(resolved, message_name, &mut stack)
+}
+
+fn message_max_bits_inner<'a>(resolved: &'a ResolvedProtocol, message_name: &'a str, stack: &mut Vec<&'a str>) -> Option<u64> {
+    if stack.contains(&message_name) {
+        return None;
+    }
+    let msg = resolved.get_message(message_name)?;
+    stack.push(message_name);
+    let fields: Vec<LayoutField> = msg.fields.iter().map(LayoutField::from).collect();
+    let mut total = section_max_bits(resolved, &fields, stack);
+    if let (Some(base), Some(sel)) = (total, msg.selector.as_ref()) {
+        let mut variant = Some(0u64);
+        for name in sel.value_to_message.iter().map(|(_, name, _)| name).chain(sel.default_message.iter()) {
+            let bits = if name == RAW_RECORD { None } else { message_max_bits_inner(resolved, name, stack) };
+            variant = variant.zip(bits).map(|(a, b)| a.max(b));
+        }
+        total = variant.and_then(|v| base.checked_add(v));
+    }
+    stack.pop();
+    total
+}
+
+/// Worst-case size in bits of one value of `spec` on its own (no sibling fields to bound a count).
+pub(crate) fn type_spec_max_bits(resolved: &ResolvedProtocol, spec: &TypeSpec) -> Option<u64> {
+    type_max_bits(resolved, spec, &mut Vec::new())
+}
+
+/// Worst case of a message or struct body; counts and presence come from sibling fields.
+fn section_max_bits<'a>(resolved: &'a ResolvedProtocol, fields: &[LayoutField<'a>], stack: &mut Vec<&'a str>) -> Option<u64> {
+    // Largest value a sibling field can hold: its constraint, else (when `width` allows) its integer range.
+    let max_value = |name: &str, width: bool| -> Option<u64> {
+        let f = fields.iter().find(|f| f.name == name)?;
+        match f.constraint {
+            Some(c) if !f.saturating && f.conditional_constraints.is_empty() => constraint_max(c),
+            _ if width => fixed_bits(resolved, f.type_spec).filter(|&w| w < 64).map(|w| (1u64 << w) - 1),
+            _ => None,
+        }
+    };
+    // Bound on the element count of list `name`, from a `count_of(name)` field.
+    let count_bound = |name: &str| {
+        fields
+            .iter()
+            .filter(|f| matches!(f.type_spec, TypeSpec::CountOf(target, _) if target == name))
+            .find_map(|f| max_value(f.name, false))
+    };
+    let mut total = 0u64;
+    let mut bitmap = false;
+    for f in fields {
+        let bits = match f.type_spec {
+            TypeSpec::PresenceBits(..) | TypeSpec::BitmapPresence { .. } => {
+                bitmap = true;
+                match f.type_spec {
+                    TypeSpec::BitmapPresence { total_bits, presence_per_block: 0, .. } => Some(total_bits.div_ceil(8) as u64 * 8),
+                    TypeSpec::BitmapPresence { total_bits, presence_per_block, .. } => {
+                        Some(total_bits.div_ceil(*presence_per_block) as u64 * 8)
+                    }
+                    spec => fixed_bits(resolved, spec),
+                }
+            }
+            // Under a presence bitmap the optional has no flag of its own.
+            TypeSpec::Optional(elem, flag) => {
+                let flag_bits = if bitmap { 0 } else { fixed_bits(resolved, &TypeSpec::Base(flag.clone()))? };
+                type_max_bits(resolved, elem, stack).map(|b| b + flag_bits)
+            }
+            TypeSpec::List(elem) => {
+                let n = count_bound(f.name)?;
+                type_max_bits(resolved, elem, stack).and_then(|b| b.checked_mul(n)).map(|b| b + 32)
+            }
+            TypeSpec::LengthPrefixedList(elem, len) => {
+                let n = count_bound(f.name)?;
+                let item = type_max_bits(resolved, elem, stack)? + fixed_bits(resolved, &TypeSpec::Base(len.clone()))?;
+                item.checked_mul(n).map(|b| b + 32)
+            }
+            TypeSpec::BytesList(_, len) => max_value(len, true).and_then(|n| n.checked_mul(8)),
+            TypeSpec::RepList(elem) => {
+                let n = count_bound(f.name).unwrap_or(255).min(255);
+                type_max_bits(resolved, elem, stack).and_then(|b| b.checked_mul(n)).map(|b| b + 8)
+            }
+            TypeSpec::Array(elem, ArrayLen::FieldRef(count), stride) => {
+                let n = max_value(count, true)?;
+                let slot = match stride {
+                    Some(m) => Some(m * 8),
+                    None => type_max_bits(resolved, elem, stack),
+                };
+                slot.and_then(|b| b.checked_mul(n))
+            }
+            TypeSpec::Group { count: ArrayLen::FieldRef(count), fields: group } => {
+                let n = max_value(count, true)?;
+                let group: Vec<LayoutField> = group.iter().map(LayoutField::from).collect();
+                section_max_bits(resolved, &group, stack).and_then(|b| b.checked_mul(n))
+            }
+            spec => type_max_bits(resolved, spec, stack),
+        };
+        total = total.checked_add(bits?)?;
+    }
+    Some(total)
+}
+
+/// Worst case of the struct or message `name`.
+fn named_max_bits<'a>(resolved: &'a ResolvedProtocol, name: &'a str, stack: &mut Vec<&'a str>) -> Option<u64> {
+    let Some(s) = resolved.get_struct(name) else {
+        return message_max_bits_inner(resolved, name, stack);
+    };
+    if stack.contains(&name) {
+        return None;
+    }
+    stack.push(name);
+    let fields: Vec<LayoutField> = s.wire_fields().map(LayoutField::from).collect();
+    let bits = section_max_bits(resolved, &fields, stack);
+    stack.pop();
+    bits
+}
+
+/// Worst case of a type on its own (no sibling fields to bound a count).
+fn type_max_bits<'a>(resolved: &'a ResolvedProtocol, spec: &'a TypeSpec, stack: &mut Vec<&'a str>) -> Option<u64> {
+    match spec {
+        TypeSpec::StructRef(name) if resolved.get_enum(name).is_none() => named_max_bits(resolved, name, stack),
+        TypeSpec::Select { cases, default, .. } => cases
+            .iter()
+            .map(|(_, name)| name)
+            .chain(default)
+            .try_fold(0, |max, name| Some(max.max(named_max_bits(resolved, name, stack)?))),
+        TypeSpec::Array(_, ArrayLen::Constant(n), Some(stride)) => (stride * 8).checked_mul(*n),
+        TypeSpec::Array(elem, ArrayLen::Constant(n), None) => type_max_bits(resolved, elem, stack)?.checked_mul(*n),
+        TypeSpec::Group { count: ArrayLen::Constant(n), fields } => {
+            let fields: Vec<LayoutField> = fields.iter().map(LayoutField::from).collect();
+            section_max_bits(resolved, &fields, stack)?.checked_mul(*n)
+        }
+        TypeSpec::Optional(elem, flag) => Some(type_max_bits(resolved, elem, stack)? + fixed_bits(resolved, &TypeSpec::Base(flag.clone()))?),
+        TypeSpec::RepList(elem) => Some(type_max_bits(resolved, elem, stack)? * 255 + 8),
+        TypeSpec::DynInt(_) => Some(64),
+        TypeSpec::List(_) | TypeSpec::LengthPrefixedList(..) | TypeSpec::BytesList(..) | TypeSpec::OctetsFx | TypeSpec::Remaining | TypeSpec::CString | TypeSpec::Custom(_) => None,
+        _ => fixed_bits(resolved, spec),
+    }
+}
+
+/// Largest integer a constraint admits (None for float tolerances or a negative maximum).
+fn constraint_max(c: &Constraint) -> Option<u64> {
+    let max = match c {
+        Constraint::Range(intervals) => intervals.iter().map(|&(_, hi)| hi).max()?,
+        Constraint::Enum(codes) => codes.iter().filter_map(|c| c.as_i64()).max()?,
+        Constraint::EnumTolerance(..) => return None,
+    };
+    u64::try_from(max).ok()
+}
+
+/// DSL spelling of a type (e.g. `optional<DataSourceId>`, `u16(12)`).
+pub(crate) fn type_spec_text(spec: &TypeSpec) -> String {
+    match spec {
+        TypeSpec::Base(bt) => base_type_text(bt).to_string(),
+        TypeSpec::SizedInt(bt, n) => format!("{}({})", base_type_text(bt), n),
+        TypeSpec::Padding(PaddingKind::Bytes(n)) => format!("padding({})", n),
+        TypeSpec::Padding(PaddingKind::Bits(n)) => format!("padding({}, bits)", n),
+        TypeSpec::Bitfield(n) => format!("bitfield({})", n),
+        TypeSpec::Bits(members) => {
+            let body: Vec<String> = members.iter().map(|(name, w)| format!("{}: {}", name, w)).collect();
+            format!("bits {{ {} }}", body.join(", "))
+        }
+        TypeSpec::LengthOf(f, BaseType::U32) => format!("length_of({})", f),
+        TypeSpec::LengthOf(f, bt) => format!("length_of<{}>({})", base_type_text(bt), f),
+        TypeSpec::CountOf(f, BaseType::U32) => format!("count_of({})", f),
+        TypeSpec::CountOf(f, bt) => format!("count_of<{}>({})", base_type_text(bt), f),
+        TypeSpec::DynInt(f) => format!("dyn_int({})", f),
+        TypeSpec::PresenceBits(n, None) => format!("presence_bits({})", n),
+        TypeSpec::PresenceBits(n, Some(k)) => format!("presence_bits({}, stop: {})", n, k),
+        TypeSpec::BitmapPresence { total_bits, presence_per_block, .. } => {
+            format!("bitmap({}, {})", total_bits, presence_per_block)
+        }
+        TypeSpec::StructRef(name) => name.clone(),
+        TypeSpec::Enum(name) => format!("enum<{}>", name),
+        TypeSpec::Select { field, cases, default } => {
+            let cases = cases.iter().map(|(k, name)| format!("{}: {}", k, name));
+            let body: Vec<String> = cases.chain(default.iter().map(|name| format!("default: {}", name))).collect();
+            format!("select<{}> {{ {} }}", field, body.join(", "))
+        }
+        TypeSpec::Group { count, fields } => {
+            let count = match count {
+                ArrayLen::Constant(n) => n.to_string(),
+                ArrayLen::FieldRef(f) => f.clone(),
+            };
+            let body: Vec<String> = fields.iter().map(|f| format!("{}: {};", f.name, type_spec_text(&f.type_spec))).collect();
+            format!("repeat({}) {{ {} }}", count, body.join(" "))
+        }
+        TypeSpec::Array(elem, len, stride) => {
+            let len = match len {
+                ArrayLen::Constant(n) => n.to_string(),
+                ArrayLen::FieldRef(f) => f.clone(),
+            };
+            match stride {
+                Some(m) => format!("{}[{}] stride({})", type_spec_text(elem), len, m),
+                None => format!("{}[{}]", type_spec_text(elem), len),
+            }
+        }
+        TypeSpec::List(elem) => format!("list<{}>", type_spec_text(elem)),
+        TypeSpec::RepList(elem) => format!("rep_list<{}>", type_spec_text(elem)),
+        TypeSpec::LengthPrefixedList(elem, len) => {
+            format!("length_prefixed_list({})<{}>", base_type_text(len), type_spec_text(elem))
+        }
+        TypeSpec::BytesList(elem, len) => format!("bytes_list({})<{}>", len, type_spec_text(elem)),
+        TypeSpec::OctetsFx => "octets_fx".to_string(),
+        TypeSpec::Optional(elem, BaseType::U8) => format!("optional<{}>", type_spec_text(elem)),
+        TypeSpec::Optional(elem, flag) => format!("optional({})<{}>", base_type_text(flag), type_spec_text(elem)),
+        TypeSpec::Timestamp { base, quantum, .. } => format!("timestamp({}, \"{}\")", type_spec_text(base), quantum),
+        TypeSpec::Bcd { bytes, swapped: false } => format!("bcd({})", bytes),
+        TypeSpec::Bcd { bytes, swapped: true } => format!("bcd_swapped({})", bytes),
+        TypeSpec::ReverseBits(elem) => format!("reverse_bits<{}>", type_spec_text(elem)),
+        TypeSpec::Remaining => "remaining".to_string(),
+        TypeSpec::Unknown(n) => format!("unknown({})", n),
+        TypeSpec::CString => "cstring".to_string(),
+        TypeSpec::FixedString(n) => format!("string({})", n),
+        TypeSpec::Checksum { algorithm, from: None } => format!("checksum({})", algorithm.name()),
+        TypeSpec::Checksum { algorithm, from: Some(f) } => format!("checksum({}, from: {})", algorithm.name(), f),
+        TypeSpec::Custom(name) => format!("custom(\"{}\")", name),
+    }
+}
+
+pub(crate) fn base_type_text(bt: &BaseType) -> &'static str {
+    match bt {
+        BaseType::U8 => "u8",
+        BaseType::U16 => "u16",
+        BaseType::U32 => "u32",
+        BaseType::U64 => "u64",
+        BaseType::U24 => "u24",
+        BaseType::U48 => "u48",
+        BaseType::I8 => "i8",
+        BaseType::I16 => "i16",
+        BaseType::I32 => "i32",
+        BaseType::I64 => "i64",
+        BaseType::Bool => "bool",
+        BaseType::Float => "float",
+        BaseType::Double => "double",
+    }
+}
+
+/// DSL spelling of the constraint clause(s), e.g. `[0..10] when mode == 1 [0..100]`.
+fn constraint_text(plain: Option<&Constraint>, conditional: &[(Option<Condition>, Constraint)]) -> Option<String> {
+    if conditional.is_empty() {
+        return plain.map(single_constraint_text);
+    }
+    let parts: Vec<String> = conditional
+        .iter()
+        .map(|(cond, c)| match cond {
+            Some(cond) => format!("{} when {} == {}", single_constraint_text(c), cond.field, literal_text(&cond.value)),
+            None => single_constraint_text(c),
+        })
+        .collect();
+    Some(parts.join(" "))
+}
+
+fn single_constraint_text(c: &Constraint) -> String {
+    match c {
+        Constraint::Range(intervals) => {
+            let parts: Vec<String> = intervals.iter().map(|(a, b)| format!("{}..{}", a, b)).collect();
+            format!("[{}]", parts.join(", "))
+        }
+        Constraint::Enum(values) if values.iter().any(|c| c.name.is_some()) => {
+            let parts: Vec<String> = values
+                .iter()
+                .map(|c| match &c.name {
+                    Some(name) => format!("{}={}", name, literal_text(&c.value)),
+                    None => literal_text(&c.value),
+                })
+                .collect();
+            format!("[in({})]", parts.join(", "))
+        }
+        Constraint::Enum(values) => {
+            let parts: Vec<String> = values.iter().map(|c| literal_text(&c.value)).collect();
+            format!("[({})]", parts.join(", "))
+        }
+        Constraint::EnumTolerance(values, tolerance) => {
+            let inner = single_constraint_text(&Constraint::Enum(values.clone()));
+            format!("{} +/- {:?}]", inner.trim_end_matches(']'), tolerance)
+        }
+    }
+}
+
+pub(crate) fn literal_text(l: &Literal) -> String {
+    match l {
+        Literal::Int(n) => n.to_string(),
+        Literal::Float(x) => format!("{:?}", x),
+        Literal::Bool(b) => b.to_string(),
+        Literal::Hex(h) => format!("0x{:X}", h),
+        Literal::String(s) => format!("\"{}\"", s),
+        Literal::List(items) => format!("[{}]", items.iter().map(literal_text).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+fn json_opt_num(n: Option<u64>) -> String {
+    n.map_or("null".to_string(), |n| n.to_string())
+}
+
+/// `s` as a quoted JSON string: quotes, backslashes and control characters escaped.
+pub fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -7,6 +7,7 @@
 //! ## DSL structure
 //!
 //! - **Transport**: optional frame/header (magic, version, length, padding)
+//! - **Inner transport**: optional per-record header repeated before each message (`inner_transport`)
 //! - **Payload**: optional; which messages can follow the transport and how to select message type from a transport field (`messages`, `selector`)
 //! - **Messages**: named message types with fields
 //! - **Structs**: reusable compound types
@@ -18,7 +19,12 @@
 //! - Sized int: `u8(n)` … `i64(n)` for integers in n bits (e.g. `u16(14)`, `i16(10)`)
 //! - `length_of(field)`, `count_of(field)` for length/count fields
 //! - Struct references, `list<T>`, `optional<T>`, `T[n]` (fixed or count-based)
-//! - Constraints: `[min..max]` or concatenation `[min1..max1, min2..max2, ...]`, `[(a, b, c)]` (enum)
+//! - `timestamp(T, "1/128 s")`: integer ticks with a time resolution (raw + seconds)
+//! - `bcd(n)` / `bcd_swapped(n)`: packed BCD digits; `reverse_bits<T>`: bit-reversed bytes of a fixed-size `T`
+//! - `checksum(crc32)` / `checksum(crc16_ccitt, from: field)`: checksum of the preceding message bytes
+//! - `custom("name")`: encoding supplied by a [`FieldCodec`] registered with [`Codec::register_custom`]
+//! - Constraints: `[min..max]` or concatenation `[min1..max1, min2..max2, ...]`, `[(a, b, c)]` (enum),
+//!   gated by another field with `[0..10] when mode == 1 [0..100] when mode == 2`
 //!
 //! ## Example DSL
 //!
@@ -42,28 +48,35 @@
 //! See the [README](https://github.com/yourusername/AIProtoDSL) and the `tests/integration.rs` for full examples.
 
 pub mod ast;
+pub mod checksum;
 pub mod codec;
+pub mod codegen;
 pub mod dump;
 pub mod frame;
 #[cfg(feature = "gui")]
 pub mod gui;
+mod layout;
 pub mod lint;
 pub mod parser;
+pub mod quantum;
 pub mod value;
 pub mod walk;
 
-pub use ast::{AbstractType, BitmapPresenceMapping, PaddingKind, Protocol, ResolvedProtocol, TypeDefSection, TypeSpec};
-pub use codec::{Codec, CodecError, Endianness, get_decode_profile, reset_decode_profile};
-pub use dump::{format_scalar_raw, format_scalar_with_quantum, format_seconds_as_tod, parse_quantum, value_summary_line, value_to_dump};
-pub use frame::{decode_frame, DecodedMessage, FrameDecodeResult};
+pub use ast::{AbstractType, BitmapPresenceMapping, PaddingKind, Protocol, ResolveOptions, ResolvedProtocol, TypeDefSection, TypeSpec};
+pub use codec::{BitOrder, Codec, CodecError, CustomTypes, DecodeLimits, EncodeOptions, Endianness, FieldCodec, get_decode_profile, reset_decode_profile};
+pub use dump::{format_scalar_raw, format_scalar_with_quantum, format_seconds_as_tod, value_summary_line, value_to_dump};
+pub use frame::{decode_frame, decode_frame_selected, split_only, DecodedMessage, StreamDecoder, FrameDecodeResult, RemovalReason, RemovedMessage, UnknownRecord};
 pub use parser::parse;
-pub use value::Value;
-pub use lint::{lint, LintMessage, LintRule, Severity};
+pub use quantum::{apply_quantum, parse_quantum};
+pub use value::{diff_records, merge_values, optional_present, optional_value, FieldDiff, Value, ValueRef};
+pub use layout::json_str;
+pub use lint::{lint, lint_resolved, LintMessage, LintRule, Severity};
 pub use walk::{
-    message_extent, validate_message_in_place,
+    message_extent, record_extent, validate_message_in_place, validate_message_first_error, validate_fixed_record_list,
+    ConstraintViolation, CounterKind,
     validate_and_zero_message_in_place,
     zero_padding_reserved_in_place,
-    remove_message_in_place, write_u32_in_place,
+    remove_message_in_place, remove_message_in_place_updating, write_field_in_place, write_u32_in_place,
     BinaryWalker, BinaryWalkerMut,
     Endianness as WalkEndianness,
     get_walk_profile, reset_walk_profile,
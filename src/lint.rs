@@ -6,10 +6,35 @@
 //! - **One field per line**: At most one field/statement per line (at most one `;` per line).
 //! - **Closing brace alone**: A line containing `}` must not also contain a field (`;`).
 //! - **No trailing whitespace**: Lines must not have trailing spaces or tabs.
+//! - **Unreachable field** (warning): A field whose `if f == v` condition contradicts a condition already
+//!   required on the path leading to it (the condition of the field it depends on, or of the enclosing struct field).
+//! - **Incomplete field** (warning): An `unknown(n)` placeholder left in the layout, a region still to be reverse-engineered.
+//! - **Ambiguous selector** (warning): A payload or message body selector that does not determine the message
+//!   uniquely: one key value mapped to several messages, a payload listing several messages without a selector,
+//!   or a payload message no key value (nor `default`) selects.
+//! - **Invalid quantum** (warning): A `quantum "..."` string that [`parse_quantum`](crate::quantum::parse_quantum)
+//!   rejects (e.g. `"1/256NM"` without the space before the unit), so the field would be shown unscaled.
+//! - **Optional without presence** (warning): An `optional<T>` with no `presence_bits`/`bitmap` field before it in
+//!   its message or struct (and no `if` condition), so its presence is an inline flag byte instead of a bitmap bit.
+//! - **Dangling field reference** (error): A `length_of(f)`, `count_of(f)`, `T[f]`, `bytes_list(f)`, `dyn_int(f)`
+//!   or `repeat(f)` naming no field of its message or struct (nor, for a struct, of a section embedding it).
+//! - **Size field order** (error): A length, count or width field declared after the field it sizes, so decode
+//!   reaches the sized field before knowing its size.
+//! - **Bit group alignment** (error): A run of consecutive `bitfield(n)`, `padding(n, bits)` and bit-packed sized
+//!   ints (`u8(3)`, or any sized int inside a run) whose widths do not add up to a whole number of bytes.
+//!
+//! Rules that need the resolved protocol are in [`lint_resolved`]; [`lint`] runs them when the source resolves.
+//! Each [`LintMessage`] gives its `line` and the `col_start..col_end` span of the offending text, for editors to
+//! underline.
 //!
 //! Run the linter via the `lint_dsl` binary: `cargo run --bin lint_dsl -- examples/file.dsl`
 //! or pipe: `lint_dsl < file.dsl`. Exit code 1 if any error-level findings.
 
+use crate::ast::{
+    selector_key_text, ArrayLen, Condition, Constraint, Literal, PaddingKind, PayloadSelector, Protocol, ResolvedProtocol, StructSection, TypeSpec,
+};
+use std::collections::HashMap;
+
 /// Severity of a lint finding.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Severity {
@@ -30,6 +55,29 @@ pub enum LintRule {
     ClosingBraceAlone,
     /// Trailing whitespace is not allowed.
     NoTrailingWhitespace,
+    /// Field can never be present: its condition contradicts a condition required earlier on the same path.
+    UnreachableField,
+    /// Field is an `unknown(n)` placeholder: its bytes are kept but their meaning is still to be worked out.
+    IncompleteField,
+    /// Selector cannot tell the reachable messages apart: a key value maps to several messages, or a payload
+    /// message is never selected.
+    AmbiguousSelector,
+    /// Field quantum does not parse as a scale (and unit): values would be shown raw instead of scaled.
+    InvalidQuantum,
+    /// An integer code of an `in(...)` constraint is outside the range of the field's type, so no value matches it.
+    EnumCodeOutOfRange,
+    /// `optional<T>` not governed by a presence bitmap: its presence is read from an inline flag, which in an
+    /// FSPEC-style layout usually means the `presence_bits`/`bitmap` field is missing or declared after it.
+    OptionalWithoutPresence,
+    /// A length, count or width reference names a field that does not exist in its scope, so encode or decode
+    /// fails with `UnknownField`.
+    DanglingFieldRef,
+    /// A `length_of`/`count_of` field follows the field it sizes, or a field refers to a count or width declared
+    /// after it: decode needs the size first.
+    SizeFieldOrder,
+    /// Consecutive bit-packed fields do not end on a byte boundary, so encode and decode fail with "not
+    /// byte-aligned" on the next byte field or at the end.
+    BitGroupAlignment,
 }
 
 /// A single lint message with location.
@@ -37,6 +85,10 @@ pub enum LintRule {
 pub struct LintMessage {
     pub line: usize,
     pub column: usize,
+    /// 1-based byte column of the first character of the offending text on `line`.
+    pub col_start: usize,
+    /// 1-based byte column just past the offending text (`col_start..col_end` is the span to underline).
+    pub col_end: usize,
     pub rule: LintRule,
     pub severity: Severity,
     pub message: String,
@@ -56,6 +108,8 @@ pub fn lint(source: &str) -> Vec<LintMessage> {
             out.push(LintMessage {
                 line: line_no,
                 column: line.len().saturating_sub(line.trim_end().len()).max(1),
+                col_start: line.trim_end().len() + 1,
+                col_end: line.len() + 1,
                 rule: LintRule::NoTrailingWhitespace,
                 severity: Severity::Warning,
                 message: "trailing whitespace not allowed".to_string(),
@@ -70,6 +124,8 @@ pub fn lint(source: &str) -> Vec<LintMessage> {
             out.push(LintMessage {
                 line: line_no,
                 column: 1,
+                col_start: 1,
+                col_end: leading.len() + 1,
                 rule: LintRule::IndentationTabsOnly,
                 severity: Severity::Error,
                 message: "indentation must use tabs only (no spaces)".to_string(),
@@ -89,6 +145,8 @@ pub fn lint(source: &str) -> Vec<LintMessage> {
                 out.push(LintMessage {
                     line: line_no,
                     column: 1,
+                    col_start: 1,
+                    col_end: leading.len().max(1) + 1,
                     rule: LintRule::IndentationDepth,
                     severity: Severity::Error,
                     message: format!(
@@ -107,9 +165,14 @@ pub fn lint(source: &str) -> Vec<LintMessage> {
         };
         let semicolon_count = content_no_line_comment.matches(';').count();
         if semicolon_count > 1 {
+            // Span from the second statement to the end of the code on the line.
+            let first_end = content_no_line_comment.find(';').map_or(0, |i| i + 1);
+            let second = first_end + (content_no_line_comment[first_end..].len() - content_no_line_comment[first_end..].trim_start().len());
             out.push(LintMessage {
                 line: line_no,
                 column: 1,
+                col_start: leading.len() + second + 1,
+                col_end: leading.len() + content_no_line_comment.len() + 1,
                 rule: LintRule::OneFieldPerLine,
                 severity: Severity::Error,
                 message: format!(
@@ -121,9 +184,12 @@ pub fn lint(source: &str) -> Vec<LintMessage> {
 
         // Closing brace alone: line containing `}` should not also contain a field (`;`)
         if content_no_line_comment.contains('}') && content_no_line_comment.contains(';') {
+            let brace = leading.len() + content_no_line_comment.find('}').unwrap_or(0);
             out.push(LintMessage {
                 line: line_no,
                 column: 1,
+                col_start: brace + 1,
+                col_end: brace + 2,
                 rule: LintRule::ClosingBraceAlone,
                 severity: Severity::Warning,
                 message: "closing `}` should be the only content on its line".to_string(),
@@ -140,9 +206,602 @@ pub fn lint(source: &str) -> Vec<LintMessage> {
         }
     }
 
+    if let Ok(protocol) = crate::parser::parse(source) {
+        lint_unreachable_fields(source, &protocol, &mut out);
+        lint_incomplete_fields(source, &protocol, &mut out);
+        lint_ambiguous_selectors(source, &protocol, &mut out);
+        lint_invalid_quanta(source, &protocol, &mut out);
+        lint_enum_codes_out_of_range(source, &protocol, &mut out);
+        if let Ok(resolved) = ResolvedProtocol::resolve(protocol) {
+            out.extend(lint_resolved(source, &resolved));
+        }
+        out.sort_by_key(|m| m.line);
+    }
+
     out
 }
 
+/// Run the lint rules that need the resolved protocol; `source` is the DSL it was resolved from, used to
+/// locate the findings. Returns messages in line order.
+pub fn lint_resolved(source: &str, resolved: &ResolvedProtocol) -> Vec<LintMessage> {
+    let mut out = Vec::new();
+    lint_optionals_without_presence(source, resolved, &mut out);
+    lint_dangling_field_refs(source, resolved, &mut out);
+    lint_size_field_order(source, resolved, &mut out);
+    lint_bit_group_alignment(source, resolved, &mut out);
+    out.sort_by_key(|m| m.line);
+    out
+}
+
+/// One warning per unconditional `optional<T>` of a message or struct with no presence field declared before it
+/// in the same scope: decode falls back to reading an inline presence flag.
+fn lint_optionals_without_presence(source: &str, resolved: &ResolvedProtocol, out: &mut Vec<LintMessage>) {
+    let protocol = &resolved.protocol;
+    let messages = protocol
+        .messages
+        .iter()
+        .map(|m| ("message", &m.name, m.fields.iter().map(|f| (&f.name, &f.type_spec, f.condition.is_some())).collect::<Vec<_>>()));
+    let structs = protocol
+        .structs
+        .iter()
+        .map(|s| ("struct", &s.name, s.fields.iter().map(|f| (&f.name, &f.type_spec, f.condition.is_some())).collect()));
+    for (keyword, section, fields) in messages.chain(structs) {
+        let mut governed = false;
+        for (name, spec, conditional) in fields {
+            match spec {
+                TypeSpec::PresenceBits(..) | TypeSpec::BitmapPresence { .. } => governed = true,
+                TypeSpec::Optional(_, flag) if !governed && !conditional => {
+                    let (line, column, col_end) = field_location(source, keyword, section, name);
+                    out.push(LintMessage {
+                        line,
+                        column,
+                        col_start: column,
+                        col_end,
+                        rule: LintRule::OptionalWithoutPresence,
+                        severity: Severity::Warning,
+                        message: format!(
+                            "optional field `{}` in {} `{}` has no presence_bits or bitmap field before it; its presence is read from an inline {} flag",
+                            name,
+                            keyword,
+                            section,
+                            crate::layout::base_type_text(flag)
+                        ),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// One warning per `unknown(n)` field of a message or struct, so placeholders are not forgotten.
+fn lint_incomplete_fields(source: &str, protocol: &Protocol, out: &mut Vec<LintMessage>) {
+    let messages = protocol.messages.iter().map(|m| ("message", &m.name, m.fields.iter().map(|f| (&f.name, &f.type_spec)).collect::<Vec<_>>()));
+    let structs = protocol.structs.iter().map(|s| ("struct", &s.name, s.fields.iter().map(|f| (&f.name, &f.type_spec)).collect()));
+    for (keyword, section, fields) in messages.chain(structs) {
+        for (name, spec) in fields {
+            let TypeSpec::Unknown(n) = spec else { continue };
+            let (line, column, col_end) = field_location(source, keyword, section, name);
+            out.push(LintMessage {
+                line,
+                column,
+                col_start: column,
+                col_end,
+                rule: LintRule::IncompleteField,
+                severity: Severity::Warning,
+                message: format!("field `{}` is an unknown({}) placeholder in {} `{}`", name, n, keyword, section),
+            });
+        }
+    }
+}
+
+/// One warning per field whose quantum string does not parse, in any section that declares quanta.
+fn lint_invalid_quanta(source: &str, protocol: &Protocol, out: &mut Vec<LintMessage>) {
+    let mut fields: Vec<(&str, &str, &str, &str)> = Vec::new();
+    for (keyword, t) in [("transport", &protocol.transport), ("inner_transport", &protocol.inner_transport)] {
+        for f in t.iter().flat_map(|t| &t.fields) {
+            fields.extend(f.quantum.as_deref().map(|q| (keyword, "", f.name.as_str(), q)));
+        }
+    }
+    for t in &protocol.type_defs {
+        fields.extend(t.fields.iter().filter_map(|f| Some(("type", t.name.as_str(), f.name.as_str(), f.quantum.as_deref()?))));
+    }
+    for m in &protocol.messages {
+        fields.extend(m.fields.iter().filter_map(|f| Some(("message", m.name.as_str(), f.name.as_str(), f.quantum.as_deref()?))));
+    }
+    for st in &protocol.structs {
+        fields.extend(st.fields.iter().filter_map(|f| Some(("struct", st.name.as_str(), f.name.as_str(), f.quantum.as_deref()?))));
+    }
+    for (keyword, section, name, quantum) in fields {
+        if crate::quantum::parse_quantum(quantum).is_some() {
+            continue;
+        }
+        let (line, column, col_end) = field_location(source, keyword, section, name);
+        let owner = if section.is_empty() { keyword.to_string() } else { format!("{} `{}`", keyword, section) };
+        out.push(LintMessage {
+            line,
+            column,
+            col_start: column,
+            col_end,
+            rule: LintRule::InvalidQuantum,
+            severity: Severity::Warning,
+            message: format!(
+                "quantum \"{}\" of field `{}` in {} does not parse (expected e.g. \"1/256 NM\" or \"2^-7 s\")",
+                quantum, name, owner
+            ),
+        });
+    }
+}
+
+/// One warning per integer code of an `in(...)` constraint that the field's type cannot hold (e.g. `300` on a
+/// `u8`). String codes on numeric fields are rejected at resolve instead.
+fn lint_enum_codes_out_of_range(source: &str, protocol: &Protocol, out: &mut Vec<LintMessage>) {
+    for (keyword, section, name, spec, codes) in crate::ast::enum_constrained_fields(protocol) {
+        let Some((min, max)) = crate::ast::type_spec_integer_range(&spec) else { continue };
+        for code in codes {
+            let in_range = match code.value {
+                Literal::Int(v) => (min..=max).contains(&v),
+                Literal::Hex(h) => i64::try_from(h).is_ok_and(|v| (min..=max).contains(&v)),
+                _ => continue,
+            };
+            if in_range {
+                continue;
+            }
+            let (line, column, col_end) = field_location(source, keyword, section, name);
+            let owner = if section.is_empty() { keyword.to_string() } else { format!("{} `{}`", keyword, section) };
+            out.push(LintMessage {
+                line,
+                column,
+                col_start: column,
+                col_end,
+                rule: LintRule::EnumCodeOutOfRange,
+                severity: Severity::Warning,
+                message: format!(
+                    "code {} of the in(...) constraint on field `{}` in {} is outside {} ({}..{}); no value matches it",
+                    crate::layout::literal_text(&code.value),
+                    name,
+                    owner,
+                    crate::layout::type_spec_text(&spec),
+                    min,
+                    max
+                ),
+            });
+        }
+    }
+}
+
+/// Warns when a selector does not determine the message uniquely: decode would pick the first mapping of a
+/// key value mapped twice, and a payload message that no key value selects is never decoded.
+fn lint_ambiguous_selectors(source: &str, protocol: &Protocol, out: &mut Vec<LintMessage>) {
+    let mut selectors: Vec<(&str, &str, &PayloadSelector)> = Vec::new();
+    if let Some(ref payload) = protocol.payload {
+        let (line, column, col_end) = field_location(source, "payload", "", "selector");
+        match payload.selector {
+            Some(ref sel) => {
+                selectors.push(("payload", "", sel));
+                let selected = |name: &String| {
+                    sel.default_message.as_ref() == Some(name) || sel.value_to_message.iter().any(|(_, m, _)| m == name)
+                };
+                for name in payload.messages.iter().filter(|n| !selected(n)) {
+                    out.push(LintMessage {
+                        line,
+                        column,
+                        col_start: column,
+                        col_end,
+                        rule: LintRule::AmbiguousSelector,
+                        severity: Severity::Warning,
+                        message: format!("payload message `{}` is not selected by any value of {}", name, sel.key_text()),
+                    });
+                }
+            }
+            None if payload.messages.len() > 1 => {
+                let (line, column, col_end) = field_location(source, "payload", "", "messages");
+                out.push(LintMessage {
+                    line,
+                    column,
+                    col_start: column,
+                    col_end,
+                    rule: LintRule::AmbiguousSelector,
+                    severity: Severity::Warning,
+                    message: format!(
+                        "payload lists {} messages but has no selector to choose between them",
+                        payload.messages.len()
+                    ),
+                });
+            }
+            None => {}
+        }
+    }
+    for m in &protocol.messages {
+        if let Some(ref sel) = m.selector {
+            selectors.push(("message", &m.name, sel));
+        }
+    }
+    for (keyword, section, sel) in selectors {
+        let mut by_key: Vec<(Vec<i64>, Vec<&str>)> = Vec::new();
+        for (lit, msg_name, _) in &sel.value_to_message {
+            let key = match lit {
+                Literal::List(items) => items.iter().map(Literal::as_i64).collect::<Option<Vec<_>>>(),
+                _ => lit.as_i64().map(|v| vec![v]),
+            };
+            let Some(key) = key else { continue };
+            match by_key.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, names)) if !names.contains(&msg_name.as_str()) => names.push(msg_name),
+                Some(_) => {}
+                None => by_key.push((key, vec![msg_name])),
+            }
+        }
+        let (line, column, col_end) = field_location(source, keyword, section, "selector");
+        for (key, names) in by_key.into_iter().filter(|(_, names)| names.len() > 1) {
+            let fields: Vec<&str> = sel.key_fields().collect();
+            out.push(LintMessage {
+                line,
+                column,
+                col_start: column,
+                col_end,
+                rule: LintRule::AmbiguousSelector,
+                severity: Severity::Warning,
+                message: format!(
+                    "{} = {} selects {}: add a distinguishing field to the selector, e.g. `selector: ({}, <field>) -> ...`",
+                    sel.key_text(),
+                    selector_key_text(&key),
+                    names.iter().map(|n| format!("`{}`", n)).collect::<Vec<_>>().join(" and "),
+                    fields.join(", ")
+                ),
+            });
+        }
+    }
+}
+
+/// One error per length, count or width reference to a field that is not in scope. Decode values are shared
+/// across nested structs, so a struct may also refer to the fields of a message or struct embedding it.
+fn lint_dangling_field_refs(source: &str, resolved: &ResolvedProtocol, out: &mut Vec<LintMessage>) {
+    let sections = sections(&resolved.protocol);
+    for (keyword, section, fields) in &sections {
+        let embeds = |spec: &TypeSpec| match spec {
+            TypeSpec::Select { cases, default, .. } => cases.iter().map(|(_, t)| t).chain(default).any(|t| t == section),
+            _ => referenced_struct(spec) == Some(*section),
+        };
+        let mut in_scope: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+        if *keyword == "struct" {
+            for (_, _, outer) in sections.iter().filter(|(_, _, outer)| outer.iter().any(|(_, spec)| embeds(spec))) {
+                in_scope.extend(outer.iter().map(|(name, _)| *name));
+            }
+        }
+        for (name, spec) in fields {
+            let mut refs = Vec::new();
+            field_refs(spec, &mut refs);
+            let Some(dangling) = refs.into_iter().find(|r| !in_scope.contains(r)) else { continue };
+            let (line, column, col_end) = field_location(source, keyword, section, name);
+            out.push(LintMessage {
+                line,
+                column,
+                col_start: column,
+                col_end,
+                rule: LintRule::DanglingFieldRef,
+                severity: Severity::Error,
+                message: format!("field `{}` in {} `{}` refers to `{}`, which is not a field in scope", name, keyword, section, dangling),
+            });
+        }
+    }
+}
+
+/// One error per field whose size comes from a field declared after it in the same message or struct: a
+/// `length_of`/`count_of` after its target, or a `T[n]`, `bytes_list(n)`, `dyn_int(n)` or `repeat(n)` before `n`.
+/// References outside the scope are left to [`LintRule::DanglingFieldRef`].
+fn lint_size_field_order(source: &str, resolved: &ResolvedProtocol, out: &mut Vec<LintMessage>) {
+    for (keyword, section, fields) in sections(&resolved.protocol) {
+        let index = |name: &str| fields.iter().position(|(f, _)| *f == name);
+        for (i, (name, spec)) in fields.iter().enumerate() {
+            let message = match spec {
+                TypeSpec::LengthOf(target, _) | TypeSpec::CountOf(target, _) => match index(target) {
+                    Some(j) if j < i => {
+                        let (target_line, ..) = field_location(source, keyword, section, target);
+                        format!(
+                            "`{}` sizes `{}` (line {}) but is declared after it; declare it before the field it sizes",
+                            name, target, target_line
+                        )
+                    }
+                    _ => continue,
+                },
+                _ => {
+                    let mut refs = Vec::new();
+                    field_refs(spec, &mut refs);
+                    let Some(late) = refs.into_iter().find(|r| index(r).is_some_and(|j| j >= i)) else { continue };
+                    let (late_line, ..) = field_location(source, keyword, section, late);
+                    format!(
+                        "field `{}` is sized by `{}` (line {}), which is not declared before it; decode reads `{}` first",
+                        name, late, late_line, late
+                    )
+                }
+            };
+            let (line, column, col_end) = field_location(source, keyword, section, name);
+            out.push(LintMessage {
+                line,
+                column,
+                col_start: column,
+                col_end,
+                rule: LintRule::SizeFieldOrder,
+                severity: Severity::Error,
+                message: format!("{} `{}`: {}", keyword, section, message),
+            });
+        }
+    }
+}
+
+/// One error per run of bit-packed fields of a message or struct that ends (at a byte field or at the end of
+/// the section) without filling whole bytes. A run closes as soon as its total is a multiple of 8.
+/// `bitmap(...)` presence fields continue a run the way decode reads them.
+fn lint_bit_group_alignment(source: &str, resolved: &ResolvedProtocol, out: &mut Vec<LintMessage>) {
+    for (keyword, section, fields) in sections(&resolved.protocol) {
+        let mut run: Option<(&str, &str)> = None;
+        let mut total = 0u64;
+        for (name, spec) in fields.iter().map(|(n, s)| (Some(*n), Some(*s))).chain([(None, None)]) {
+            let bits = match spec {
+                Some(TypeSpec::Bitfield(n)) | Some(TypeSpec::Padding(PaddingKind::Bits(n))) => Some(*n),
+                // As in the codec, a sized int of 8 bits or more starting on a byte boundary takes whole bytes.
+                Some(TypeSpec::SizedInt(_, n)) if *n < 8 || run.is_some() => Some(*n),
+                Some(TypeSpec::Bits(widths)) => Some(widths.iter().map(|(_, w)| w).sum()),
+                // A one-bit `bitmap(1, 0)` is the last bit of the byte in progress (an FX bit); a wider one
+                // without blocks takes its bits from the run.
+                Some(TypeSpec::BitmapPresence { total_bits, presence_per_block: 0, .. }) if run.is_some() => {
+                    Some(if *total_bits == 1 { 8 - total % 8 } else { u64::from(*total_bits) })
+                }
+                _ => None,
+            };
+            if let (Some(bits), Some(name)) = (bits, name) {
+                run = Some((run.map_or(name, |(first, _)| first), name));
+                total += bits;
+                if total.is_multiple_of(8) {
+                    run = None;
+                    total = 0;
+                }
+                continue;
+            }
+            let Some((first, last)) = run.take() else { continue };
+            let (line, column, col_end) = field_location(source, keyword, section, first);
+            let fields = if first == last { format!("`{}`", first) } else { format!("`{}`..`{}`", first, last) };
+            out.push(LintMessage {
+                line,
+                column,
+                col_start: column,
+                col_end,
+                rule: LintRule::BitGroupAlignment,
+                severity: Severity::Error,
+                message: format!(
+                    "bit group {} in {} `{}` totals {} bits, not a multiple of 8; pad it with padding({}, bits)",
+                    fields,
+                    keyword,
+                    section,
+                    total,
+                    8 - total % 8
+                ),
+            });
+            total = 0;
+        }
+    }
+}
+
+/// A message or struct as (keyword, name, (field name, type) in wire order).
+type Section<'p> = (&'static str, &'p str, Vec<(&'p str, &'p TypeSpec)>);
+
+fn sections(protocol: &Protocol) -> Vec<Section<'_>> {
+    let messages = protocol
+        .messages
+        .iter()
+        .map(|m| ("message", m.name.as_str(), m.fields.iter().map(|f| (f.name.as_str(), &f.type_spec)).collect()));
+    let structs = protocol
+        .structs
+        .iter()
+        .map(|s| ("struct", s.name.as_str(), s.fields.iter().map(|f| (f.name.as_str(), &f.type_spec)).collect()));
+    messages.chain(structs).collect()
+}
+
+/// Fields a type refers to for a length, count or width (inside list/array/optional elements too).
+fn field_refs<'s>(spec: &'s TypeSpec, out: &mut Vec<&'s str>) {
+    match spec {
+        TypeSpec::LengthOf(field, _) | TypeSpec::CountOf(field, _) | TypeSpec::DynInt(field) => out.push(field),
+        TypeSpec::Group { count: ArrayLen::FieldRef(field), .. } => out.push(field),
+        TypeSpec::BytesList(inner, field) | TypeSpec::Array(inner, ArrayLen::FieldRef(field), _) => {
+            out.push(field);
+            field_refs(inner, out);
+        }
+        TypeSpec::Array(inner, ..)
+        | TypeSpec::List(inner)
+        | TypeSpec::RepList(inner)
+        | TypeSpec::LengthPrefixedList(inner, _)
+        | TypeSpec::Optional(inner, _) => field_refs(inner, out),
+        _ => {}
+    }
+}
+
+/// A condition `field == value` required for a field to be present, with the line that imposed it.
+#[derive(Debug, Clone)]
+struct Requirement {
+    field: String,
+    value: i64,
+    line: usize,
+}
+
+/// Field view shared by messages and structs for the unreachable-field pass.
+struct FieldInfo<'a> {
+    name: &'a str,
+    type_spec: &'a TypeSpec,
+    constraint: Option<&'a Constraint>,
+    condition: Option<&'a Condition>,
+}
+
+/// Light constant propagation over `if` conditions: each field inherits the conditions of the field it
+/// depends on and of the struct field enclosing it (decode values are shared across nested structs),
+/// plus single-value constraints (`[(2)]`, `[2..2]`). A condition contradicting one of those is dead.
+fn lint_unreachable_fields(source: &str, protocol: &Protocol, out: &mut Vec<LintMessage>) {
+    let mut found = Vec::new();
+    for m in &protocol.messages {
+        let fields: Vec<FieldInfo> = m
+            .fields
+            .iter()
+            .map(|f| FieldInfo {
+                name: &f.name,
+                type_spec: &f.type_spec,
+                constraint: f.constraint.as_ref(),
+                condition: f.condition.as_ref(),
+            })
+            .collect();
+        let mut stack = Vec::new();
+        check_section(source, "message", &m.name, &fields, &[], protocol, &mut stack, &mut found);
+    }
+    for s in &protocol.structs {
+        let mut stack = vec![s.name.clone()];
+        check_section(source, "struct", &s.name, &struct_fields(s), &[], protocol, &mut stack, &mut found);
+    }
+    // The same struct reached along several paths reports the same contradiction once.
+    for msg in found {
+        if !out.iter().any(|m| m.line == msg.line && m.message == msg.message) {
+            out.push(msg);
+        }
+    }
+}
+
+fn struct_fields(s: &StructSection) -> Vec<FieldInfo<'_>> {
+    s.fields
+        .iter()
+        .map(|f| FieldInfo {
+            name: &f.name,
+            type_spec: &f.type_spec,
+            constraint: f.constraint.as_ref(),
+            condition: f.condition.as_ref(),
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_section(
+    source: &str,
+    keyword: &str,
+    section: &str,
+    fields: &[FieldInfo],
+    path: &[Requirement],
+    protocol: &Protocol,
+    stack: &mut Vec<String>,
+    out: &mut Vec<LintMessage>,
+) {
+    let mut known: Vec<Requirement> = Vec::new();
+    let mut field_requirements: HashMap<&str, Vec<Requirement>> = HashMap::new();
+    for f in fields {
+        let (line, column, col_end) = field_location(source, keyword, section, f.name);
+        let mut reqs = path.to_vec();
+        if let Some(cond) = f.condition {
+            if let Some(dep) = field_requirements.get(cond.field.as_str()) {
+                reqs.extend(dep.iter().cloned());
+            }
+            if let Some(value) = cond.value.as_i64() {
+                let contradiction = reqs
+                    .iter()
+                    .chain(known.iter())
+                    .find(|r| r.field == cond.field && r.value != value);
+                if let Some(earlier) = contradiction {
+                    out.push(LintMessage {
+                        line,
+                        column,
+                        col_start: column,
+                        col_end,
+                        rule: LintRule::UnreachableField,
+                        severity: Severity::Warning,
+                        message: format!(
+                            "field `{}` is unreachable: condition `{} == {}` (line {}) contradicts `{} == {}` (line {})",
+                            f.name, cond.field, value, line, earlier.field, earlier.value, earlier.line
+                        ),
+                    });
+                    continue;
+                }
+                reqs.push(Requirement { field: cond.field.clone(), value, line });
+            }
+        }
+        if let Some(value) = single_value(f.constraint) {
+            known.push(Requirement { field: f.name.to_string(), value, line });
+        }
+        if !reqs.is_empty() {
+            if let Some(name) = referenced_struct(f.type_spec) {
+                if !stack.iter().any(|s| s == name) {
+                    if let Some(s) = protocol.structs.iter().find(|s| s.name == name) {
+                        stack.push(s.name.clone());
+                        check_section(source, "struct", &s.name, &struct_fields(s), &reqs, protocol, stack, out);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        field_requirements.insert(f.name, reqs);
+    }
+}
+
+/// Value fixed by a constraint admitting exactly one value.
+fn single_value(constraint: Option<&Constraint>) -> Option<i64> {
+    match constraint? {
+        Constraint::Range(r) if r.len() == 1 && r[0].0 == r[0].1 => Some(r[0].0),
+        Constraint::Enum(v) if v.len() == 1 => v[0].as_i64(),
+        _ => None,
+    }
+}
+
+/// Struct decoded by a field type (directly or inside list/array/optional/timestamp).
+fn referenced_struct(spec: &TypeSpec) -> Option<&str> {
+    match spec {
+        TypeSpec::StructRef(name) => Some(name),
+        TypeSpec::Array(inner, ..)
+        | TypeSpec::List(inner)
+        | TypeSpec::RepList(inner)
+        | TypeSpec::LengthPrefixedList(inner, _)
+        | TypeSpec::BytesList(inner, _)
+        | TypeSpec::Optional(inner, _) => referenced_struct(inner),
+        _ => None,
+    }
+}
+
+/// 1-based (line, column) of the declaration of `field` inside `keyword name { ... }`, and the column just past
+/// its `;` (or the end of the code on the line); (1, 1, 1) if not found.
+fn field_location(source: &str, keyword: &str, section: &str, field: &str) -> (usize, usize, usize) {
+    let mut depth: i32 = 0;
+    let mut inside = false;
+    for (i, line) in source.lines().enumerate() {
+        let content = line.find("//").map(|c| &line[..c]).unwrap_or(line);
+        let trimmed = content.trim_start();
+        if !inside && depth == 0 {
+            let mut words = trimmed.split(|c: char| c.is_whitespace() || c == '{');
+            if words.next() == Some(keyword) && words.find(|w| !w.is_empty()).unwrap_or("") == section {
+                inside = true;
+            }
+        } else if inside && depth == 1 {
+            let decl = if trimmed.starts_with("@doc") {
+                trimmed.splitn(3, '"').nth(2).unwrap_or("").trim_start()
+            } else {
+                trimmed
+            };
+            if let Some(rest) = decl.strip_prefix(field) {
+                if rest.trim_start().starts_with(':') {
+                    let column = decl.as_ptr() as usize - line.as_ptr() as usize + 1;
+                    let col_end = match content[column - 1..].find(';') {
+                        Some(semi) => column + semi + 1,
+                        None => content.trim_end().len() + 1,
+                    };
+                    return (i + 1, column, col_end);
+                }
+            }
+        }
+        for c in content.chars() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        if inside && depth == 0 && content.contains('}') {
+            inside = false;
+        }
+    }
+    (1, 1, 1)
+}
+
 /// Fix DSL source to satisfy lint rules: tabs for indentation (by depth), one field per line, closing brace alone, no trailing whitespace.
 pub fn lint_fix(source: &str) -> String {
     let mut depth: i32 = 0;
@@ -260,6 +919,7 @@ mod tests {
         let msgs = lint(src);
         let tabs_only: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::IndentationTabsOnly).collect();
         assert!(!tabs_only.is_empty(), "expected IndentationTabsOnly (spaces used)");
+        assert_eq!((tabs_only[0].col_start, tabs_only[0].col_end), (1, 3), "span covers the leading spaces");
     }
 
     #[test]
@@ -288,4 +948,127 @@ mod tests {
         let errors: Vec<_> = msgs.iter().filter(|m| m.severity == Severity::Error).collect();
         assert!(errors.is_empty(), "fixed output should pass lint: {:?}", msgs);
     }
+
+    #[test]
+    fn lint_unreachable_field() {
+        let src = "message M {\n\ttype: u8;\n\text: Ext if type == 2;\n}\n\nstruct Ext {\n\tflag: u8;\n\tspecial: u8 if type == 1;\n\tnormal: u8 if type == 2;\n}\n";
+        let msgs = lint(src);
+        let unreachable: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::UnreachableField).collect();
+        assert_eq!(unreachable.len(), 1, "expected one UnreachableField: {:?}", msgs);
+        assert_eq!(unreachable[0].line, 8);
+        assert_eq!(unreachable[0].severity, Severity::Warning);
+        assert!(unreachable[0].message.contains("line 3"), "{}", unreachable[0].message);
+    }
+
+    #[test]
+    fn lint_ambiguous_selector() {
+        let src = "transport {\n\tcategory: u8;\n}\n\npayload {\n\tmessages: A, B, C;\n\tselector: category -> 48: A, 48: B;\n}\n\nmessage A {\n\tx: u8;\n}\n\nmessage B {\n\ty: u8;\n}\n\nmessage C {\n\tz: u8;\n}\n";
+        let msgs = lint(src);
+        let ambiguous: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::AmbiguousSelector).collect();
+        assert_eq!(ambiguous.len(), 2, "expected two AmbiguousSelector: {:?}", msgs);
+        assert!(ambiguous.iter().all(|m| (m.line, m.column) == (7, 2)), "{:?}", ambiguous);
+        assert!(ambiguous.iter().any(|m| m.message.contains("`A` and `B`") && m.message.contains("(category, <field>)")), "{:?}", ambiguous);
+        assert!(ambiguous.iter().any(|m| m.message.contains("`C` is not selected")), "{:?}", ambiguous);
+    }
+
+    #[test]
+    fn lint_invalid_quantum() {
+        let src = "message M {\n\trange: u16 quantum \"1/256 NM\";\n\tbearing: u16 quantum \"1/256NM\";\n\tspeed: u16 quantum \"2^(-x)\";\n}\n";
+        let msgs = lint(src);
+        let invalid: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::InvalidQuantum).collect();
+        assert_eq!(invalid.len(), 2, "expected two InvalidQuantum: {:?}", msgs);
+        assert_eq!((invalid[0].line, invalid[0].column), (3, 2));
+        assert_eq!(invalid[1].line, 4);
+        assert_eq!(invalid[0].severity, Severity::Warning);
+        assert!(invalid[0].message.contains("`bearing`") && invalid[0].message.contains("\"1/256NM\""), "{}", invalid[0].message);
+    }
+
+    #[test]
+    fn lint_enum_code_out_of_range() {
+        let src = "message M {\n\tmode: u8 [in(OFF=0, ON=300)];\n\tlevel: u16(4) [(1, 2, 0x10)] when mode == 0;\n\tok: i8 [(-1, 1)];\n}\n";
+        let msgs = lint(src);
+        let out: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::EnumCodeOutOfRange).collect();
+        assert_eq!(out.len(), 2, "expected two EnumCodeOutOfRange: {:?}", msgs);
+        assert_eq!((out[0].line, out[0].column), (2, 2));
+        assert_eq!(out[0].severity, Severity::Warning);
+        assert!(out[0].message.contains("300") && out[0].message.contains("`mode`") && out[0].message.contains("0..255"), "{}", out[0].message);
+        assert!(out[1].message.contains("0x10") && out[1].message.contains("u16(4)"), "{}", out[1].message);
+    }
+
+    #[test]
+    fn lint_optional_without_presence() {
+        let governed = "message M {\n\tfspec: presence_bits(1);\n\ta: optional<u8>;\n\tb: optional<u16>;\n}\n";
+        let msgs = lint(governed);
+        assert!(!msgs.iter().any(|m| m.rule == LintRule::OptionalWithoutPresence), "{:?}", msgs);
+
+        let src = "message M {\n\tkind: u8;\n\ta: optional<u8>;\n\tb: optional<u8> if kind == 1;\n}\n\nstruct S {\n\tc: optional(u16)<u8>;\n\tfspec: presence_bits(1);\n\td: optional<u8>;\n}\n";
+        let msgs = lint(src);
+        let found: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::OptionalWithoutPresence).collect();
+        assert_eq!(found.len(), 2, "expected two OptionalWithoutPresence: {:?}", msgs);
+        assert_eq!((found[0].line, found[0].column), (3, 2));
+        assert_eq!(found[0].severity, Severity::Warning);
+        assert!(found[0].message.contains("`a`") && found[0].message.contains("message `M`"), "{}", found[0].message);
+        assert_eq!(found[1].line, 8);
+        assert!(found[1].message.contains("`c`") && found[1].message.contains("u16 flag"), "{}", found[1].message);
+        let resolved = ResolvedProtocol::resolve(crate::parser::parse(src).unwrap()).unwrap();
+        assert_eq!(lint_resolved(src, &resolved).len(), 2);
+    }
+
+    #[test]
+    fn lint_dangling_field_ref() {
+        let good = "message M {\n\tlen: length_of<u16>(payload);\n\tn: u8;\n\tpayload: u8[n];\n}\n";
+        let msgs = lint(good);
+        assert!(!msgs.iter().any(|m| m.rule == LintRule::DanglingFieldRef), "{:?}", msgs);
+
+        let src = "message M {\n\tlen: length_of<u16>(paylod);\n\tn: u8;\n\tinner: Inner;\n\tpayload: u8[n];\n}\n\nstruct Inner {\n\titems: u8[n];\n\trest: u8[m];\n}\n";
+        let msgs = lint(src);
+        let found: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::DanglingFieldRef).collect();
+        assert_eq!(found.len(), 2, "expected two DanglingFieldRef: {:?}", msgs);
+        assert_eq!((found[0].line, found[0].column), (2, 2));
+        assert_eq!(&src.lines().nth(1).unwrap()[found[0].col_start - 1..found[0].col_end - 1], "len: length_of<u16>(paylod);");
+        assert_eq!(found[0].severity, Severity::Error);
+        assert!(found[0].message.contains("`len`") && found[0].message.contains("`paylod`"), "{}", found[0].message);
+        assert_eq!(found[1].line, 10);
+        assert!(found[1].message.contains("`m`") && found[1].message.contains("struct `Inner`"), "{}", found[1].message);
+    }
+
+    #[test]
+    fn lint_size_field_order() {
+        let good = "message M {\n\tn: count_of<u8>(items);\n\titems: u8[n];\n\tlen: u16;\n\tdata: bytes_list(len)<u8>;\n}\n";
+        let msgs = lint(good);
+        assert!(!msgs.iter().any(|m| m.rule == LintRule::SizeFieldOrder), "{:?}", msgs);
+
+        let src = "message M {\n\titems: u8[n];\n\tn: u8;\n\tdata: u8[2];\n\tlen: length_of<u8>(data);\n}\n";
+        let msgs = lint(src);
+        let found: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::SizeFieldOrder).collect();
+        assert_eq!(found.len(), 2, "expected two SizeFieldOrder: {:?}", msgs);
+        assert_eq!((found[0].line, found[0].column), (2, 2));
+        assert_eq!(found[0].severity, Severity::Error);
+        assert!(found[0].message.contains("`items` is sized by `n` (line 3)"), "{}", found[0].message);
+        assert_eq!(found[1].line, 5);
+        assert!(found[1].message.contains("`len` sizes `data` (line 4)"), "{}", found[1].message);
+    }
+
+    #[test]
+    fn lint_bit_group_alignment() {
+        let clean = "message M {\n\ta: bitfield(12);\n\tb: bitfield(4);\n\tc: u16(12);\n\td: u8;\n}\n";
+        let msgs = lint(clean);
+        assert!(!msgs.iter().any(|m| m.rule == LintRule::BitGroupAlignment), "{:?}", msgs);
+
+        let src = "message M {\n\ta: bitfield(12);\n\tb: bitfield(3);\n\tc: u8;\n}\n\nstruct S {\n\tx: u8;\n\ty: padding(5, bits);\n}\n";
+        let msgs = lint(src);
+        let found: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::BitGroupAlignment).collect();
+        assert_eq!(found.len(), 2, "expected two BitGroupAlignment: {:?}", msgs);
+        assert_eq!((found[0].line, found[0].column), (2, 2));
+        assert_eq!(found[0].severity, Severity::Error);
+        assert!(found[0].message.contains("`a`..`b` in message `M` totals 15 bits"), "{}", found[0].message);
+        assert_eq!(found[1].line, 9);
+        assert!(found[1].message.contains("`y` in struct `S` totals 5 bits"), "{}", found[1].message);
+
+        // A byte-aligned `u16(12)` takes two whole bytes, so the bitfield after it starts a run of its own.
+        let msgs = lint("message M {\n\ta: u16(12);\n\tb: bitfield(4);\n\tc: u8;\n}\n");
+        let found: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::BitGroupAlignment).collect();
+        assert_eq!(found.len(), 1, "{:?}", msgs);
+        assert!(found[0].message.contains("`b` in message `M` totals 4 bits"), "{}", found[0].message);
+    }
 }
@@ -1,6 +1,8 @@
 //! Parse DSL source into AST using PEST.
 
 use crate::ast::{PaddingKind, *};
+use crate::checksum::ChecksumAlgorithm;
+use crate::codec::Endianness;
 use pest::Parser;
 use pest_derive::Parser as PestParser;
 
@@ -18,7 +20,9 @@ pub fn parse(source: &str) -> Result<Protocol, String> {
 
 fn build_protocol(pair: pest::iterators::Pair<Rule>) -> Result<Protocol, String> {
     let mut transport = None;
+    let mut inner_transport = None;
     let mut payload = None;
+    let mut endianness_switch = None;
     let mut type_defs = Vec::new();
     let mut enum_defs = Vec::new();
     let mut messages = Vec::new();
@@ -27,7 +31,9 @@ fn build_protocol(pair: pest::iterators::Pair<Rule>) -> Result<Protocol, String>
     for inner in pair.into_inner() {
         match inner.as_rule() {
             Rule::transport_section => transport = Some(build_transport(inner)?),
+            Rule::inner_transport_section => inner_transport = Some(build_transport(inner)?),
             Rule::payload_section => payload = Some(build_payload(inner)?),
+            Rule::endianness_section => endianness_switch = Some(build_endianness_switch(inner)?),
             Rule::type_section => type_defs.push(build_type_def_section(inner)?),
             Rule::enum_section => enum_defs.push(build_enum_section(inner)?),
             Rule::message_section => messages.push(build_message(inner)?),
@@ -38,7 +44,9 @@ fn build_protocol(pair: pest::iterators::Pair<Rule>) -> Result<Protocol, String>
 
     Ok(Protocol {
         transport,
+        inner_transport,
         payload,
+        endianness_switch,
         type_defs,
         enum_defs,
         messages,
@@ -48,6 +56,7 @@ fn build_protocol(pair: pest::iterators::Pair<Rule>) -> Result<Protocol, String>
 
 fn build_enum_section(pair: pest::iterators::Pair<Rule>) -> Result<EnumSection, String> {
     let mut name = String::new();
+    let mut base = BaseType::U8;
     let mut variants = Vec::new();
     for inner in pair.into_inner() {
         match inner.as_rule() {
@@ -58,6 +67,7 @@ fn build_enum_section(pair: pest::iterators::Pair<Rule>) -> Result<EnumSection,
                     return Err("enum_variant: ident".to_string());
                 }
             }
+            Rule::base_type => base = parse_base_type(inner.as_str())?,
             Rule::enum_variant => {
                 let mut it = inner.into_inner();
                 let name_pair = it.next().ok_or("enum variant: name")?;
@@ -72,7 +82,7 @@ fn build_enum_section(pair: pest::iterators::Pair<Rule>) -> Result<EnumSection,
     if name.is_empty() {
         return Err("enum section: missing name".to_string());
     }
-    Ok(EnumSection { name, variants })
+    Ok(EnumSection { name, base, variants })
 }
 
 fn build_payload(pair: pest::iterators::Pair<Rule>) -> Result<PayloadSection, String> {
@@ -109,19 +119,85 @@ fn build_payload(pair: pest::iterators::Pair<Rule>) -> Result<PayloadSection, St
     Ok(PayloadSection { messages, selector, repeated })
 }
 
+/// `endianness from field { value: big|little, ... }`: values must be distinct.
+fn build_endianness_switch(pair: pest::iterators::Pair<Rule>) -> Result<EndiannessSwitch, String> {
+    let mut it = pair.into_inner();
+    let field = it.next().ok_or("endianness: field")?.as_str().to_string();
+    let mut cases: Vec<(i64, Endianness)> = Vec::new();
+    for case in it {
+        let mut parts = case.into_inner();
+        let value = parse_literal(parts.next().ok_or("endianness case: value")?.as_str())
+            .as_i64()
+            .ok_or("endianness case: value is not an integer")?;
+        let order = match parts.next().ok_or("endianness case: byte order")?.as_str() {
+            "big" => Endianness::Big,
+            _ => Endianness::Little,
+        };
+        if cases.iter().any(|(v, _)| *v == value) {
+            return Err(format!("endianness from {}: value {} is mapped twice", field, value));
+        }
+        cases.push((value, order));
+    }
+    Ok(EndiannessSwitch { field, cases })
+}
+
+/// `bits { name: width, ... }`: widths 1..=64, unique names, total a multiple of 8.
+fn build_bits_type(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String> {
+    let mut members: Vec<(String, u64)> = Vec::new();
+    for m in pair.into_inner() {
+        let mut it = m.into_inner();
+        let name = it.next().ok_or("bits member name")?.as_str().to_string();
+        let width: u64 = it.next().and_then(|p| p.as_str().parse().ok()).ok_or("bits member width")?;
+        if !(1..=64).contains(&width) {
+            return Err(format!("bits {{ ... }}: '{}' has width {} (must be 1..64)", name, width));
+        }
+        if members.iter().any(|(n, _)| *n == name) {
+            return Err(format!("bits {{ ... }}: duplicate member '{}'", name));
+        }
+        members.push((name, width));
+    }
+    let total: u64 = members.iter().map(|(_, w)| w).sum();
+    if !total.is_multiple_of(8) {
+        return Err(format!("bits {{ ... }}: total width {} is not a multiple of 8", total));
+    }
+    Ok(TypeSpec::Bits(members))
+}
+
 fn build_selector_spec(pair: pest::iterators::Pair<Rule>) -> Result<PayloadSelector, String> {
     let mut inner = pair.into_inner();
-    let transport_field = inner
-        .find(|p| p.as_rule() == Rule::ident)
-        .map(|p| p.as_str().to_string())
-        .ok_or("selector: missing transport field")?;
+    let key = inner.next().ok_or("selector: missing transport field")?;
+    let mut key_fields: Vec<String> = match key.as_rule() {
+        Rule::selector_fields => key.into_inner().map(|p| p.as_str().to_string()).collect(),
+        _ => vec![key.as_str().to_string()],
+    };
+    let transport_field = key_fields.remove(0);
+    let extra_fields = key_fields;
     let mut value_to_message = Vec::new();
+    let mut default_message = None;
     for part in inner {
         if part.as_rule() == Rule::selector_mapping {
             let mut it = part.into_inner();
             let lit_pair = it.next().ok_or("selector mapping: literal")?;
             let msg_type_pair = it.next().ok_or("selector mapping: message type")?;
-            let literal = parse_literal(lit_pair.as_str());
+            let is_default = lit_pair.as_rule() == Rule::selector_default;
+            let lit_text = lit_pair.as_str();
+            let literal = match lit_pair.as_rule() {
+                Rule::selector_tuple => Literal::List(lit_pair.into_inner().map(|p| parse_literal(p.as_str())).collect()),
+                _ => parse_literal(lit_pair.as_str()),
+            };
+            let arity = match &literal {
+                Literal::List(items) => items.len(),
+                _ => 1,
+            };
+            if !is_default && arity != extra_fields.len() + 1 {
+                return Err(format!(
+                    "selector on '{}': value {} has {} component(s), expected {}",
+                    transport_field,
+                    lit_text,
+                    arity,
+                    extra_fields.len() + 1
+                ));
+            }
             // selector_msg_type: either selector_list_type (list<ident>) or plain ident
             let (message_name, is_list) = if msg_type_pair.as_rule() == Rule::selector_msg_type {
                 let first = msg_type_pair.into_inner().next().ok_or("selector msg type")?;
@@ -138,7 +214,16 @@ fn build_selector_spec(pair: pest::iterators::Pair<Rule>) -> Result<PayloadSelec
             } else {
                 (msg_type_pair.as_str().to_string(), false)
             };
-            value_to_message.push((literal, message_name, is_list));
+            if is_default {
+                if is_list {
+                    return Err("selector default: list<...> is not supported".to_string());
+                }
+                if default_message.replace(message_name).is_some() {
+                    return Err("selector: duplicate default mapping".to_string());
+                }
+            } else {
+                value_to_message.push((literal, message_name, is_list));
+            }
         }
     }
     if value_to_message.is_empty() {
@@ -146,7 +231,9 @@ fn build_selector_spec(pair: pest::iterators::Pair<Rule>) -> Result<PayloadSelec
     }
     Ok(PayloadSelector {
         transport_field,
+        extra_fields,
         value_to_message,
+        default_message,
     })
 }
 
@@ -251,11 +338,16 @@ fn build_transport_field(
     let mut default = None;
     let mut constraint = None;
     let mut quantum = None;
+    let mut size_of = None;
 
     for inner in pair.into_inner() {
         match inner.as_rule() {
             Rule::ident => name = inner.as_str().to_string(),
-            Rule::transport_type_spec => type_spec = Some(build_transport_type_spec(inner)?),
+            Rule::transport_type_spec => {
+                let (spec, size) = build_transport_type_spec(inner)?;
+                type_spec = Some(spec);
+                size_of = size;
+            }
             Rule::literal => default = Some(parse_literal(inner.as_str())),
             Rule::constraint => constraint = Some(build_constraint(inner)?),
             Rule::quantum_spec => quantum = Some(parse_quantum_string(inner)?),
@@ -269,14 +361,29 @@ fn build_transport_field(
         default,
         constraint,
         quantum,
+        size_of,
     })
 }
 
+/// Transport field type; a `length_of` / `count_of` field is its width and the size it holds.
 fn build_transport_type_spec(
     pair: pest::iterators::Pair<Rule>,
-) -> Result<TransportTypeSpec, String> {
+) -> Result<(TransportTypeSpec, Option<TransportSize>), String> {
     let inner = pair.into_inner().next().ok_or("Empty transport type")?;
-    match inner.as_rule() {
+    let spec = match inner.as_rule() {
+        Rule::length_of_type => {
+            let (id, width) = build_size_field(inner, "length_of")?;
+            let size = match id.as_str() {
+                "record" => TransportSize::Record,
+                "message" => TransportSize::Message,
+                _ => TransportSize::LengthOf(id),
+            };
+            return Ok((TransportTypeSpec::Base(width), Some(size)));
+        }
+        Rule::count_of_type => {
+            let (id, width) = build_size_field(inner, "count_of")?;
+            return Ok((TransportTypeSpec::Base(width), Some(TransportSize::CountOf(id))));
+        }
         Rule::base_type => Ok(TransportTypeSpec::Base(parse_base_type(inner.as_str())?)),
         Rule::sized_int_type => {
             let mut it = inner.into_inner();
@@ -301,70 +408,174 @@ fn build_transport_type_spec(
             Ok(TransportTypeSpec::Magic(bytes))
         }
         _ => Err("Unknown transport type".to_string()),
-    }
+    };
+    spec.map(|s| (s, None))
 }
 
 fn build_message(pair: pest::iterators::Pair<Rule>) -> Result<MessageSection, String> {
     let mut name = String::new();
     let mut fields = Vec::new();
+    let mut selector = None;
+    let mut checks = Vec::new();
     for inner in pair.into_inner() {
         match inner.as_rule() {
             Rule::ident => name = inner.as_str().to_string(),
             Rule::message_field => fields.push(build_message_field(inner)?),
+            Rule::message_check => {
+                let expr = inner.into_inner().next().ok_or("check: missing expression")?;
+                let text = expr.as_str().split_whitespace().collect::<Vec<_>>().join(" ");
+                checks.push(MessageCheck { expr: build_check_expr(expr)?, text });
+            }
+            Rule::selector_spec => {
+                let sel = build_selector_spec(inner)?;
+                if let Some((_, variant, _)) = sel.value_to_message.iter().find(|(_, _, is_list)| *is_list) {
+                    return Err(format!("message {}: selector variant list<{}> is not supported", name, variant));
+                }
+                selector = Some(sel);
+            }
             _ => {}
         }
     }
-    Ok(MessageSection { name, fields })
+    Ok(MessageSection { name, fields, selector, checks })
+}
+
+/// Any level of the `check { ... }` expression grammar: operands joined left to right by their operators.
+fn build_check_expr(pair: pest::iterators::Pair<Rule>) -> Result<CheckExpr, String> {
+    let rule = pair.as_rule();
+    let mut it = pair.into_inner();
+    match rule {
+        Rule::check_atom => {
+            let inner = it.next().ok_or("check: empty operand")?;
+            let text = inner.as_str();
+            return match inner.as_rule() {
+                Rule::check_expr => build_check_expr(inner),
+                Rule::ident if text == "true" || text == "false" => Ok(CheckExpr::Bool(text == "true")),
+                Rule::ident => Ok(CheckExpr::Field(text.to_string())),
+                _ => match parse_literal(text) {
+                    Literal::Float(x) => Ok(CheckExpr::Float(x)),
+                    lit => lit.as_i64().map(CheckExpr::Int).ok_or_else(|| format!("check: bad number {}", text)),
+                },
+            };
+        }
+        Rule::check_unary => {
+            let mut nots = 0;
+            for p in it {
+                if p.as_rule() == Rule::check_not_op {
+                    nots += 1;
+                } else {
+                    let mut e = build_check_expr(p)?;
+                    for _ in 0..nots {
+                        e = CheckExpr::Not(Box::new(e));
+                    }
+                    return Ok(e);
+                }
+            }
+            return Err("check: missing operand after '!'".to_string());
+        }
+        _ => {}
+    }
+    let mut expr = build_check_expr(it.next().ok_or("check: missing operand")?)?;
+    while let Some(op) = it.next() {
+        let op = match op.as_str() {
+            "=>" => CheckOp::Implies,
+            "||" => CheckOp::Or,
+            "&&" => CheckOp::And,
+            "==" => CheckOp::Eq,
+            "!=" => CheckOp::Ne,
+            "<" => CheckOp::Lt,
+            "<=" => CheckOp::Le,
+            ">" => CheckOp::Gt,
+            ">=" => CheckOp::Ge,
+            "+" => CheckOp::Add,
+            "-" => CheckOp::Sub,
+            "*" => CheckOp::Mul,
+            "/" => CheckOp::Div,
+            "%" => CheckOp::Rem,
+            other => return Err(format!("check: unknown operator {}", other)),
+        };
+        let rhs = build_check_expr(it.next().ok_or("check: missing operand")?)?;
+        expr = CheckExpr::Binary(op, Box::new(expr), Box::new(rhs));
+    }
+    Ok(expr)
 }
 
 fn build_message_field(pair: pest::iterators::Pair<Rule>) -> Result<MessageField, String> {
-    build_generic_field(pair, build_type_spec).map(|(name, type_spec, default, constraint, condition, quantum, doc)| MessageField {
-        name,
-        type_spec,
-        default,
-        constraint,
-        condition,
-        quantum,
-        doc,
+    let f = build_generic_field(pair, build_type_spec)?;
+    Ok(MessageField {
+        name: f.name,
+        type_spec: f.type_spec,
+        default: f.default,
+        constraint: f.constraint,
+        condition: f.condition,
+        quantum: f.quantum,
+        doc: f.doc,
+        conditional_constraints: f.conditional_constraints,
         saturating: false,
+        referenced_by_condition: false,
+        referenced_by_width: false,
+        referenced_by_selector: false,
     })
 }
 
 fn build_struct(pair: pest::iterators::Pair<Rule>) -> Result<StructSection, String> {
     let mut name = String::new();
     let mut fields = Vec::new();
+    let mut reversed = false;
     for inner in pair.into_inner() {
         match inner.as_rule() {
+            Rule::struct_attr => {
+                let attr = inner.into_inner().next().ok_or("struct attribute")?.as_str();
+                match attr {
+                    "reversed" => reversed = true,
+                    _ => return Err(format!("unknown struct attribute #[{}]", attr)),
+                }
+            }
             Rule::ident => name = inner.as_str().to_string(),
             Rule::struct_field => fields.push(build_struct_field(inner)?),
             _ => {}
         }
     }
-    Ok(StructSection { name, fields })
+    Ok(StructSection { name, fields, reversed })
 }
 
 fn build_struct_field(pair: pest::iterators::Pair<Rule>) -> Result<StructField, String> {
-    build_generic_field(pair, build_type_spec).map(|(name, type_spec, default, constraint, condition, quantum, _doc)| StructField {
-        name,
-        type_spec,
-        default,
-        constraint,
-        condition,
-        quantum,
+    let f = build_generic_field(pair, build_type_spec)?;
+    Ok(StructField {
+        name: f.name,
+        type_spec: f.type_spec,
+        default: f.default,
+        constraint: f.constraint,
+        condition: f.condition,
+        quantum: f.quantum,
+        conditional_constraints: f.conditional_constraints,
+        referenced_by_condition: false,
+        referenced_by_width: false,
     })
 }
 
+/// Parts shared by message and struct fields.
+struct GenericField {
+    name: String,
+    type_spec: TypeSpec,
+    default: Option<Literal>,
+    constraint: Option<Constraint>,
+    condition: Option<Condition>,
+    quantum: Option<String>,
+    doc: Option<String>,
+    conditional_constraints: Vec<(Option<Condition>, Constraint)>,
+}
+
 fn build_generic_field<F>(
     pair: pest::iterators::Pair<Rule>,
     type_builder: F,
-) -> Result<(String, TypeSpec, Option<Literal>, Option<Constraint>, Option<Condition>, Option<String>, Option<String>), String>
+) -> Result<GenericField, String>
 where
     F: FnOnce(pest::iterators::Pair<Rule>) -> Result<TypeSpec, String>,
 {
     let mut name = String::new();
     let mut type_spec_pair = None;
     let mut default = None;
-    let mut constraint = None;
+    let mut clauses = Vec::new();
     let mut cond_field = None;
     let mut cond_value = None;
     let mut quantum = None;
@@ -387,14 +598,46 @@ where
                     default = Some(parse_literal(inner.as_str()));
                 }
             }
-            Rule::constraint => constraint = Some(build_constraint(inner)?),
+            Rule::literal_list => default = Some(Literal::List(inner.into_inner().map(|p| parse_literal(p.as_str())).collect())),
+            Rule::constraint_clause => clauses.push(build_constraint_clause(inner)?),
             Rule::quantum_spec => quantum = Some(parse_quantum_string(inner)?),
             _ => {}
         }
     }
     let type_spec = type_builder(type_spec_pair.ok_or("Missing type in field")?)?;
+    if matches!(default, Some(Literal::List(_)))
+        && !matches!(type_spec, TypeSpec::List(_) | TypeSpec::RepList(_) | TypeSpec::LengthPrefixedList(..) | TypeSpec::BytesList(..) | TypeSpec::Array(..))
+    {
+        return Err(format!("field {}: a [..] default needs a list or array type", name));
+    }
     let condition = cond_field.zip(cond_value).map(|(field, value)| Condition { field, value });
-    Ok((name, type_spec, default, constraint, condition, quantum, doc))
+    // A single unconditional clause is the plain `[..]` constraint; any `when` makes them all conditional.
+    let (constraint, conditional_constraints) = if clauses.iter().any(|(c, _)| c.is_some()) {
+        (None, clauses)
+    } else if clauses.len() > 1 {
+        return Err(format!("field {}: multiple constraints need `when` conditions", name));
+    } else {
+        (clauses.pop().map(|(_, c)| c), Vec::new())
+    };
+    Ok(GenericField { name, type_spec, default, constraint, condition, quantum, doc, conditional_constraints })
+}
+
+fn build_constraint_clause(pair: pest::iterators::Pair<Rule>) -> Result<(Option<Condition>, Constraint), String> {
+    let mut constraint = None;
+    let mut condition = None;
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::constraint => constraint = Some(build_constraint(inner)?),
+            Rule::constraint_when => {
+                let mut it = inner.into_inner();
+                let field = it.next().ok_or("when: missing field")?.as_str().to_string();
+                let value = parse_literal(it.next().ok_or("when: missing value")?.as_str());
+                condition = Some(Condition { field, value });
+            }
+            _ => {}
+        }
+    }
+    Ok((condition, constraint.ok_or("Empty constraint clause")?))
 }
 
 fn build_type_spec(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String> {
@@ -418,20 +661,32 @@ fn build_type_spec(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String
             let n = inner.into_inner().next().and_then(|p| p.as_str().parse().ok()).ok_or("bitfield(n)")?;
             Ok(TypeSpec::Bitfield(n))
         }
+        Rule::bits_type => build_bits_type(inner),
         Rule::length_of_type => {
-            let id = inner.into_inner().next().ok_or("length_of(field)")?.as_str().to_string();
-            Ok(TypeSpec::LengthOf(id))
+            let (id, width) = build_size_field(inner, "length_of")?;
+            Ok(TypeSpec::LengthOf(id, width))
         }
         Rule::count_of_type => {
-            let id = inner.into_inner().next().ok_or("count_of(field)")?.as_str().to_string();
-            Ok(TypeSpec::CountOf(id))
+            let (id, width) = build_size_field(inner, "count_of")?;
+            Ok(TypeSpec::CountOf(id, width))
         }
         Rule::presence_bits_type => {
-            let n = inner.into_inner().next().and_then(|p| p.as_str().parse().ok()).ok_or("presence_bits(n)")?;
+            let mut nums = inner.into_inner();
+            let n = nums.next().and_then(|p| p.as_str().parse().ok()).ok_or("presence_bits(n)")?;
             if ![1, 2, 4].contains(&n) {
                 return Err("presence_bits(n): n must be 1, 2, or 4".to_string());
             }
-            Ok(TypeSpec::PresenceBits(n))
+            let stop = match nums.next() {
+                Some(p) => {
+                    let k: u32 = p.as_str().parse().map_err(|_| "presence_bits(n, stop: k)")?;
+                    if u64::from(k) >= n * 8 {
+                        return Err(format!("presence_bits({}, stop: {}): stop bit must be below {}", n, k, n * 8));
+                    }
+                    Some(k)
+                }
+                None => None,
+            };
+            Ok(TypeSpec::PresenceBits(n, stop))
         }
         Rule::bitmap_type => {
             let pairs: Vec<_> = inner.into_inner().collect();
@@ -485,10 +740,39 @@ fn build_type_spec(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String
             })
         }
         Rule::struct_ref_type => Ok(TypeSpec::StructRef(inner.as_str().to_string())),
+        Rule::enum_type => Ok(TypeSpec::Enum(inner.into_inner().next().ok_or("enum<E>")?.as_str().to_string())),
+        Rule::select_type => {
+            let mut it = inner.into_inner();
+            let field = it.next().ok_or("select<field>")?.as_str().to_string();
+            let mut cases: Vec<(i64, String)> = Vec::new();
+            let mut default = None;
+            for case in it {
+                let mut c = case.into_inner();
+                let key = c.next().ok_or("select case")?;
+                let target = c.next().ok_or("select case: struct")?.as_str().to_string();
+                if key.as_rule() == Rule::selector_default {
+                    if default.replace(target).is_some() {
+                        return Err(format!("select<{}>: more than one default", field));
+                    }
+                    continue;
+                }
+                let value = parse_literal(key.as_str()).as_i64().ok_or_else(|| format!("select<{}>: case {}", field, key.as_str()))?;
+                if cases.iter().any(|(k, _)| *k == value) {
+                    return Err(format!("select<{}>: duplicate case {}", field, key.as_str()));
+                }
+                cases.push((value, target));
+            }
+            Ok(TypeSpec::Select { field, cases, default })
+        }
         Rule::array_type => {
             let mut inner_iter = inner.into_inner();
             let elem_type = inner_iter.next().ok_or("array type")?;
             let len_pair = inner_iter.next().ok_or("array len")?;
+            let len_pair = if len_pair.as_rule() == Rule::array_len {
+                len_pair.into_inner().next().ok_or("array len")?
+            } else {
+                len_pair
+            };
             let elem_spec = match elem_type.as_rule() {
                 Rule::type_spec_inner => build_type_spec_inner(elem_type)?,
                 _ => build_type_spec(elem_type)?,
@@ -498,7 +782,31 @@ fn build_type_spec(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String
                 Rule::ident => ArrayLen::FieldRef(len_pair.as_str().to_string()),
                 _ => return Err("array length".to_string()),
             };
-            Ok(TypeSpec::Array(Box::new(elem_spec), len))
+            let stride = match inner_iter.next() {
+                Some(p) => {
+                    let m: u64 = p.into_inner().next().ok_or("stride(m)")?.as_str().parse().map_err(|_| "array stride")?;
+                    if let Some(size) = type_spec_fixed_bytes(&elem_spec).filter(|&size| m < size as u64) {
+                        return Err(format!("array stride({}) is smaller than its {}-byte element", m, size));
+                    }
+                    Some(m)
+                }
+                None => None,
+            };
+            Ok(TypeSpec::Array(Box::new(elem_spec), len, stride))
+        }
+        Rule::dyn_int_type => {
+            let field = inner.into_inner().next().ok_or("dyn_int(field)")?;
+            Ok(TypeSpec::DynInt(field.as_str().to_string()))
+        }
+        Rule::repeat_type => {
+            let mut it = inner.into_inner();
+            let len_pair = it.next().ok_or("repeat count")?.into_inner().next().ok_or("repeat count")?;
+            let count = match len_pair.as_rule() {
+                Rule::num => ArrayLen::Constant(len_pair.as_str().parse().map_err(|_| "repeat count")?),
+                _ => ArrayLen::FieldRef(len_pair.as_str().to_string()),
+            };
+            let fields = it.map(build_struct_field).collect::<Result<Vec<_>, _>>()?;
+            Ok(TypeSpec::Group { count, fields })
         }
         Rule::list_type => {
             let inner_type = inner.into_inner().next().ok_or("list<T>")?;
@@ -509,14 +817,73 @@ fn build_type_spec(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String
             Ok(TypeSpec::RepList(Box::new(build_type_spec_inner(inner_type)?)))
         }
         Rule::octets_fx_type => Ok(TypeSpec::OctetsFx),
+        Rule::timestamp_type => build_timestamp_type(inner),
+        Rule::bcd_type => build_bcd_type(inner),
+        Rule::reverse_bits_type => build_reverse_bits_type(inner),
+        Rule::remaining_type => Ok(TypeSpec::Remaining),
+        Rule::unknown_type => {
+            let n: u64 = inner.into_inner().next().and_then(|p| p.as_str().parse().ok()).ok_or("unknown(n) needs number")?;
+            if n == 0 {
+                return Err("unknown(0): n must be at least 1 byte".to_string());
+            }
+            Ok(TypeSpec::Unknown(n))
+        }
+        Rule::cstring_type => Ok(TypeSpec::CString),
+        Rule::string_type => build_string_type(inner),
+        Rule::checksum_type => {
+            let mut it = inner.into_inner();
+            let name = it.next().ok_or("checksum(alg)")?.as_str();
+            let algorithm = ChecksumAlgorithm::from_name(name)
+                .ok_or_else(|| format!("checksum({}): unknown algorithm (expected crc16_ccitt or crc32)", name))?;
+            Ok(TypeSpec::Checksum { algorithm, from: it.next().map(|p| p.as_str().to_string()) })
+        }
+        Rule::custom_type => build_custom_type(inner),
+        Rule::length_prefixed_list_type => {
+            let mut it = inner.into_inner();
+            let width = it.next().ok_or("length_prefixed_list(width)")?;
+            let len = match parse_base_type(width.as_str())? {
+                bt @ (BaseType::U8 | BaseType::U16 | BaseType::U24 | BaseType::U32 | BaseType::U48 | BaseType::U64) => bt,
+                _ => return Err(format!("length_prefixed_list({})<T>: length must be an unsigned integer type", width.as_str())),
+            };
+            let inner_type = it.next().ok_or("length_prefixed_list<T>")?;
+            Ok(TypeSpec::LengthPrefixedList(Box::new(build_type_spec_inner(inner_type)?), len))
+        }
+        Rule::bytes_list_type => {
+            let mut it = inner.into_inner();
+            let len_field = it.next().ok_or("bytes_list(len)")?.as_str().to_string();
+            let inner_type = it.next().ok_or("bytes_list<T>")?;
+            Ok(TypeSpec::BytesList(Box::new(build_type_spec_inner(inner_type)?), len_field))
+        }
         Rule::optional_type => {
-            let inner_type = inner.into_inner().next().ok_or("optional<T>")?;
-            Ok(TypeSpec::Optional(Box::new(build_type_spec_inner(inner_type)?)))
+            let mut it = inner.into_inner().peekable();
+            let flag = match it.next_if(|p| p.as_rule() == Rule::base_type) {
+                Some(p) => match parse_base_type(p.as_str())? {
+                    bt @ (BaseType::U8 | BaseType::U16 | BaseType::U24 | BaseType::U32 | BaseType::U48 | BaseType::U64) => bt,
+                    _ => return Err(format!("optional({})<T>: presence flag must be an unsigned integer type", p.as_str())),
+                },
+                None => BaseType::U8,
+            };
+            let inner_type = it.next().ok_or("optional<T>")?;
+            Ok(TypeSpec::Optional(Box::new(build_type_spec_inner(inner_type)?), flag))
         }
         _ => Err(format!("Unhandled type rule: {:?}", inner.as_rule())),
     }
 }
 
+/// Field name and width of `length_of<u16>(field)` / `count_of(field)`; the width must be unsigned (`u32` when omitted).
+fn build_size_field(pair: pest::iterators::Pair<Rule>, keyword: &str) -> Result<(String, BaseType), String> {
+    let mut it = pair.into_inner().peekable();
+    let width = match it.next_if(|p| p.as_rule() == Rule::base_type) {
+        Some(p) => match parse_base_type(p.as_str())? {
+            bt @ (BaseType::U8 | BaseType::U16 | BaseType::U24 | BaseType::U32 | BaseType::U48 | BaseType::U64) => bt,
+            _ => return Err(format!("{}<{}>(field): width must be an unsigned integer type", keyword, p.as_str())),
+        },
+        None => BaseType::U32,
+    };
+    let id = it.next().ok_or_else(|| format!("{}(field)", keyword))?.as_str().to_string();
+    Ok((id, width))
+}
+
 fn build_type_spec_inner(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String> {
     let inner = pair.into_inner().next().ok_or("Empty type_spec_inner")?;
     match inner.as_rule() {
@@ -538,7 +905,9 @@ fn build_type_spec_inner(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec,
             let n = inner.into_inner().next().and_then(|p| p.as_str().parse().ok()).ok_or("bitfield")?;
             Ok(TypeSpec::Bitfield(n))
         }
+        Rule::bits_type => build_bits_type(inner),
         Rule::struct_ref_type => Ok(TypeSpec::StructRef(inner.as_str().to_string())),
+        Rule::enum_type => Ok(TypeSpec::Enum(inner.into_inner().next().ok_or("enum<E>")?.as_str().to_string())),
         Rule::list_type => {
             let inner_type = inner.into_inner().next().ok_or("list<T>")?;
             Ok(TypeSpec::List(Box::new(build_type_spec_inner(inner_type)?)))
@@ -548,10 +917,78 @@ fn build_type_spec_inner(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec,
             Ok(TypeSpec::RepList(Box::new(build_type_spec_inner(inner_type)?)))
         }
         Rule::octets_fx_type => Ok(TypeSpec::OctetsFx),
+        Rule::timestamp_type => build_timestamp_type(inner),
+        Rule::bcd_type => build_bcd_type(inner),
+        Rule::reverse_bits_type => build_reverse_bits_type(inner),
+        Rule::cstring_type => Ok(TypeSpec::CString),
+        Rule::string_type => build_string_type(inner),
+        Rule::custom_type => build_custom_type(inner),
         _ => Err("Invalid inner type".to_string()),
     }
 }
 
+fn build_string_type(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String> {
+    let n: u64 = pair.into_inner().next().and_then(|p| p.as_str().parse().ok()).ok_or("string(n) needs number")?;
+    if n == 0 {
+        return Err("string(0): n must be at least 1 byte".to_string());
+    }
+    Ok(TypeSpec::FixedString(n))
+}
+
+fn build_timestamp_type(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String> {
+    let mut it = pair.into_inner();
+    let base_pair = it.next().ok_or("timestamp(T, \"quantum\")")?;
+    let base = match base_pair.as_rule() {
+        Rule::base_type => {
+            let bt = parse_base_type(base_pair.as_str())?;
+            if matches!(bt, BaseType::Bool | BaseType::Float | BaseType::Double) {
+                return Err(format!("timestamp: base type must be an integer, got {}", base_pair.as_str()));
+            }
+            TypeSpec::Base(bt)
+        }
+        Rule::sized_int_type => {
+            let mut si = base_pair.into_inner();
+            let bt = parse_base_type(si.next().ok_or("sized_int base")?.as_str())?;
+            let n = si.next().and_then(|p| p.as_str().parse().ok()).ok_or("sized_int(n)")?;
+            TypeSpec::SizedInt(bt, n)
+        }
+        _ => return Err("timestamp: base type must be an integer".to_string()),
+    };
+    let lit = it.next().ok_or("timestamp: missing quantum")?.as_str();
+    let quantum = lit.trim_matches('"').to_string();
+    let scale = crate::dump::quantum_seconds_per_tick(&quantum)
+        .ok_or_else(|| format!("timestamp: quantum \"{}\" is not a time resolution (e.g. \"1/128 s\", \"1 ms\")", quantum))?;
+    Ok(TypeSpec::Timestamp { base: Box::new(base), quantum, scale })
+}
+
+fn build_bcd_type(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String> {
+    let mut it = pair.into_inner();
+    let swapped = it.next().ok_or("bcd(n)")?.as_str() == "bcd_swapped";
+    let bytes: u64 = it.next().and_then(|p| p.as_str().parse().ok()).ok_or("bcd(n) needs number")?;
+    if bytes == 0 || bytes > 9 {
+        return Err(format!("bcd({}): n must be 1..=9 bytes", bytes));
+    }
+    Ok(TypeSpec::Bcd { bytes, swapped })
+}
+
+fn build_reverse_bits_type(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String> {
+    let inner_type = pair.into_inner().next().ok_or("reverse_bits<T>")?;
+    let inner = build_type_spec_inner(inner_type)?;
+    if type_spec_fixed_bytes(&inner).is_none() {
+        return Err("reverse_bits<T>: T must be a fixed-size scalar type".to_string());
+    }
+    Ok(TypeSpec::ReverseBits(Box::new(inner)))
+}
+
+fn build_custom_type(pair: pest::iterators::Pair<Rule>) -> Result<TypeSpec, String> {
+    let lit = pair.into_inner().next().ok_or("custom(\"name\")")?.as_str();
+    let name = lit.trim_matches('"');
+    if name.is_empty() {
+        return Err("custom(\"name\"): name must not be empty".to_string());
+    }
+    Ok(TypeSpec::Custom(name.to_string()))
+}
+
 fn build_constraint(pair: pest::iterators::Pair<Rule>) -> Result<Constraint, String> {
     let inner = pair.into_inner().next().ok_or("Empty constraint")?;
     match inner.as_rule() {
@@ -573,13 +1010,36 @@ fn build_constraint(pair: pest::iterators::Pair<Rule>) -> Result<Constraint, Str
             Ok(Constraint::Range(intervals))
         }
         Rule::enum_constraint => {
-            let mut literals = Vec::new();
+            let mut codes = Vec::new();
             for p in inner.into_inner() {
-                if matches!(p.as_rule(), Rule::literal) {
-                    literals.push(parse_literal(p.as_str()));
+                match p.as_rule() {
+                    Rule::literal => codes.push(EnumCode { value: parse_literal(p.as_str()), name: None }),
+                    Rule::enum_code => {
+                        let mut it = p.into_inner();
+                        let first = it.next().ok_or("enum code: missing value")?;
+                        let code = match it.next() {
+                            Some(value) => EnumCode { value: parse_literal(value.as_str()), name: Some(first.as_str().to_string()) },
+                            None => EnumCode { value: parse_literal(first.as_str()), name: None },
+                        };
+                        if let Some(name) = &code.name {
+                            if codes.iter().any(|c: &EnumCode| c.name.as_ref() == Some(name)) {
+                                return Err(format!("enum constraint: duplicate code name '{}'", name));
+                            }
+                        }
+                        codes.push(code);
+                    }
+                    Rule::enum_tolerance => {
+                        let text = p.into_inner().next().ok_or("enum tolerance: missing value")?.as_str();
+                        let tolerance: f64 = text.parse().map_err(|_| format!("enum tolerance: bad number {}", text))?;
+                        if tolerance < 0.0 {
+                            return Err(format!("enum tolerance {} is negative", text));
+                        }
+                        return Ok(Constraint::EnumTolerance(codes, tolerance));
+                    }
+                    _ => {}
                 }
             }
-            Ok(Constraint::Enum(literals))
+            Ok(Constraint::Enum(codes))
         }
         _ => Err("Unknown constraint".to_string()),
     }
@@ -602,6 +1062,8 @@ fn parse_base_type(s: &str) -> Result<BaseType, String> {
         "u16" => Ok(BaseType::U16),
         "u32" => Ok(BaseType::U32),
         "u64" => Ok(BaseType::U64),
+        "u24" => Ok(BaseType::U24),
+        "u48" => Ok(BaseType::U48),
         "i8" => Ok(BaseType::I8),
         "i16" => Ok(BaseType::I16),
         "i32" => Ok(BaseType::I32),
@@ -629,6 +1091,11 @@ fn parse_literal(s: &str) -> Literal {
     if let Ok(n) = s.parse::<i64>() {
         return Literal::Int(n);
     }
+    if s.contains(['.', 'e', 'E']) && !s.starts_with('"') {
+        if let Ok(x) = s.parse::<f64>() {
+            return Literal::Float(x);
+        }
+    }
     if s.starts_with('"') && s.ends_with('"') {
         let inner = &s[1..s.len() - 1];
         let unescaped = inner.replace("\\n", "\n").replace("\\t", "\t").replace("\\\"", "\"");
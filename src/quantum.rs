@@ -0,0 +1,104 @@
+//! Quantum (resolution and unit) of a field, as written in the DSL: `quantum "1/256 NM"`. Converts raw
+//! integers to physical values for tools that do not go through [`Codec::quantum_scaling`](crate::codec::Codec::quantum_scaling).
+
+use crate::value::Value;
+
+/// Parse quantum string (e.g. "1/256 NM", "360/65536 °") into (scale, unit).
+/// The scale is `num/denom`, `num/2^exp`, `2^exp`, `2^(-exp)` or a plain number; the unit is the rest (may be empty).
+pub fn parse_quantum(quantum_str: &str) -> Option<(f64, String)> {
+    let s = quantum_str.trim();
+    let (scale_str, unit) = match s.find(' ') {
+        Some(i) => (s[..i].trim(), s[i + 1..].trim().to_string()),
+        None => (s, String::new()),
+    };
+    let scale = parse_scale_expr(scale_str)?;
+    Some((scale, unit))
+}
+
+/// Physical value of `v` (raw value times the scale of quantum `q`); None when `v` is not a number or `q`
+/// does not parse.
+pub fn apply_quantum(v: &Value, q: &str) -> Option<f64> {
+    let raw = match v {
+        Value::U8(x) => *x as f64,
+        Value::U16(x) => *x as f64,
+        Value::U32(x) => *x as f64,
+        Value::U64(x) => *x as f64,
+        Value::I8(x) => *x as f64,
+        Value::I16(x) => *x as f64,
+        Value::I32(x) => *x as f64,
+        Value::I64(x) => *x as f64,
+        Value::Float(x) => *x as f64,
+        Value::Double(x) => *x,
+        _ => return None,
+    };
+    parse_quantum(q).map(|(scale, _)| raw * scale)
+}
+
+fn parse_scale_expr(s: &str) -> Option<f64> {
+    let s = s.trim();
+    if let Some(slash) = s.find('/') {
+        let num: f64 = s[..slash].trim().parse().ok()?;
+        let denom_str = s[slash + 1..].trim();
+        let denom: f64 = match denom_str.strip_prefix("2^") {
+            Some(exp_str) => power_of_two(exp_str)?,
+            None => denom_str.parse().ok()?,
+        };
+        return Some(num / denom);
+    }
+    match s.strip_prefix("2^") {
+        Some(exp_str) => power_of_two(exp_str),
+        None => s.parse::<f64>().ok(),
+    }
+}
+
+/// `2^exp` for an exponent written `10`, `-10` or `(-10)`.
+fn power_of_two(exp_str: &str) -> Option<f64> {
+    let exp: i32 = exp_str.trim_matches(|c| c == '(' || c == ')').parse().ok()?;
+    Some(2f64.powi(exp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantum_fraction() {
+        assert_eq!(parse_quantum("1/256 NM"), Some((1.0 / 256.0, "NM".to_string())));
+        assert_eq!(parse_quantum("360/65536 °"), Some((360.0 / 65536.0, "°".to_string())));
+    }
+
+    #[test]
+    fn quantum_fraction_with_power_of_two() {
+        assert_eq!(parse_quantum("1/2^7 s"), Some((1.0 / 128.0, "s".to_string())));
+        assert_eq!(parse_quantum("360/2^(16) deg"), Some((360.0 / 65536.0, "deg".to_string())));
+    }
+
+    #[test]
+    fn quantum_power_of_two() {
+        assert_eq!(parse_quantum("2^3 m"), Some((8.0, "m".to_string())));
+        assert_eq!(parse_quantum("2^(-10) NM/s"), Some((1.0 / 1024.0, "NM/s".to_string())));
+        assert_eq!(parse_quantum("2^-2"), Some((0.25, String::new())));
+    }
+
+    #[test]
+    fn quantum_plain_number() {
+        assert_eq!(parse_quantum("0.25 FL"), Some((0.25, "FL".to_string())));
+        assert_eq!(parse_quantum("  6.25  ft "), Some((6.25, "ft".to_string())));
+    }
+
+    #[test]
+    fn quantum_malformed() {
+        assert_eq!(parse_quantum("NM"), None);
+        assert_eq!(parse_quantum("1/x NM"), None);
+        assert_eq!(parse_quantum("2^(a) s"), None);
+        assert_eq!(parse_quantum(""), None);
+    }
+
+    #[test]
+    fn apply_quantum_scales_numbers() {
+        assert_eq!(apply_quantum(&Value::U16(640), "1/256 NM"), Some(2.5));
+        assert_eq!(apply_quantum(&Value::I16(-512), "2^(-10) NM/s"), Some(-0.5));
+        assert_eq!(apply_quantum(&Value::Bytes(vec![1]), "1/256 NM"), None);
+        assert_eq!(apply_quantum(&Value::U8(1), "fast"), None);
+    }
+}
@@ -1,9 +1,18 @@
 //! Runtime values for encoding/decoding (codec representation).
 
+use crate::ast::{
+    active_constraint, presence_bits_wire, select_case, type_spec_integer_range, ArrayLen, BaseType, BitmapPresenceMapping, Condition, Constraint,
+    EnumCode, Literal, ResolvedProtocol, StructField, TypeSpec,
+};
+use crate::codec::CodecError;
 use std::collections::HashMap;
 
 /// A single decoded value (field or compound).
+///
+/// With the `serde` feature, values serialize externally tagged by variant (`{"U16": 4660}`,
+/// `{"Struct": {...}}`, `{"List": [...]}`, `"Padding"`); `Bytes` is an array of byte numbers.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     U8(u8),
     U16(u16),
@@ -21,6 +30,11 @@ pub enum Value {
     List(Vec<Value>),
     /// Padding (bytes or bits): must be zero on encode.
     Padding,
+    /// Decoded `timestamp(T, "quantum")`: raw wire ticks and the same instant in seconds (raw × resolution).
+    Timestamp { raw: i64, seconds: f64 },
+    /// Decoded `enum<E>`: the enum `name`, the variant whose value is `raw` (None when no variant has it)
+    /// and the wire integer.
+    Enum { name: String, variant: Option<String>, raw: i64 },
 }
 
 impl Value {
@@ -30,6 +44,9 @@ impl Value {
             Value::U16(x) => Some(*x as u64),
             Value::U32(x) => Some(*x as u64),
             Value::U64(x) => Some(*x),
+            Value::Timestamp { raw, .. } | Value::Enum { raw, .. } => (*raw).try_into().ok(),
+            Value::Float(x) => Some(*x as u64),
+            Value::Double(x) => Some(*x as u64),
             _ => None,
         }
     }
@@ -44,10 +61,49 @@ impl Value {
             Value::U16(x) => Some(*x as i64),
             Value::U32(x) => Some(*x as i64),
             Value::U64(x) => Some(*x as i64),
+            Value::Timestamp { raw, .. } | Value::Enum { raw, .. } => Some(*raw),
+            Value::Float(x) => Some(*x as i64),
+            Value::Double(x) => Some(*x as i64),
             _ => None,
         }
     }
 
+    /// Strict [`as_u64`](Self::as_u64): any integer, or a float holding an exact integer, that fits in
+    /// a `u64`. Negative, fractional, out-of-range and non-numeric values are a [`CodecError::Validation`].
+    pub fn try_as_u64(&self) -> Result<u64, CodecError> {
+        match self {
+            Value::U8(x) => Ok(*x as u64),
+            Value::U16(x) => Ok(*x as u64),
+            Value::U32(x) => Ok(*x as u64),
+            Value::U64(x) => Ok(*x),
+            Value::Float(x) => float_to_int(*x as f64, "u64"),
+            Value::Double(x) => float_to_int(*x, "u64"),
+            _ => {
+                let n = self.try_as_i64()?;
+                u64::try_from(n).map_err(|_| CodecError::Validation(format!("value {} is negative, not a u64", n)))
+            }
+        }
+    }
+
+    /// Strict [`as_i64`](Self::as_i64): any integer, or a float holding an exact integer, that fits in
+    /// an `i64` (a `U64` above `i64::MAX` does not). Other values are a [`CodecError::Validation`].
+    pub fn try_as_i64(&self) -> Result<i64, CodecError> {
+        match self {
+            Value::I8(x) => Ok(*x as i64),
+            Value::I16(x) => Ok(*x as i64),
+            Value::I32(x) => Ok(*x as i64),
+            Value::I64(x) => Ok(*x),
+            Value::U8(x) => Ok(*x as i64),
+            Value::U16(x) => Ok(*x as i64),
+            Value::U32(x) => Ok(*x as i64),
+            Value::U64(x) => i64::try_from(*x).map_err(|_| CodecError::Validation(format!("value {} does not fit in an i64", x))),
+            Value::Timestamp { raw, .. } | Value::Enum { raw, .. } => Ok(*raw),
+            Value::Float(x) => float_to_int(*x as f64, "i64"),
+            Value::Double(x) => float_to_int(*x, "i64"),
+            other => Err(CodecError::Validation(format!("{:?} is not an integer", other))),
+        }
+    }
+
     pub fn as_struct(&self) -> Option<&HashMap<String, Value>> {
         match self {
             Value::Struct(m) => Some(m),
@@ -62,17 +118,788 @@ impl Value {
         }
     }
 
+    /// Any number as an `f32` (integers and doubles are rounded to the nearest `f32`).
     pub fn as_f32(&self) -> Option<f32> {
         match self {
             Value::Float(x) => Some(*x),
-            _ => None,
+            _ => self.as_f64().map(|x| x as f32),
         }
     }
 
+    /// Any number as an `f64` (`U64`/`I64` beyond 2^53 lose precision).
     pub fn as_f64(&self) -> Option<f64> {
         match self {
+            Value::U8(x) => Some(*x as f64),
+            Value::U16(x) => Some(*x as f64),
+            Value::U32(x) => Some(*x as f64),
+            Value::U64(x) => Some(*x as f64),
+            Value::I8(x) => Some(*x as f64),
+            Value::I16(x) => Some(*x as f64),
+            Value::I32(x) => Some(*x as f64),
+            Value::I64(x) => Some(*x as f64),
+            Value::Float(x) => Some(*x as f64),
             Value::Double(x) => Some(*x),
             _ => None,
         }
     }
+
+    /// `overrides` laid over `self`: when both are structs their fields merge recursively (see
+    /// [`merge_values`]); otherwise `overrides` replaces `self` (lists are replaced, not merged).
+    pub fn merge(&self, overrides: &Value) -> Value {
+        match (self, overrides) {
+            (Value::Struct(base), Value::Struct(over)) => Value::Struct(merge_values(base, over)),
+            _ => overrides.clone(),
+        }
+    }
+}
+
+/// `x` as an integer type `T` (named `ty` in errors) when it is a whole number in range.
+fn float_to_int<T: TryFrom<i128>>(x: f64, ty: &str) -> Result<T, CodecError> {
+    // Every whole f64 below 2^127 is exact in i128, so the range check happens in T::try_from.
+    if x.fract() != 0.0 || !x.is_finite() || x.abs() >= 2f64.powi(127) {
+        return Err(CodecError::Validation(format!("value {} is not a whole number that fits in {}", x, ty)));
+    }
+    T::try_from(x as i128).map_err(|_| CodecError::Validation(format!("value {} does not fit in {}", x, ty)))
+}
+
+/// Value map `base` with `overrides` applied: fields only in `base` are kept, fields in `overrides` win,
+/// and nested structs present in both are merged field by field. Handy for encoding many records that
+/// differ from a template in a few fields.
+pub fn merge_values(base: &HashMap<String, Value>, overrides: &HashMap<String, Value>) -> HashMap<String, Value> {
+    let mut out = base.clone();
+    for (name, v) in overrides {
+        let merged = match out.get(name) {
+            Some(b) => b.merge(v),
+            None => v.clone(),
+        };
+        out.insert(name.clone(), merged);
+    }
+    out
+}
+
+/// Whether optional `field` is present in `values`: the field is there and is not an empty list (how an
+/// absent optional decodes, and how [`crate::codec::Codec::encode_message`] takes one).
+pub fn optional_present(values: &HashMap<String, Value>, field: &str) -> bool {
+    match values.get(field) {
+        Some(Value::List(l)) => !l.is_empty(),
+        v => v.is_some(),
+    }
+}
+
+/// Value of optional `field`, or None when it is absent (see [`optional_present`]). Works on decoded maps
+/// (present value stored bare) and on maps built for encoding (present value in a one-element list, which
+/// is unwrapped); for an optional whose element type is itself a list, read the map entry directly.
+pub fn optional_value<'v>(values: &'v HashMap<String, Value>, field: &str) -> Option<&'v Value> {
+    match values.get(field)? {
+        Value::List(l) if l.len() <= 1 => l.first(),
+        v => Some(v),
+    }
+}
+
+/// A field whose value differs between two records (see [`diff_records`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    /// Dotted path from the record root; list elements are indexed, e.g. `i048_010.sac` or `plots[2].range`.
+    pub path: String,
+    /// Value in the first record; None when only the second one has the field (or list element).
+    pub left: Option<Value>,
+    /// Value in the second record; None when only the first one has the field (or list element).
+    pub right: Option<Value>,
+}
+
+/// Fields that differ between records `a` and `b`, sorted by path, e.g. to see which values a decode /
+/// re-encode round trip changed. Structs and lists are compared element by element, so a change deep in
+/// a record reports only the innermost differing values; values of different variants (`U8(1)` vs
+/// `U16(1)`) differ.
+pub fn diff_records(a: &HashMap<String, Value>, b: &HashMap<String, Value>) -> Vec<FieldDiff> {
+    let mut out = Vec::new();
+    diff_maps("", a, b, &mut out);
+    out
+}
+
+fn diff_maps(prefix: &str, a: &HashMap<String, Value>, b: &HashMap<String, Value>, out: &mut Vec<FieldDiff>) {
+    let mut names: Vec<&String> = a.keys().chain(b.keys().filter(|k| !a.contains_key(*k))).collect();
+    names.sort();
+    for name in names {
+        let path = if prefix.is_empty() { name.clone() } else { format!("{}.{}", prefix, name) };
+        diff_values(path, a.get(name), b.get(name), out);
+    }
+}
+
+fn diff_values(path: String, a: Option<&Value>, b: Option<&Value>, out: &mut Vec<FieldDiff>) {
+    match (a, b) {
+        (Some(Value::Struct(x)), Some(Value::Struct(y))) => diff_maps(&path, x, y, out),
+        (Some(Value::List(x)), Some(Value::List(y))) => {
+            for i in 0..x.len().max(y.len()) {
+                diff_values(format!("{}[{}]", path, i), x.get(i), y.get(i), out);
+            }
+        }
+        (a, b) if a != b => out.push(FieldDiff { path, left: a.cloned(), right: b.cloned() }),
+        _ => {}
+    }
+}
+
+/// Borrowing counterpart of [`Value`], produced by [`crate::codec::Codec::decode_message_ref`]: byte fields
+/// are slices of the input buffer and struct field names borrow from the codec's protocol, so a decode only
+/// allocates list and struct containers. [`ValueRef::to_owned`] converts to the owning [`Value`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueRef<'a> {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Bool(bool),
+    Float(f32),
+    Double(f64),
+    Bytes(&'a [u8]),
+    Struct(HashMap<&'a str, ValueRef<'a>>),
+    List(Vec<ValueRef<'a>>),
+    Padding,
+    Timestamp { raw: i64, seconds: f64 },
+    /// Value rebuilt while decoding rather than read verbatim from the input (FSPEC presence bytes,
+    /// output of a registered custom codec).
+    Owned(Value),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Owning copy: borrowed bytes and field names are copied.
+    pub fn to_owned(&self) -> Value {
+        match self {
+            ValueRef::U8(x) => Value::U8(*x),
+            ValueRef::U16(x) => Value::U16(*x),
+            ValueRef::U32(x) => Value::U32(*x),
+            ValueRef::U64(x) => Value::U64(*x),
+            ValueRef::I8(x) => Value::I8(*x),
+            ValueRef::I16(x) => Value::I16(*x),
+            ValueRef::I32(x) => Value::I32(*x),
+            ValueRef::I64(x) => Value::I64(*x),
+            ValueRef::Bool(x) => Value::Bool(*x),
+            ValueRef::Float(x) => Value::Float(*x),
+            ValueRef::Double(x) => Value::Double(*x),
+            ValueRef::Bytes(b) => Value::Bytes(b.to_vec()),
+            ValueRef::Struct(m) => Value::Struct(m.iter().map(|(k, v)| (k.to_string(), v.to_owned())).collect()),
+            ValueRef::List(items) => Value::List(items.iter().map(ValueRef::to_owned).collect()),
+            ValueRef::Padding => Value::Padding,
+            ValueRef::Timestamp { raw, seconds } => Value::Timestamp { raw: *raw, seconds: *seconds },
+            ValueRef::Owned(v) => v.clone(),
+        }
+    }
+
+    /// Scalars keep their variant; bytes, structs and lists become [`ValueRef::Owned`].
+    pub fn from_owned(v: Value) -> Self {
+        match v {
+            Value::U8(x) => ValueRef::U8(x),
+            Value::U16(x) => ValueRef::U16(x),
+            Value::U32(x) => ValueRef::U32(x),
+            Value::U64(x) => ValueRef::U64(x),
+            Value::I8(x) => ValueRef::I8(x),
+            Value::I16(x) => ValueRef::I16(x),
+            Value::I32(x) => ValueRef::I32(x),
+            Value::I64(x) => ValueRef::I64(x),
+            Value::Bool(x) => ValueRef::Bool(x),
+            Value::Float(x) => ValueRef::Float(x),
+            Value::Double(x) => ValueRef::Double(x),
+            Value::Padding => ValueRef::Padding,
+            Value::Timestamp { raw, seconds } => ValueRef::Timestamp { raw, seconds },
+            v @ (Value::Bytes(_) | Value::Struct(_) | Value::List(_) | Value::Enum { .. }) => ValueRef::Owned(v),
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            ValueRef::U8(x) => Some(*x as u64),
+            ValueRef::U16(x) => Some(*x as u64),
+            ValueRef::U32(x) => Some(*x as u64),
+            ValueRef::U64(x) => Some(*x),
+            ValueRef::Timestamp { raw, .. } => (*raw).try_into().ok(),
+            ValueRef::Owned(v) => v.as_u64(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            ValueRef::I8(x) => Some(*x as i64),
+            ValueRef::I16(x) => Some(*x as i64),
+            ValueRef::I32(x) => Some(*x as i64),
+            ValueRef::I64(x) => Some(*x),
+            ValueRef::U8(x) => Some(*x as i64),
+            ValueRef::U16(x) => Some(*x as i64),
+            ValueRef::U32(x) => Some(*x as i64),
+            ValueRef::U64(x) => Some(*x as i64),
+            ValueRef::Timestamp { raw, .. } => Some(*raw),
+            ValueRef::Owned(v) => v.as_i64(),
+            _ => None,
+        }
+    }
+
+    /// Bytes borrowed from the input (`remaining`, `octets_fx`); None for rebuilt bytes in [`ValueRef::Owned`].
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            ValueRef::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_struct(&self) -> Option<&HashMap<&'a str, ValueRef<'a>>> {
+        match self {
+            ValueRef::Struct(m) => Some(m),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[ValueRef<'a>]> {
+        match self {
+            ValueRef::List(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+// ==================== Random valid values (roundtrip testing) ====================
+
+/// Generate a random value map for `message_name` that decodes back unchanged: ranges and enums
+/// (including `when` clauses) are respected, `if` conditions decide which fields exist, lists get
+/// 0–3 elements, `count_of`/`length_of` match their target, and presence bitmaps match the optionals.
+/// Values use the decoded form (what [`crate::codec::Codec::decode_message`] returns): a present
+/// `optional<T>` is the bare `T` value, an absent one is an empty list. Same `seed`, same values.
+/// A message with a body selector gets the fields of one of its mapped variants too.
+/// Returns None if the message does not exist.
+pub fn random_message_values(
+    resolved: &ResolvedProtocol,
+    message_name: &str,
+    seed: u64,
+) -> Option<HashMap<String, Value>> {
+    let mut msg = resolved.get_message(message_name)?;
+    let mut gen = ValueGen {
+        resolved,
+        state: seed,
+        ctx: HashMap::new(),
+        presence: Vec::new(),
+        codec: None,
+    };
+    let mut values = HashMap::new();
+    loop {
+        let fields: Vec<GenField> = msg
+            .fields
+            .iter()
+            .map(|f| GenField {
+                name: &f.name,
+                type_spec: &f.type_spec,
+                condition: f.condition.as_ref(),
+                constraint: f.constraint.as_ref(),
+                conditional_constraints: &f.conditional_constraints,
+            })
+            .collect();
+        let mapping = resolved.bitmap_presence_mapping_message(&msg.name);
+        values.extend(gen.scope(&fields, false, mapping));
+        let Some(sel) = &msg.selector else { return Some(values) };
+        // Pin the key fields to a random mapping so the variant is a declared one.
+        let (key, variant, _) = &sel.value_to_message[gen.below(sel.value_to_message.len() as u64) as usize];
+        let key = match key {
+            Literal::List(items) => items.iter().collect(),
+            lit => vec![lit],
+        };
+        for (name, lit) in sel.key_fields().zip(key) {
+            let v = match (msg.fields.iter().find(|f| f.name == name).map(|f| &f.type_spec), lit.as_i64()) {
+                (Some(TypeSpec::Base(bt) | TypeSpec::SizedInt(bt, _)), Some(v)) => int_as_base(bt, v),
+                (Some(TypeSpec::Bitfield(_)), Some(v)) => Value::U64(v as u64),
+                _ => continue,
+            };
+            gen.set(&mut values, name, v);
+        }
+        msg = resolved.get_message(variant)?;
+    }
+}
+
+/// Proptest strategy over [`random_message_values`]: `decode(encode(v)) == v` should hold for every generated map.
+#[cfg(feature = "proptest")]
+pub fn arbitrary_for_message(
+    resolved: &ResolvedProtocol,
+    message_name: &str,
+) -> impl proptest::strategy::Strategy<Value = HashMap<String, Value>> {
+    use proptest::strategy::Strategy;
+    let resolved = resolved.clone();
+    let message_name = message_name.to_string();
+    proptest::prelude::any::<u64>()
+        .prop_map(move |seed| random_message_values(&resolved, &message_name, seed).unwrap_or_default())
+}
+
+/// Field view shared by messages and structs for the generator.
+struct GenField<'a> {
+    name: &'a str,
+    type_spec: &'a TypeSpec,
+    condition: Option<&'a Condition>,
+    constraint: Option<&'a Constraint>,
+    conditional_constraints: &'a [(Option<Condition>, Constraint)],
+}
+
+/// Presence bitmap being filled while the optionals that follow it are generated (mirrors the decoder's presence stack).
+struct PresenceGen {
+    field: String,
+    kind: PresenceKind,
+    set_bits: Vec<u32>,
+    bit_index: u32,
+}
+
+enum PresenceKind {
+    /// `presence_bits(n)`: n bytes, bit i (LSB first) for the i-th optional (skipping the `stop` bit, if any).
+    Bits(u64, Option<u32>),
+    /// `bitmap(total_bits, presence_per_block)`: stored MSB first, FX in bit 0 when blocked.
+    Bitmap { total_bits: u32, presence_per_block: u32 },
+}
+
+impl PresenceGen {
+    fn capacity(&self) -> u32 {
+        match self.kind {
+            PresenceKind::Bits(n, stop) => (n * 8) as u32 - u32::from(stop.is_some()),
+            PresenceKind::Bitmap { total_bits, presence_per_block: 0 } => total_bits,
+            PresenceKind::Bitmap { total_bits, presence_per_block: k } => total_bits.div_ceil(k) * k,
+        }
+    }
+
+    fn value(&self) -> Value {
+        match self.kind {
+            PresenceKind::Bits(_, stop) => {
+                Value::U64(presence_bits_wire(self.set_bits.iter().fold(0u64, |acc, &b| acc | (1 << b)), stop))
+            }
+            PresenceKind::Bitmap { total_bits, presence_per_block } => {
+                let per_byte = if presence_per_block == 0 { 8 } else { presence_per_block };
+                let len = if presence_per_block == 0 {
+                    total_bits.div_ceil(8) as usize
+                } else {
+                    self.set_bits.iter().map(|&b| (b / per_byte) as usize + 1).max().unwrap_or(1)
+                };
+                let mut bytes = vec![0u8; len];
+                for &b in &self.set_bits {
+                    bytes[(b / per_byte) as usize] |= 1 << (7 - b % per_byte);
+                }
+                if presence_per_block != 0 {
+                    for byte in bytes.iter_mut().take(len - 1) {
+                        *byte |= 0x01; // FX: more blocks follow
+                    }
+                }
+                Value::Bytes(bytes)
+            }
+        }
+    }
+}
+
+struct ValueGen<'a> {
+    resolved: &'a ResolvedProtocol,
+    state: u64,
+    /// Every value generated so far by field name (the decoder's context: conditions and counts look here).
+    ctx: HashMap<String, Value>,
+    presence: Vec<PresenceGen>,
+    /// Built on first `length_of` to measure encoded sizes.
+    codec: Option<crate::codec::Codec>,
+}
+
+impl ValueGen<'_> {
+    /// splitmix64
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            0
+        } else {
+            self.next_u64() % n
+        }
+    }
+
+    fn in_range(&mut self, lo: i64, hi: i64) -> i64 {
+        let span = (hi as i128 - lo as i128 + 1) as u128;
+        let r = (((self.next_u64() as u128) << 64) | self.next_u64() as u128) % span;
+        (lo as i128 + r as i128) as i64
+    }
+
+    /// Generate the fields of a message (`in_struct == false`) or struct, in decode order.
+    fn scope(
+        &mut self,
+        fields: &[GenField],
+        in_struct: bool,
+        mapping: Option<&BitmapPresenceMapping>,
+    ) -> HashMap<String, Value> {
+        let presence_len = self.presence.len();
+        let mut out = HashMap::new();
+        let mut deferred = Vec::new();
+        // Plain integer fields used as an array length stay small.
+        let array_counts: Vec<&str> = fields
+            .iter()
+            .filter_map(|f| match f.type_spec {
+                TypeSpec::Array(_, ArrayLen::FieldRef(count_field), _)
+                | TypeSpec::Group { count: ArrayLen::FieldRef(count_field), .. } => Some(count_field.as_str()),
+                _ => None,
+            })
+            .collect();
+        // Fields giving a `dyn_int` width hold 1..8.
+        let dyn_widths: Vec<&str> = fields
+            .iter()
+            .filter_map(|f| match f.type_spec {
+                TypeSpec::DynInt(width_field) => Some(width_field.as_str()),
+                _ => None,
+            })
+            .collect();
+        // Fields keying a `select` hold one of its case values.
+        let select_keys: Vec<(&str, &[(i64, String)])> = fields
+            .iter()
+            .filter_map(|f| match f.type_spec {
+                TypeSpec::Select { field, cases, .. } if !cases.is_empty() => Some((field.as_str(), cases.as_slice())),
+                _ => None,
+            })
+            .collect();
+        for f in fields {
+            if let Some(cond) = f.condition {
+                let holds = self.ctx.get(&cond.field).and_then(Value::as_i64) == cond.value.as_i64();
+                if !holds {
+                    if in_struct && matches!(f.type_spec, TypeSpec::Optional(..)) {
+                        self.set(&mut out, f.name, Value::List(vec![]));
+                    }
+                    continue;
+                }
+                if in_struct {
+                    if let TypeSpec::Optional(elem, _) = f.type_spec {
+                        let inner = self.type_value(elem, None);
+                        self.set(&mut out, f.name, Value::List(vec![inner]));
+                        continue;
+                    }
+                }
+            }
+            let v = match f.type_spec {
+                TypeSpec::LengthOf(..) | TypeSpec::CountOf(..) => {
+                    if !out.contains_key(f.name) {
+                        deferred.push(f);
+                    }
+                    continue;
+                }
+                TypeSpec::PresenceBits(n, stop) => {
+                    self.presence.push(PresenceGen {
+                        field: f.name.to_string(),
+                        kind: PresenceKind::Bits(*n, *stop),
+                        set_bits: Vec::new(),
+                        bit_index: 0,
+                    });
+                    continue;
+                }
+                TypeSpec::BitmapPresence { total_bits, presence_per_block, .. } => {
+                    self.presence.push(PresenceGen {
+                        field: f.name.to_string(),
+                        kind: PresenceKind::Bitmap { total_bits: *total_bits, presence_per_block: *presence_per_block },
+                        set_bits: Vec::new(),
+                        bit_index: 0,
+                    });
+                    continue;
+                }
+                TypeSpec::Base(bt) | TypeSpec::SizedInt(bt, _) if array_counts.contains(&f.name) => {
+                    int_as_base(bt, self.below(4) as i64)
+                }
+                TypeSpec::Base(bt) | TypeSpec::SizedInt(bt, _) if dyn_widths.contains(&f.name) => {
+                    int_as_base(bt, 1 + self.below(8) as i64)
+                }
+                TypeSpec::Base(bt) | TypeSpec::SizedInt(bt, _) if select_keys.iter().any(|(k, _)| *k == f.name) => {
+                    let cases = select_keys.iter().find(|(k, _)| *k == f.name).map(|(_, c)| *c).unwrap_or_default();
+                    int_as_base(bt, cases[self.below(cases.len() as u64) as usize].0)
+                }
+                TypeSpec::Optional(elem, _) => {
+                    let top_is_bitmap = matches!(self.presence.last(), Some(PresenceGen { kind: PresenceKind::Bitmap { .. }, .. }));
+                    let bit = if self.presence.len() == 1 && top_is_bitmap && !in_struct {
+                        mapping.and_then(|m| m.bit_for_field(f.name))
+                    } else {
+                        None
+                    };
+                    self.optional_value(elem, bit)
+                }
+                TypeSpec::Array(elem, ArrayLen::FieldRef(count_field), _) => {
+                    let n = match self.ctx.get(count_field).and_then(Value::as_u64) {
+                        Some(n) => n,
+                        None => {
+                            let n = self.below(4);
+                            self.set(&mut out, count_field, Value::U32(n as u32));
+                            n
+                        }
+                    };
+                    Value::List((0..n).map(|_| self.type_value(elem, None)).collect())
+                }
+                TypeSpec::Group { count: ArrayLen::FieldRef(count_field), fields: group } => {
+                    let n = match self.ctx.get(count_field).and_then(Value::as_u64) {
+                        Some(n) => n,
+                        None => {
+                            let n = self.below(4);
+                            self.set(&mut out, count_field, Value::U32(n as u32));
+                            n
+                        }
+                    };
+                    self.group_value(group, n)
+                }
+                _ => {
+                    let c = active_constraint(f.constraint, f.conditional_constraints, |name| {
+                        self.ctx.get(name).and_then(Value::as_i64)
+                    })
+                    .cloned();
+                    self.type_value(f.type_spec, c.as_ref())
+                }
+            };
+            self.set(&mut out, f.name, v);
+        }
+        for f in deferred {
+            if out.contains_key(f.name) {
+                continue;
+            }
+            let v = match f.type_spec {
+                TypeSpec::CountOf(target, bt) => {
+                    let n = match out.get(target) {
+                        Some(Value::List(l)) => l.len(),
+                        Some(Value::Bytes(b)) => b.len(),
+                        _ => 0,
+                    };
+                    int_as_base(bt, n as i64)
+                }
+                TypeSpec::LengthOf(target, bt) => {
+                    let len = match (fields.iter().find(|t| t.name == target.as_str()), out.get(target)) {
+                        (Some(t), Some(v)) => self.encoded_len(t.type_spec, v),
+                        _ => 0,
+                    };
+                    int_as_base(bt, len as i64)
+                }
+                _ => continue,
+            };
+            self.set(&mut out, f.name, v);
+        }
+        while self.presence.len() > presence_len {
+            let p = self.presence.pop().expect("presence pushed in this scope");
+            let v = p.value();
+            self.set(&mut out, &p.field, v);
+        }
+        out
+    }
+
+    fn set(&mut self, out: &mut HashMap<String, Value>, name: &str, v: Value) {
+        self.ctx.insert(name.to_string(), v.clone());
+        out.insert(name.to_string(), v);
+    }
+
+    /// Optional outside a condition: presence comes from the innermost bitmap, else a presence byte.
+    fn optional_value(&mut self, elem: &TypeSpec, mapped_bit: Option<u32>) -> Value {
+        let present = self.below(2) == 1;
+        let present = match self.presence.last_mut() {
+            Some(p) => {
+                let bit = mapped_bit.unwrap_or_else(|| {
+                    p.bit_index += 1;
+                    p.bit_index - 1
+                });
+                let present = present && bit < p.capacity();
+                if present {
+                    p.set_bits.push(bit);
+                }
+                present
+            }
+            None => present,
+        };
+        if !present {
+            return Value::List(vec![]);
+        }
+        // A present empty list would read back as absent.
+        match elem {
+            TypeSpec::List(inner) | TypeSpec::RepList(inner) => {
+                let n = 1 + self.below(3);
+                Value::List((0..n).map(|_| self.type_value(inner, None)).collect())
+            }
+            _ => self.type_value(elem, None),
+        }
+    }
+
+    fn int_value(&mut self, spec: &TypeSpec, constraint: Option<&Constraint>) -> i64 {
+        let (lo, hi) = type_spec_integer_range(spec).unwrap_or((0, i64::MAX));
+        let allowed: Vec<(i64, i64)> = match constraint {
+            Some(Constraint::Range(intervals)) => intervals
+                .iter()
+                .map(|&(a, b)| (a.max(lo), b.min(hi)))
+                .filter(|(a, b)| a <= b)
+                .collect(),
+            Some(Constraint::Enum(lits) | Constraint::EnumTolerance(lits, _)) => lits
+                .iter()
+                .filter_map(EnumCode::as_i64)
+                .filter(|v| (lo..=hi).contains(v))
+                .map(|v| (v, v))
+                .collect(),
+            None => vec![(lo, hi)],
+        };
+        let (a, b) = if allowed.is_empty() {
+            (lo, hi)
+        } else {
+            allowed[self.below(allowed.len() as u64) as usize]
+        };
+        self.in_range(a, b)
+    }
+
+    fn type_value(&mut self, spec: &TypeSpec, constraint: Option<&Constraint>) -> Value {
+        let float_codes: Vec<f64> = match constraint {
+            Some(Constraint::Enum(codes) | Constraint::EnumTolerance(codes, _)) => codes.iter().filter_map(|c| c.value.as_f64()).collect(),
+            _ => Vec::new(),
+        };
+        match spec {
+            TypeSpec::Base(BaseType::Bool) => Value::Bool(self.below(2) == 1),
+            TypeSpec::Base(BaseType::Float) if !float_codes.is_empty() => {
+                Value::Float(float_codes[self.below(float_codes.len() as u64) as usize] as f32)
+            }
+            TypeSpec::Base(BaseType::Double) if !float_codes.is_empty() => {
+                Value::Double(float_codes[self.below(float_codes.len() as u64) as usize])
+            }
+            TypeSpec::Base(BaseType::Float) => Value::Float(self.in_range(-1 << 20, 1 << 20) as f32 / 16.0),
+            TypeSpec::Base(BaseType::Double) => Value::Double(self.in_range(-1 << 40, 1 << 40) as f64 / 1024.0),
+            TypeSpec::Base(bt) | TypeSpec::SizedInt(bt, _) => {
+                let v = self.int_value(spec, constraint);
+                int_as_base(bt, v)
+            }
+            TypeSpec::Bitfield(n) if *n >= 64 => Value::U64(self.next_u64()),
+            TypeSpec::Bitfield(_) => Value::U64(self.int_value(spec, constraint) as u64),
+            TypeSpec::Bits(members) => Value::Struct(
+                members
+                    .iter()
+                    .map(|(name, w)| (name.clone(), Value::U64(if *w >= 64 { self.next_u64() } else { self.below(1 << w) })))
+                    .collect(),
+            ),
+            TypeSpec::Padding(_) => Value::Padding,
+            TypeSpec::Bcd { .. } => Value::U64(self.int_value(spec, constraint) as u64),
+            TypeSpec::ReverseBits(inner) => self.type_value(inner, constraint),
+            TypeSpec::Remaining => Value::Bytes((0..self.below(4)).map(|_| self.below(256) as u8).collect()),
+            TypeSpec::Unknown(n) => Value::Bytes((0..*n).map(|_| self.below(256) as u8).collect()),
+            // No zero bytes: a cstring ends at its first one, a string(n) loses its trailing ones.
+            TypeSpec::CString => Value::Bytes((0..self.below(4)).map(|_| 1 + self.below(255) as u8).collect()),
+            // Written by the encoder.
+            TypeSpec::Checksum { algorithm, .. } => int_as_base(&algorithm.base_type(), 0),
+            TypeSpec::FixedString(n) => Value::Bytes((0..self.below(n + 1)).map(|_| 1 + self.below(255) as u8).collect()),
+            TypeSpec::Custom(_) => Value::Bytes(vec![]),
+            TypeSpec::Enum(name) => match self.resolved.get_enum(name) {
+                Some(e) if !e.variants.is_empty() => {
+                    let (variant, lit) = &e.variants[self.below(e.variants.len() as u64) as usize];
+                    Value::Enum { name: name.clone(), variant: Some(variant.clone()), raw: lit.as_i64().unwrap_or(0) }
+                }
+                _ => Value::Enum { name: name.clone(), variant: None, raw: 0 },
+            },
+            TypeSpec::Select { field, cases, default } => {
+                let key = self.ctx.get(field).and_then(Value::as_i64).unwrap_or(0);
+                match select_case(cases, default.as_ref(), key) {
+                    Some(name) => self.type_value(&TypeSpec::StructRef(name.to_string()), None),
+                    None => Value::Struct(HashMap::new()),
+                }
+            }
+            TypeSpec::StructRef(name) => {
+                if let Some(e) = self.resolved.get_enum(name) {
+                    let raws: Vec<i64> = e.variants.iter().filter_map(|(_, lit)| lit.as_i64()).collect();
+                    let raw = if raws.is_empty() { 0 } else { raws[self.below(raws.len() as u64) as usize] };
+                    int_as_base(&e.base, raw)
+                } else if let Some(s) = self.resolved.get_struct(name) {
+                    let fields: Vec<GenField> = s
+                        .fields
+                        .iter()
+                        .map(|f| GenField {
+                            name: &f.name,
+                            type_spec: &f.type_spec,
+                            condition: f.condition.as_ref(),
+                            constraint: f.constraint.as_ref(),
+                            conditional_constraints: &f.conditional_constraints,
+                        })
+                        .collect();
+                    Value::Struct(self.scope(&fields, true, None))
+                } else if let Some(m) = self.resolved.get_message(name) {
+                    let fields: Vec<GenField> = m
+                        .fields
+                        .iter()
+                        .map(|f| GenField {
+                            name: &f.name,
+                            type_spec: &f.type_spec,
+                            condition: f.condition.as_ref(),
+                            constraint: f.constraint.as_ref(),
+                            conditional_constraints: &f.conditional_constraints,
+                        })
+                        .collect();
+                    let mapping = self.resolved.bitmap_presence_mapping_message(name);
+                    Value::Struct(self.scope(&fields, false, mapping))
+                } else {
+                    Value::Struct(HashMap::new())
+                }
+            }
+            TypeSpec::Array(elem, ArrayLen::Constant(k), _) => {
+                Value::List((0..*k).map(|_| self.type_value(elem, None)).collect())
+            }
+            TypeSpec::Group { count: ArrayLen::Constant(k), fields } => self.group_value(fields, *k),
+            TypeSpec::Group { fields, .. } => {
+                let n = self.below(4);
+                self.group_value(fields, n)
+            }
+            TypeSpec::Array(elem, ArrayLen::FieldRef(_), _)
+            | TypeSpec::List(elem)
+            | TypeSpec::RepList(elem)
+            | TypeSpec::LengthPrefixedList(elem, _)
+            | TypeSpec::BytesList(elem, _) => {
+                let n = self.below(4);
+                Value::List((0..n).map(|_| self.type_value(elem, None)).collect())
+            }
+            TypeSpec::OctetsFx => {
+                let n = 1 + self.below(3) as usize;
+                Value::Bytes((0..n).map(|i| (self.below(128) as u8) | if i + 1 < n { 0x80 } else { 0 }).collect())
+            }
+            TypeSpec::Optional(elem, _) => self.optional_value(elem, None),
+            TypeSpec::Timestamp { base, scale, .. } => {
+                // Keep ticks exact through the seconds conversion done on encode.
+                let raw = self.int_value(base, constraint).min(1 << 40);
+                Value::Timestamp { raw, seconds: raw as f64 * scale }
+            }
+            TypeSpec::DynInt(width_field) => {
+                let width = self.ctx.get(width_field).and_then(Value::as_u64).unwrap_or(8).clamp(1, 8);
+                let v = self.next_u64();
+                Value::U64(if width == 8 { v } else { v & ((1u64 << (width * 8)) - 1) })
+            }
+            TypeSpec::LengthOf(_, bt) | TypeSpec::CountOf(_, bt) => int_as_base(bt, 0),
+            TypeSpec::PresenceBits(..) => Value::U64(0),
+            TypeSpec::BitmapPresence { .. } => Value::Bytes(vec![0]),
+        }
+    }
+
+    /// `n` repetitions of a `repeat(..) { ... }` group, each generated like a struct body.
+    fn group_value(&mut self, fields: &[StructField], n: u64) -> Value {
+        let fields: Vec<GenField> = fields
+            .iter()
+            .map(|f| GenField {
+                name: &f.name,
+                type_spec: &f.type_spec,
+                condition: f.condition.as_ref(),
+                constraint: f.constraint.as_ref(),
+                conditional_constraints: &f.conditional_constraints,
+            })
+            .collect();
+        Value::List((0..n).map(|_| Value::Struct(self.scope(&fields, true, None))).collect())
+    }
+
+    fn encoded_len(&mut self, spec: &TypeSpec, v: &Value) -> usize {
+        let resolved = self.resolved;
+        let codec = self
+            .codec
+            .get_or_insert_with(|| crate::codec::Codec::new(resolved.clone(), crate::codec::Endianness::Big));
+        codec.encoded_type_len(spec, v).unwrap_or(0)
+    }
+}
+
+fn int_as_base(bt: &BaseType, v: i64) -> Value {
+    match bt {
+        BaseType::U8 => Value::U8(v as u8),
+        BaseType::U16 => Value::U16(v as u16),
+        BaseType::U24 | BaseType::U32 => Value::U32(v as u32),
+        BaseType::I8 => Value::I8(v as i8),
+        BaseType::I16 => Value::I16(v as i16),
+        BaseType::I32 => Value::I32(v as i32),
+        BaseType::I64 => Value::I64(v),
+        _ => Value::U64(v as u64),
+    }
 }
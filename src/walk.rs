@@ -20,7 +20,10 @@
 //! | Use case | Prefer |
 //! |----------|--------|
 //! | Get byte length of one message | [`message_extent`] |
+//! | Jump to the Nth record of a frame | [`BinaryWalker::nth_record_range`] |
 //! | Check constraints without decoding | [`validate_message_in_place`] |
+//! | Drop fast, reporting where the record failed | [`validate_message_first_error`] |
+//! | Validate long lists of fixed-size structs | [`validate_fixed_record_list`] |
 //! | Zero padding in a buffer | [`zero_padding_reserved_in_place`] |
 //! | Remove a message and shift bytes | [`remove_message_in_place`] + [`write_u32_in_place`] |
 //! | Full decode for inspection/display | [codec](crate::codec) |
@@ -34,10 +37,18 @@
 //! `optional<T>` fields. Numeric values (e.g. from `length_of` / `count_of`) and the current
 //! presence state are kept internally for conditional and repeated fields.
 //!
+//! ## Bit-packed fields
+//!
+//! `bitfield(n)`, `padding(n, bits)` and sized ints that are narrower than a byte or start inside
+//! one are read through a bit cursor with the codec's order: bits are consumed LSB-first within
+//! each byte, so in a multi-byte bitfield the first byte holds the least significant bits. The
+//! cursor is local to each message and struct, as in the codec.
+//!
 //! ## Public API summary
 //!
 //! - **Extent:** [`message_extent`] — returns number of bytes one message occupies.
-//! - **Validation:** [`validate_message_in_place`] — checks constraints in place.
+//! - **Validation:** [`validate_message_in_place`] — checks constraints in place;
+//!   [`validate_message_first_error`] reports the first failing field as a [`ConstraintViolation`].
 //! - **In-place edits:** [`zero_padding_reserved_in_place`], [`remove_message_in_place`],
 //!   [`write_u32_in_place`].
 //! - **Low-level:** [`BinaryWalker`] / [`BinaryWalkerMut`] for custom loops (e.g. skip
@@ -68,7 +79,7 @@
 //! ```
 
 use crate::ast::{PaddingKind, *};
-use crate::codec::CodecError;
+use crate::codec::{bcd_to_u64, select_target, CodecError, CustomTypes};
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use std::collections::HashMap;
 
@@ -85,7 +96,7 @@ pub enum Endianness {
 }
 
 /// Presence state for optional fields: fixed bitmap (presence_bits) or bitmap presence (bitmap_presence).
-#[derive(Default)]
+#[derive(Default, Clone)]
 enum WalkPresence {
     #[default]
     None,
@@ -101,6 +112,66 @@ enum WalkPresence {
 struct WalkContext {
     values: HashMap<String, u64>,
     presence: WalkPresence,
+    bits: WalkBits,
+    /// Set while validating: struct fields are checked against their active constraint as well.
+    validate: bool,
+}
+
+/// Bit cursor for packed message/struct fields (`bitfield(n)`, `padding(n, bits)`, sub-byte or
+/// unaligned sized ints), mirroring the codec: a byte is loaded when the first bit is needed and
+/// its bits are consumed LSB-first; `pos` already points past it. Local to each message and struct.
+#[derive(Clone, Copy)]
+struct WalkBits {
+    cur: u8,
+    next_bit: u8, // 0..=8, where 8 means "need new byte"
+}
+
+impl Default for WalkBits {
+    fn default() -> Self {
+        WalkBits { cur: 0, next_bit: 8 }
+    }
+}
+
+impl WalkBits {
+    fn is_aligned(&self) -> bool {
+        self.next_bit == 8
+    }
+
+    /// Read `n` bits; bit i of the result is the i-th bit consumed.
+    fn read(&mut self, data: &[u8], pos: &mut usize, n: u64) -> Result<u64, CodecError> {
+        if n > 64 {
+            return Err(CodecError::Validation(format!("bitfield({}): too many bits (max 64)", n)));
+        }
+        let mut out = 0u64;
+        for i in 0..n {
+            if self.next_bit == 8 {
+                self.cur = read_u8(data, pos)?;
+                self.next_bit = 0;
+            }
+            out |= (((self.cur >> self.next_bit) & 1) as u64) << i;
+            self.next_bit += 1;
+        }
+        Ok(out)
+    }
+
+    /// Overwrite the next `n` bits in `data` with the low bits of `value` (bit i of `value` goes to the i-th
+    /// bit consumed), consuming them as [`read`](Self::read) does; the other bits of their bytes are left alone.
+    fn write(&mut self, data: &mut [u8], pos: &mut usize, n: u64, value: u64) -> Result<(), CodecError> {
+        for i in 0..n {
+            if self.next_bit == 8 {
+                self.cur = read_u8(data, pos)?;
+                self.next_bit = 0;
+            }
+            let bit = 1 << self.next_bit;
+            if (value >> i) & 1 != 0 {
+                data[*pos - 1] |= bit;
+            } else {
+                data[*pos - 1] &= !bit;
+            }
+            self.next_bit += 1;
+        }
+        Ok(())
+    }
 }
 
 /// Read-only walker: advances over binary data by following the message/struct layout.
@@ -115,6 +186,7 @@ pub struct BinaryWalker<'a> {
     resolved: &'a ResolvedProtocol,
     endianness: Endianness,
     ctx: WalkContext,
+    custom: Option<&'a CustomTypes>,
 }
 
 /// Mutable walker: same as [`BinaryWalker`] but operates on `&mut [u8]`.
@@ -128,49 +200,104 @@ pub struct BinaryWalkerMut<'a> {
     resolved: &'a ResolvedProtocol,
     endianness: Endianness,
     ctx: WalkContext,
+    custom: Option<&'a CustomTypes>,
+}
+
+/// First failed constraint reported by [`validate_message_first_error`].
+#[derive(Debug, Clone)]
+pub struct ConstraintViolation {
+    /// Message field that failed.
+    pub field: String,
+    /// Byte offset of the field in the walked buffer.
+    pub offset: usize,
+    /// Raw value read from the buffer.
+    pub value: i64,
+    /// The constraint active for the field (after `when` gating).
+    pub constraint: Constraint,
+}
+
+/// [`CodecError::Truncated`] for a read of `needed` bytes at `pos` in a buffer of `len` bytes.
+fn truncated(pos: usize, needed: usize, len: usize) -> CodecError {
+    CodecError::Truncated { field: String::new(), needed, available: len.saturating_sub(pos), offset: pos }
 }
 
 fn base_type_size(bt: &BaseType) -> usize {
     match bt {
         BaseType::U8 | BaseType::I8 | BaseType::Bool => 1,
         BaseType::U16 | BaseType::I16 => 2,
+        BaseType::U24 => 3,
         BaseType::U32 | BaseType::I32 | BaseType::Float => 4,
+        BaseType::U48 => 6,
         BaseType::U64 | BaseType::I64 | BaseType::Double => 8,
     }
 }
 
 fn read_u8(data: &[u8], pos: &mut usize) -> Result<u8, CodecError> {
     if *pos >= data.len() {
-        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+        return Err(truncated(*pos, 1, data.len()));
     }
     let v = data[*pos];
     *pos += 1;
     Ok(v)
 }
 
-/// Read n bits from data at (pos, bit_pos), LSB first. Returns (value, new_pos, new_bit_pos).
-fn read_bits_walk(data: &[u8], pos: usize, bit_pos: u8, n: u8) -> Result<(u64, usize, u8), CodecError> {
-    let mut pos = pos;
-    let mut bit_pos = bit_pos;
-    let mut value = 0u64;
-    for i in 0..n {
-        if pos >= data.len() {
-            return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+/// Presence bytes of a `bitmap(total_bits, presence_per_block)` field, in the stored format the
+/// codec uses (presence bits MSB-first, FX in bit 0); sub-byte blocks are read through `bits`.
+fn read_bitmap_presence(
+    data: &[u8],
+    pos: &mut usize,
+    bits: &mut WalkBits,
+    total_bits: u32,
+    presence_per_block: u32,
+) -> Result<WalkPresence, CodecError> {
+    if presence_per_block == 0 {
+        let mut bytes = vec![0u8; total_bits.div_ceil(8) as usize];
+        if total_bits == 1 {
+            // Single presence bit in the same byte as preceding bitfields (bit 0); the byte is consumed.
+            if bits.is_aligned() {
+                bits.cur = read_u8(data, pos)?;
+            }
+            bytes[0] = (bits.cur & 1) << 7;
+            bits.next_bit = 8;
+        } else {
+            for i in 0..total_bits as usize {
+                if bits.read(data, pos, 1)? != 0 {
+                    bytes[i / 8] |= 1 << (7 - i % 8);
+                }
+            }
         }
-        let bit = (data[pos] >> bit_pos) & 1;
-        value |= (bit as u64) << i;
-        bit_pos += 1;
-        if bit_pos == 8 {
-            pos += 1;
-            bit_pos = 0;
+        return Ok(WalkPresence::BitmapPresenceConsecutive(bytes, 0, 0));
+    }
+    let block_bits = (presence_per_block + 1) as u64;
+    let max_blocks = total_bits.div_ceil(presence_per_block) as usize;
+    let mut bytes = Vec::new();
+    for _ in 0..max_blocks {
+        let b = if block_bits >= 8 {
+            read_u8(data, pos)?
+        } else {
+            let b = bits.read(data, pos, block_bits)? as u8;
+            let mut stored = b & 1;
+            for j in 0..presence_per_block {
+                stored |= ((b >> (j + 1)) & 1) << (7 - j);
+            }
+            stored
+        };
+        bytes.push(b);
+        if b & 0x01 == 0 {
+            break;
         }
     }
-    Ok((value, pos, bit_pos))
+    if bytes.len() == max_blocks && bytes.last().is_some_and(|&b| b & 0x01 != 0) {
+        return Err(CodecError::Validation(
+            "bitmap presence: last FSPEC byte must have FX=0 (max size reached)".to_string(),
+        ));
+    }
+    Ok(WalkPresence::BitmapPresence(bytes, 0, presence_per_block))
 }
 
 fn read_u32_slice(data: &[u8], pos: usize, endianness: Endianness) -> Result<u32, CodecError> {
     if pos + 4 > data.len() {
-        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+        return Err(truncated(pos, 4, data.len()));
     }
     let v = match endianness {
         Endianness::Big => BigEndian::read_u32(&data[pos..]),
@@ -187,7 +314,7 @@ fn read_bitmap_n(data: &[u8], pos: &mut usize, endianness: Endianness, n: u64) -
         _ => return Err(CodecError::Validation("presence_bits(n): n must be 1, 2, or 4".to_string())),
     };
     if *pos + len > data.len() {
-        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+        return Err(truncated(*pos, len, data.len()));
     }
     let v = match len {
         1 => data[*pos] as u64,
@@ -207,6 +334,33 @@ fn read_bitmap_n(data: &[u8], pos: &mut usize, endianness: Endianness, n: u64) -
 
 fn read_i64_slice(data: &[u8], pos: &mut usize, spec: &TypeSpec, endianness: Endianness) -> Result<i64, CodecError> {
     match spec {
+        TypeSpec::Timestamp { base, .. } => return read_i64_slice(data, pos, base, endianness),
+        TypeSpec::Bcd { bytes, swapped } => {
+            let n = *bytes as usize;
+            if *pos + n > data.len() {
+                return Err(truncated(*pos, n, data.len()));
+            }
+            let v = bcd_to_u64(&data[*pos..*pos + n], *swapped)
+                .ok_or_else(|| CodecError::Validation("bcd: invalid digit".to_string()))?;
+            *pos += n;
+            return Ok(v as i64);
+        }
+        TypeSpec::ReverseBits(inner) => {
+            let n = type_spec_fixed_bytes(inner).unwrap_or(0);
+            let mut buf = [0u8; 16];
+            if n > buf.len() {
+                return Err(CodecError::Validation("reverse_bits<T>: T too large".to_string()));
+            }
+            if *pos + n > data.len() {
+                return Err(truncated(*pos, n, data.len()));
+            }
+            for (d, b) in buf.iter_mut().zip(&data[*pos..*pos + n]) {
+                *d = b.reverse_bits();
+            }
+            let v = read_i64_slice(&buf[..n], &mut 0, inner, endianness)?;
+            *pos += n;
+            return Ok(v);
+        }
         TypeSpec::Bitfield(n) => {
             let size = ((*n + 7) / 8) as usize;
             let raw = read_bytes_to_u64(data, pos, size, endianness)?;
@@ -218,27 +372,17 @@ fn read_i64_slice(data: &[u8], pos: &mut usize, spec: &TypeSpec, endianness: End
             let mask = if *n >= 64 { u64::MAX } else { (1u64 << n) - 1 };
             let raw = read_bytes_to_u64(data, pos, size, endianness)? & mask;
             *pos += size;
-            let signed = matches!(bt, BaseType::I8 | BaseType::I16 | BaseType::I32 | BaseType::I64);
-            let val = if signed && *n > 0 {
-                let sign_bit = 1i64 << (*n as i64 - 1);
-                if (raw as i64) >= sign_bit {
-                    (raw as i64) - (1i64 << *n as i64)
-                } else {
-                    raw as i64
-                }
-            } else {
-                raw as i64
-            };
-            return Ok(val);
+            return Ok(sign_extend(raw, bt, *n));
         }
         _ => {}
     }
     let (size, signed) = match spec {
         TypeSpec::Base(bt) => (base_type_size(bt), matches!(bt, BaseType::I8 | BaseType::I16 | BaseType::I32 | BaseType::I64)),
+        TypeSpec::LengthOf(_, bt) | TypeSpec::CountOf(_, bt) => (base_type_size(bt), false),
         _ => return Err(CodecError::Validation("not a numeric type".to_string())),
     };
     if *pos + size > data.len() {
-        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+        return Err(truncated(*pos, size, data.len()));
     }
     let n = match (size, endianness) {
         (1, _) => data[*pos] as i64,
@@ -248,15 +392,120 @@ fn read_i64_slice(data: &[u8], pos: &mut usize, spec: &TypeSpec, endianness: End
         (4, Endianness::Little) => LittleEndian::read_i32(&data[*pos..]) as i64,
         (8, Endianness::Big) => BigEndian::read_i64(&data[*pos..]),
         (8, Endianness::Little) => LittleEndian::read_i64(&data[*pos..]),
+        (3 | 6, _) => read_bytes_to_u64(data, pos, size, endianness)? as i64,
         _ => return Err(CodecError::Validation("unsupported size".to_string())),
     };
     *pos += size;
-    Ok(if signed { n } else { n as u64 as i64 })
+    Ok(if signed || size >= 8 { n } else { n & ((1i64 << (size * 8)) - 1) })
+}
+
+/// Numeric value of a message/struct field. Packed fields (bitfields, sub-byte or unaligned sized
+/// ints) go through `bits` like the codec; everything else is read as whole bytes by [`read_i64_slice`].
+fn read_field_i64(
+    data: &[u8],
+    pos: &mut usize,
+    bits: &mut WalkBits,
+    spec: &TypeSpec,
+    endianness: Endianness,
+) -> Result<i64, CodecError> {
+    match spec {
+        TypeSpec::Timestamp { base, .. } => read_field_i64(data, pos, bits, base, endianness),
+        TypeSpec::Bitfield(n) => Ok(bits.read(data, pos, *n)? as i64),
+        TypeSpec::SizedInt(bt, n) if *n < 8 || !bits.is_aligned() => {
+            let raw = bits.read(data, pos, *n)?;
+            Ok(sign_extend(raw, bt, *n))
+        }
+        _ => read_i64_slice(data, pos, spec, endianness),
+    }
+}
+
+/// Message fields as [`BinaryWalker::read_field`] seeks them: name, condition, type, and whether a later
+/// field refers to the value.
+fn message_fields(fields: &[MessageField]) -> impl Iterator<Item = (&str, Option<&Condition>, &TypeSpec, bool)> {
+    fields.iter().map(|f| {
+        let record = f.referenced_by_condition || f.referenced_by_width || f.referenced_by_selector;
+        (f.name.as_str(), f.condition.as_ref(), &f.type_spec, record)
+    })
+}
+
+/// The protocol's `endianness from` directive when `field` is its field.
+fn endianness_switch<'r>(resolved: &'r ResolvedProtocol, field: &str) -> Option<&'r EndiannessSwitch> {
+    resolved.protocol.endianness_switch.as_ref().filter(|s| s.field == field)
+}
+
+/// Byte order `switch` selects for the recorded value of its field, as the codec switches after the field.
+fn switched_endianness(switch: &EndiannessSwitch, value: Option<u64>) -> Result<Endianness, CodecError> {
+    let value = value.ok_or_else(|| {
+        CodecError::Validation(format!("endianness from {}: value is not an integer", switch.field))
+    })? as i64;
+    switch.endianness_for(value).map(Endianness::from).ok_or_else(|| {
+        CodecError::Validation(format!("endianness from {}: no byte order for value {}", switch.field, value))
+    })
+}
+
+/// `raw` holds an `n`-bit value; signed base types take its top bit as the sign.
+fn sign_extend(raw: u64, bt: &BaseType, n: u64) -> i64 {
+    let signed = matches!(bt, BaseType::I8 | BaseType::I16 | BaseType::I32 | BaseType::I64);
+    if signed && n > 0 && n < 64 && (raw >> (n - 1)) & 1 != 0 {
+        (raw as i64) - (1i64 << n)
+    } else {
+        raw as i64
+    }
+}
+
+/// True if the struct declares its own presence field; its optionals then use that field, and the
+/// enclosing presence state is restored when the struct ends (the codec's presence stack).
+fn own_presence(fields: &[StructField]) -> bool {
+    fields
+        .iter()
+        .any(|f| matches!(f.type_spec, TypeSpec::PresenceBits(..) | TypeSpec::BitmapPresence { .. }))
+}
+
+/// Leading presence flag of `optional(flag)<T>` (no bitmap): non-zero means present.
+fn read_presence_flag(data: &[u8], pos: &mut usize, flag: &BaseType, endianness: Endianness) -> Result<bool, CodecError> {
+    let size = base_type_size(flag);
+    let raw = read_bytes_to_u64(data, pos, size, endianness)?;
+    *pos += size;
+    Ok(raw != 0)
+}
+
+/// Skip a `length_prefixed_list`: the `u32` count, then each element's length and that many bytes.
+fn skip_length_prefixed_list(data: &[u8], pos: &mut usize, len_type: &BaseType, endianness: Endianness) -> Result<(), CodecError> {
+    let n = read_bytes_to_u64(data, pos, 4, endianness)?;
+    *pos += 4;
+    let size = base_type_size(len_type);
+    for _ in 0..n {
+        let len = read_bytes_to_u64(data, pos, size, endianness)? as usize;
+        *pos += size;
+        if len > data.len() - *pos {
+            return Err(truncated(*pos, len, data.len()));
+        }
+        *pos += len;
+    }
+    Ok(())
+}
+
+/// End of a `bytes_list(len_field)` starting at `pos`, given the value of `len_field` read earlier.
+fn skip_bytes_list(data_len: usize, pos: usize, len: Option<u64>, len_field: &str) -> Result<usize, CodecError> {
+    let len = len.ok_or_else(|| CodecError::UnknownField(len_field.to_string()))? as usize;
+    if len > data_len - pos {
+        return Err(truncated(pos, len, data_len));
+    }
+    Ok(pos + len)
+}
+
+/// End of the `stride(m)` slot of an array element that started at `start` and ends at `pos`.
+fn skip_stride_padding(len: usize, start: usize, pos: usize, stride: u64) -> Result<usize, CodecError> {
+    let gap = crate::codec::stride_gap(stride, pos - start)?;
+    if gap > len - pos {
+        return Err(truncated(pos, gap, len));
+    }
+    Ok(pos + gap)
 }
 
 fn read_bytes_to_u64(data: &[u8], pos: &mut usize, len: usize, endianness: Endianness) -> Result<u64, CodecError> {
     if *pos + len > data.len() {
-        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+        return Err(truncated(*pos, len, data.len()));
     }
     let v = match (len, endianness) {
         (1, _) => data[*pos] as u64,
@@ -268,11 +517,15 @@ fn read_bytes_to_u64(data: &[u8], pos: &mut usize, len: usize, endianness: Endia
         (8, Endianness::Little) => LittleEndian::read_u64(&data[*pos..]),
         _ => {
             let mut b = [0u8; 8];
-            let start = 8 - len;
-            b[start..].copy_from_slice(&data[*pos..*pos + len]);
             match endianness {
-                Endianness::Big => BigEndian::read_u64(&b),
-                Endianness::Little => LittleEndian::read_u64(&b),
+                Endianness::Big => {
+                    b[8 - len..].copy_from_slice(&data[*pos..*pos + len]);
+                    BigEndian::read_u64(&b)
+                }
+                Endianness::Little => {
+                    b[..len].copy_from_slice(&data[*pos..*pos + len]);
+                    LittleEndian::read_u64(&b)
+                }
             }
         }
     };
@@ -295,13 +548,25 @@ fn validate_constraint_raw(value_i64: i64, c: &Constraint) -> Result<(), CodecEr
         Constraint::Enum(allowed) => {
             let ok = allowed.iter().any(|l| l.as_i64() == Some(value_i64));
             if !ok {
-                return Err(CodecError::Validation("value not in allowed enum".to_string()));
+                return Err(CodecError::Validation(enum_violation(allowed, value_i64)));
             }
         }
+        Constraint::EnumTolerance(..) => check_float_constraint(value_i64 as f64, c).map_err(CodecError::Validation)?,
     }
     Ok(())
 }
 
+/// Like [`validate_constraint_raw`] for a field read by [`read_field_i64`]: the raw bits of a
+/// `float`/`double` field are checked as the float they encode.
+fn validate_field_raw(spec: &TypeSpec, value_i64: i64, c: &Constraint) -> Result<(), CodecError> {
+    let x = match spec {
+        TypeSpec::Base(BaseType::Float) => f32::from_bits(value_i64 as u32) as f64,
+        TypeSpec::Base(BaseType::Double) => f64::from_bits(value_i64 as u64),
+        _ => return validate_constraint_raw(value_i64, c),
+    };
+    check_float_constraint(x, c).map_err(CodecError::Validation)
+}
+
 impl WalkContext {
     fn get(&self, k: &str) -> Option<u64> {
         self.values.get(k).copied()
@@ -309,15 +574,62 @@ impl WalkContext {
     fn set(&mut self, k: String, v: u64) {
         self.values.insert(k, v);
     }
+    /// Byte width of a `dyn_int(width_field)`, as recorded for the width field (1..8).
+    fn dyn_int_len(&self, width_field: &str) -> Result<usize, CodecError> {
+        match self.get(width_field) {
+            Some(n @ 1..=8) => Ok(n as usize),
+            Some(n) => Err(CodecError::Validation(format!("dyn_int({}): width {} is not in 1..8", width_field, n))),
+            None => Err(CodecError::UnknownField(width_field.to_string())),
+        }
+    }
+    /// Struct or message a `select<field>` holds, from the recorded value of `field`.
+    fn select<'s>(&self, field: &str, cases: &'s [(i64, String)], default: Option<&'s String>) -> Result<&'s str, CodecError> {
+        select_target(field, cases, default, self.get(field).map(|u| u as i64))
+    }
+    /// Message continuing `msg` after its fields, chosen by its body selector from the recorded key values.
+    /// None when `msg` has no body selector.
+    fn variant<'m>(&self, msg: &'m MessageSection) -> Result<Option<&'m str>, CodecError> {
+        let Some(sel) = &msg.selector else { return Ok(None) };
+        let value_of = |name: &str| self.get(name).map(|u| u as i64);
+        sel.select(value_of)
+            .map(Some)
+            .ok_or_else(|| CodecError::Validation(sel.no_variant_text(&msg.name, value_of)))
+    }
+    /// Constraint struct field `f` is checked against: while validating, the active one of an integer field.
+    fn struct_constraint<'f>(&self, f: &'f StructField) -> Option<&'f Constraint> {
+        if !self.validate || type_spec_integer_range(&f.type_spec).is_none() {
+            return None;
+        }
+        f.active_constraint(|name| self.get(name).map(|u| u as i64))
+    }
 }
 
 impl<'a> BinaryWalker<'a> {
     pub fn new(data: &'a [u8], resolved: &'a ResolvedProtocol, endianness: Endianness) -> Self {
-        BinaryWalker { data, pos: 0, resolved, endianness, ctx: WalkContext::default() }
+        BinaryWalker { data, pos: 0, resolved, endianness, ctx: WalkContext::default(), custom: None }
     }
 
     pub fn at(data: &'a [u8], start: usize, resolved: &'a ResolvedProtocol, endianness: Endianness) -> Self {
-        BinaryWalker { data, pos: start, resolved, endianness, ctx: WalkContext::default() }
+        BinaryWalker { data, pos: start, resolved, endianness, ctx: WalkContext::default(), custom: None }
+    }
+
+    /// Use `custom` to skip `custom("name")` fields (e.g. [`Codec::custom_types`](crate::codec::Codec::custom_types));
+    /// without it such fields fail with a validation error.
+    pub fn with_custom_types(mut self, custom: &'a CustomTypes) -> Self {
+        self.custom = Some(custom);
+        self
+    }
+
+    /// Bytes occupied by the `custom(name)` field at the current position.
+    fn skip_custom(&self, name: &str) -> Result<usize, CodecError> {
+        let custom = self
+            .custom
+            .ok_or_else(|| CodecError::Validation(format!("custom type \"{}\" is not registered", name)))?;
+        let n = custom.get(name)?.skip(self.data, self.pos)?;
+        if self.pos + n > self.data.len() {
+            return Err(truncated(self.pos, n, self.data.len()));
+        }
+        Ok(n)
     }
 
     pub fn position(&self) -> usize {
@@ -331,20 +643,197 @@ impl<'a> BinaryWalker<'a> {
     /// Skip one message by structure; returns number of bytes skipped. No allocation.
     pub fn skip_message(&mut self, message_name: &str) -> Result<usize, CodecError> {
         let start = self.pos;
-        let msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
-        self.skip_message_fields(msg.fields.as_slice())?;
+        self.walk_message(message_name, Self::skip_message_fields)?;
         Ok(self.pos - start)
     }
 
+    /// Locate the `n`th record (0-based) of `message_name`, counting from the current position.
+    /// Skips the `n` preceding records and returns the `(start, end)` byte range of the nth; the
+    /// walker is left at `end`. Still O(n) in the number of records, but allocation-free.
+    pub fn nth_record_range(&mut self, message_name: &str, n: usize) -> Result<(usize, usize), CodecError> {
+        for _ in 0..n {
+            self.skip_message(message_name)?;
+        }
+        let start = self.pos;
+        self.skip_message(message_name)?;
+        Ok((start, self.pos))
+    }
+
+    /// `(start, end)` byte range of each record of `message_name` from the current position to the end of the
+    /// data, one [`skip_message`](Self::skip_message) at a time. Ends after the last record, after the first
+    /// error (yielded once), or at a record taking no bytes. No allocation.
+    pub fn messages<'w>(&'w mut self, message_name: &'w str) -> impl Iterator<Item = Result<(usize, usize), CodecError>> + use<'a, 'w> {
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done || self.pos >= self.data.len() {
+                return None;
+            }
+            let start = self.pos;
+            match self.skip_message(message_name) {
+                Ok(0) => None,
+                Ok(_) => Some(Ok((start, self.pos))),
+                Err(e) => {
+                    done = true;
+                    Some(Err(e))
+                }
+            }
+        })
+    }
+
+    /// Numeric value of one field of `message_name`, without decoding the rest: `field_path` names a message
+    /// field or, dotted, a field of the struct it holds (`"i048_040.rho"`). The fields before it are skipped as
+    /// by [`skip_message`](Self::skip_message), honouring conditions and presence; a field absent from the
+    /// record (condition not met, presence bit or flag clear) is a validation error. The walker is left
+    /// after the field. No allocation.
+    pub fn read_field(&mut self, message_name: &str, field_path: &str) -> Result<i64, CodecError> {
+        let spec = self.seek_path(message_name, field_path)?;
+        if let TypeSpec::StructRef(name) | TypeSpec::Enum(name) = spec {
+            if let Some(e) = self.resolved.get_enum(name) {
+                return read_i64_slice(self.data, &mut self.pos, &TypeSpec::Base(e.base.clone()), self.endianness);
+            }
+        }
+        read_field_i64(self.data, &mut self.pos, &mut self.ctx.bits, spec, self.endianness)
+    }
+
+    /// Moves the walker to the start of the field `field_path` of [`read_field`](Self::read_field) and returns
+    /// its type (the type inside when it is a present optional).
+    fn seek_path(&mut self, message_name: &str, field_path: &str) -> Result<&'a TypeSpec, CodecError> {
+        let mut path = field_path.split('.');
+        let first = path.next().unwrap_or_default();
+        let mut msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
+        self.ctx.bits = WalkBits::default();
+        let mut spec = loop {
+            if let Some(spec) = self.seek_field(message_fields(&msg.fields), first, field_path, true)? {
+                break spec;
+            }
+            // Not in this message: it may be in the message its body selector continues with.
+            let variant = self.ctx.variant(msg)?.ok_or_else(|| CodecError::UnknownField(field_path.to_string()))?;
+            msg = self.resolved.get_message(variant).ok_or_else(|| CodecError::UnknownField(field_path.to_string()))?;
+        };
+        for name in path {
+            let not_struct = || CodecError::Validation(format!("{}: field before {} is not a struct", field_path, name));
+            let container = match self.present_spec(spec, field_path)? {
+                TypeSpec::StructRef(container) => container.as_str(),
+                TypeSpec::Select { field, cases, default } => self.ctx.select(field, cases, default.as_ref())?,
+                _ => return Err(not_struct()),
+            };
+            self.ctx.bits = WalkBits::default();
+            let found = if let Some(st) = self.resolved.get_struct(container) {
+                let fields = st.wire_fields().map(|f| (f.name.as_str(), f.condition.as_ref(), &f.type_spec, f.referenced_by_width));
+                self.seek_field(fields, name, field_path, false)?
+            } else if let Some(m) = self.resolved.get_message(container) {
+                self.seek_field(message_fields(&m.fields), name, field_path, true)?
+            } else {
+                return Err(not_struct());
+            };
+            spec = found.ok_or_else(|| CodecError::UnknownField(field_path.to_string()))?;
+        }
+        self.present_spec(spec, field_path)
+    }
+
+    /// Skips `fields` (name, condition, type, whether later fields refer to its value) up to the one named
+    /// `name` and returns its type, the walker at its start; None when no field has that name. Message fields
+    /// (`message_scope`) also follow the `endianness from` switch.
+    fn seek_field(
+        &mut self,
+        fields: impl Iterator<Item = (&'a str, Option<&'a Condition>, &'a TypeSpec, bool)>,
+        name: &str,
+        field_path: &str,
+        message_scope: bool,
+    ) -> Result<Option<&'a TypeSpec>, CodecError> {
+        for (f, condition, spec, record) in fields {
+            if let Some(cond) = condition {
+                if self.ctx.get(cond.field.as_str()).map(|u| u as i64) != cond.value.as_i64() {
+                    if f == name {
+                        return Err(CodecError::Validation(format!("{}: not present (condition on {})", field_path, cond.field)));
+                    }
+                    continue;
+                }
+            }
+            if f == name {
+                return Ok(Some(spec));
+            }
+            let switch = endianness_switch(self.resolved, f).filter(|_| message_scope);
+            if record || switch.is_some() {
+                self.record_condition_value(f, spec);
+            }
+            self.skip_type_spec(spec, Some(f))?;
+            if let Some(switch) = switch {
+                self.endianness = switched_endianness(switch, self.ctx.get(f))?;
+            }
+        }
+        Ok(None)
+    }
+
+    /// `spec`, or the type inside it when it is an optional that is present; an absent optional is an error.
+    fn present_spec(&mut self, spec: &'a TypeSpec, field_path: &str) -> Result<&'a TypeSpec, CodecError> {
+        match spec {
+            TypeSpec::Optional(elem, flag) if self.optional_present(flag)? => Ok(elem),
+            TypeSpec::Optional(..) => Err(CodecError::Validation(format!("{}: not present", field_path))),
+            spec => Ok(spec),
+        }
+    }
+
     /// Validate current message in place (read only constrained fields, check ranges). No allocation.
     /// Fields whose constraint saturates the type range (flag set on each [`MessageField`](crate::ast::MessageField) at resolve) are skipped without range check.
     pub fn validate_message(&mut self, message_name: &str) -> Result<(), CodecError> {
-        let msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
-        self.validate_and_skip_message_fields(msg.fields.as_slice())?;
-        Ok(())
+        self.ctx.validate = true;
+        let result = self.walk_message(message_name, |w, fields| w.validate_and_skip_message_fields(fields, None));
+        self.ctx.validate = false;
+        result
+    }
+
+    /// Like [`validate_message`](Self::validate_message), but a failed constraint is returned as
+    /// `Ok(Some(..))` with the field, its offset and raw value; the walker stops at that field.
+    /// `Err` is left for structural failures (truncation, unknown message).
+    pub fn first_violation(&mut self, message_name: &str) -> Result<Option<ConstraintViolation>, CodecError> {
+        let mut violation = None;
+        self.ctx.validate = true;
+        let result = self.walk_message(message_name, |w, fields| w.validate_and_skip_message_fields(fields, Some(&mut violation)));
+        self.ctx.validate = false;
+        match result {
+            Ok(()) => Ok(None),
+            Err(_) if violation.is_some() => Ok(violation),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Walks `message_name` with `walk_fields`, then each message its body selector continues with;
+    /// the built-in `RawRecord` variant takes the rest of the data.
+    fn walk_message(
+        &mut self,
+        message_name: &str,
+        mut walk_fields: impl FnMut(&mut Self, &'a [MessageField]) -> Result<(), CodecError>,
+    ) -> Result<(), CodecError> {
+        // An `endianness from` switch holds for the rest of the record (variants included), not the next one.
+        let order = self.endianness;
+        let result = self.walk_message_variants(message_name, &mut walk_fields);
+        self.endianness = order;
+        result
+    }
+
+    fn walk_message_variants(
+        &mut self,
+        message_name: &str,
+        walk_fields: &mut impl FnMut(&mut Self, &'a [MessageField]) -> Result<(), CodecError>,
+    ) -> Result<(), CodecError> {
+        let mut msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
+        loop {
+            walk_fields(self, msg.fields.as_slice())?;
+            let Some(variant) = self.ctx.variant(msg)? else { return Ok(()) };
+            msg = match self.resolved.get_message(variant) {
+                Some(m) => m,
+                None if variant == RAW_RECORD => {
+                    self.pos = self.data.len();
+                    return Ok(());
+                }
+                None => return Err(CodecError::UnknownStruct(variant.to_string())),
+            };
+        }
     }
 
     fn skip_message_fields(&mut self, fields: &[MessageField]) -> Result<(), CodecError> {
+        let saved_bits = std::mem::take(&mut self.ctx.bits);
         for f in fields {
             if let Some(ref cond) = f.condition {
                 let cond_val = self.ctx.get(cond.field.as_str()).map(|u| u as i64);
@@ -353,14 +842,28 @@ impl<'a> BinaryWalker<'a> {
                     continue;
                 }
             }
+            let switch = endianness_switch(self.resolved, &f.name);
+            if switch.is_some() || f.referenced_by_width || f.referenced_by_selector {
+                self.record_condition_value(&f.name, &f.type_spec);
+            }
             self.skip_type_spec(&f.type_spec, Some(&f.name))?;
+            if let Some(switch) = switch {
+                self.endianness = switched_endianness(switch, self.ctx.get(&f.name))?;
+            }
         }
+        self.ctx.bits = saved_bits;
         Ok(())
     }
 
     /// Validation: for each field we skip (saturating or no constraint) or run range check.
     /// Saturating flag is set on each [`MessageField`](crate::ast::MessageField) at resolve.
-    fn validate_and_skip_message_fields(&mut self, fields: &[MessageField]) -> Result<(), CodecError> {
+    /// With `violation`, a failed constraint is also recorded there (position left at the field).
+    fn validate_and_skip_message_fields(
+        &mut self,
+        fields: &[MessageField],
+        mut violation: Option<&mut Option<ConstraintViolation>>,
+    ) -> Result<(), CodecError> {
+        let saved_bits = std::mem::take(&mut self.ctx.bits);
         for f in fields.iter() {
             if let Some(ref cond) = f.condition {
                 let cond_val = self.ctx.get(cond.field.as_str()).map(|u| u as i64);
@@ -369,30 +872,58 @@ impl<'a> BinaryWalker<'a> {
                     continue;
                 }
             }
-            if f.saturating || f.constraint.is_none() {
-                self.skip_type_spec(&f.type_spec, Some(&f.name))?;
-            } else {
-                self.validate_field_and_skip(f)?;
+            let switch = endianness_switch(self.resolved, &f.name);
+            if switch.is_some() || f.referenced_by_condition || f.referenced_by_width || f.referenced_by_selector {
+                self.record_condition_value(&f.name, &f.type_spec);
+            }
+            match f.active_constraint(|name| self.ctx.get(name).map(|u| u as i64)) {
+                Some(c) if !f.saturating => {
+                    let (start, start_bits) = (self.pos, self.ctx.bits);
+                    if let Err(e) = self.validate_field_and_skip(&f.name, &f.type_spec, c) {
+                        if let (Some(slot), CodecError::Validation(_)) = (violation.as_deref_mut(), &e) {
+                            let (mut pos, mut bits) = (start, start_bits);
+                            if let Ok(value) = read_field_i64(self.data, &mut pos, &mut bits, &f.type_spec, self.endianness) {
+                                // A field starting inside an already loaded bit byte begins at that byte.
+                                let offset = start - usize::from(!start_bits.is_aligned());
+                                self.pos = offset;
+                                *slot = Some(ConstraintViolation { field: f.name.clone(), offset, value, constraint: c.clone() });
+                            }
+                        }
+                        return Err(e);
+                    }
+                }
+                _ => self.skip_type_spec(&f.type_spec, Some(&f.name))?,
+            }
+            if let Some(switch) = switch {
+                self.endianness = switched_endianness(switch, self.ctx.get(&f.name))?;
             }
         }
+        self.ctx.bits = saved_bits;
         Ok(())
     }
 
     /// Range-check slow path: read field value from buffer then validate interval/enum.
-    /// Called only for message-level fields that have a constraint and are not saturating (see [`MessageField::saturating`](crate::ast::MessageField)).
-    fn validate_field_and_skip(&mut self, f: &MessageField) -> Result<(), CodecError> {
+    /// Called for message-level fields that have a constraint and are not saturating (see [`MessageField::saturating`](crate::ast::MessageField)),
+    /// and for constrained struct fields while validating.
+    fn validate_field_and_skip(&mut self, name: &str, spec: &TypeSpec, c: &Constraint) -> Result<(), CodecError> {
         #[cfg(feature = "walk_profile")]
         let _g = ProfileGuard::new("ValidateField");
-        let value_i64 = read_i64_slice(self.data, &mut self.pos, &f.type_spec, self.endianness)?;
-        if let Some(ref c) = f.constraint {
-            validate_constraint_raw(value_i64, c)?;
-        }
-        if matches!(f.type_spec, TypeSpec::LengthOf(_) | TypeSpec::CountOf(_)) {
-            self.ctx.set(f.name.clone(), value_i64 as u64);
+        let value_i64 = read_field_i64(self.data, &mut self.pos, &mut self.ctx.bits, spec, self.endianness)?;
+        validate_field_raw(spec, value_i64, c)?;
+        if matches!(spec, TypeSpec::LengthOf(..) | TypeSpec::CountOf(..)) {
+            self.ctx.set(name.to_string(), value_i64 as u64);
         }
         Ok(())
     }
 
+    /// Peek the numeric value of a field that a `when` clause or a `dyn_int` width refers to, for use later in the message.
+    fn record_condition_value(&mut self, name: &str, spec: &TypeSpec) {
+        let (mut pos, mut bits) = (self.pos, self.ctx.bits);
+        if let Ok(v) = read_field_i64(self.data, &mut pos, &mut bits, spec, self.endianness) {
+            self.ctx.set(name.to_string(), v as u64);
+        }
+    }
+
     /// **Slow path** (run with `--features walk_profile` and see bench walk_validate_pcap hotspot):
     /// **Optional** (~48%), **StructRef** (~34%), **RepList** (~10%); then BitfieldSizedInt, Base.
     /// For walk+validate, **ValidateField** (range/enum check) is a small fraction when most fields are saturating.
@@ -403,122 +934,125 @@ impl<'a> BinaryWalker<'a> {
                 let _g = ProfileGuard::new("Base");
                 let n = base_type_size(bt);
                 if self.pos + n > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, n, self.data.len()));
                 }
                 self.pos += n;
             }
-            TypeSpec::Padding(kind) => {
+            TypeSpec::Padding(PaddingKind::Bytes(n)) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("Padding");
-                let byte_len = match kind {
-                    PaddingKind::Bytes(n) => *n as usize,
-                    PaddingKind::Bits(n) => ((*n + 7) / 8) as usize,
-                };
+                let byte_len = *n as usize;
                 if self.pos + byte_len > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, byte_len, self.data.len()));
                 }
                 self.pos += byte_len;
             }
+            TypeSpec::Padding(PaddingKind::Bits(n)) => {
+                #[cfg(feature = "walk_profile")]
+                let _g = ProfileGuard::new("Padding");
+                self.ctx.bits.read(self.data, &mut self.pos, *n)?;
+            }
             TypeSpec::Bitfield(n) | TypeSpec::SizedInt(_, n) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("BitfieldSizedInt");
-                self.pos += ((*n + 7) / 8) as usize;
+                if matches!(spec, TypeSpec::Bitfield(_)) || *n < 8 || !self.ctx.bits.is_aligned() {
+                    self.ctx.bits.read(self.data, &mut self.pos, *n)?;
+                } else {
+                    let byte_len = n.div_ceil(8) as usize;
+                    if self.pos + byte_len > self.data.len() {
+                        return Err(truncated(self.pos, byte_len, self.data.len()));
+                    }
+                    self.pos += byte_len;
+                }
             }
-            TypeSpec::LengthOf(_) | TypeSpec::CountOf(_) => {
+            TypeSpec::LengthOf(_, width) | TypeSpec::CountOf(_, width) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("LengthOfCountOf");
-                if self.pos + 4 > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
-                }
+                let size = base_type_size(width);
+                let v = read_bytes_to_u64(self.data, &mut self.pos, size, self.endianness)?;
                 if let Some(name) = field_name {
-                    let v = read_u32_slice(self.data, self.pos, self.endianness)?;
-                    self.ctx.set(name.to_string(), v as u64);
+                    self.ctx.set(name.to_string(), v);
                 }
-                self.pos += 4;
+                self.pos += size;
             }
-            TypeSpec::PresenceBits(n) => {
+            TypeSpec::PresenceBits(n, stop) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("PresenceBits");
                 let bitmap = read_bitmap_n(self.data, &mut self.pos, self.endianness, *n)?;
-                self.ctx.presence = WalkPresence::Bitmap(bitmap, 0);
+                self.ctx.presence = WalkPresence::Bitmap(presence_bits_logical(bitmap, *stop), 0);
             }
             TypeSpec::BitmapPresence { total_bits, presence_per_block, .. } => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("BitmapPresence");
-                let max_encoded_bits = if *presence_per_block == 0 { *total_bits } else { ((*total_bits + presence_per_block - 1) / presence_per_block) * (presence_per_block + 1) };
-                let max_bytes = ((max_encoded_bits + 7) / 8) as usize;
-                let mut bytes = Vec::new();
-                if *presence_per_block == 0 {
-                    if self.pos + max_bytes > self.data.len() {
-                        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                self.ctx.presence =
+                    read_bitmap_presence(self.data, &mut self.pos, &mut self.ctx.bits, *total_bits, *presence_per_block)?;
+            }
+            TypeSpec::StructRef(name) | TypeSpec::Enum(name) => {
+                #[cfg(feature = "walk_profile")]
+                let _g = ProfileGuard::new("StructRef");
+                if let Some(enum_sec) = self.resolved.get_enum(name) {
+                    let n = base_type_size(&enum_sec.base);
+                    if self.pos + n > self.data.len() {
+                        return Err(truncated(self.pos, n, self.data.len()));
                     }
-                    bytes.extend_from_slice(&self.data[self.pos..self.pos + max_bytes]);
-                    self.pos += max_bytes;
+                    self.pos += n;
+                } else if let Some(s) = self.resolved.get_struct(name) {
+                    self.skip_struct_fields(s.wire_fields(), own_presence(&s.fields))?;
                 } else {
-                    let block_bits = (presence_per_block + 1) as u8;
-                    let max_blocks = (*total_bits + presence_per_block - 1) / presence_per_block;
-                    let k = *presence_per_block as usize;
-                    if block_bits >= 8 {
-                        for _ in 0..max_blocks {
-                            if self.pos >= self.data.len() {
-                                return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
-                            }
-                            let b = self.data[self.pos];
-                            self.pos += 1;
-                            bytes.push(b);
-                            if b & 0x01 == 0 || bytes.len() >= max_blocks as usize {
-                                break;
-                            }
-                        }
-                    } else {
-                        let mut pos = self.pos;
-                        let mut bit_pos = 0u8;
-                        for _ in 0..max_blocks {
-                            let (b, p, bp) = read_bits_walk(self.data, pos, bit_pos, block_bits)?;
-                            pos = p;
-                            bit_pos = bp;
-                            let mut stored = (b & 1) as u8;
-                            for j in 0..k {
-                                stored |= (((b >> (j + 1)) & 1) as u8) << (7 - j);
-                            }
-                            bytes.push(stored);
-                            if b & 0x01 == 0 || bytes.len() >= max_blocks as usize {
-                                break;
-                            }
-                        }
-                        if bit_pos != 0 {
-                            pos += 1;
-                        }
-                        self.pos = pos;
-                    }
-                    if bytes.len() == max_blocks as usize {
-                        if bytes.last().map(|&b| b & 0x01 != 0).unwrap_or(false) {
-                            return Err(CodecError::Validation(
-                                "bitmap presence: last FSPEC byte must have FX=0 (max size reached)".to_string(),
-                            ));
-                        }
-                    }
+                    let m = self.resolved.get_message(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
+                    // An embedded message's `endianness from` switch ends with it.
+                    let order = self.endianness;
+                    self.skip_message_fields(m.fields.as_slice())?;
+                    self.endianness = order;
                 }
-                self.ctx.presence = if *presence_per_block == 0 {
-                    WalkPresence::BitmapPresenceConsecutive(bytes, 0, 0)
-                } else {
-                    WalkPresence::BitmapPresence(bytes, 0, *presence_per_block)
-                };
             }
-            TypeSpec::StructRef(name) => {
+            TypeSpec::Select { field, cases, default } => {
                 #[cfg(feature = "walk_profile")]
-                let _g = ProfileGuard::new("StructRef");
-                if self.resolved.get_enum(name).is_some() {
-                    if self.pos + 1 > self.data.len() {
-                        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
-                    }
-                    self.pos += 1;
+                let _g = ProfileGuard::new("Select");
+                let name = self.ctx.select(field, cases, default.as_ref())?;
+                if let Some(s) = self.resolved.get_struct(name) {
+                    self.skip_struct_fields(s.wire_fields(), own_presence(&s.fields))?;
                 } else {
-                    let s = self.resolved.get_struct(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
-                    self.skip_struct_fields(s.fields.as_slice())?;
+                    let m = self.resolved.get_message(name).ok_or_else(|| CodecError::UnknownStruct(name.to_string()))?;
+                    // An embedded message's `endianness from` switch ends with it.
+                    let order = self.endianness;
+                    self.skip_message_fields(m.fields.as_slice())?;
+                    self.endianness = order;
+                }
+            }
+            TypeSpec::Bits(members) => {
+                #[cfg(feature = "walk_profile")]
+                let _g = ProfileGuard::new("Bits");
+                if !self.ctx.bits.is_aligned() {
+                    return Err(CodecError::Validation("bits { ... }: must start on a byte boundary".to_string()));
+                }
+                let n = (members.iter().map(|(_, w)| w).sum::<u64>() / 8) as usize;
+                if self.pos + n > self.data.len() {
+                    return Err(truncated(self.pos, n, self.data.len()));
+                }
+                self.pos += n;
+            }
+            TypeSpec::DynInt(width_field) => {
+                #[cfg(feature = "walk_profile")]
+                let _g = ProfileGuard::new("DynInt");
+                let n = self.ctx.dyn_int_len(width_field)?;
+                if self.pos + n > self.data.len() {
+                    return Err(truncated(self.pos, n, self.data.len()));
+                }
+                self.pos += n;
+            }
+            TypeSpec::Group { count, fields } => {
+                #[cfg(feature = "walk_profile")]
+                let _g = ProfileGuard::new("Group");
+                let n = match count {
+                    ArrayLen::Constant(k) => *k,
+                    ArrayLen::FieldRef(field) => self.ctx.get(field).ok_or_else(|| CodecError::UnknownField(field.clone()))?,
+                };
+                for _ in 0..n {
+                    self.skip_struct_fields(fields.iter(), own_presence(fields))?;
                 }
             }
-            TypeSpec::Array(elem, len) => {
+            TypeSpec::Array(elem, len, stride) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("Array");
                 let n = match len {
@@ -526,14 +1060,18 @@ impl<'a> BinaryWalker<'a> {
                     ArrayLen::FieldRef(field) => self.ctx.get(field).ok_or_else(|| CodecError::UnknownField(field.clone()))?,
                 };
                 for _ in 0..n {
+                    let start = self.pos;
                     self.skip_type_spec(elem, None)?;
+                    if let Some(stride) = stride {
+                        self.pos = skip_stride_padding(self.data.len(), start, self.pos, *stride)?;
+                    }
                 }
             }
             TypeSpec::List(elem) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("List");
                 if self.pos + 4 > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, 4, self.data.len()));
                 }
                 let n = read_u32_slice(self.data, self.pos, self.endianness)?;
                 self.pos += 4;
@@ -541,11 +1079,21 @@ impl<'a> BinaryWalker<'a> {
                     self.skip_type_spec(elem, None)?;
                 }
             }
+            TypeSpec::LengthPrefixedList(_, len_type) => {
+                #[cfg(feature = "walk_profile")]
+                let _g = ProfileGuard::new("LengthPrefixedList");
+                skip_length_prefixed_list(self.data, &mut self.pos, len_type, self.endianness)?;
+            }
+            TypeSpec::BytesList(_, len_field) => {
+                #[cfg(feature = "walk_profile")]
+                let _g = ProfileGuard::new("BytesList");
+                self.pos = skip_bytes_list(self.data.len(), self.pos, self.ctx.get(len_field), len_field)?;
+            }
             TypeSpec::RepList(elem) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("RepList");
                 if self.pos + 1 > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, 1, self.data.len()));
                 }
                 let n = self.data[self.pos] as u32;
                 self.pos += 1;
@@ -564,44 +1112,78 @@ impl<'a> BinaryWalker<'a> {
                     }
                 }
             }
-            TypeSpec::Optional(elem) => {
+            TypeSpec::Optional(elem, flag) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("Optional");
-                let present = match &mut self.ctx.presence {
-                    WalkPresence::Bitmap(bitmap, i) => {
-                        let bit = (*bitmap >> *i) & 1;
-                        *i += 1;
-                        bit != 0
-                    }
-                    WalkPresence::BitmapPresenceConsecutive(bytes, byte_idx, bit_offset) => {
-                        let present = *byte_idx < bytes.len() && ((bytes[*byte_idx] >> (7 - *bit_offset)) & 1) != 0;
-                        if *bit_offset == 7 {
-                            *byte_idx += 1;
-                            *bit_offset = 0;
-                        } else {
-                            *bit_offset += 1;
-                        }
-                        present
-                    }
-                    WalkPresence::BitmapPresence(bytes, i, presence_per_block) => {
-                        let bits_per_block = *presence_per_block as usize;
-                        let byte_idx = *i / bits_per_block;
-                        let bit_idx = *i % bits_per_block;
-                        *i += 1;
-                        let bit = if byte_idx < bytes.len() { (bytes[byte_idx] >> (7 - bit_idx)) & 1 } else { 0 };
-                        bit != 0
-                    }
-                    WalkPresence::None => read_u8(self.data, &mut self.pos)? != 0,
-                };
-                if present {
+                if self.optional_present(flag)? {
                     self.skip_type_spec(elem, None)?;
                 }
             }
+            TypeSpec::Timestamp { base, .. } => {
+                #[cfg(feature = "walk_profile")]
+                let _g = ProfileGuard::new("Timestamp");
+                self.skip_type_spec(base, None)?;
+            }
+            TypeSpec::Bcd { .. } | TypeSpec::ReverseBits(_) | TypeSpec::Unknown(_) | TypeSpec::FixedString(_) | TypeSpec::Checksum { .. } => {
+                let n = type_spec_fixed_bytes(spec).unwrap_or(0);
+                if self.pos + n > self.data.len() {
+                    return Err(truncated(self.pos, n, self.data.len()));
+                }
+                self.pos += n;
+            }
+            TypeSpec::Remaining => {
+                self.pos = self.data.len();
+            }
+            TypeSpec::CString => {
+                let rest = &self.data[self.pos.min(self.data.len())..];
+                let len = rest.iter().position(|&b| b == 0).ok_or_else(|| truncated(self.pos, rest.len() + 1, self.data.len()))?;
+                self.pos += len + 1;
+            }
+            TypeSpec::Custom(name) => {
+                self.pos += self.skip_custom(name)?;
+            }
         }
         Ok(())
     }
 
-    fn skip_struct_fields(&mut self, fields: &[StructField]) -> Result<(), CodecError> {
+    /// Presence of the next `optional(flag)<T>`: its bit in the current bitmap, else its leading flag (read).
+    fn optional_present(&mut self, flag: &BaseType) -> Result<bool, CodecError> {
+        Ok(match &mut self.ctx.presence {
+            WalkPresence::Bitmap(bitmap, i) => {
+                let bit = (*bitmap >> *i) & 1;
+                *i += 1;
+                bit != 0
+            }
+            WalkPresence::BitmapPresenceConsecutive(bytes, byte_idx, bit_offset) => {
+                let present = *byte_idx < bytes.len() && ((bytes[*byte_idx] >> (7 - *bit_offset)) & 1) != 0;
+                if *bit_offset == 7 {
+                    *byte_idx += 1;
+                    *bit_offset = 0;
+                } else {
+                    *bit_offset += 1;
+                }
+                present
+            }
+            WalkPresence::BitmapPresence(bytes, i, presence_per_block) => {
+                let bits_per_block = *presence_per_block as usize;
+                let byte_idx = *i / bits_per_block;
+                let bit_idx = *i % bits_per_block;
+                *i += 1;
+                let bit = if byte_idx < bytes.len() { (bytes[byte_idx] >> (7 - bit_idx)) & 1 } else { 0 };
+                bit != 0
+            }
+            WalkPresence::None => read_presence_flag(self.data, &mut self.pos, flag, self.endianness)?,
+        })
+    }
+
+    /// Struct body (`fields` in wire order) or one repetition of a `repeat(n) { ... }` group.
+    fn skip_struct_fields<'f>(
+        &mut self,
+        fields: impl Iterator<Item = &'f StructField>,
+        own_presence: bool,
+    ) -> Result<(), CodecError> {
+        let saved_bits = std::mem::take(&mut self.ctx.bits);
+        let saved_presence = own_presence.then(|| self.ctx.presence.clone());
         for f in fields {
             if let Some(ref cond) = f.condition {
                 let cond_val = self.ctx.get(cond.field.as_str()).map(|u| u as i64);
@@ -610,7 +1192,17 @@ impl<'a> BinaryWalker<'a> {
                     continue;
                 }
             }
-            self.skip_type_spec(&f.type_spec, Some(&f.name))?;
+            if f.referenced_by_condition || f.referenced_by_width {
+                self.record_condition_value(&f.name, &f.type_spec);
+            }
+            match self.ctx.struct_constraint(f) {
+                Some(c) => self.validate_field_and_skip(&f.name, &f.type_spec, c)?,
+                None => self.skip_type_spec(&f.type_spec, Some(&f.name))?,
+            }
+        }
+        self.ctx.bits = saved_bits;
+        if let Some(presence) = saved_presence {
+            self.ctx.presence = presence;
         }
         Ok(())
     }
@@ -618,11 +1210,30 @@ impl<'a> BinaryWalker<'a> {
 
 impl<'a> BinaryWalkerMut<'a> {
     pub fn new(data: &'a mut [u8], resolved: &'a ResolvedProtocol, endianness: Endianness) -> Self {
-        BinaryWalkerMut { data, pos: 0, resolved, endianness, ctx: WalkContext::default() }
+        BinaryWalkerMut { data, pos: 0, resolved, endianness, ctx: WalkContext::default(), custom: None }
     }
 
     pub fn at(data: &'a mut [u8], start: usize, resolved: &'a ResolvedProtocol, endianness: Endianness) -> Self {
-        BinaryWalkerMut { data, pos: start, resolved, endianness, ctx: WalkContext::default() }
+        BinaryWalkerMut { data, pos: start, resolved, endianness, ctx: WalkContext::default(), custom: None }
+    }
+
+    /// Use `custom` to skip `custom("name")` fields (e.g. [`Codec::custom_types`](crate::codec::Codec::custom_types));
+    /// without it such fields fail with a validation error.
+    pub fn with_custom_types(mut self, custom: &'a CustomTypes) -> Self {
+        self.custom = Some(custom);
+        self
+    }
+
+    /// Bytes occupied by the `custom(name)` field at the current position.
+    fn skip_custom(&self, name: &str) -> Result<usize, CodecError> {
+        let custom = self
+            .custom
+            .ok_or_else(|| CodecError::Validation(format!("custom type \"{}\" is not registered", name)))?;
+        let n = custom.get(name)?.skip(self.data, self.pos)?;
+        if self.pos + n > self.data.len() {
+            return Err(truncated(self.pos, n, self.data.len()));
+        }
+        Ok(n)
     }
 
     pub fn position(&self) -> usize {
@@ -631,20 +1242,55 @@ impl<'a> BinaryWalkerMut<'a> {
 
     /// Zero all padding and reserved fields in one message, in place. No other allocation.
     pub fn zero_padding_reserved_message(&mut self, message_name: &str) -> Result<(), CodecError> {
-        let msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
-        self.zero_padding_reserved_message_fields(msg.fields.as_slice())?;
-        Ok(())
+        self.walk_message(message_name, Self::zero_padding_reserved_message_fields)
     }
 
     /// One-pass validate and zero: for each field, validate constrained non-saturating fields and zero padding; returns bytes consumed.
     pub fn validate_and_zero_message(&mut self, message_name: &str) -> Result<usize, CodecError> {
         let start = self.pos;
-        let msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
-        self.validate_and_zero_message_fields(msg.fields.as_slice())?;
+        self.ctx.validate = true;
+        let result = self.walk_message(message_name, Self::validate_and_zero_message_fields);
+        self.ctx.validate = false;
+        result?;
         Ok(self.pos - start)
     }
 
+    /// Walks `message_name` with `walk_fields`, then each message its body selector continues with;
+    /// the built-in `RawRecord` variant takes the rest of the data.
+    fn walk_message(
+        &mut self,
+        message_name: &str,
+        mut walk_fields: impl FnMut(&mut Self, &'a [MessageField]) -> Result<(), CodecError>,
+    ) -> Result<(), CodecError> {
+        // An `endianness from` switch holds for the rest of the record (variants included), not the next one.
+        let order = self.endianness;
+        let result = self.walk_message_variants(message_name, &mut walk_fields);
+        self.endianness = order;
+        result
+    }
+
+    fn walk_message_variants(
+        &mut self,
+        message_name: &str,
+        walk_fields: &mut impl FnMut(&mut Self, &'a [MessageField]) -> Result<(), CodecError>,
+    ) -> Result<(), CodecError> {
+        let mut msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
+        loop {
+            walk_fields(self, msg.fields.as_slice())?;
+            let Some(variant) = self.ctx.variant(msg)? else { return Ok(()) };
+            msg = match self.resolved.get_message(variant) {
+                Some(m) => m,
+                None if variant == RAW_RECORD => {
+                    self.pos = self.data.len();
+                    return Ok(());
+                }
+                None => return Err(CodecError::UnknownStruct(variant.to_string())),
+            };
+        }
+    }
+
     fn validate_and_zero_message_fields(&mut self, fields: &[MessageField]) -> Result<(), CodecError> {
+        let saved_bits = std::mem::take(&mut self.ctx.bits);
         for f in fields.iter() {
             if let Some(ref cond) = f.condition {
                 let cond_val = self.ctx.get(cond.field.as_str()).map(|u| u as i64);
@@ -653,35 +1299,47 @@ impl<'a> BinaryWalkerMut<'a> {
                     continue;
                 }
             }
-            if f.saturating || f.constraint.is_none() {
-                self.zero_or_skip_type_spec(&f.type_spec, Some(&f.name))?;
-            } else {
-                self.validate_field_and_skip(f)?;
+            let switch = endianness_switch(self.resolved, &f.name);
+            if switch.is_some() || f.referenced_by_condition || f.referenced_by_width || f.referenced_by_selector {
+                self.record_condition_value(&f.name, &f.type_spec);
+            }
+            match f.active_constraint(|name| self.ctx.get(name).map(|u| u as i64)) {
+                Some(c) if !f.saturating => self.validate_field_and_skip(&f.name, &f.type_spec, c)?,
+                _ => self.zero_or_skip_type_spec(&f.type_spec, Some(&f.name))?,
+            }
+            if let Some(switch) = switch {
+                self.endianness = switched_endianness(switch, self.ctx.get(&f.name))?;
             }
         }
+        self.ctx.bits = saved_bits;
         Ok(())
     }
 
-    fn validate_field_and_skip(&mut self, f: &MessageField) -> Result<(), CodecError> {
-        let value_i64 = read_i64_slice(self.data, &mut self.pos, &f.type_spec, self.endianness)?;
-        if let Some(ref c) = f.constraint {
-            validate_constraint_raw(value_i64, c)?;
-        }
-        if matches!(f.type_spec, TypeSpec::LengthOf(_) | TypeSpec::CountOf(_)) {
-            self.ctx.set(f.name.clone(), value_i64 as u64);
+    fn validate_field_and_skip(&mut self, name: &str, spec: &TypeSpec, c: &Constraint) -> Result<(), CodecError> {
+        let value_i64 = read_field_i64(self.data, &mut self.pos, &mut self.ctx.bits, spec, self.endianness)?;
+        validate_field_raw(spec, value_i64, c)?;
+        if matches!(spec, TypeSpec::LengthOf(..) | TypeSpec::CountOf(..)) {
+            self.ctx.set(name.to_string(), value_i64 as u64);
         }
         Ok(())
     }
 
+    fn record_condition_value(&mut self, name: &str, spec: &TypeSpec) {
+        let (mut pos, mut bits) = (self.pos, self.ctx.bits);
+        if let Ok(v) = read_field_i64(self.data, &mut pos, &mut bits, spec, self.endianness) {
+            self.ctx.set(name.to_string(), v as u64);
+        }
+    }
+
     /// Skip one message (same as BinaryWalker).
     pub fn skip_message(&mut self, message_name: &str) -> Result<usize, CodecError> {
         let start = self.pos;
-        let msg = self.resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
-        self.skip_message_fields(msg.fields.as_slice())?;
+        self.walk_message(message_name, Self::skip_message_fields)?;
         Ok(self.pos - start)
     }
 
     fn zero_padding_reserved_message_fields(&mut self, fields: &[MessageField]) -> Result<(), CodecError> {
+        let saved_bits = std::mem::take(&mut self.ctx.bits);
         for f in fields {
             if let Some(ref cond) = f.condition {
                 let cond_val = self.ctx.get(cond.field.as_str()).map(|u| u as i64);
@@ -690,20 +1348,63 @@ impl<'a> BinaryWalkerMut<'a> {
                     continue;
                 }
             }
+            let switch = endianness_switch(self.resolved, &f.name);
+            if switch.is_some() || f.referenced_by_width || f.referenced_by_selector {
+                self.record_condition_value(&f.name, &f.type_spec);
+            }
             self.zero_or_skip_type_spec(&f.type_spec, Some(&f.name))?;
+            if let Some(switch) = switch {
+                self.endianness = switched_endianness(switch, self.ctx.get(&f.name))?;
+            }
+        }
+        self.ctx.bits = saved_bits;
+        Ok(())
+    }
+
+    /// Struct body (`fields` in wire order) or one repetition of a `repeat(n) { ... }` group.
+    fn zero_struct_fields<'f>(
+        &mut self,
+        fields: impl Iterator<Item = &'f StructField>,
+        own_presence: bool,
+    ) -> Result<(), CodecError> {
+        let saved_bits = std::mem::take(&mut self.ctx.bits);
+        let saved_presence = own_presence.then(|| self.ctx.presence.clone());
+        for f in fields {
+            if let Some(ref cond) = f.condition {
+                let cond_val = self.ctx.get(cond.field.as_str()).map(|u| u as i64);
+                let expected = cond.value.as_i64();
+                if cond_val != expected {
+                    continue;
+                }
+            }
+            if f.referenced_by_condition || f.referenced_by_width {
+                self.record_condition_value(&f.name, &f.type_spec);
+            }
+            match self.ctx.struct_constraint(f) {
+                Some(c) => self.validate_field_and_skip(&f.name, &f.type_spec, c)?,
+                None => self.zero_or_skip_type_spec(&f.type_spec, Some(&f.name))?,
+            }
+        }
+        self.ctx.bits = saved_bits;
+        if let Some(presence) = saved_presence {
+            self.ctx.presence = presence;
         }
         Ok(())
     }
 
     fn zero_or_skip_type_spec(&mut self, spec: &TypeSpec, field_name: Option<&str>) -> Result<(), CodecError> {
         match spec {
+            TypeSpec::Padding(PaddingKind::Bits(n)) if !self.ctx.bits.is_aligned() || n % 8 != 0 => {
+                // Spare bits sharing a byte with other packed fields: only they are cleared.
+                self.ctx.bits.write(self.data, &mut self.pos, *n, 0)?;
+            }
             TypeSpec::Padding(kind) => {
                 let byte_len = match kind {
                     PaddingKind::Bytes(n) => *n as usize,
-                    PaddingKind::Bits(n) => ((*n + 7) / 8) as usize,
+                    PaddingKind::Bits(n) => (*n / 8) as usize,
                 };
                 if self.pos + byte_len > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, byte_len, self.data.len()));
                 }
                 self.data[self.pos..self.pos + byte_len].fill(0);
                 self.pos += byte_len;
@@ -711,113 +1412,96 @@ impl<'a> BinaryWalkerMut<'a> {
             TypeSpec::Base(_) | TypeSpec::Bitfield(_) | TypeSpec::SizedInt(_, _) => {
                 self.skip_type_spec(spec, None)?;
             }
-            TypeSpec::LengthOf(_) | TypeSpec::CountOf(_) => {
-                if self.pos + 4 > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
-                }
+            TypeSpec::LengthOf(_, width) | TypeSpec::CountOf(_, width) => {
+                let size = base_type_size(width);
+                let v = read_bytes_to_u64(self.data, &mut self.pos, size, self.endianness)?;
                 if let Some(name) = field_name {
-                    let v = read_u32_slice(self.data, self.pos, self.endianness)?;
-                    self.ctx.set(name.to_string(), v as u64);
+                    self.ctx.set(name.to_string(), v);
                 }
-                self.pos += 4;
+                self.pos += size;
             }
-            TypeSpec::PresenceBits(n) => {
+            TypeSpec::PresenceBits(n, stop) => {
                 let bitmap = read_bitmap_n(self.data, &mut self.pos, self.endianness, *n)?;
-                self.ctx.presence = WalkPresence::Bitmap(bitmap, 0);
+                self.ctx.presence = WalkPresence::Bitmap(presence_bits_logical(bitmap, *stop), 0);
             }
             TypeSpec::BitmapPresence { total_bits, presence_per_block, .. } => {
-                let max_encoded_bits = if *presence_per_block == 0 { *total_bits } else { ((*total_bits + presence_per_block - 1) / presence_per_block) * (presence_per_block + 1) };
-                let max_bytes = ((max_encoded_bits + 7) / 8) as usize;
-                let mut bytes = Vec::new();
-                if *presence_per_block == 0 {
-                    if self.pos + max_bytes > self.data.len() {
-                        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                self.ctx.presence =
+                    read_bitmap_presence(self.data, &mut self.pos, &mut self.ctx.bits, *total_bits, *presence_per_block)?;
+            }
+            TypeSpec::StructRef(name) | TypeSpec::Enum(name) => {
+                if let Some(enum_sec) = self.resolved.get_enum(name) {
+                    let n = base_type_size(&enum_sec.base);
+                    let end = (self.pos + n).min(self.data.len());
+                    if self.pos < end {
+                        self.data[self.pos..end].fill(0);
                     }
-                    bytes.extend_from_slice(&self.data[self.pos..self.pos + max_bytes]);
-                    self.pos += max_bytes;
+                    self.pos += n;
+                } else if let Some(s) = self.resolved.get_struct(name) {
+                    self.zero_struct_fields(s.wire_fields(), own_presence(&s.fields))?;
                 } else {
-                    let block_bits = (presence_per_block + 1) as u8;
-                    let max_blocks = (*total_bits + presence_per_block - 1) / presence_per_block;
-                    let k = *presence_per_block as usize;
-                    if block_bits >= 8 {
-                        for _ in 0..max_blocks {
-                            if self.pos >= self.data.len() {
-                                return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
-                            }
-                            let b = self.data[self.pos];
-                            self.pos += 1;
-                            bytes.push(b);
-                            if b & 0x01 == 0 || bytes.len() >= max_blocks as usize {
-                                break;
-                            }
-                        }
-                    } else {
-                        let mut pos = self.pos;
-                        let mut bit_pos = 0u8;
-                        for _ in 0..max_blocks {
-                            let (b, p, bp) = read_bits_walk(self.data, pos, bit_pos, block_bits)?;
-                            pos = p;
-                            bit_pos = bp;
-                            let mut stored = (b & 1) as u8;
-                            for j in 0..k {
-                                stored |= (((b >> (j + 1)) & 1) as u8) << (7 - j);
-                            }
-                            bytes.push(stored);
-                            if b & 0x01 == 0 || bytes.len() >= max_blocks as usize {
-                                break;
-                            }
-                        }
-                        if bit_pos != 0 {
-                            pos += 1;
-                        }
-                        self.pos = pos;
-                    }
-                    if bytes.len() == max_blocks as usize {
-                        if bytes.last().map(|&b| b & 0x01 != 0).unwrap_or(false) {
-                            return Err(CodecError::Validation(
-                                "bitmap presence: last FSPEC byte must have FX=0 (max size reached)".to_string(),
-                            ));
-                        }
-                    }
+                    let m = self.resolved.get_message(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
+                    // An embedded message's `endianness from` switch ends with it.
+                    let order = self.endianness;
+                    self.zero_padding_reserved_message_fields(m.fields.as_slice())?;
+                    self.endianness = order;
                 }
-                self.ctx.presence = if *presence_per_block == 0 {
-                    WalkPresence::BitmapPresenceConsecutive(bytes, 0, 0)
-                } else {
-                    WalkPresence::BitmapPresence(bytes, 0, *presence_per_block)
-                };
             }
-            TypeSpec::StructRef(name) => {
-                if self.resolved.get_enum(name).is_some() {
-                    if self.pos < self.data.len() {
-                        self.data[self.pos] = 0;
-                    }
-                    self.pos += 1;
+            TypeSpec::Select { field, cases, default } => {
+                let name = self.ctx.select(field, cases, default.as_ref())?;
+                if let Some(s) = self.resolved.get_struct(name) {
+                    self.zero_struct_fields(s.wire_fields(), own_presence(&s.fields))?;
                 } else {
-                    let s = self.resolved.get_struct(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
-                    for f in &s.fields {
-                        if let Some(ref cond) = f.condition {
-                            let cond_val = self.ctx.get(cond.field.as_str()).map(|u| u as i64);
-                            let expected = cond.value.as_i64();
-                            if cond_val != expected {
-                                continue;
-                            }
-                        }
-                        self.zero_or_skip_type_spec(&f.type_spec, Some(&f.name))?;
-                    }
+                    let m = self.resolved.get_message(name).ok_or_else(|| CodecError::UnknownStruct(name.to_string()))?;
+                    // An embedded message's `endianness from` switch ends with it.
+                    let order = self.endianness;
+                    self.zero_padding_reserved_message_fields(m.fields.as_slice())?;
+                    self.endianness = order;
                 }
             }
-            TypeSpec::Array(elem, len) => {
+            TypeSpec::Bits(members) => {
+                if !self.ctx.bits.is_aligned() {
+                    return Err(CodecError::Validation("bits { ... }: must start on a byte boundary".to_string()));
+                }
+                let n = (members.iter().map(|(_, w)| w).sum::<u64>() / 8) as usize;
+                if self.pos + n > self.data.len() {
+                    return Err(truncated(self.pos, n, self.data.len()));
+                }
+                self.pos += n;
+            }
+            TypeSpec::DynInt(width_field) => {
+                let n = self.ctx.dyn_int_len(width_field)?;
+                if self.pos + n > self.data.len() {
+                    return Err(truncated(self.pos, n, self.data.len()));
+                }
+                self.pos += n;
+            }
+            TypeSpec::Group { count, fields } => {
+                let n = match count {
+                    ArrayLen::Constant(k) => *k,
+                    ArrayLen::FieldRef(field) => self.ctx.get(field).ok_or_else(|| CodecError::UnknownField(field.clone()))?,
+                };
+                for _ in 0..n {
+                    self.zero_struct_fields(fields.iter(), own_presence(fields))?;
+                }
+            }
+            TypeSpec::Array(elem, len, stride) => {
                 let n = match len {
                     ArrayLen::Constant(k) => *k,
                     ArrayLen::FieldRef(field) => self.ctx.get(field).ok_or_else(|| CodecError::UnknownField(field.clone()))?,
                 };
                 for _ in 0..n {
+                    let start = self.pos;
                     self.zero_or_skip_type_spec(elem, None)?;
+                    if let Some(stride) = stride {
+                        let end = skip_stride_padding(self.data.len(), start, self.pos, *stride)?;
+                        self.data[self.pos..end].fill(0);
+                        self.pos = end;
+                    }
                 }
             }
             TypeSpec::List(elem) => {
                 if self.pos + 4 > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, 4, self.data.len()));
                 }
                 let n = read_u32_slice(self.data, self.pos, self.endianness)?;
                 self.pos += 4;
@@ -827,7 +1511,7 @@ impl<'a> BinaryWalkerMut<'a> {
             }
             TypeSpec::RepList(elem) => {
                 if self.pos + 1 > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, 1, self.data.len()));
                 }
                 let n = self.data[self.pos] as usize;
                 self.pos += 1;
@@ -835,6 +1519,46 @@ impl<'a> BinaryWalkerMut<'a> {
                     self.zero_or_skip_type_spec(elem, None)?;
                 }
             }
+            TypeSpec::LengthPrefixedList(elem, len_type) => {
+                let n = read_bytes_to_u64(self.data, &mut self.pos, 4, self.endianness)?;
+                self.pos += 4;
+                let size = base_type_size(len_type);
+                for i in 0..n {
+                    let len = read_bytes_to_u64(self.data, &mut self.pos, size, self.endianness)? as usize;
+                    self.pos += size;
+                    if len > self.data.len() - self.pos {
+                        return Err(truncated(self.pos, len, self.data.len()));
+                    }
+                    let end = self.pos + len;
+                    self.zero_or_skip_type_spec(elem, None)?;
+                    if self.pos != end {
+                        return Err(CodecError::Validation(format!(
+                            "length_prefixed_list item {}/{}: length is {} but the element takes {} bytes",
+                            i + 1,
+                            n,
+                            len,
+                            self.pos as isize - (end - len) as isize
+                        )));
+                    }
+                }
+            }
+            TypeSpec::BytesList(elem, len_field) => {
+                let end = skip_bytes_list(self.data.len(), self.pos, self.ctx.get(len_field), len_field)?;
+                while self.pos < end {
+                    let before = self.pos;
+                    self.zero_or_skip_type_spec(elem, None)?;
+                    if self.pos == before {
+                        return Err(CodecError::Validation(format!("bytes_list({}): element takes no bytes", len_field)));
+                    }
+                }
+                if self.pos != end {
+                    return Err(CodecError::Validation(format!(
+                        "bytes_list({}): elements overrun the byte length by {} bytes",
+                        len_field,
+                        self.pos - end
+                    )));
+                }
+            }
             TypeSpec::OctetsFx => {
                 while self.pos < self.data.len() {
                     let b = self.data[self.pos];
@@ -844,7 +1568,7 @@ impl<'a> BinaryWalkerMut<'a> {
                     }
                 }
             }
-            TypeSpec::Optional(elem) => {
+            TypeSpec::Optional(elem, flag) => {
                 let present = match &mut self.ctx.presence {
                     WalkPresence::Bitmap(bitmap, i) => {
                         let bit = (*bitmap >> *i) & 1;
@@ -869,12 +1593,33 @@ impl<'a> BinaryWalkerMut<'a> {
                         let bit = if byte_idx < bytes.len() { (bytes[byte_idx] >> (7 - bit_idx)) & 1 } else { 0 };
                         bit != 0
                     }
-                    WalkPresence::None => read_u8(self.data, &mut self.pos)? != 0,
+                    WalkPresence::None => read_presence_flag(self.data, &mut self.pos, flag, self.endianness)?,
                 };
                 if present {
                     self.zero_or_skip_type_spec(elem, None)?;
                 }
             }
+            TypeSpec::Timestamp { base, .. } => {
+                self.skip_type_spec(base, None)?;
+            }
+            TypeSpec::Bcd { .. } | TypeSpec::ReverseBits(_) | TypeSpec::Unknown(_) | TypeSpec::FixedString(_) | TypeSpec::Checksum { .. } => {
+                let n = type_spec_fixed_bytes(spec).unwrap_or(0);
+                if self.pos + n > self.data.len() {
+                    return Err(truncated(self.pos, n, self.data.len()));
+                }
+                self.pos += n;
+            }
+            TypeSpec::Remaining => {
+                self.pos = self.data.len();
+            }
+            TypeSpec::CString => {
+                let rest = &self.data[self.pos.min(self.data.len())..];
+                let len = rest.iter().position(|&b| b == 0).ok_or_else(|| truncated(self.pos, rest.len() + 1, self.data.len()))?;
+                self.pos += len + 1;
+            }
+            TypeSpec::Custom(name) => {
+                self.pos += self.skip_custom(name)?;
+            }
         }
         Ok(())
     }
@@ -884,149 +1629,142 @@ impl<'a> BinaryWalkerMut<'a> {
             TypeSpec::Base(bt) => {
                 let n = base_type_size(bt);
                 if self.pos + n > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, n, self.data.len()));
                 }
                 self.pos += n;
             }
-            TypeSpec::Padding(kind) => {
+            TypeSpec::Padding(PaddingKind::Bytes(n)) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("Padding");
-                let byte_len = match kind {
-                    PaddingKind::Bytes(n) => *n as usize,
-                    PaddingKind::Bits(n) => ((*n + 7) / 8) as usize,
-                };
+                let byte_len = *n as usize;
                 if self.pos + byte_len > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, byte_len, self.data.len()));
                 }
                 self.pos += byte_len;
             }
+            TypeSpec::Padding(PaddingKind::Bits(n)) => {
+                #[cfg(feature = "walk_profile")]
+                let _g = ProfileGuard::new("Padding");
+                self.ctx.bits.read(self.data, &mut self.pos, *n)?;
+            }
             TypeSpec::Bitfield(n) | TypeSpec::SizedInt(_, n) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("BitfieldSizedInt");
-                self.pos += ((*n + 7) / 8) as usize;
+                if matches!(spec, TypeSpec::Bitfield(_)) || *n < 8 || !self.ctx.bits.is_aligned() {
+                    self.ctx.bits.read(self.data, &mut self.pos, *n)?;
+                } else {
+                    let byte_len = n.div_ceil(8) as usize;
+                    if self.pos + byte_len > self.data.len() {
+                        return Err(truncated(self.pos, byte_len, self.data.len()));
+                    }
+                    self.pos += byte_len;
+                }
             }
-            TypeSpec::LengthOf(_) | TypeSpec::CountOf(_) => {
+            TypeSpec::LengthOf(_, width) | TypeSpec::CountOf(_, width) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("LengthOfCountOf");
-                if self.pos + 4 > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
-                }
+                let size = base_type_size(width);
+                let v = read_bytes_to_u64(self.data, &mut self.pos, size, self.endianness)?;
                 if let Some(name) = field_name {
-                    let v = read_u32_slice(self.data, self.pos, self.endianness)?;
-                    self.ctx.set(name.to_string(), v as u64);
+                    self.ctx.set(name.to_string(), v);
                 }
-                self.pos += 4;
+                self.pos += size;
             }
-            TypeSpec::PresenceBits(n) => {
+            TypeSpec::PresenceBits(n, stop) => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("PresenceBits");
                 let bitmap = read_bitmap_n(self.data, &mut self.pos, self.endianness, *n)?;
-                self.ctx.presence = WalkPresence::Bitmap(bitmap, 0);
+                self.ctx.presence = WalkPresence::Bitmap(presence_bits_logical(bitmap, *stop), 0);
             }
             TypeSpec::BitmapPresence { total_bits, presence_per_block, .. } => {
                 #[cfg(feature = "walk_profile")]
                 let _g = ProfileGuard::new("BitmapPresence");
-                let max_encoded_bits = if *presence_per_block == 0 { *total_bits } else { ((*total_bits + presence_per_block - 1) / presence_per_block) * (presence_per_block + 1) };
-                let max_bytes = ((max_encoded_bits + 7) / 8) as usize;
-                let mut bytes = Vec::new();
-                if *presence_per_block == 0 {
-                    if self.pos + max_bytes > self.data.len() {
-                        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
-                    }
-                    bytes.extend_from_slice(&self.data[self.pos..self.pos + max_bytes]);
-                    self.pos += max_bytes;
-                } else {
-                    let block_bits = (presence_per_block + 1) as u8;
-                    let max_blocks = (*total_bits + presence_per_block - 1) / presence_per_block;
-                    let k = *presence_per_block as usize;
-                    if block_bits >= 8 {
-                        for _ in 0..max_blocks {
-                            if self.pos >= self.data.len() {
-                                return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
-                            }
-                            let b = self.data[self.pos];
-                            self.pos += 1;
-                            bytes.push(b);
-                            if b & 0x01 == 0 || bytes.len() >= max_blocks as usize {
-                                break;
-                            }
-                        }
-                    } else {
-                        let mut pos = self.pos;
-                        let mut bit_pos = 0u8;
-                        for _ in 0..max_blocks {
-                            let (b, p, bp) = read_bits_walk(self.data, pos, bit_pos, block_bits)?;
-                            pos = p;
-                            bit_pos = bp;
-                            let mut stored = (b & 1) as u8;
-                            for j in 0..k {
-                                stored |= (((b >> (j + 1)) & 1) as u8) << (7 - j);
-                            }
-                            bytes.push(stored);
-                            if b & 0x01 == 0 || bytes.len() >= max_blocks as usize {
-                                break;
-                            }
-                        }
-                        if bit_pos != 0 {
-                            pos += 1;
-                        }
-                        self.pos = pos;
-                    }
-                    if bytes.len() == max_blocks as usize {
-                        if bytes.last().map(|&b| b & 0x01 != 0).unwrap_or(false) {
-                            return Err(CodecError::Validation(
-                                "bitmap presence: last FSPEC byte must have FX=0 (max size reached)".to_string(),
-                            ));
-                        }
-                    }
+                self.ctx.presence =
+                    read_bitmap_presence(self.data, &mut self.pos, &mut self.ctx.bits, *total_bits, *presence_per_block)?;
+            }
+            TypeSpec::Enum(name) => {
+                // Any value is valid: one no variant has decodes with no variant name.
+                let e = self.resolved.get_enum(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
+                let n = base_type_size(&e.base);
+                if self.pos + n > self.data.len() {
+                    return Err(truncated(self.pos, n, self.data.len()));
                 }
-                self.ctx.presence = if *presence_per_block == 0 {
-                    WalkPresence::BitmapPresenceConsecutive(bytes, 0, 0)
-                } else {
-                    WalkPresence::BitmapPresence(bytes, 0, *presence_per_block)
-                };
+                self.pos += n;
             }
             TypeSpec::StructRef(name) => {
-                if self.resolved.get_enum(name).is_some() {
-                    if self.pos + 1 > self.data.len() {
-                        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
-                    }
-                    let byte = self.data[self.pos] as i64;
-                    let enum_sec = self.resolved.get_enum(name).unwrap();
-                    let ok = enum_sec.variants.iter().any(|(_, lit)| lit.as_i64() == Some(byte));
+                if let Some(enum_sec) = self.resolved.get_enum(name) {
+                    let raw = read_i64_slice(self.data, &mut self.pos, &TypeSpec::Base(enum_sec.base.clone()), self.endianness)?;
+                    let ok = enum_sec.variants.iter().any(|(_, lit)| lit.as_i64() == Some(raw));
                     if !ok {
                         return Err(CodecError::Validation(format!(
                             "enum {}: value {} not in allowed set",
-                            name, byte
+                            name, raw
                         )));
                     }
-                    self.pos += 1;
+                } else if let Some(s) = self.resolved.get_struct(name) {
+                    self.skip_struct_fields(s.wire_fields(), own_presence(&s.fields))?;
                 } else {
-                    let s = self.resolved.get_struct(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
-                    for f in &s.fields {
-                        if let Some(ref cond) = f.condition {
-                            let cond_val = self.ctx.get(cond.field.as_str()).map(|u| u as i64);
-                            let expected = cond.value.as_i64();
-                            if cond_val != expected {
-                                continue;
-                            }
-                        }
-                        self.skip_type_spec(&f.type_spec, Some(&f.name))?;
-                    }
+                    let m = self.resolved.get_message(name).ok_or_else(|| CodecError::UnknownStruct(name.clone()))?;
+                    // An embedded message's `endianness from` switch ends with it.
+                    let order = self.endianness;
+                    self.skip_message_fields(m.fields.as_slice())?;
+                    self.endianness = order;
+                }
+            }
+            TypeSpec::Select { field, cases, default } => {
+                let name = self.ctx.select(field, cases, default.as_ref())?;
+                if let Some(s) = self.resolved.get_struct(name) {
+                    self.skip_struct_fields(s.wire_fields(), own_presence(&s.fields))?;
+                } else {
+                    let m = self.resolved.get_message(name).ok_or_else(|| CodecError::UnknownStruct(name.to_string()))?;
+                    // An embedded message's `endianness from` switch ends with it.
+                    let order = self.endianness;
+                    self.skip_message_fields(m.fields.as_slice())?;
+                    self.endianness = order;
+                }
+            }
+            TypeSpec::Bits(members) => {
+                if !self.ctx.bits.is_aligned() {
+                    return Err(CodecError::Validation("bits { ... }: must start on a byte boundary".to_string()));
+                }
+                let n = (members.iter().map(|(_, w)| w).sum::<u64>() / 8) as usize;
+                if self.pos + n > self.data.len() {
+                    return Err(truncated(self.pos, n, self.data.len()));
+                }
+                self.pos += n;
+            }
+            TypeSpec::DynInt(width_field) => {
+                let n = self.ctx.dyn_int_len(width_field)?;
+                if self.pos + n > self.data.len() {
+                    return Err(truncated(self.pos, n, self.data.len()));
                 }
+                self.pos += n;
             }
-            TypeSpec::Array(elem, len) => {
+            TypeSpec::Group { count, fields } => {
+                let n = match count {
+                    ArrayLen::Constant(k) => *k,
+                    ArrayLen::FieldRef(field) => self.ctx.get(field).ok_or_else(|| CodecError::UnknownField(field.clone()))?,
+                };
+                for _ in 0..n {
+                    self.skip_struct_fields(fields.iter(), own_presence(fields))?;
+                }
+            }
+            TypeSpec::Array(elem, len, stride) => {
                 let n = match len {
                     ArrayLen::Constant(k) => *k,
                     ArrayLen::FieldRef(field) => self.ctx.get(field).ok_or_else(|| CodecError::UnknownField(field.clone()))?,
                 };
                 for _ in 0..n {
+                    let start = self.pos;
                     self.skip_type_spec(elem, None)?;
+                    if let Some(stride) = stride {
+                        self.pos = skip_stride_padding(self.data.len(), start, self.pos, *stride)?;
+                    }
                 }
             }
             TypeSpec::List(elem) => {
                 if self.pos + 4 > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, 4, self.data.len()));
                 }
                 let n = read_u32_slice(self.data, self.pos, self.endianness)?;
                 self.pos += 4;
@@ -1034,9 +1772,15 @@ impl<'a> BinaryWalkerMut<'a> {
                     self.skip_type_spec(elem, None)?;
                 }
             }
+            TypeSpec::LengthPrefixedList(_, len_type) => {
+                skip_length_prefixed_list(self.data, &mut self.pos, len_type, self.endianness)?;
+            }
+            TypeSpec::BytesList(_, len_field) => {
+                self.pos = skip_bytes_list(self.data.len(), self.pos, self.ctx.get(len_field), len_field)?;
+            }
             TypeSpec::RepList(elem) => {
                 if self.pos + 1 > self.data.len() {
-                    return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+                    return Err(truncated(self.pos, 1, self.data.len()));
                 }
                 let n = self.data[self.pos] as u32;
                 self.pos += 1;
@@ -1053,7 +1797,7 @@ impl<'a> BinaryWalkerMut<'a> {
                     }
                 }
             }
-            TypeSpec::Optional(elem) => {
+            TypeSpec::Optional(elem, flag) => {
                 let present = match &mut self.ctx.presence {
                     WalkPresence::Bitmap(bitmap, i) => {
                         let bit = (*bitmap >> *i) & 1;
@@ -1078,17 +1822,70 @@ impl<'a> BinaryWalkerMut<'a> {
                         let bit = if byte_idx < bytes.len() { (bytes[byte_idx] >> (7 - bit_idx)) & 1 } else { 0 };
                         bit != 0
                     }
-                    WalkPresence::None => read_u8(self.data, &mut self.pos)? != 0,
+                    WalkPresence::None => read_presence_flag(self.data, &mut self.pos, flag, self.endianness)?,
                 };
                 if present {
                     self.skip_type_spec(elem, None)?;
                 }
             }
+            TypeSpec::Timestamp { base, .. } => {
+                self.skip_type_spec(base, None)?;
+            }
+            TypeSpec::Bcd { .. } | TypeSpec::ReverseBits(_) | TypeSpec::Unknown(_) | TypeSpec::FixedString(_) | TypeSpec::Checksum { .. } => {
+                let n = type_spec_fixed_bytes(spec).unwrap_or(0);
+                if self.pos + n > self.data.len() {
+                    return Err(truncated(self.pos, n, self.data.len()));
+                }
+                self.pos += n;
+            }
+            TypeSpec::Remaining => {
+                self.pos = self.data.len();
+            }
+            TypeSpec::CString => {
+                let rest = &self.data[self.pos.min(self.data.len())..];
+                let len = rest.iter().position(|&b| b == 0).ok_or_else(|| truncated(self.pos, rest.len() + 1, self.data.len()))?;
+                self.pos += len + 1;
+            }
+            TypeSpec::Custom(name) => {
+                self.pos += self.skip_custom(name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Struct body (`fields` in wire order) or one repetition of a `repeat(n) { ... }` group.
+    fn skip_struct_fields<'f>(
+        &mut self,
+        fields: impl Iterator<Item = &'f StructField>,
+        own_presence: bool,
+    ) -> Result<(), CodecError> {
+        let saved_bits = std::mem::take(&mut self.ctx.bits);
+        let saved_presence = own_presence.then(|| self.ctx.presence.clone());
+        for f in fields {
+            if let Some(ref cond) = f.condition {
+                let cond_val = self.ctx.get(cond.field.as_str()).map(|u| u as i64);
+                let expected = cond.value.as_i64();
+                if cond_val != expected {
+                    continue;
+                }
+            }
+            if f.referenced_by_condition || f.referenced_by_width {
+                self.record_condition_value(&f.name, &f.type_spec);
+            }
+            match self.ctx.struct_constraint(f) {
+                Some(c) => self.validate_field_and_skip(&f.name, &f.type_spec, c)?,
+                None => self.skip_type_spec(&f.type_spec, Some(&f.name))?,
+            }
+        }
+        self.ctx.bits = saved_bits;
+        if let Some(presence) = saved_presence {
+            self.ctx.presence = presence;
         }
         Ok(())
     }
 
     fn skip_message_fields(&mut self, fields: &[MessageField]) -> Result<(), CodecError> {
+        let saved_bits = std::mem::take(&mut self.ctx.bits);
         for f in fields {
             if let Some(ref cond) = f.condition {
                 let cond_val = self.ctx.get(cond.field.as_str()).map(|u| u as i64);
@@ -1097,8 +1894,16 @@ impl<'a> BinaryWalkerMut<'a> {
                     continue;
                 }
             }
+            let switch = endianness_switch(self.resolved, &f.name);
+            if switch.is_some() || f.referenced_by_width || f.referenced_by_selector {
+                self.record_condition_value(&f.name, &f.type_spec);
+            }
             self.skip_type_spec(&f.type_spec, Some(&f.name))?;
+            if let Some(switch) = switch {
+                self.endianness = switched_endianness(switch, self.ctx.get(&f.name))?;
+            }
         }
+        self.ctx.bits = saved_bits;
         Ok(())
     }
 }
@@ -1122,6 +1927,76 @@ pub fn message_extent(
     w.skip_message(message_name)
 }
 
+/// Returns the byte extent of one record and the message type chosen for it by the payload selector.
+///
+/// Reads the transport fields from `transport_bytes` up to the last selector key field, maps the key to a
+/// message with [`ResolvedProtocol::message_for_transport_values`] semantics, then walks that message
+/// from `start` like [`message_extent`]. The built-in `RawRecord` default consumes the rest of `data`.
+/// Fails if there is no selector, the transport is too short, or the value has no mapping.
+pub fn record_extent<'r>(
+    data: &[u8],
+    start: usize,
+    resolved: &'r ResolvedProtocol,
+    endianness: Endianness,
+    transport_bytes: &[u8],
+) -> Result<(usize, &'r str), CodecError> {
+    let sel = resolved
+        .protocol
+        .payload
+        .as_ref()
+        .and_then(|p| p.selector.as_ref())
+        .ok_or_else(|| CodecError::Validation("record extent: no payload selector".to_string()))?;
+    let transport = resolved
+        .protocol
+        .transport
+        .as_ref()
+        .ok_or_else(|| CodecError::UnknownField(sel.transport_field.clone()))?;
+    let key_len = sel.extra_fields.len() + 1;
+    let mut pos = 0;
+    let mut key: Vec<(&str, i64)> = Vec::with_capacity(key_len);
+    for f in &transport.fields {
+        let spec = match &f.type_spec {
+            TransportTypeSpec::Base(bt) => TypeSpec::Base(bt.clone()),
+            TransportTypeSpec::SizedInt(bt, n) => TypeSpec::SizedInt(bt.clone(), *n),
+            TransportTypeSpec::Bitfield(n) => TypeSpec::Bitfield(*n),
+            TransportTypeSpec::Padding(kind) => {
+                pos += match kind {
+                    PaddingKind::Bytes(n) => *n as usize,
+                    PaddingKind::Bits(n) => n.div_ceil(8) as usize,
+                };
+                continue;
+            }
+            TransportTypeSpec::Magic(bytes) => {
+                pos += bytes.len();
+                continue;
+            }
+        };
+        let v = read_i64_slice(transport_bytes, &mut pos, &spec, endianness)?;
+        if sel.key_fields().any(|k| k == f.name) {
+            key.push((f.name.as_str(), v));
+            if key.len() == key_len {
+                break;
+            }
+        }
+    }
+    if let Some(missing) = sel.key_fields().find(|k| !key.iter().any(|(name, _)| name == k)) {
+        return Err(CodecError::UnknownField(missing.to_string()));
+    }
+    let message_name = sel
+        .lookup(|k| key.iter().find(|(name, _)| *name == k).map(|(_, v)| *v))
+        .map(|(name, _)| name)
+        .or(sel.default_message.as_deref())
+        .ok_or_else(|| {
+            let values: Vec<i64> = sel.key_fields().filter_map(|k| key.iter().find(|(name, _)| *name == k).map(|(_, v)| *v)).collect();
+            CodecError::Validation(format!("record extent: no message for {} = {}", sel.key_text(), selector_key_text(&values)))
+        })?;
+    if message_name == RAW_RECORD && resolved.get_message(message_name).is_none() {
+        return Ok((data.len().saturating_sub(start), message_name));
+    }
+    let extent = message_extent(data, start, resolved, endianness, message_name)?;
+    Ok((extent, message_name))
+}
+
 /// Validates a message in place by reading only constrained fields and checking ranges/enums.
 ///
 /// Walks the message from `start` and verifies every field that has a `[min..max]` or
@@ -1139,6 +2014,229 @@ pub fn validate_message_in_place(
     w.validate_message(message_name)
 }
 
+/// Drop-fast validation: walks the message from `start` and stops at the first failed constraint.
+///
+/// Returns `Ok(None)` if the message is valid and `Ok(Some(violation))` with the failing field,
+/// its byte offset in `data`, the raw value and the constraint otherwise. `Err` means the message
+/// could not be walked (e.g. [`CodecError::Truncated`](crate::codec::CodecError::Truncated)).
+pub fn validate_message_first_error(
+    data: &[u8],
+    start: usize,
+    resolved: &ResolvedProtocol,
+    endianness: Endianness,
+    message_name: &str,
+) -> Result<Option<ConstraintViolation>, CodecError> {
+    let mut w = BinaryWalker::at(data, start, resolved, endianness);
+    w.first_violation(message_name)
+}
+
+/// Batch validation of a message made of fixed-size fields and `list`/`rep_list`s of fixed-size structs
+/// (e.g. a track list of `{ lat: i32 [..]; lon: i32 [..]; alt: u16 [..] }`); returns bytes consumed.
+///
+/// Instead of walking field by field, each list is bounds-checked once and every constrained struct field
+/// is checked as a column over all elements (one tight loop per column), which is much faster on long
+/// homogeneous lists. Unlike [`validate_message_in_place`], the element structs' constraints are checked,
+/// as decode does. Messages of any other shape (conditions, optionals, bit fields, `when` clauses, enum
+/// constraints, variable-size elements) fall back to [`validate_message_in_place`].
+pub fn validate_fixed_record_list(
+    data: &[u8],
+    start: usize,
+    resolved: &ResolvedProtocol,
+    endianness: Endianness,
+    message_name: &str,
+) -> Result<usize, CodecError> {
+    let msg = resolved.get_message(message_name).ok_or_else(|| CodecError::UnknownStruct(message_name.to_string()))?;
+    let Some(steps) = fixed_record_steps(resolved, msg) else {
+        let mut w = BinaryWalker::at(data, start, resolved, endianness);
+        w.validate_message(message_name)?;
+        return Ok(w.position() - start);
+    };
+    let mut pos = start;
+    for step in &steps {
+        let (n, layout, field) = match step {
+            FixedStep::Record(layout) => (1, layout, None),
+            FixedStep::List { field, rep, layout } => {
+                let n = if *rep {
+                    read_u8(data, &mut pos)? as usize
+                } else {
+                    let n = read_u32_slice(data, pos, endianness)? as usize;
+                    pos += 4;
+                    n
+                };
+                (n, layout, Some(field.as_str()))
+            }
+        };
+        let len = n.saturating_mul(layout.stride);
+        if pos.saturating_add(len) > data.len() {
+            return Err(truncated(pos, len, data.len()));
+        }
+        let block = &data[pos..pos + len];
+        for col in &layout.columns {
+            if let Some((i, value)) = column_violation(block, layout.stride, col, endianness) {
+                let at = match field {
+                    Some(list) => format!("{}[{}].{}", list, i, col.name),
+                    None => col.name.clone(),
+                };
+                return Err(CodecError::Validation(format!(
+                    "{}: value {} not in any interval {:?}",
+                    at, value, col.intervals
+                )));
+            }
+        }
+        pos += len;
+    }
+    Ok(pos - start)
+}
+
+/// Range-constrained field at a fixed byte offset of a fixed-size record.
+struct FixedColumn {
+    name: String,
+    offset: usize,
+    width: usize,
+    signed: bool,
+    intervals: Vec<(i64, i64)>,
+}
+
+/// Fixed-size record: its size and the fields that need a range check.
+#[derive(Default)]
+struct FixedLayout {
+    stride: usize,
+    columns: Vec<FixedColumn>,
+}
+
+enum FixedStep {
+    /// Consecutive fixed-size message fields, checked as one record.
+    Record(FixedLayout),
+    /// `list<S>` (u32 count) or `rep_list<S>` (u8 count) of a fixed-size struct `S`.
+    List { field: String, rep: bool, layout: FixedLayout },
+}
+
+/// Batch plan for `msg`, or None if it does not have the shape [`validate_fixed_record_list`] handles.
+fn fixed_record_steps(resolved: &ResolvedProtocol, msg: &MessageSection) -> Option<Vec<FixedStep>> {
+    if msg.selector.is_some() {
+        return None;
+    }
+    let mut steps = Vec::new();
+    let mut record = FixedLayout::default();
+    for f in &msg.fields {
+        if f.condition.is_some() || !f.conditional_constraints.is_empty() {
+            return None;
+        }
+        let elem = match &f.type_spec {
+            TypeSpec::List(elem) => Some((elem, false)),
+            TypeSpec::RepList(elem) => Some((elem, true)),
+            _ => None,
+        };
+        match elem {
+            Some((elem, rep)) => {
+                let TypeSpec::StructRef(name) = elem.as_ref() else { return None };
+                let s = resolved.get_struct(name)?;
+                let mut layout = FixedLayout::default();
+                for sf in s.wire_fields() {
+                    if sf.condition.is_some() || !sf.conditional_constraints.is_empty() {
+                        return None;
+                    }
+                    push_fixed_field(resolved, &mut layout, &sf.name, &sf.type_spec, sf.constraint.as_ref())?;
+                }
+                if layout.stride == 0 {
+                    return None;
+                }
+                if record.stride > 0 {
+                    steps.push(FixedStep::Record(std::mem::take(&mut record)));
+                }
+                steps.push(FixedStep::List { field: f.name.clone(), rep, layout });
+            }
+            None => {
+                let constraint = if f.saturating { None } else { f.constraint.as_ref() };
+                push_fixed_field(resolved, &mut record, &f.name, &f.type_spec, constraint)?;
+            }
+        }
+    }
+    if record.stride > 0 {
+        steps.push(FixedStep::Record(record));
+    }
+    Some(steps)
+}
+
+/// Appends a byte-aligned fixed-size field to `layout` (a column if it has a range constraint).
+fn push_fixed_field(
+    resolved: &ResolvedProtocol,
+    layout: &mut FixedLayout,
+    name: &str,
+    spec: &TypeSpec,
+    constraint: Option<&Constraint>,
+) -> Option<()> {
+    let (width, signed, numeric) = fixed_field_width(resolved, spec)?;
+    match constraint {
+        None => {}
+        Some(Constraint::Range(intervals)) if numeric => layout.columns.push(FixedColumn {
+            name: name.to_string(),
+            offset: layout.stride,
+            width,
+            signed,
+            intervals: intervals.clone(),
+        }),
+        Some(_) => return None,
+    }
+    layout.stride += width;
+    Some(())
+}
+
+/// (bytes, signed, integer) of a field whose size never depends on the data and that starts and ends on a byte.
+fn fixed_field_width(resolved: &ResolvedProtocol, spec: &TypeSpec) -> Option<(usize, bool, bool)> {
+    let signed = |bt: &BaseType| matches!(bt, BaseType::I8 | BaseType::I16 | BaseType::I32 | BaseType::I64);
+    match spec {
+        TypeSpec::Base(bt) => Some((base_type_size(bt), signed(bt), !matches!(bt, BaseType::Float | BaseType::Double))),
+        TypeSpec::SizedInt(bt, n) if *n > 0 && *n <= 64 && n % 8 == 0 => Some(((n / 8) as usize, signed(bt), true)),
+        TypeSpec::Padding(PaddingKind::Bytes(n)) | TypeSpec::Unknown(n) | TypeSpec::FixedString(n) => Some((*n as usize, false, false)),
+        TypeSpec::Checksum { algorithm, .. } => Some((algorithm.size(), false, true)),
+        TypeSpec::Timestamp { base, .. } => fixed_field_width(resolved, base),
+        TypeSpec::StructRef(name) | TypeSpec::Enum(name) => {
+            let e = resolved.get_enum(name)?;
+            Some((base_type_size(&e.base), signed(&e.base), false))
+        }
+        _ => None,
+    }
+}
+
+/// First record of `block` (records of `stride` bytes) whose `col` value is outside its intervals.
+fn column_violation(block: &[u8], stride: usize, col: &FixedColumn, endianness: Endianness) -> Option<(usize, i64)> {
+    let big = matches!(endianness, Endianness::Big);
+    match col.width {
+        1 => scan_column::<1>(block, stride, col, big),
+        2 => scan_column::<2>(block, stride, col, big),
+        3 => scan_column::<3>(block, stride, col, big),
+        4 => scan_column::<4>(block, stride, col, big),
+        5 => scan_column::<5>(block, stride, col, big),
+        6 => scan_column::<6>(block, stride, col, big),
+        7 => scan_column::<7>(block, stride, col, big),
+        _ => scan_column::<8>(block, stride, col, big),
+    }
+}
+
+/// Column scan with the field width `W` known at compile time, so the loop over records stays branch-light.
+fn scan_column<const W: usize>(block: &[u8], stride: usize, col: &FixedColumn, big: bool) -> Option<(usize, i64)> {
+    let shift = 64 - 8 * W as u32;
+    let off = col.offset;
+    let load = |rec: &[u8]| -> i64 {
+        let mut v = 0u64;
+        for i in 0..W {
+            let b = if big { rec[off + i] } else { rec[off + W - 1 - i] };
+            v = (v << 8) | b as u64;
+        }
+        if col.signed {
+            ((v << shift) as i64) >> shift
+        } else {
+            v as i64
+        }
+    };
+    let mut records = block.chunks_exact(stride).map(load).enumerate();
+    match col.intervals.as_slice() {
+        [(lo, hi)] => records.find(|(_, v)| v < lo || v > hi),
+        intervals => records.find(|(_, v)| !intervals.iter().any(|(lo, hi)| v >= lo && v <= hi)),
+    }
+}
+
 /// Zeros all `padding` (bytes and bits) fields in the given message range, in place.
 ///
 /// Walks the message from `start` and sets every padding byte (or bit span) to 0. Useful before
@@ -1171,7 +2269,8 @@ pub fn validate_and_zero_message_in_place(
 /// The range `buffer[start..start+len]` is the message to remove. Bytes after
 /// `start+len` are shifted left to `start`. Returns the new length of the buffer
 /// (`original_len - len`). The caller is responsible for updating any length/count
-/// fields (e.g. with [`write_u32_in_place`]) so the frame remains valid.
+/// fields (e.g. with [`write_u32_in_place`]) so the frame remains valid, or calls
+/// [`remove_message_in_place_updating`] to do both at once.
 pub fn remove_message_in_place(buffer: &mut [u8], start: usize, len: usize) -> usize {
     let end = start + len;
     if end > buffer.len() {
@@ -1184,13 +2283,128 @@ pub fn remove_message_in_place(buffer: &mut [u8], start: usize, len: usize) -> u
     buffer.len() - len
 }
 
+/// What a header counter given to [`remove_message_in_place_updating`] counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterKind {
+    /// Bytes (e.g. a block length): decreased by the removed length.
+    Length,
+    /// Records (e.g. a record count): decreased by one.
+    Count,
+}
+
+/// [`remove_message_in_place`] that also adjusts header counters so the frame stays valid.
+///
+/// Each `(offset, width, kind)` in `counters` is an unsigned integer (`u8`, `u16`, `u32` or `u64`) at `offset`
+/// in the buffer before removal, outside `[start..start+len]`. Every counter is checked (in bounds, no
+/// underflow) before anything is written, so on error the buffer is unchanged. Returns the new length of the
+/// buffer; the caller truncates it.
+pub fn remove_message_in_place_updating(
+    buffer: &mut [u8],
+    start: usize,
+    len: usize,
+    endianness: Endianness,
+    counters: &[(usize, BaseType, CounterKind)],
+) -> Result<usize, CodecError> {
+    let end = start + len;
+    if end > buffer.len() {
+        return Err(truncated(start, len, buffer.len()));
+    }
+    let mut updates = Vec::with_capacity(counters.len());
+    for (offset, width, kind) in counters {
+        if !matches!(width, BaseType::U8 | BaseType::U16 | BaseType::U32 | BaseType::U64) {
+            return Err(CodecError::Validation(format!("counter at {}: {:?} is not an unsigned integer", offset, width)));
+        }
+        let size = base_type_size(width);
+        if *offset < end && offset + size > start {
+            return Err(CodecError::Validation(format!("counter at {} overlaps the removed range", offset)));
+        }
+        let old = read_bytes_to_u64(buffer, &mut offset.clone(), size, endianness)?;
+        let delta = match kind {
+            CounterKind::Length => len as u64,
+            CounterKind::Count => 1,
+        };
+        let new = old
+            .checked_sub(delta)
+            .ok_or_else(|| CodecError::Validation(format!("counter at {}: {} is less than {}", offset, old, delta)))?;
+        updates.push((*offset, size, new));
+    }
+    for (offset, size, value) in updates {
+        match endianness {
+            Endianness::Big => BigEndian::write_uint(&mut buffer[offset..], value, size),
+            Endianness::Little => LittleEndian::write_uint(&mut buffer[offset..], value, size),
+        }
+    }
+    Ok(remove_message_in_place(buffer, start, len))
+}
+
+/// Overwrites one integer field of the message at `start` in place, found as by [`BinaryWalker::read_field`]
+/// (`field_name` may be dotted into a struct). The value is written in the field's width and byte order: a
+/// sized int or bitfield keeps the other bits of its bytes. Fails when the field is absent (condition not met,
+/// presence bit or flag clear), is not an integer, or `value` does not fit in it (signed fields take the
+/// value's two's complement, e.g. `-1i64 as u64`).
+pub fn write_field_in_place(
+    data: &mut [u8],
+    start: usize,
+    resolved: &ResolvedProtocol,
+    endianness: Endianness,
+    message_name: &str,
+    field_name: &str,
+    value: u64,
+) -> Result<(), CodecError> {
+    // The field is written in the byte order in force at it, after any `endianness from` switch.
+    let (mut pos, mut bits, spec, endianness) = {
+        let mut w = BinaryWalker::at(data, start, resolved, endianness);
+        let spec = w.seek_path(message_name, field_name)?.clone();
+        (w.pos, w.ctx.bits, spec, w.endianness)
+    };
+    let spec = match &spec {
+        TypeSpec::StructRef(name) | TypeSpec::Enum(name) => match resolved.get_enum(name) {
+            Some(e) => TypeSpec::Base(e.base.clone()),
+            None => spec,
+        },
+        TypeSpec::LengthOf(_, width) | TypeSpec::CountOf(_, width) => TypeSpec::Base(width.clone()),
+        _ => spec,
+    };
+    let (width, signed) = match &spec {
+        TypeSpec::Base(BaseType::Float | BaseType::Double) => (0, false),
+        TypeSpec::Base(bt) => (base_type_size(bt) as u64 * 8, matches!(bt, BaseType::I8 | BaseType::I16 | BaseType::I32 | BaseType::I64)),
+        TypeSpec::SizedInt(bt, n) => (*n, matches!(bt, BaseType::I8 | BaseType::I16 | BaseType::I32 | BaseType::I64)),
+        TypeSpec::Bitfield(n) => (*n, false),
+        _ => (0, false),
+    };
+    if width == 0 || width > 64 {
+        return Err(CodecError::Validation(format!("{}: not an integer field", field_name)));
+    }
+    let mask = if width == 64 { u64::MAX } else { (1u64 << width) - 1 };
+    let negative_fits = signed && width < 64 && (value as i64) < 0 && (value as i64) >= -(1i64 << (width - 1));
+    if value & !mask != 0 && !negative_fits {
+        return Err(CodecError::Validation(format!("{}: value {} does not fit in {} bits", field_name, value, width)));
+    }
+    let value = value & mask;
+    match spec {
+        TypeSpec::Bitfield(n) => bits.write(data, &mut pos, n, value),
+        TypeSpec::SizedInt(_, n) if n < 8 || !bits.is_aligned() => bits.write(data, &mut pos, n, value),
+        _ => {
+            // Whole bytes; a sized int keeps the bits above its width.
+            let size = width.div_ceil(8) as usize;
+            let old = read_bytes_to_u64(data, &mut pos.clone(), size, endianness)?;
+            let raw = (old & !mask) | value;
+            match endianness {
+                Endianness::Big => BigEndian::write_uint(&mut data[pos..], raw, size),
+                Endianness::Little => LittleEndian::write_uint(&mut data[pos..], raw, size),
+            }
+            Ok(())
+        }
+    }
+}
+
 /// Writes a `u32` at the given offset (4 bytes) with the given endianness.
 ///
 /// Typical use: after [`remove_message_in_place`], update a length or count field
 /// in the frame header so the remaining buffer describes the new size.
 pub fn write_u32_in_place(buffer: &mut [u8], offset: usize, value: u32, endianness: Endianness) -> Result<(), CodecError> {
     if offset + 4 > buffer.len() {
-        return Err(CodecError::Io(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)));
+        return Err(truncated(offset, 4, buffer.len()));
     }
     match endianness {
         Endianness::Big => BigEndian::write_u32(&mut buffer[offset..], value),
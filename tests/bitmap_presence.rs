@@ -55,6 +55,7 @@
 //! | `bitmap_presence_encode_bit_order_optional_1` | Optional 1 → bit 6 (0x40) |
 //! | `bitmap_presence_roundtrip_*` | Encode then decode preserves presence and values |
 //! | `bitmap_presence_decode_reject_last_fx1_at_max_size` | Max-size FSPEC with last byte FX=1 → validation error |
+//! | `bitmap_presence_strict_fspec_rejects_padded` | With `strict_fspec`, a trailing all-zero block (0x81 0x00) → validation error |
 //!
 //! ### bitmap(14, 3) — 3 presence bits + 1 FX per byte, max 5 bytes
 //!
@@ -65,9 +66,12 @@
 //! | `bitmap_14_3_encode_*` | Encode all absent / first present (FSPEC may be truncated to max_bytes) |
 //! | `bitmap_14_3_decode_reject_last_fx1_at_max_size` | 5 bytes with last FX=1 → validation error |
 //! | `bitmap_14_3_roundtrip_four_present` | Roundtrip with first 4 optionals present |
+//! | `optional_accessors_on_decoded_and_encode_maps` | `optional_present` / `optional_value` read both map forms |
+//! | `encode_message_present_uses_named_fields` | `encode_message_present` sets exactly the named presence bits |
+//! | `decode_message_with_presence_reports_bits` | `decode_message_with_presence` tells an absent optional from a present empty list |
 
 use aiprotodsl::codec::{Codec, CodecError, Endianness};
-use aiprotodsl::{parse, ResolvedProtocol, Value};
+use aiprotodsl::{optional_present, optional_value, parse, ResolvedProtocol, Value};
 use std::collections::HashMap;
 
 // -----------------------------------------------------------------------------
@@ -138,15 +142,11 @@ fn resolve(proto: &str) -> ResolvedProtocol {
 
 /// Optional fields: when present the codec returns the inner value (e.g. `Value::U8(x)`); when absent, `Value::List(vec![])`.
 fn optional_u8(decoded: &HashMap<String, Value>, name: &str) -> Option<u8> {
-    match decoded.get(name) {
-        Some(Value::U8(x)) => Some(*x),
-        Some(Value::List(l)) if !l.is_empty() => l.first().and_then(Value::as_u64).map(|x| x as u8),
-        _ => None,
-    }
+    optional_value(decoded, name).and_then(Value::as_u64).map(|x| x as u8)
 }
 
 fn optional_absent(decoded: &HashMap<String, Value>, name: &str) -> bool {
-    matches!(decoded.get(name), Some(Value::List(l)) if l.is_empty())
+    decoded.contains_key(name) && !optional_present(decoded, name)
 }
 
 /// Helper: build value map for bitmap2_7 with a/b present or absent.
@@ -625,3 +625,101 @@ fn bitmap_presence_decode_reject_last_fx1_at_max_size() {
         other => panic!("expected Validation error, got: {:?}", other),
     }
 }
+
+/// **Behaviour**: With `Codec::strict_fspec`, an FSPEC whose last block has no presence bit set is not
+/// minimal (0x81 0x00 should have been 0x80) and decode fails; the minimal encoding and a single 0x00
+/// byte are accepted. Without it both decode alike.
+#[test]
+fn bitmap_presence_strict_fspec_rejects_padded() {
+    let mut codec = Codec::new(resolve(BITMAP_14_7), Endianness::Big);
+    let minimal: Vec<u8> = vec![0x80, 0x05];
+    let padded: Vec<u8> = vec![0x81, 0x00, 0x05];
+    let lenient = codec.decode_message("Bitmap14_7", &padded).expect("lenient decode");
+    assert_eq!(optional_u8(&lenient, "a"), Some(5));
+
+    codec.strict_fspec = true;
+    let decoded = codec.decode_message("Bitmap14_7", &minimal).expect("minimal FSPEC");
+    assert_eq!(optional_u8(&decoded, "a"), Some(5));
+    assert!(codec.decode_message("Bitmap14_7", &[0x00]).is_ok(), "one all-zero byte is minimal");
+    match codec.decode_message("Bitmap14_7", &padded) {
+        Err(CodecError::Validation(msg)) => assert!(msg.contains("non-minimal FSPEC"), "{}", msg),
+        other => panic!("expected Validation error, got: {:?}", other),
+    }
+    // A second block carrying a presence bit is needed, hence minimal.
+    assert!(codec.decode_message("Bitmap14_7", &[0x01, 0x80, 0x07]).is_ok());
+}
+
+// -----------------------------------------------------------------------------
+// Library accessors for optionals
+// -----------------------------------------------------------------------------
+
+/// **Behaviour**: `optional_present` / `optional_value` read presence the same way from a map built for
+/// encoding (present = one-element list) and from the decoded map (present = bare value); absent is an
+/// empty list in both, and a missing field counts as absent.
+#[test]
+fn optional_accessors_on_decoded_and_encode_maps() {
+    let resolved = resolve(BITMAP_2_7);
+    let codec = Codec::new(resolved, Endianness::Big);
+    let v = vals_2_7(Some(5), None);
+    assert!(optional_present(&v, "a"));
+    assert_eq!(optional_value(&v, "a"), Some(&Value::U8(5)));
+    assert!(!optional_present(&v, "b"));
+    assert_eq!(optional_value(&v, "b"), None);
+
+    let decoded = codec.decode_message("Bitmap2_7", &codec.encode_message("Bitmap2_7", &v).expect("encode")).expect("decode");
+    assert_eq!(decoded.get("a"), Some(&Value::U8(5)));
+    assert!(optional_present(&decoded, "a"));
+    assert_eq!(optional_value(&decoded, "a"), Some(&Value::U8(5)));
+    assert!(!optional_present(&decoded, "b"));
+    assert_eq!(optional_value(&decoded, "b"), None);
+    assert!(!optional_present(&decoded, "missing"));
+    assert_eq!(optional_value(&decoded, "missing"), None);
+}
+
+/// **Behaviour**: `encode_message_present` sets the presence bits of exactly the named fields: a named
+/// field holding the default value is still written, a named field missing from the values is written
+/// with the type default, and an unnamed field with a value is written absent.
+#[test]
+fn encode_message_present_uses_named_fields() {
+    let codec = Codec::new(resolve(BITMAP_7_7), Endianness::Big);
+    let mut v = HashMap::new();
+    v.insert("fspec".to_string(), Value::Bytes(vec![]));
+    v.insert("a".to_string(), Value::U8(0));
+    v.insert("b".to_string(), Value::List(vec![Value::U8(9)]));
+    v.insert("e".to_string(), Value::List(vec![]));
+    let encoded = codec.encode_message_present("Bitmap7_7", &v, &["a", "c", "e"]).expect("encode");
+    assert_eq!(encoded, [0xA8, 0, 0, 0], "bits 7, 5, 3 (a, c, e); b absent although it has a value");
+    let decoded = codec.decode_message("Bitmap7_7", &encoded).expect("decode");
+    assert_eq!(optional_value(&decoded, "a"), Some(&Value::U8(0)));
+    assert!(!optional_present(&decoded, "b"));
+    assert!(optional_present(&decoded, "c"));
+    assert!(optional_present(&decoded, "e"));
+
+    // Nothing named: every optional absent.
+    assert_eq!(codec.encode_message_present("Bitmap7_7", &v, &[]).expect("encode"), [0x00]);
+    assert!(matches!(codec.encode_message_present("Bitmap7_7", &v, &["fspec"]), Err(CodecError::UnknownField(_))));
+}
+
+/// **Behaviour**: `decode_message_with_presence` returns each optional's presence bit next to the values, so an
+/// optional list that is present but empty is not mistaken for an absent one.
+#[test]
+fn decode_message_with_presence_reports_bits() {
+    let dsl = r#"
+message Tracks {
+  fspec: bitmap(3, 7) -> (0: ids, 1: extra, 2: code);
+  ids: optional<list<u8>>;
+  extra: optional<list<u8>>;
+  code: optional<u8>;
+}
+"#;
+    let codec = Codec::new(resolve(dsl), Endianness::Big);
+    // ids present with zero elements, extra absent, code present.
+    let bytes = [0xA0, 0, 0, 0, 0, 7];
+    let (values, presence) = codec.decode_message_with_presence("Tracks", &bytes).expect("decode");
+    assert_eq!(values.get("ids"), values.get("extra"), "both decode to an empty list");
+    assert_eq!(presence.get("ids"), Some(&true));
+    assert_eq!(presence.get("extra"), Some(&false));
+    assert_eq!(presence.get("code"), Some(&true));
+    assert_eq!(presence.len(), 3, "only optional fields: {:?}", presence);
+    assert_eq!(values, codec.decode_message("Tracks", &bytes).expect("decode"));
+}
@@ -1,6 +1,6 @@
 //! Extensive DSL unit tests: syntax (parse success/failure) and semantics (resolve, references).
 
-use aiprotodsl::{parse, ResolvedProtocol};
+use aiprotodsl::{parse, ResolveOptions, ResolvedProtocol};
 
 // ==================== Syntax: valid programs ====================
 
@@ -111,6 +111,65 @@ message B { y: u16; }
     assert_eq!(sel.value_to_message.len(), 2);
 }
 
+#[test]
+fn selector_on_category_and_subtype() {
+    use aiprotodsl::walk::{record_extent, Endianness};
+    use aiprotodsl::Value;
+    use std::collections::HashMap;
+    let src = r#"
+transport { category: u8; subtype: u8; len: u16; }
+payload {
+  messages: CatA, CatB;
+  selector: (category, subtype) -> { (48, 1): CatA, (48, 2): CatB, default: RawRecord };
+}
+message CatA { x: u8; }
+message CatB { y: u16; }
+"#;
+    let p = parse(src).expect("parse");
+    let sel = p.payload.as_ref().unwrap().selector.as_ref().unwrap();
+    assert_eq!(sel.transport_field, "category");
+    assert_eq!(sel.extra_fields, ["subtype"]);
+    assert_eq!(sel.value_to_message.len(), 2);
+    let resolved = ResolvedProtocol::resolve(p).expect("resolve");
+    let transport = |category: u8, subtype: u8| {
+        HashMap::from([
+            ("category".to_string(), Value::U8(category)),
+            ("subtype".to_string(), Value::U8(subtype)),
+            ("len".to_string(), Value::U16(0)),
+        ])
+    };
+    assert_eq!(resolved.message_for_transport_values(&transport(48, 1)), Some("CatA"));
+    assert_eq!(resolved.message_for_transport_values(&transport(48, 2)), Some("CatB"));
+    assert_eq!(resolved.message_for_transport_values(&transport(48, 3)), Some("RawRecord"));
+    assert_eq!(resolved.message_for_transport_values(&transport(1, 48)), Some("RawRecord"));
+    let no_subtype = HashMap::from([("category".to_string(), Value::U8(48))]);
+    assert_eq!(resolved.message_for_transport_values(&no_subtype), None);
+    let walk = |transport: &[u8]| record_extent(&[0, 5, 9], 0, &resolved, Endianness::Big, transport).unwrap();
+    assert_eq!(walk(&[48, 1, 0, 3]), (1, "CatA"));
+    assert_eq!(walk(&[48, 2, 0, 3]), (2, "CatB"));
+    assert_eq!(walk(&[48, 7, 0, 3]), (3, "RawRecord"));
+
+    let dup = parse(&src.replace("(48, 2): CatB", "(48, 1): CatB")).expect("parse");
+    let err = ResolvedProtocol::resolve(dup).unwrap_err();
+    assert!(err.contains("(48, 1) is mapped twice"), "{}", err);
+    let arity = parse(&src.replace("(48, 2): CatB", "48: CatB")).unwrap_err();
+    assert!(arity.contains("expected 2"), "{}", arity);
+}
+
+#[test]
+fn parse_inner_transport() {
+    let src = r#"
+transport { cat: u8; len: u16; }
+inner_transport { seq: u32; source: u16; }
+message R { x: u8; }
+"#;
+    let p = parse(src).expect("parse");
+    assert_eq!(p.transport.as_ref().expect("transport").fields.len(), 2);
+    let inner = p.inner_transport.as_ref().expect("inner_transport");
+    assert_eq!(inner.fields.len(), 2);
+    assert_eq!(inner.fields[0].name, "seq");
+}
+
 #[test]
 fn parse_payload_repeated() {
     let src = r#"
@@ -152,6 +211,24 @@ message M { k: u8; }
     assert_eq!(p.enum_defs[0].variants.len(), 3);
 }
 
+#[test]
+fn parse_enum_base_type() {
+    let src = r#"
+enum Color : u16 { Red = 1; Blue = 0x0200; }
+message M { c: Color; }
+"#;
+    let p = parse(src).expect("parse");
+    assert_eq!(p.enum_defs[0].base, aiprotodsl::ast::BaseType::U16);
+    assert!(ResolvedProtocol::resolve(p).is_ok());
+
+    let too_wide = r#"
+enum Color : u8 { Red = 1; Blue = 0x0200; }
+message M { c: Color; }
+"#;
+    let err = ResolvedProtocol::resolve(parse(too_wide).expect("parse")).unwrap_err();
+    assert!(err.contains("does not fit"), "{}", err);
+}
+
 #[test]
 fn parse_struct_and_ref() {
     let src = r#"
@@ -208,6 +285,75 @@ message M {
     assert!(matches!(c, aiprotodsl::ast::Constraint::Enum(_)));
 }
 
+#[test]
+fn parse_constraints_conditional() {
+    let src = r#"
+message M {
+  mode: u8;
+  x: u8 [0..10] when mode == 1 [0..100];
+}
+"#;
+    let p = parse(src).expect("parse");
+    let f = &p.messages[0].fields[1];
+    assert!(f.constraint.is_none());
+    assert_eq!(f.conditional_constraints.len(), 2);
+    let cond = f.conditional_constraints[0].0.as_ref().expect("when clause");
+    assert_eq!(cond.field, "mode");
+    assert!(f.conditional_constraints[1].0.is_none());
+}
+
+#[test]
+fn parse_timestamp() {
+    let src = r#"
+message M {
+  tod: timestamp(u32(24), "1/128 s");
+  t_ms: optional<timestamp(u64, "1 ms")>;
+}
+"#;
+    let p = parse(src).expect("parse");
+    match &p.messages[0].fields[0].type_spec {
+        aiprotodsl::TypeSpec::Timestamp { quantum, scale, .. } => {
+            assert_eq!(quantum, "1/128 s");
+            assert_eq!(*scale, 1.0 / 128.0);
+        }
+        other => panic!("expected timestamp, got {:?}", other),
+    }
+    assert!(parse("message M { t: timestamp(u32, \"1/256 NM\"); }").is_err(), "quantum must be a time unit");
+}
+
+#[test]
+fn parse_bcd_and_reverse_bits() {
+    let src = r#"
+message M {
+  a: bcd(2);
+  b: bcd_swapped(3);
+  c: reverse_bits<u16>;
+}
+"#;
+    let p = parse(src).expect("parse");
+    let f = &p.messages[0].fields;
+    assert!(matches!(f[0].type_spec, aiprotodsl::TypeSpec::Bcd { bytes: 2, swapped: false }));
+    assert!(matches!(f[1].type_spec, aiprotodsl::TypeSpec::Bcd { bytes: 3, swapped: true }));
+    assert!(matches!(&f[2].type_spec, aiprotodsl::TypeSpec::ReverseBits(inner) if matches!(**inner, aiprotodsl::TypeSpec::Base(_))));
+    assert!(parse("message M { x: reverse_bits<list<u8>>; }").is_err(), "reverse_bits needs a fixed-size type");
+    assert!(parse("message M { x: bcd(0); }").is_err());
+}
+
+#[test]
+fn parse_remaining() {
+    let p = parse("message M { a: u8; rest: remaining; }").expect("parse");
+    assert!(matches!(p.messages[0].fields[1].type_spec, aiprotodsl::TypeSpec::Remaining));
+    assert!(ResolvedProtocol::resolve(p).is_ok());
+
+    let p = parse("message M { rest: remaining; a: u8; }").expect("parse");
+    let err = ResolvedProtocol::resolve(p).unwrap_err();
+    assert!(err.contains("must be the last field"), "{}", err);
+
+    // A struct whose name starts with the keyword is still a struct ref.
+    let p = parse("struct remainingInfo { a: u8; } message M { r: remainingInfo; }").expect("parse");
+    assert!(matches!(&p.messages[0].fields[0].type_spec, aiprotodsl::TypeSpec::StructRef(n) if n == "remainingInfo"));
+}
+
 #[test]
 fn parse_sized_int_bitfield_padding() {
     let src = r#"
@@ -358,6 +504,27 @@ message R { x: u8; }
     assert!(sel.value_to_message[0].2); // is_list
 }
 
+#[test]
+fn parse_selector_default_raw_record() {
+    let src = r#"
+transport { cat: u8; len: u16; }
+payload {
+  messages: R;
+  selector: cat -> 48: R, default: RawRecord;
+}
+message R { x: u8; }
+"#;
+    let p = parse(src).expect("parse");
+    let sel = p.payload.as_ref().unwrap().selector.as_ref().unwrap();
+    assert_eq!(sel.value_to_message.len(), 1);
+    assert_eq!(sel.default_message.as_deref(), Some("RawRecord"));
+    ResolvedProtocol::resolve(p).expect("built-in RawRecord needs no declaration");
+
+    let undefined = parse(&src.replace("RawRecord", "Missing")).expect("parse");
+    let r = ResolvedProtocol::resolve(undefined);
+    assert!(r.is_err(), "undefined default message should fail resolve");
+}
+
 // ==================== Syntax: invalid / parse errors ====================
 
 #[test]
@@ -543,6 +710,81 @@ message M { x: u8; }
     assert!(r.unwrap_err().contains("not a defined message"));
 }
 
+#[test]
+fn resolve_selector_duplicate_value_fails() {
+    let src = r#"
+transport { cat: u8; len: u16; }
+payload { messages: A, B; selector: cat -> 48: A, 0x30: B; }
+message A { x: u8; }
+message B { y: u8; }
+"#;
+    let p = parse(src).expect("parse");
+    let err = ResolvedProtocol::resolve(p).unwrap_err();
+    assert!(err.contains("mapped twice") && err.contains("'A'") && err.contains("'B'"), "{}", err);
+}
+
+#[test]
+fn resolve_recursive_struct_fails() {
+    let src = r#"
+struct A { x: u8; b: B; }
+struct B { a: A[2]; }
+message M { a: A; }
+"#;
+    let err = ResolvedProtocol::resolve(parse(src).expect("parse")).unwrap_err();
+    assert!(err.contains("recursive type: A -> B -> A"), "{}", err);
+
+    // A list may be empty, so a message listing itself is fine.
+    let tree = r#"
+message Node { id: u8; children: list<Node>; }
+"#;
+    assert!(ResolvedProtocol::resolve(parse(tree).expect("parse")).is_ok());
+}
+
+#[test]
+fn resolve_with_options_toggles_checks() {
+    let draft = r#"
+transport { cat: u8; len: u16; }
+payload { messages: A; selector: cat -> 1: A, 1: Later; }
+struct A { b: B; }
+struct B { a: A; }
+message A { s: A; }
+"#;
+    let p = parse(draft).expect("parse");
+    assert!(ResolvedProtocol::resolve(p.clone()).is_err());
+    ResolvedProtocol::resolve_with_options(p.clone(), ResolveOptions::permissive()).expect("permissive");
+
+    let opts = ResolveOptions { check_selector_duplicates: true, ..ResolveOptions::permissive() };
+    let err = ResolvedProtocol::resolve_with_options(p.clone(), opts).unwrap_err();
+    assert!(err.contains("mapped twice"), "{}", err);
+    let opts = ResolveOptions { check_references: true, ..ResolveOptions::permissive() };
+    let err = ResolvedProtocol::resolve_with_options(p.clone(), opts).unwrap_err();
+    assert!(err.contains("'Later' is not a defined message"), "{}", err);
+    let opts = ResolveOptions { check_cycles: true, ..ResolveOptions::permissive() };
+    let err = ResolvedProtocol::resolve_with_options(p, opts).unwrap_err();
+    assert!(err.contains("recursive type"), "{}", err);
+}
+
+#[test]
+fn resolve_selector_exhaustive_option() {
+    let src = r#"
+transport { kind: bitfield(2); pad: padding(6, bits); len: u16; }
+payload { messages: A, B; selector: kind -> 0: A, 1: B, 3: A; }
+message A { x: u8; }
+message B { y: u8; }
+"#;
+    let p = parse(src).expect("parse");
+    assert!(ResolvedProtocol::resolve(p.clone()).is_ok(), "not checked by default");
+    let opts = ResolveOptions { check_selector_exhaustive: true, ..ResolveOptions::default() };
+    let err = ResolvedProtocol::resolve_with_options(p, opts).unwrap_err();
+    assert!(err.contains("not exhaustive: no mapping for 2"), "{}", err);
+
+    let with_default = parse(&src.replace("3: A;", "3: A, default: RawRecord;")).expect("parse");
+    assert!(ResolvedProtocol::resolve_with_options(with_default, opts).is_ok());
+    let wide = parse(&src.replace("kind: bitfield(2); pad: padding(6, bits);", "kind: u32;")).expect("parse");
+    let err = ResolvedProtocol::resolve_with_options(wide, opts).unwrap_err();
+    assert!(err.contains("add a `default` mapping"), "{}", err);
+}
+
 #[test]
 fn resolve_struct_ref_undefined_allowed() {
     // Struct refs are not resolved at resolve() time; codec fails at encode/decode if missing
@@ -556,6 +798,68 @@ message M { id: u8; s: UnknownStruct; }
     let _ = r;
 }
 
+#[test]
+fn resolve_sized_int_wider_than_base_fails() {
+    let ok = "message M { a: u8(8); b: i16(10); c: u32(24); d: u64(64); }";
+    assert!(ResolvedProtocol::resolve(parse(ok).expect("parse")).is_ok());
+
+    let err = ResolvedProtocol::resolve(parse("message M { id: u8; flags: u8(10); }").expect("parse")).unwrap_err();
+    assert!(err.contains("M.flags") && err.contains("u8(10)") && err.contains("at most 8 bits"), "{}", err);
+
+    let err = ResolvedProtocol::resolve(parse("struct S { v: i16(17); } message M { s: S; }").expect("parse")).unwrap_err();
+    assert!(err.contains("S.v") && err.contains("at most 16 bits"), "{}", err);
+
+    let err = ResolvedProtocol::resolve(parse("message M { xs: list<u32(40)>; }").expect("parse")).unwrap_err();
+    assert!(err.contains("M.xs") && err.contains("at most 32 bits"), "{}", err);
+
+    let src = r#"
+transport { cat: u8; len: u16(20); }
+payload { messages: M; }
+message M { x: u8; }
+"#;
+    let err = ResolvedProtocol::resolve(parse(src).expect("parse")).unwrap_err();
+    assert!(err.contains("transport.len") && err.contains("at most 16 bits"), "{}", err);
+}
+
+#[test]
+fn resolve_select_field_checks() {
+    let ok = "struct A { v: u8; } message M { kind: u8; body: select<kind> { 1: A, default: A }; }";
+    assert!(ResolvedProtocol::resolve(parse(ok).expect("parse")).is_ok());
+
+    let src = "struct A { v: u8; } message M { body: select<kind> { 1: A }; kind: u8; }";
+    let err = ResolvedProtocol::resolve(parse(src).expect("parse")).unwrap_err();
+    assert!(err.contains("M.body") && err.contains("not an earlier field"), "{}", err);
+
+    let src = "message M { kind: u8; body: select<kind> { 1: Missing }; }";
+    let err = ResolvedProtocol::resolve(parse(src).expect("parse")).unwrap_err();
+    assert!(err.contains("no struct or message named Missing"), "{}", err);
+    assert!(ResolvedProtocol::resolve_with_options(parse(src).expect("parse"), ResolveOptions::permissive()).is_ok());
+
+    let err = parse("struct A { v: u8; } message M { kind: u8; body: select<kind> { 1: A, 1: A }; }").unwrap_err();
+    assert!(err.contains("duplicate case 1"), "{}", err);
+}
+
+#[test]
+fn resolve_string_code_on_numeric_field_fails() {
+    let ok = r#"message M { mode: u8 [in(OFF=0, ON=1)]; tag: string(2) [in("AB", "CD")]; }"#;
+    assert!(ResolvedProtocol::resolve(parse(ok).expect("parse")).is_ok());
+
+    let err = ResolvedProtocol::resolve(parse(r#"message M { mode: u8 [in(0, "ON")]; }"#).expect("parse")).unwrap_err();
+    assert!(err.contains("M.mode") && err.contains("\"ON\"") && err.contains("u8"), "{}", err);
+
+    let src = r#"struct S { v: optional<u16> [("x")]; } message M { s: S; }"#;
+    let err = ResolvedProtocol::resolve(parse(src).expect("parse")).unwrap_err();
+    assert!(err.contains("S.v") && err.contains("\"x\""), "{}", err);
+
+    let src = r#"
+transport { cat: u8 [in(1, "two")]; }
+payload { messages: M; }
+message M { a: u8; }
+"#;
+    let err = ResolvedProtocol::resolve(parse(src).expect("parse")).unwrap_err();
+    assert!(err.contains("transport.cat"), "{}", err);
+}
+
 #[test]
 fn parse_payload_without_messages_list_fails() {
     // payload must list at least one message
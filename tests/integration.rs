@@ -1,10 +1,12 @@
 //! Integration tests: parse DSL, encode/decode, validation, frame, walk-only, and DSL lint.
 
-use aiprotodsl::codec::{Codec, Endianness};
+use aiprotodsl::codec::{BitOrder, Codec, CodecError, DecodeLimits, Endianness, FieldCodec};
+use aiprotodsl::ast::{BaseType, Literal};
+use aiprotodsl::codegen;
 use aiprotodsl::frame;
 use aiprotodsl::lint::{lint, LintRule, Severity};
-use aiprotodsl::walk::{message_extent, validate_message_in_place, zero_padding_reserved_in_place, remove_message_in_place, Endianness as WalkEndianness};
-use aiprotodsl::{parse, AbstractType, PaddingKind, ResolvedProtocol, TypeSpec, Value};
+use aiprotodsl::walk::{message_extent, record_extent, BinaryWalker, validate_fixed_record_list, validate_message_first_error, validate_message_in_place, validate_and_zero_message_in_place, zero_padding_reserved_in_place, remove_message_in_place, remove_message_in_place_updating, write_field_in_place, CounterKind, Endianness as WalkEndianness};
+use aiprotodsl::{diff_records, format_seconds_as_tod, merge_values, FieldDiff, parse, RemovalReason, value_to_dump, AbstractType, PaddingKind, ResolvedProtocol, TypeSpec, Value, ValueRef};
 use std::collections::HashMap;
 
 const SIMPLE_PROTO: &str = r#"
@@ -77,79 +79,2536 @@ fn test_encode_decode_simple() {
     let decoded = codec.decode_message("Simple", &encoded).expect("decode");
     assert_eq!(decoded.get("id").and_then(Value::as_u64), Some(42));
     assert_eq!(decoded.get("len").and_then(Value::as_u64), Some(3));
+
+    // encode_message_into appends to a reusable buffer.
+    let mut buf = vec![0xFF];
+    codec.encode_message_into("Simple", &values, &mut buf).expect("encode into");
+    assert_eq!(buf[0], 0xFF);
+    assert_eq!(&buf[1..], encoded.as_slice());
+}
+
+#[test]
+fn test_validation_constraint() {
+    let protocol = parse(WITH_CONSTRAINTS).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Little);
+
+    let mut values = HashMap::new();
+    values.insert("kind".to_string(), Value::U8(5));
+    values.insert("value".to_string(), Value::U32(500));
+    let encoded = codec.encode_message("Bounded", &values).expect("encode");
+    let decoded = codec.decode_message("Bounded", &encoded).expect("decode");
+    assert_eq!(decoded.get("kind").and_then(Value::as_u64), Some(5));
+
+    // Decode invalid data (kind = 20 out of range) - should fail validation but still consume bytes
+    let mut bad = encoded.clone();
+    if bad.len() >= 1 {
+        bad[0] = 20;
+    }
+    let (consumed, result) = codec.decode_message_with_extent("Bounded", &bad);
+    assert!(consumed > 0);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_validation_constraint_multiple_intervals() {
+    // Range can be a concatenation of intervals; value valid if in any interval
+    let src = r#"
+message MultiRange {
+  code: u8 [0..2, 10..15, 100..100];
+}
+"#;
+    let protocol = parse(src).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Little);
+
+    for (val, valid) in [(0u8, true), (2, true), (5, false), (10, true), (15, true), (20, false), (100, true)] {
+        let mut values = HashMap::new();
+        values.insert("code".to_string(), Value::U8(val));
+        let encoded = codec.encode_message("MultiRange", &values).expect("encode");
+        let result = codec.decode_message("MultiRange", &encoded);
+        if valid {
+            let decoded = result.expect("decode");
+            assert_eq!(decoded.get("code").and_then(Value::as_u64), Some(val as u64));
+        } else {
+            assert!(result.is_err(), "value {} should fail validation", val);
+        }
+    }
+}
+
+#[test]
+fn test_validation_conditional_constraint() {
+    // The applicable range depends on `mode`; first matching `when` clause wins.
+    let src = r#"
+message Moded {
+  mode: u8;
+  value: u8 [0..10] when mode == 1 [0..100] when mode == 2;
+}
+"#;
+    let protocol = parse(src).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Little);
+    let endianness = WalkEndianness::from(Endianness::Little);
+
+    for (mode, val, valid) in [(1u8, 10u8, true), (1, 50, false), (2, 50, true), (2, 150, false), (3, 200, true)] {
+        let mut values = HashMap::new();
+        values.insert("mode".to_string(), Value::U8(mode));
+        values.insert("value".to_string(), Value::U8(val));
+        let encoded = codec.encode_message("Moded", &values).expect("encode");
+        let decoded = codec.decode_message("Moded", &encoded);
+        let walked = validate_message_in_place(&encoded, 0, &resolved, endianness, "Moded");
+        assert_eq!(decoded.is_ok(), valid, "decode mode {} value {}", mode, val);
+        assert_eq!(walked.is_ok(), valid, "walk mode {} value {}", mode, val);
+    }
+
+    // The same inside a struct: the walkers check struct fields against their active constraint too.
+    let src = r#"
+struct Reading {
+  mode: u8;
+  value: u8 [0..10] when mode == 1 [0..100] when mode == 2;
+}
+message Wrapped {
+  id: u8;
+  reading: Reading;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(src).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Little);
+    for (mode, val, valid) in [(1u8, 10u8, true), (1, 50, false), (2, 50, true), (2, 150, false), (3, 200, true)] {
+        let mut bytes = [7, mode, val];
+        let decoded = codec.decode_message("Wrapped", &bytes);
+        let walked = validate_message_in_place(&bytes, 0, &resolved, endianness, "Wrapped");
+        assert_eq!(decoded.is_ok(), valid, "decode mode {} value {}", mode, val);
+        assert_eq!(walked.is_ok(), valid, "walk mode {} value {}", mode, val);
+        let zeroed = validate_and_zero_message_in_place(&mut bytes, 0, &resolved, endianness, "Wrapped");
+        assert_eq!(zeroed.is_ok(), valid, "validate and zero mode {} value {}", mode, val);
+    }
+    assert!(message_extent(&[7, 1, 50], 0, &resolved, endianness, "Wrapped").is_ok());
+}
+
+#[test]
+fn test_frame_decode_multiple_messages() {
+    let protocol = parse(SIMPLE_PROTO).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Little);
+
+    let mut v1 = HashMap::new();
+    v1.insert("id".to_string(), Value::U8(1));
+    v1.insert("len".to_string(), Value::U16(0));
+    v1.insert("data".to_string(), Value::List(vec![]));
+    let mut v2 = HashMap::new();
+    v2.insert("id".to_string(), Value::U8(2));
+    v2.insert("len".to_string(), Value::U16(0));
+    v2.insert("data".to_string(), Value::List(vec![]));
+
+    let b1 = codec.encode_message("Simple", &v1).expect("encode");
+    let b2 = codec.encode_message("Simple", &v2).expect("encode");
+    let frame_bytes: Vec<u8> = b1.into_iter().chain(b2.into_iter()).collect();
+
+    let result = frame::decode_frame(&codec, "Simple", &frame_bytes, None).expect("frame decode");
+    assert_eq!(result.messages.len(), 2);
+    assert_eq!(result.removed.len(), 0);
+}
+
+#[test]
+fn test_frame_removal_causes() {
+    let dsl = r#"
+struct Inner { k: u8 [0..9]; }
+message Rec {
+  kind: u8 [1..5];
+  inner: Inner;
+  v: u16;
+}
+message Broken {
+  a: u8;
+  x: Missing;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    // ok, kind out of range, nested constraint (decoding stops after `inner`), then a record cut after its first byte.
+    let frame_bytes = [1, 0, 0, 1, 9, 0, 0, 2, 2, 50, 3];
+    let result = frame::decode_frame(&codec, "Rec", &frame_bytes, None).expect("frame decode");
+    assert_eq!(result.messages.len(), 1);
+    let causes: Vec<&RemovalReason> = result.removed.iter().map(|r| &r.cause).collect();
+    assert_eq!(
+        causes,
+        [
+            &RemovalReason::ConstraintFailed { field: "kind".to_string(), value: 9 },
+            &RemovalReason::DecodeError,
+            &RemovalReason::Truncated { needed: 1, available: 0 },
+        ]
+    );
+    assert_eq!(result.removed[0].byte_range, (4, 8));
+    assert!(result.removed[0].reason.contains('9'), "{}", result.removed[0].reason);
+    assert_eq!(result.removed[2].cause.kind(), "truncated");
+
+    let result = frame::decode_frame(&codec, "Broken", &[1, 2], None).expect("frame decode");
+    assert_eq!(result.removed[0].cause, RemovalReason::UnknownType("Missing".to_string()));
+}
+
+#[test]
+fn test_decode_length_prefixed_message() {
+    let dsl = r#"
+message Tagged {
+  kind: u8;
+  len: u8;
+  payload: remaining;
+}
+message Fixed {
+  len: u8;
+  a: u16;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    // The length bounds `remaining`: the trailing bytes belong to the next record.
+    let (values, len) = codec.decode_length_prefixed_message("Tagged", &[7, 5, 0xA, 0xB, 0xC, 0xFF, 0xFF], "len").expect("decode");
+    assert_eq!(len, 5);
+    assert_eq!(values["payload"], Value::Bytes(vec![0xA, 0xB, 0xC]));
+
+    assert_eq!(codec.decode_length_prefixed_message("Fixed", &[3, 0, 1, 9], "len").expect("decode").1, 3);
+    // Overrun (record needs 3 bytes, length says 2) and underrun (length says 4) are validation errors.
+    assert!(matches!(codec.decode_length_prefixed_message("Fixed", &[2, 0, 1], "len"), Err(CodecError::Validation(_))));
+    assert!(matches!(codec.decode_length_prefixed_message("Fixed", &[4, 0, 1, 9], "len"), Err(CodecError::Validation(_))));
+    // Input shorter than the stated length is truncation.
+    assert!(matches!(
+        codec.decode_length_prefixed_message("Fixed", &[9, 0, 1], "len"),
+        Err(CodecError::Truncated { ref field, needed: 9, available: 3, offset: 0 }) if field == "len"
+    ));
+    assert!(matches!(codec.decode_length_prefixed_message("Fixed", &[3, 0, 1], "size"), Err(CodecError::UnknownField(_))));
+}
+
+#[test]
+fn test_frame_inner_transport_per_record() {
+    let dsl = r#"
+transport {
+  block: u8;
+  length: u16;
+}
+inner_transport {
+  seq: u16;
+}
+message Rec {
+  x: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    // Outer header once, then (seq, record) twice.
+    let frame_bytes: Vec<u8> = vec![7, 0x00, 0x09, 0x00, 0x01, 0xaa, 0x00, 0x02, 0xbb];
+    let result = frame::decode_frame(&codec, "Rec", &frame_bytes, Some(3)).expect("frame decode");
+    assert_eq!(result.messages.len(), 2);
+    assert!(result.removed.is_empty());
+    assert_eq!(result.messages[0].header.get("seq"), Some(&Value::U16(1)));
+    assert_eq!(result.messages[0].values.get("x"), Some(&Value::U8(0xaa)));
+    assert_eq!(result.messages[0].byte_range, (3, 6));
+    assert_eq!(result.messages[1].header.get("seq"), Some(&Value::U16(2)));
+    assert_eq!(result.messages[1].values.get("x"), Some(&Value::U8(0xbb)));
+    assert_eq!(result.messages[1].byte_range, (6, 9));
+
+    let transport_values = codec.decode_transport(&frame_bytes).expect("transport");
+    let out = frame::encode_frame_with_compliant_only(&codec, "Rec", &result, Some(&transport_values), Some(3))
+        .expect("encode");
+    assert_eq!(out, frame_bytes);
+}
+
+#[test]
+fn test_truncated_distinct_from_validation() {
+    let dsl = r#"
+struct Inner {
+  a: u8;
+  b: u32;
+}
+message Rec {
+  kind: u8 [0..10];
+  inner: Inner;
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    match codec.decode_message("Rec", &[1, 2, 0, 0]) {
+        Err(e @ CodecError::Truncated { .. }) => {
+            assert_eq!(e.to_string(), "Truncated in inner.b: needed 4 bytes at offset 2, 2 available");
+            let CodecError::Truncated { field, needed, available, offset } = e else { unreachable!() };
+            assert_eq!((field.as_str(), needed, available, offset), ("inner.b", 4, 2, 2));
+        }
+        other => panic!("expected Truncated, got {:?}", other),
+    }
+    match message_extent(&[1, 2, 0, 0], 0, &resolved, WalkEndianness::Big, "Rec") {
+        Err(CodecError::Truncated { needed, available, offset, .. }) => assert_eq!((needed, available, offset), (4, 2, 2)),
+        other => panic!("expected Truncated, got {:?}", other),
+    }
+    assert!(matches!(codec.decode_message("Rec", &[11, 2, 0, 0, 0, 1]), Err(CodecError::Validation(_))));
+
+    // Offsets count from the start of the input, also inside a body selector variant or a byte-sized list.
+    let dsl = r#"
+message Report {
+  kind: u8;
+  seq: u16;
+  selector: kind -> { 1: Position }
+}
+message Position {
+  x: u32;
+}
+message Tlv {
+  len: u8;
+  items: bytes_list(len)<u16>;
+}
+"#;
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve"), Endianness::Big);
+    match codec.decode_message("Report", &[1, 0, 5, 0xAA]) {
+        Err(CodecError::Truncated { offset, .. }) => assert_eq!(offset, 3),
+        other => panic!("expected Truncated, got {:?}", other),
+    }
+    let err = codec.decode_message("Tlv", &[3, 0, 1, 2]).unwrap_err();
+    assert!(err.to_string().contains("at offset 3"), "{}", err);
+}
+
+#[test]
+fn test_remaining_trailer() {
+    let dsl = r#"
+message Rec {
+  kind: u8;
+  rest: remaining;
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    let bytes = [0x07, 0xDE, 0xAD, 0xBE];
+    let (consumed, decoded) = codec.decode_message_with_extent("Rec", &bytes);
+    let decoded = decoded.expect("decode");
+    assert_eq!(consumed, 4);
+    assert_eq!(decoded.get("rest"), Some(&Value::Bytes(vec![0xDE, 0xAD, 0xBE])));
+    assert_eq!(codec.encode_message("Rec", &decoded).expect("encode"), bytes);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Rec").expect("extent"), 4);
+
+    // The boundary is the end of the buffer handed to the decoder.
+    let decoded = codec.decode_message("Rec", &bytes[..1]).expect("decode");
+    assert_eq!(decoded.get("rest"), Some(&Value::Bytes(vec![])));
+}
+
+#[test]
+fn test_reversed_struct_roundtrip() {
+    let dsl = r#"
+#[reversed]
+struct Legacy {
+  first: u8;
+  second: u16;
+  third: u8;
+}
+message M {
+  tag: u8;
+  body: Legacy;
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    let body: HashMap<String, Value> = [("first", Value::U8(0x11)), ("second", Value::U16(0x2233)), ("third", Value::U8(0x44))]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let mut values = HashMap::new();
+    values.insert("tag".to_string(), Value::U8(0xAA));
+    values.insert("body".to_string(), Value::Struct(body));
+    let bytes = codec.encode_message("M", &values).expect("encode");
+    assert_eq!(bytes, [0xAA, 0x44, 0x22, 0x33, 0x11]);
+
+    let decoded = codec.decode_message("M", &bytes).expect("decode");
+    assert_eq!(decoded, values);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "M").expect("extent"), 5);
+
+    assert!(parse("#[packed] struct S { a: u8; }").unwrap_err().contains("unknown struct attribute"));
+}
+
+#[test]
+fn test_list_and_optional_defaults_on_encode() {
+    let dsl = r#"
+message M {
+  tag: u8;
+  items: list<u8> = [1, 2, 3];
+  flags: presence_bits(1);
+  extra: optional<u16> = 0x0506;
+  more: optional<u8>;
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    let mut values = HashMap::new();
+    values.insert("tag".to_string(), Value::U8(9));
+    let bytes = codec.encode_message("M", &values).expect("encode");
+    // list<u8> is count-prefixed (u32).
+    assert_eq!(bytes, [9, 0, 0, 0, 3, 1, 2, 3, 0b01, 0x05, 0x06]);
+
+    // Supplied values win over the defaults, including an explicitly absent optional.
+    values.insert("items".to_string(), Value::List(vec![Value::U8(7), Value::U8(8), Value::U8(9)]));
+    values.insert("extra".to_string(), Value::List(vec![]));
+    assert_eq!(codec.encode_message("M", &values).expect("encode"), [9, 0, 0, 0, 3, 7, 8, 9, 0]);
+
+    let err = parse("message M { a: u8 = [1, 2]; }").unwrap_err();
+    assert!(err.contains("needs a list or array type"), "{}", err);
+}
+
+#[test]
+fn test_validate_message_first_error() {
+    let dsl = r#"
+message M {
+  a: u8 [0..10];
+  b: u16 [100..200];
+  c: u8 [0..1];
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let ok = [0x00, 5, 0x00, 150, 1];
+    assert!(validate_message_first_error(&ok, 1, &resolved, WalkEndianness::Big, "M").expect("walk").is_none());
+
+    // b and c both fail; only b is reported.
+    let bad = [5, 0x01, 0x2C, 9];
+    let v = validate_message_first_error(&bad, 0, &resolved, WalkEndianness::Big, "M")
+        .expect("walk")
+        .expect("violation");
+    assert_eq!((v.field.as_str(), v.offset, v.value), ("b", 1, 300));
+    assert!(matches!(v.constraint, aiprotodsl::ast::Constraint::Range(ref r) if r == &[(100, 200)]));
+
+    assert!(matches!(
+        validate_message_first_error(&bad[..2], 0, &resolved, WalkEndianness::Big, "M"),
+        Err(CodecError::Truncated { .. })
+    ));
+}
+
+/// LEB128 varint: 7 bits per byte, high bit set on all but the last byte.
+struct Varint;
+
+impl FieldCodec for Varint {
+    fn decode(&self, r: &mut std::io::Cursor<&[u8]>, _endianness: Endianness) -> Result<Value, CodecError> {
+        let len = self.skip(r.get_ref(), r.position() as usize)?;
+        let start = r.position() as usize;
+        let v = r.get_ref()[start..start + len]
+            .iter()
+            .rev()
+            .fold(0u64, |acc, b| (acc << 7) | (b & 0x7F) as u64);
+        r.set_position((start + len) as u64);
+        Ok(Value::U64(v))
+    }
+
+    fn encode(&self, w: &mut Vec<u8>, v: &Value) -> Result<(), CodecError> {
+        let mut n = v.as_u64().ok_or_else(|| CodecError::Validation("varint: expected integer".into()))?;
+        loop {
+            let b = (n & 0x7F) as u8;
+            n >>= 7;
+            if n == 0 {
+                w.push(b);
+                return Ok(());
+            }
+            w.push(b | 0x80);
+        }
+    }
+
+    fn skip(&self, data: &[u8], pos: usize) -> Result<usize, CodecError> {
+        let rest = data.get(pos..).unwrap_or(&[]);
+        rest.iter()
+            .position(|b| b & 0x80 == 0)
+            .map(|i| i + 1)
+            .ok_or(CodecError::Truncated { field: String::new(), needed: rest.len() + 1, available: rest.len(), offset: pos })
+    }
+}
+
+#[test]
+fn test_custom_field_codec() {
+    let dsl = r#"
+message Rec {
+  id: custom("varint");
+  tag: u8;
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let mut codec = Codec::new(resolved.clone(), Endianness::Big);
+    let bytes = [0xAC, 0x02, 0x09];
+    assert!(matches!(codec.decode_message("Rec", &bytes), Err(CodecError::Validation(_))));
+
+    codec.register_custom("varint", Varint);
+    let decoded = codec.decode_message("Rec", &bytes).expect("decode");
+    assert_eq!(decoded.get("id").and_then(|v| v.as_u64()), Some(300));
+    assert_eq!(decoded.get("tag").and_then(|v| v.as_u64()), Some(9));
+    assert_eq!(codec.encode_message("Rec", &decoded).expect("encode"), bytes);
+
+    let mut w = BinaryWalker::new(&bytes, &resolved, WalkEndianness::Big).with_custom_types(codec.custom_types());
+    assert_eq!(w.skip_message("Rec").expect("walk"), 3);
+    assert!(BinaryWalker::new(&bytes, &resolved, WalkEndianness::Big).skip_message("Rec").is_err());
+}
+
+#[test]
+fn test_list_of_messages() {
+    let dsl = r#"
+message Entry {
+  id: u8;
+  value: u16 [0..1000];
+}
+message Container {
+  tag: u8;
+  entries: list<Entry>;
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    let entry = |id: u8, value: u16| {
+        Value::Struct(HashMap::from([("id".to_string(), Value::U8(id)), ("value".to_string(), Value::U16(value))]))
+    };
+    let mut values = HashMap::new();
+    values.insert("tag".to_string(), Value::U8(9));
+    values.insert("entries".to_string(), Value::List(vec![entry(1, 10), entry(2, 1000)]));
+    let encoded = codec.encode_message("Container", &values).expect("encode");
+    assert_eq!(encoded, vec![9, 0, 0, 0, 2, 1, 0x00, 0x0A, 2, 0x03, 0xE8]);
+    assert_eq!(codec.decode_message("Container", &encoded).expect("decode"), values);
+    assert_eq!(message_extent(&encoded, 0, &resolved, WalkEndianness::Big, "Container").expect("extent"), encoded.len());
+
+    let out_of_range = [9, 0, 0, 0, 1, 1, 0x03, 0xE9];
+    assert!(codec.decode_message("Container", &out_of_range).is_err(), "embedded message constraints are checked");
+}
+
+#[test]
+fn test_decode_message_with_offsets() {
+    let dsl = r#"
+message Rec {
+  id: u8;
+  len: u16;
+  hi: bitfield(4);
+  lo: bitfield(4);
+  tail: u8;
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    let bytes = [0x01, 0x00, 0x02, 0x3A, 0xFF, 0xEE];
+    let (values, consumed) = codec.decode_message_with_offsets("Rec", &bytes).expect("decode");
+    assert_eq!(consumed, 5);
+    assert_eq!(values["id"], (Value::U8(1), 0..1));
+    assert_eq!(values["len"], (Value::U16(2), 1..3));
+    assert_eq!(values["hi"], (Value::U64(0xA), 3..4));
+    assert_eq!(values["lo"], (Value::U64(0x3), 3..4));
+    assert_eq!(values["tail"], (Value::U8(0xFF), 4..5));
+}
+
+#[test]
+fn test_decode_message_spanned() {
+    let dsl = r#"
+struct Point { x: u8; y: u16; }
+message Rec {
+  id: u8;
+  kind: u8;
+  pos: Point;
+  items: list<u8>;
+  extra: u16 if kind == 1;
+}
+"#;
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve"), Endianness::Big);
+
+    let bytes = [7, 0, 1, 0, 2, 0, 0, 0, 2, 9, 9];
+    let (values, spans) = codec.decode_message_spanned("Rec", &bytes).expect("decode");
+    assert_eq!(values, codec.decode_message("Rec", &bytes).expect("decode"));
+    assert_eq!(spans["id"], (0, 1));
+    assert_eq!(spans["kind"], (1, 2));
+    // Nested structs and lists get the span of the whole top-level field.
+    assert_eq!(spans["pos"], (2, 5));
+    assert_eq!(spans["items"], (5, 11));
+    // A field skipped by its condition has no span.
+    assert!(!spans.contains_key("extra"));
+    assert_eq!(spans.len(), values.len());
+}
+
+#[test]
+fn test_multibyte_bitfield_codec_and_walk_agree() {
+    let dsl = r#"
+message Packed {
+  flag: bitfield(2);
+  angle: bitfield(14) [0..9999];
+  code: u16(14) [0..9999];
+  tail: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    // angle = 1234 LSB-first: low 6 bits (18) in bits 2..8 of byte 0, high 8 bits (19) in byte 1.
+    // code = 1234 as a big-endian u16 (top two bits unused).
+    let bytes = [(18 << 2) | 1, 19, 0x04, 0xD2, 0xAA];
+    let (decoded, consumed) = codec.decode_message_with_offsets("Packed", &bytes).expect("decode");
+    assert_eq!(decoded["flag"].0, Value::U64(1));
+    assert_eq!(decoded["angle"].0, Value::U64(1234));
+    assert_eq!(decoded["code"].0, Value::U16(1234));
+    assert_eq!(consumed, bytes.len());
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Packed").expect("extent"), consumed);
+    assert!(validate_message_in_place(&bytes, 0, &resolved, WalkEndianness::Big, "Packed").is_ok());
+    let encoded = codec.encode_message("Packed", &codec.decode_message("Packed", &bytes).expect("decode")).expect("encode");
+    assert_eq!(encoded, bytes);
+
+    // angle = 12345 is out of range: codec and walk report the same value at the same field.
+    let bad = [(57 << 2) | 1, 192, 0x04, 0xD2, 0xAA];
+    let err = codec.decode_message("Packed", &bad).unwrap_err().to_string();
+    assert!(err.contains("12345"), "{}", err);
+    let violation = validate_message_first_error(&bad, 0, &resolved, WalkEndianness::Big, "Packed")
+        .expect("walk")
+        .expect("violation");
+    assert_eq!((violation.field.as_str(), violation.offset, violation.value), ("angle", 0, 12345));
+}
+
+#[test]
+fn test_repeat_group_decode_encode_walk() {
+    let dsl = r#"
+message Track {
+  n: count_of(points);
+  points: repeat(n) { lat: i16; lon: i16; flags: bitfield(3); spare: padding(5, bits); };
+  pair: repeat(2) { hi: bitfield(4); lo: bitfield(4); };
+  tail: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    let bytes = [0, 0, 0, 2, 0x00, 0x01, 0xFF, 0xFF, 0x05, 0x00, 0x02, 0x00, 0x03, 0x07, 0x21, 0x43, 0xAA];
+    let decoded = codec.decode_message("Track", &bytes).expect("decode");
+    let points = decoded["points"].as_list().expect("list");
+    assert_eq!(points.len(), 2);
+    let first = points[0].as_struct().expect("struct");
+    assert_eq!((&first["lat"], &first["lon"], &first["flags"]), (&Value::I16(1), &Value::I16(-1), &Value::U64(5)));
+    let second = points[1].as_struct().expect("struct");
+    assert_eq!((&second["lat"], &second["lon"], &second["flags"]), (&Value::I16(2), &Value::I16(3), &Value::U64(7)));
+    // Each repetition starts its own bit group, like a struct.
+    let pair = decoded["pair"].as_list().expect("list");
+    assert_eq!(pair[0].as_struct().expect("struct")["hi"], Value::U64(1));
+    assert_eq!(pair[1].as_struct().expect("struct")["lo"], Value::U64(4));
+    assert_eq!(decoded["tail"], Value::U8(0xAA));
+
+    assert_eq!(codec.encode_message("Track", &decoded).expect("encode"), bytes);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Track").expect("extent"), bytes.len());
+    assert!(message_extent(&bytes[..9], 0, &resolved, WalkEndianness::Big, "Track").is_err());
+
+    let layout = codec.message_layout_json("Track").expect("layout");
+    assert!(layout.contains("repeat(n) { lat: i16; lon: i16; flags: bitfield(3); spare: padding(5, bits); }"), "{}", layout);
+    assert!(layout.contains("\"name\":\"points.lon\""), "{}", layout);
+    assert!(layout.contains("\"name\":\"pair\",\"type\":\"repeat(2) { hi: bitfield(4); lo: bitfield(4); }\",\"width_bits\":16"), "{}", layout);
+}
+
+#[test]
+fn test_merge_nested_overrides_onto_template() {
+    let dsl = r#"
+struct Position { lat: i16; lon: i16; }
+message Report {
+  id: u8;
+  pos: Position;
+  speed: u16;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+    let template = codec.decode_message("Report", &[7, 0x00, 0x10, 0x00, 0x20, 0x01, 0x00]).expect("decode");
+
+    // Override one field of the nested struct and one top-level field; the rest comes from the template.
+    let overrides = HashMap::from([
+        ("pos".to_string(), Value::Struct(HashMap::from([("lon".to_string(), Value::I16(-1))]))),
+        ("speed".to_string(), Value::U16(2)),
+    ]);
+    let merged = merge_values(&template, &overrides);
+    assert_eq!(merged["id"], Value::U8(7));
+    assert_eq!(merged["pos"].as_struct().expect("struct")["lat"], Value::I16(0x10));
+    assert_eq!(merged["pos"].as_struct().expect("struct")["lon"], Value::I16(-1));
+    assert_eq!(codec.encode_message("Report", &merged).expect("encode"), [7, 0x00, 0x10, 0xFF, 0xFF, 0x00, 0x02]);
+
+    // A non-struct override replaces the base value as a whole.
+    assert_eq!(Value::List(vec![Value::U8(1)]).merge(&Value::List(vec![])), Value::List(vec![]));
+    assert_eq!(Value::Struct(template.clone()).merge(&Value::Struct(overrides)), Value::Struct(merged));
+}
+
+#[test]
+fn test_dyn_int_width_from_length_field() {
+    let dsl = r#"
+message Tlv {
+  w1: u8;
+  a: dyn_int(w1);
+  w3: u8;
+  b: dyn_int(w3);
+  w8: u8;
+  c: dyn_int(w8);
+  tail: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    let bytes = [1, 0x7F, 3, 0x01, 0x02, 0x03, 8, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0xEE];
+    let decoded = codec.decode_message("Tlv", &bytes).expect("decode");
+    assert_eq!(decoded["a"], Value::U64(0x7F));
+    assert_eq!(decoded["b"], Value::U64(0x01_0203));
+    assert_eq!(decoded["c"], Value::U64(0x0102_0304_0506_0708));
+    assert_eq!(decoded["tail"], Value::U8(0xEE));
+    assert_eq!(codec.encode_message("Tlv", &decoded).expect("encode"), bytes);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Tlv").expect("extent"), bytes.len());
+
+    // Widths left out are set to the fewest bytes holding the value.
+    let values: HashMap<String, Value> =
+        ["a", "b", "c", "tail"].iter().map(|k| (k.to_string(), decoded[*k].clone())).collect();
+    assert_eq!(codec.encode_message("Tlv", &values).expect("encode"), bytes);
+
+    // A value wider than its width field, or a width outside 1..8, is rejected.
+    let mut too_wide = decoded.clone();
+    too_wide.insert("a".to_string(), Value::U64(0x100));
+    assert!(matches!(codec.encode_message("Tlv", &too_wide), Err(CodecError::Validation(_))));
+    let mut bad = bytes.to_vec();
+    bad[0] = 9;
+    assert!(matches!(codec.decode_message("Tlv", &bad), Err(CodecError::Validation(_))));
+    assert!(matches!(message_extent(&bad, 0, &resolved, WalkEndianness::Big, "Tlv"), Err(CodecError::Validation(_))));
+
+    for seed in 0..20 {
+        let values = aiprotodsl::value::random_message_values(&resolved, "Tlv", seed).expect("message");
+        let encoded = codec.encode_message("Tlv", &values).expect("encode");
+        assert_eq!(codec.decode_message("Tlv", &encoded).expect("decode"), values, "seed {}", seed);
+    }
+}
+
+#[test]
+fn test_validate_fixed_record_list() {
+    let dsl = r#"
+struct Point {
+  lat: i32 [-90..90];
+  lon: i32 [-180..180];
+  alt: u16 [0..5000, 9000..9999];
+  spare: padding(1);
+  q: u8;
+}
+message Track {
+  id: u16 [1..100];
+  points: list<Point>;
+  tail: rep_list<Point>;
+}
+message Bytes {
+  id: u8 [1..9];
+  data: list<u8>;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let point = |lat: i32, alt: u16| {
+        Value::Struct(HashMap::from([
+            ("lat".to_string(), Value::I32(lat)),
+            ("lon".to_string(), Value::I32(-lat * 2)),
+            ("alt".to_string(), Value::U16(alt)),
+            ("spare".to_string(), Value::Padding),
+            ("q".to_string(), Value::U8(7)),
+        ]))
+    };
+    for (endianness, walk_endianness) in [(Endianness::Big, WalkEndianness::Big), (Endianness::Little, WalkEndianness::Little)] {
+        let codec = Codec::new(resolved.clone(), endianness);
+        let track = |points: Vec<Value>| {
+            let values = HashMap::from([
+                ("id".to_string(), Value::U16(5)),
+                ("points".to_string(), Value::List(points)),
+                ("tail".to_string(), Value::List(vec![point(1, 9500)])),
+            ]);
+            codec.encode_message("Track", &values).expect("encode")
+        };
+        let good = track((-45..45).map(|i| point(i, (i + 45) as u16 * 50)).collect());
+        assert_eq!(validate_fixed_record_list(&good, 0, &resolved, walk_endianness, "Track").expect("valid"), good.len());
+
+        // Element constraints are checked (with sign extension), reporting the list index.
+        let bad = track(vec![point(0, 10), point(-91, 10)]);
+        let err = validate_fixed_record_list(&bad, 0, &resolved, walk_endianness, "Track").unwrap_err().to_string();
+        assert!(err.contains("points[1].lat: value -91"), "{}", err);
+        assert!(codec.decode_message("Track", &bad).is_err());
+        assert!(codec.decode_message("Track", &good).is_ok());
+        let bad = track(vec![point(0, 10), point(0, 10), point(0, 6000)]);
+        let err = validate_fixed_record_list(&bad, 0, &resolved, walk_endianness, "Track").unwrap_err().to_string();
+        assert!(err.contains("points[2].alt: value 6000"), "{}", err);
+        assert!(matches!(
+            validate_fixed_record_list(&good[..good.len() - 1], 0, &resolved, walk_endianness, "Track"),
+            Err(CodecError::Truncated { .. })
+        ));
+    }
+
+    // Other shapes fall back to the generic walker.
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let values = HashMap::from([("id".to_string(), Value::U8(3)), ("data".to_string(), Value::List(vec![Value::U8(1)]))]);
+    let bytes = codec.encode_message("Bytes", &values).expect("encode");
+    assert_eq!(validate_fixed_record_list(&bytes, 0, &resolved, WalkEndianness::Big, "Bytes").expect("valid"), bytes.len());
+    assert!(validate_fixed_record_list(&[0, 0, 0, 0, 0], 0, &resolved, WalkEndianness::Big, "Bytes").is_err());
+}
+
+#[test]
+fn test_decode_message_ref_borrows_input() {
+    let dsl = r#"
+struct Item {
+  tag: u8 [1..9];
+  ext: octets_fx;
+}
+message Packet {
+  kind: u8;
+  items: list<Item>;
+  stamp: timestamp(u16, "1/128");
+  rest: remaining;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+    let bytes = [7, 0, 0, 0, 2, 1, 0x81, 0x02, 9, 0x05, 0x01, 0x00, 0xAA, 0xBB, 0xCC];
+
+    let values = codec.decode_message_ref("Packet", &bytes).unwrap();
+    assert_eq!(values["kind"], ValueRef::U8(7));
+    assert_eq!(values["stamp"].as_i64(), Some(256));
+    let rest = values["rest"].as_bytes().unwrap();
+    assert_eq!(rest, &[0xAA, 0xBB, 0xCC]);
+    assert!(std::ptr::eq(rest.as_ptr(), bytes[12..].as_ptr()), "remaining borrows the input");
+    let items = values["items"].as_list().unwrap();
+    assert_eq!(items.len(), 2);
+    let first = items[0].as_struct().unwrap();
+    assert_eq!(first["tag"], ValueRef::U8(1));
+    let ext = first["ext"].as_bytes().unwrap();
+    assert_eq!(ext, &[0x81, 0x02]);
+    assert!(std::ptr::eq(ext.as_ptr(), bytes[6..].as_ptr()), "octets_fx borrows the input");
+
+    // The owning copy matches the regular decode.
+    let owned: HashMap<String, Value> = values.iter().map(|(k, v)| (k.to_string(), v.to_owned())).collect();
+    assert_eq!(owned, codec.decode_message("Packet", &bytes).unwrap());
+
+    // Same validation and truncation errors as decode_message.
+    let mut bad = bytes;
+    bad[8] = 10;
+    let err = codec.decode_message_ref("Packet", &bad).unwrap_err().to_string();
+    assert!(err.contains("value 10 not in any interval"), "{}", err);
+    assert!(matches!(
+        codec.decode_message_ref("Packet", &bytes[..7]),
+        Err(CodecError::Truncated { .. })
+    ));
+}
+
+#[cfg(feature = "frame_profile")]
+#[test]
+fn test_frame_decode_timing_per_message() {
+    let resolved = ResolvedProtocol::resolve(parse(SIMPLE_PROTO).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+    let values = HashMap::from([
+        ("id".to_string(), Value::U8(1)),
+        ("len".to_string(), Value::U16(2)),
+        ("data".to_string(), Value::List(vec![Value::U8(3), Value::U8(4)])),
+    ]);
+    let record = codec.encode_message("Simple", &values).unwrap();
+    let frame_bytes = [record.clone(), record].concat();
+    let result = frame::decode_frame(&codec, "Simple", &frame_bytes, None).unwrap();
+    assert_eq!(result.messages.len(), 2);
+    assert_eq!(result.decode_ns.len(), 1);
+    assert!(result.decode_ns["Simple"] > 0);
+}
+
+#[test]
+fn test_resync_scan_skips_garbage_between_frames() {
+    let dsl = r#"
+transport {
+  kind: u8 [1..3];
+  sync: magic("SY");
+  length: u16;
+}
+message Rec {
+  a: u8;
+  b: u16;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+    let frame_of = |kind: u8, a: u8| {
+        let header = HashMap::from([("kind".to_string(), Value::U8(kind)), ("length".to_string(), Value::U16(3))]);
+        let values = HashMap::from([("a".to_string(), Value::U8(a)), ("b".to_string(), Value::U16(0x0102))]);
+        [codec.encode_transport(&header).unwrap(), codec.encode_message("Rec", &values).unwrap()].concat()
+    };
+    let first = frame_of(1, 10);
+    // Garbage holding a false sync word: its header would have kind = 7, outside [1..3].
+    let garbage = [0x00, 0x07, b'S', b'Y', 0x00, 0xFF];
+    let second = frame_of(2, 20);
+    let stream = [first.as_slice(), &garbage, &second].concat();
+    let second_at = first.len() + garbage.len();
+
+    assert_eq!(frame::resync_scan(&codec, &stream, 0), Some(0));
+    assert_eq!(frame::resync_scan(&codec, &stream, 1), Some(second_at));
+    assert_eq!(frame::resync_scan(&codec, &stream, second_at + 1), None);
+    let header = codec.decode_transport(&stream[second_at..]).unwrap();
+    assert_eq!(header["kind"], Value::U8(2));
+    let values = codec.decode_message("Rec", &stream[second_at + 5..]).unwrap();
+    assert_eq!(values["a"], Value::U8(20));
+
+    // User-supplied pattern: every occurrence counts, plausible or not.
+    assert_eq!(frame::resync_scan_for(&stream, 2, b"SY"), Some(first.len() + 2));
+    assert_eq!(frame::resync_scan_for(&stream, 0, b""), None);
+    let no_magic = Codec::new(ResolvedProtocol::resolve(parse(SIMPLE_PROTO).unwrap()).unwrap(), Endianness::Big);
+    assert_eq!(frame::resync_scan(&no_magic, &stream, 0), None);
+}
+
+#[test]
+fn test_value_checked_integer_accessors() {
+    assert_eq!(Value::I8(-3).as_u64(), None);
+    assert!(Value::I8(-3).try_as_u64().unwrap_err().to_string().contains("negative"));
+    assert_eq!(Value::I8(3).try_as_u64().unwrap(), 3);
+    assert_eq!(Value::U64(u64::MAX).try_as_u64().unwrap(), u64::MAX);
+    // as_i64 wraps a large U64; try_as_i64 refuses it.
+    assert_eq!(Value::U64(u64::MAX).as_i64(), Some(-1));
+    assert!(Value::U64(u64::MAX).try_as_i64().is_err());
+    assert_eq!(Value::Timestamp { raw: -5, seconds: -0.5 }.try_as_i64().unwrap(), -5);
+    // Floats convert only when they hold a whole number in range.
+    assert_eq!(Value::Double(42.0).try_as_u64().unwrap(), 42);
+    assert_eq!(Value::Float(-7.0).try_as_i64().unwrap(), -7);
+    assert!(Value::Double(2.5).try_as_i64().is_err());
+    assert!(Value::Double(-1.0).try_as_u64().is_err());
+    assert!(Value::Double(1e30).try_as_u64().is_err());
+    assert!(Value::Double(f64::NAN).try_as_i64().is_err());
+    assert!(matches!(Value::Bytes(vec![1]).try_as_u64(), Err(CodecError::Validation(_))));
+    assert!(Value::Bool(true).try_as_i64().is_err());
+}
+
+#[test]
+fn test_inline_named_enum_codes() {
+    let dsl = r#"
+message Reply {
+  status: u8 [in(OK=0, FAIL=1, RETRY=2)];
+  level: u8 [(1, 2)];
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let decoded = codec.decode_message("Reply", &[2, 1]).unwrap();
+    assert_eq!(decoded["status"], Value::U8(2));
+    assert_eq!(value_to_dump(&resolved, "Reply", "status", &decoded["status"], 0), "RETRY");
+    assert_eq!(value_to_dump(&resolved, "Reply", "level", &decoded["level"], 0), "1");
+
+    let err = codec.decode_message("Reply", &[5, 1]).unwrap_err().to_string();
+    assert!(err.contains("value 5 not in allowed codes (OK=0, FAIL=1, RETRY=2)"), "{}", err);
+    let err = validate_message_in_place(&[5, 1], 0, &resolved, WalkEndianness::Big, "Reply").unwrap_err().to_string();
+    assert!(err.contains("not in allowed codes"), "{}", err);
+    // Unnamed codes keep the plain message.
+    let err = codec.decode_message("Reply", &[0, 3]).unwrap_err().to_string();
+    assert!(err.contains("value not in allowed enum"), "{}", err);
+
+    let layout = codec.message_layout_json("Reply").unwrap();
+    assert!(layout.contains("[in(OK=0, FAIL=1, RETRY=2)]"), "{}", layout);
+    assert!(parse("message M { s: u8 [in(A=0, A=1)]; }").unwrap_err().contains("duplicate code name 'A'"));
+}
+
+#[test]
+fn test_bits_group_roundtrip() {
+    let dsl = r#"
+message Status {
+  id: u8;
+  flags: bits { flag_a: 1, flag_b: 1, code: 6 };
+  wide: bits { hi: 4, mid: 8, lo: 4 };
+  tail: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let bits = |members: &[(&str, u64)]| {
+        Value::Struct(members.iter().map(|(n, v)| (n.to_string(), Value::U64(*v))).collect())
+    };
+    let values = HashMap::from([
+        ("id".to_string(), Value::U8(9)),
+        ("flags".to_string(), bits(&[("flag_a", 1), ("flag_b", 0), ("code", 0x2A)])),
+        ("wide".to_string(), bits(&[("hi", 0x3), ("mid", 0xC5), ("lo", 0xF)])),
+        ("tail".to_string(), Value::U8(7)),
+    ]);
+    let bytes = codec.encode_message("Status", &values).unwrap();
+    // LSB first, like bitfield(n): flag_a in bit 0, flag_b in bit 1, code in bits 2..7.
+    assert_eq!(bytes, [9, 0x2A << 2 | 1, 0x53, 0xFC, 7]);
+    assert_eq!(codec.decode_message("Status", &bytes).unwrap(), values);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Status").unwrap(), 5);
+    assert_eq!(message_extent(&bytes[..3], 0, &resolved, WalkEndianness::Big, "Status").map_err(|e| matches!(e, CodecError::Truncated { .. })), Err(true));
+    let layout = codec.message_layout_json("Status").unwrap();
+    assert!(layout.contains("\"type\":\"bits { flag_a: 1, flag_b: 1, code: 6 }\",\"width_bits\":8,\"offset_bits\":8"), "{}", layout);
+    assert!(layout.contains("\"name\":\"flags.code\",\"type\":\"bitfield(6)\",\"width_bits\":6,\"offset_bits\":10"), "{}", layout);
+
+    let err = parse("message M { f: bits { a: 1, b: 2 }; }").unwrap_err();
+    assert!(err.contains("not a multiple of 8"), "{}", err);
+    assert!(parse("message M { f: bits { a: 4, a: 4 }; }").unwrap_err().contains("duplicate member 'a'"));
+    // The group must start on a byte boundary.
+    let unaligned = ResolvedProtocol::resolve(parse("message M { x: bitfield(4); f: bits { a: 8 }; }").unwrap()).unwrap();
+    let err = Codec::new(unaligned, Endianness::Big).decode_message("M", &[0, 0]).unwrap_err().to_string();
+    assert!(err.contains("byte boundary"), "{}", err);
+}
+
+#[test]
+fn test_message_body_selector_picks_variant_tail() {
+    let dsl = r#"
+        message Report {
+          kind: u8;
+          seq: u16;
+          selector: kind -> { 1: Position, 2: Status }
+        }
+        message Position { lat: i32; lon: i32; }
+        message Status { code: u8 [0..3]; }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    let position = [1u8, 0, 7, 0, 0, 0, 10, 0xFF, 0xFF, 0xFF, 0xFE];
+    let values = codec.decode_message("Report", &position).unwrap();
+    assert_eq!(values.get("seq").and_then(Value::as_u64), Some(7));
+    assert_eq!(values.get("lat").and_then(Value::as_i64), Some(10));
+    assert_eq!(values.get("lon").and_then(Value::as_i64), Some(-2));
+    assert_eq!(resolved.message_for_message_field("Report", &values), Some("Position"));
+    assert_eq!(codec.encode_message("Report", &values).unwrap(), position);
+    assert_eq!(message_extent(&position, 0, &resolved, WalkEndianness::Big, "Report").unwrap(), position.len());
+
+    let status = [2u8, 0, 8, 3];
+    let values = codec.decode_message("Report", &status).unwrap();
+    assert_eq!(values.get("code").and_then(Value::as_u64), Some(3));
+    assert!(!values.contains_key("lat"));
+    assert_eq!(resolved.message_for_message_field("Report", &values), Some("Status"));
+    assert_eq!(codec.encode_message("Report", &values).unwrap(), status);
+    assert!(validate_message_in_place(&status, 0, &resolved, WalkEndianness::Big, "Report").is_ok());
+
+    // The variant's constraints apply; an unmapped kind has no variant.
+    assert!(matches!(codec.decode_message("Report", &[2, 0, 8, 9]), Err(CodecError::Validation(_))));
+    assert!(validate_message_in_place(&[2, 0, 8, 9], 0, &resolved, WalkEndianness::Big, "Report").is_err());
+    match codec.decode_message("Report", &[5, 0, 8]) {
+        Err(CodecError::Validation(msg)) => assert_eq!(msg, "Report: no variant for kind = 5"),
+        other => panic!("expected no-variant error, got {:?}", other),
+    }
+
+    // Variant fields must not repeat the base message's fields.
+    let err = ResolvedProtocol::resolve(
+        parse("message H { kind: u8; selector: kind -> { 1: A } } message A { kind: u8; }").unwrap(),
+    )
+    .unwrap_err();
+    assert_eq!(err, "message H: selector variant 'A' repeats field 'kind'");
+}
+
+#[test]
+fn test_float_enum_constraint_with_tolerance() {
+    let dsl = r#"
+        message Gain {
+          coarse: double [in(0.0, 0.5, 1.0) +/- 0.125];
+          fine: float [in(0.0, 0.5, 1.0) +/- 1e-6];
+          exact: double [(0.25, 2.5)];
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let encode = |coarse: f64, fine: f32, exact: f64| {
+        let mut out = coarse.to_be_bytes().to_vec();
+        out.extend_from_slice(&fine.to_be_bytes());
+        out.extend_from_slice(&exact.to_be_bytes());
+        out
+    };
+    let check = |bytes: &[u8]| {
+        let decoded = codec.decode_message("Gain", bytes).is_ok();
+        let walked = validate_message_in_place(bytes, 0, &resolved, WalkEndianness::Big, "Gain").is_ok();
+        assert_eq!(decoded, walked);
+        decoded
+    };
+
+    // |x - code| <= tolerance is accepted, on both sides of a code.
+    assert!(check(&encode(0.625, 0.5, 0.25)));
+    assert!(check(&encode(0.375, 0.5, 2.5)));
+    assert!(!check(&encode(0.625 + 1e-9, 0.5, 0.25)));
+    assert!(!check(&encode(0.75, 0.5, 0.25)));
+    // A float one ULP off its code passes a 1e-6 tolerance; 2e-6 off does not.
+    assert!(check(&encode(1.0, f32::from_bits(0.5f32.to_bits() + 1), 0.25)));
+    assert!(!check(&encode(1.0, 0.500002, 0.25)));
+    // Without a tolerance, float codes match exactly.
+    assert!(!check(&encode(1.0, 0.0, 0.2500001)));
+    match codec.decode_message("Gain", &encode(0.8, 0.0, 0.25)) {
+        Err(CodecError::Validation(msg)) => {
+            assert!(msg.contains("value 0.8 not within 0.125 of allowed values (0.0, 0.5, 1.0)"), "{}", msg)
+        }
+        other => panic!("expected validation error, got {:?}", other),
+    }
+
+    assert!(parse("message M { x: double [in(1.0) +/- -0.5]; }").is_err());
+}
+
+#[test]
+fn test_message_check_cross_field_invariants() {
+    let dsl = r#"
+        message Frame {
+          kind: u8;
+          len: u8;
+          a: u8;
+          b: u8;
+          total: u16;
+          check { kind == 2 => len >= 4 }
+          check { a + b == total }
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+    let values = |kind: u8, len: u8, a: u8, b: u8, total: u16| {
+        HashMap::from([
+            ("kind".to_string(), Value::U8(kind)),
+            ("len".to_string(), Value::U8(len)),
+            ("a".to_string(), Value::U8(a)),
+            ("b".to_string(), Value::U8(b)),
+            ("total".to_string(), Value::U16(total)),
+        ])
+    };
+
+    // Both invariants hold; `=>` is vacuously true when kind != 2.
+    let ok = codec.decode_message("Frame", &[2, 4, 200, 100, 0x01, 0x2c]).unwrap();
+    assert_eq!(ok.get("total"), Some(&Value::U16(300)));
+    assert!(codec.decode_message("Frame", &[1, 0, 1, 2, 0, 3]).is_ok());
+    assert!(codec.encode_message("Frame", &values(2, 5, 1, 2, 3)).is_ok());
+
+    match codec.decode_message("Frame", &[1, 0, 1, 2, 0, 4]) {
+        Err(CodecError::Validation(msg)) => assert_eq!(msg, "Frame: check failed: a + b == total"),
+        other => panic!("expected validation error, got {:?}", other),
+    }
+    match codec.encode_message("Frame", &values(2, 3, 1, 2, 3)) {
+        Err(CodecError::Validation(msg)) => assert_eq!(msg, "Frame: check failed: kind == 2 => len >= 4"),
+        other => panic!("expected validation error, got {:?}", other),
+    }
+
+    let unknown = "message M { a: u8; check { a == b } }";
+    assert!(ResolvedProtocol::resolve(parse(unknown).unwrap()).unwrap_err().contains("unknown field 'b'"));
+}
+
+#[test]
+fn test_split_only_then_decode_records_independently() {
+    let dsl = r#"
+transport {
+  block: u8;
+  length: u16;
+}
+inner_transport {
+  seq: u16;
+}
+message Rec {
+  data: list<u8>;
+  flag: u8 [0..1];
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    // Three records; the second fails its constraint but its boundary is still found.
+    let frame_bytes: Vec<u8> = vec![
+        7, 0x00, 0x1b, //
+        0, 1, 0, 0, 0, 2, 0xaa, 0xbb, 0, //
+        0, 2, 0, 0, 0, 0, 5, //
+        0, 3, 0, 0, 0, 1, 0xcc, 1,
+    ];
+    let ranges = frame::split_only(&codec, "Rec", &frame_bytes, Some(3)).expect("split");
+    assert_eq!(ranges, vec![(5, 12), (14, 19), (21, 27)]);
+    let slices: Vec<&[u8]> = ranges.iter().map(|&(start, end)| &frame_bytes[start..end]).collect();
+    let serial: Vec<_> = slices.iter().map(|r| codec.decode_message("Rec", r)).collect();
+    assert!(serial[0].is_ok() && serial[1].is_err() && serial[2].is_ok());
+    #[cfg(feature = "rayon")]
+    {
+        let parallel = codec.decode_many_parallel("Rec", &slices);
+        for (p, s) in parallel.iter().zip(&serial) {
+            assert_eq!(p.as_ref().ok(), s.as_ref().ok());
+            assert_eq!(p.as_ref().err().map(|e| e.to_string()), s.as_ref().err().map(|e| e.to_string()));
+        }
+    }
+
+    // A truncated last record has no boundary.
+    assert!(matches!(
+        frame::split_only(&codec, "Rec", &frame_bytes[..26], Some(3)),
+        Err(CodecError::Truncated { .. })
+    ));
+}
+
+#[test]
+fn test_stream_decoder_buffers_partial_records() {
+    let dsl = r#"
+inner_transport {
+  seq: u16;
+}
+message Rec {
+  data: list<u8>;
+  flag: u8 [0..1];
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+    let stream: Vec<u8> = vec![
+        0, 1, 0, 0, 0, 2, 0xaa, 0xbb, 0, //
+        0, 2, 0, 0, 0, 0, 5, //
+        0, 3, 0, 0, 0, 1, 0xcc, 1,
+    ];
+
+    let mut decoder = frame::StreamDecoder::new(&codec, "Rec");
+    // The first read ends inside the second record's list.
+    let first = decoder.push(&stream[..12]).expect("push");
+    assert_eq!(first.messages.len(), 1);
+    assert_eq!(first.messages[0].byte_range, (0, 9));
+    assert_eq!(first.messages[0].header.get("seq"), Some(&Value::U16(1)));
+    assert_eq!((decoder.offset(), decoder.pending()), (9, &stream[9..12]));
+
+    // Header of the third record split across reads; the second record fails its constraint.
+    let second = decoder.push(&stream[12..17]).expect("push");
+    assert!(second.messages.is_empty());
+    assert_eq!(second.removed.len(), 1);
+    assert_eq!(second.removed[0].byte_range, (9, 16));
+    assert_eq!(decoder.pending(), &stream[16..17]);
+
+    let third = decoder.push(&stream[17..]).expect("push");
+    assert_eq!(third.messages.len(), 1);
+    assert_eq!(third.messages[0].byte_range, (16, 24));
+    assert_eq!(third.messages[0].values.get("flag"), Some(&Value::U8(1)));
+    assert!(decoder.pending().is_empty());
+
+    let mut unknown = frame::StreamDecoder::new(&codec, "Missing");
+    assert!(unknown.push(&stream).is_err());
+    assert_eq!(unknown.pending(), &stream[..]);
+}
+
+#[test]
+fn test_u24_u48_roundtrip_max_values() {
+    let dsl = r#"
+message Counters {
+  small: u24;
+  large: u48 [0..281474976710655];
+  tail: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    for (endianness, walk) in [(Endianness::Big, WalkEndianness::Big), (Endianness::Little, WalkEndianness::Little)] {
+        let codec = Codec::new(resolved.clone(), endianness);
+        let mut values = HashMap::new();
+        values.insert("small".to_string(), Value::U32(0xFF_FFFF));
+        values.insert("large".to_string(), Value::U64(0xFFFF_FFFF_FFFF));
+        values.insert("tail".to_string(), Value::U8(7));
+        let bytes = codec.encode_message("Counters", &values).expect("encode");
+        assert_eq!(bytes, [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 7]);
+        assert_eq!(codec.decode_message("Counters", &bytes).expect("decode"), values);
+        assert_eq!(message_extent(&bytes, 0, &resolved, walk, "Counters").unwrap(), 10);
+    }
+
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let bytes = [0x12, 0x34, 0x56, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0];
+    let values = codec.decode_message("Counters", &bytes).expect("decode");
+    assert_eq!(values.get("small"), Some(&Value::U32(0x12_3456)));
+    assert_eq!(values.get("large"), Some(&Value::U64(0x0102_0304_0506)));
+    let little = Codec::new(resolved, Endianness::Little);
+    let values = little.decode_message("Counters", &bytes).expect("decode");
+    assert_eq!(values.get("small"), Some(&Value::U32(0x56_3412)));
+    assert_eq!(values.get("large"), Some(&Value::U64(0x0605_0403_0201)));
+    assert!(matches!(codec.decode_message("Counters", &bytes[..5]), Err(CodecError::Truncated { .. })));
+}
+
+#[test]
+fn test_array_count_from_bitfield() {
+    let dsl = r#"
+message Packed {
+  count: bitfield(4);
+  spare: bitfield(4);
+  items: u8[count];
+  tail: u8;
+}
+struct Inner {
+  count: bitfield(4);
+  spare: bitfield(4);
+  items: u8[count];
+}
+message Nested {
+  inner: Inner;
+  tail: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let bytes = [0x02, 0xaa, 0xbb, 9];
+
+    let values = codec.decode_message("Packed", &bytes).expect("decode");
+    assert_eq!(values.get("items"), Some(&Value::List(vec![Value::U8(0xaa), Value::U8(0xbb)])));
+    assert_eq!(values.get("tail"), Some(&Value::U8(9)));
+    assert_eq!(codec.encode_message("Packed", &values).expect("encode"), bytes);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Packed").unwrap(), 4);
+    assert!(validate_message_in_place(&bytes, 0, &resolved, WalkEndianness::Big, "Packed").is_ok());
+
+    let values = codec.decode_message("Nested", &bytes).expect("decode");
+    assert_eq!(values.get("tail"), Some(&Value::U8(9)));
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Nested").unwrap(), 4);
+    assert!(matches!(
+        message_extent(&bytes[..2], 0, &resolved, WalkEndianness::Big, "Packed"),
+        Err(CodecError::Truncated { .. })
+    ));
+}
+
+#[test]
+fn test_array_stride_roundtrip() {
+    let dsl = r#"
+message Table {
+  n: u8 [0..4];
+  slots: u32[n] stride(8);
+  tail: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let mut values = HashMap::new();
+    values.insert("n".to_string(), Value::U8(2));
+    values.insert("slots".to_string(), Value::List(vec![Value::U32(0x0102_0304), Value::U32(0x0a0b_0c0d)]));
+    values.insert("tail".to_string(), Value::U8(9));
+    let bytes = codec.encode_message("Table", &values).expect("encode");
+    assert_eq!(bytes, [2, 1, 2, 3, 4, 0, 0, 0, 0, 0x0a, 0x0b, 0x0c, 0x0d, 0, 0, 0, 0, 9]);
+    assert_eq!(codec.decode_message("Table", &bytes).expect("decode"), values);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Table").unwrap(), bytes.len());
+    // The last slot's padding is part of the array.
+    assert!(matches!(codec.decode_message("Table", &bytes[..15]), Err(CodecError::Truncated { .. })));
+    assert_eq!(resolved.max_size("Table"), Some(1 + 4 * 8 + 1));
+
+    let err = parse("message M { xs: u32[2] stride(2); }").unwrap_err();
+    assert!(err.contains("stride(2) is smaller than its 4-byte element"), "{}", err);
+}
+
+#[test]
+fn test_validation_error_names_message_and_field() {
+    let dsl = r#"
+        struct Plot { rho: u16 [0..4000]; theta: u16; }
+        message Report { a: u8 [0..10]; pos: Plot; }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    let err = codec.decode_message("Report", &[20, 0, 1, 0, 2]).unwrap_err();
+    assert!(matches!(err, CodecError::Validation(_)));
+    assert!(err.to_string().starts_with("Validation: message Report: field a: value 20"), "{}", err);
+
+    // 5000 = 0x1388: nested struct fields are named struct.field, with a single `Validation:` prefix.
+    let err = codec.decode_message("Report", &[1, 0x13, 0x88, 0, 2]).unwrap_err();
+    let text = err.to_string();
+    assert!(text.starts_with("Validation: message Report: field pos: Plot.rho: value 5000"), "{}", text);
+    assert_eq!(text.matches("Validation:").count(), 1, "{}", text);
+
+    // Truncation is passed through unchanged.
+    assert!(matches!(codec.decode_message("Report", &[1, 0]), Err(CodecError::Truncated { .. })));
+}
+
+#[test]
+fn test_auto_length_and_count_on_encode() {
+    let dsl = r#"
+        struct Inner { a: u16; b: list<u8>; }
+        message M {
+            len: length_of(body);
+            n: count_of(items);
+            items: u8[n];
+            body: Inner;
+            tail_len: length_of(note);
+            note: optional<u32>;
+        }
+    "#;
+    let mut codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap(), Endianness::Big);
+    let inner = HashMap::from([
+        ("a".to_string(), Value::U16(7)),
+        ("b".to_string(), Value::List(vec![Value::U8(1), Value::U8(2), Value::U8(3)])),
+    ]);
+    let mut values = HashMap::from([
+        ("items".to_string(), Value::List(vec![Value::U8(9), Value::U8(8)])),
+        ("body".to_string(), Value::Struct(inner)),
+        ("note".to_string(), Value::List(vec![Value::U32(5)])),
+        // Stale values: auto_length replaces them.
+        ("n".to_string(), Value::U32(40)),
+    ]);
+
+    // Off by default: the map is written as given, a missing length is 0.
+    let bytes = codec.encode_message("M", &values).unwrap();
+    assert_eq!(&bytes[..8], &[0, 0, 0, 0, 0, 0, 0, 40]);
+
+    codec.encode_options.auto_length = true;
+    let bytes = codec.encode_message("M", &values).unwrap();
+    let decoded = codec.decode_message("M", &bytes).unwrap();
+    // body: u16 + u32 count + 3 bytes; the length field comes before the field it measures.
+    assert_eq!(decoded.get("len"), Some(&Value::U32(9)));
+    assert_eq!(decoded.get("n"), Some(&Value::U32(2)));
+    assert_eq!(decoded.get("items"), Some(&Value::List(vec![Value::U8(9), Value::U8(8)])));
+    // A present optional is measured without its presence flag; an absent one is 0.
+    assert_eq!(decoded.get("tail_len"), Some(&Value::U32(4)));
+    values.insert("note".to_string(), Value::List(vec![]));
+    values.insert("tail_len".to_string(), Value::U32(99));
+    let decoded = codec.decode_message("M", &codec.encode_message("M", &values).unwrap()).unwrap();
+    assert_eq!(decoded.get("tail_len"), Some(&Value::U32(0)));
+}
+
+#[test]
+fn test_length_and_count_widths() {
+    let dsl = r#"
+        message M {
+            n8: count_of<u8>(a);
+            a: u8[n8];
+            n16: count_of<u16>(b);
+            b: u16[n16];
+            len16: length_of<u16>(c);
+            n32: count_of(c);
+            c: u8[n32];
+            len8: length_of<u8>(d) [0..10];
+            d: u32;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let mut codec = Codec::new(resolved.clone(), Endianness::Big);
+    codec.encode_options.auto_length = true;
+    let values = HashMap::from([
+        ("a".to_string(), Value::List(vec![Value::U8(1), Value::U8(2)])),
+        ("b".to_string(), Value::List(vec![Value::U16(0x0304)])),
+        ("c".to_string(), Value::List(vec![Value::U8(5), Value::U8(6), Value::U8(7)])),
+        ("d".to_string(), Value::U32(8)),
+    ]);
+    let bytes = codec.encode_message("M", &values).unwrap();
+    assert_eq!(bytes, [2, 1, 2, 0, 1, 3, 4, 0, 3, 0, 0, 0, 3, 5, 6, 7, 4, 0, 0, 0, 8]);
+    // Each size field decodes to the value type of its width.
+    let decoded = codec.decode_message("M", &bytes).unwrap();
+    assert_eq!(decoded.get("n8"), Some(&Value::U8(2)));
+    assert_eq!(decoded.get("n16"), Some(&Value::U16(1)));
+    assert_eq!(decoded.get("len16"), Some(&Value::U16(3)));
+    assert_eq!(decoded.get("n32"), Some(&Value::U32(3)));
+    assert_eq!(decoded.get("len8"), Some(&Value::U8(4)));
+    assert_eq!(codec.encode_message("M", &decoded).unwrap(), bytes);
+    // The walker skips and checks size fields at the same widths.
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "M").unwrap(), bytes.len());
+    assert!(validate_message_in_place(&bytes, 0, &resolved, WalkEndianness::Big, "M").is_ok());
+
+    // An auto-filled size that does not fit its width is an error.
+    let long = HashMap::from([("a".to_string(), Value::List(vec![Value::U8(0); 256]))]);
+    let dsl = "message L { n: count_of<u8>(a); a: u8[n]; }";
+    let mut codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap(), Endianness::Big);
+    codec.encode_options.auto_length = true;
+    assert!(matches!(codec.encode_message("L", &long), Err(CodecError::Validation(_))));
+    assert!(parse("message L { n: length_of<i16>(a); a: u8; }").is_err());
+}
+
+#[test]
+fn test_cstring_and_fixed_string() {
+    let dsl = "message Label {\n\tid: u8;\n\tname: cstring;\n\tcode: string(6);\n\ttags: list<cstring>;\n\ttail: u8;\n}\n";
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    // string(6) keeps its embedded zero and drops the trailing ones; the cstring terminator is not part of the value.
+    let bytes = [7, b'A', b'B', 0, b'X', 0, b'Y', 0, 0, 0, 0, 0, 0, 2, b'p', 0, 0, 9];
+    let values = codec.decode_message("Label", &bytes).unwrap();
+    assert_eq!(values.get("name"), Some(&Value::Bytes(b"AB".to_vec())));
+    assert_eq!(values.get("code"), Some(&Value::Bytes(b"X\0Y".to_vec())));
+    assert_eq!(values.get("tags"), Some(&Value::List(vec![Value::Bytes(b"p".to_vec()), Value::Bytes(vec![])])));
+    assert_eq!(values.get("tail"), Some(&Value::U8(9)));
+    assert_eq!(codec.encode_message("Label", &values).unwrap(), bytes);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Label").unwrap(), bytes.len());
+    assert!(validate_message_in_place(&bytes, 0, &resolved, WalkEndianness::Big, "Label").is_ok());
+
+    // No terminator before the end of the buffer.
+    assert!(matches!(codec.decode_message("Label", &bytes[..3]), Err(CodecError::Truncated { .. })));
+    assert!(message_extent(&bytes[..3], 0, &resolved, WalkEndianness::Big, "Label").is_err());
+
+    let mut bad = values.clone();
+    bad.insert("code".to_string(), Value::Bytes(b"TOOLONG".to_vec()));
+    assert!(matches!(codec.encode_message("Label", &bad), Err(CodecError::Validation(_))));
+    let mut bad = values.clone();
+    bad.insert("name".to_string(), Value::Bytes(b"A\0B".to_vec()));
+    assert!(matches!(codec.encode_message("Label", &bad), Err(CodecError::Validation(_))));
+    assert!(parse("message M { s: string(0); }").is_err());
+}
+
+#[test]
+fn test_checksum_fields() {
+    assert_eq!(aiprotodsl::checksum::crc16_ccitt(b"123456789"), 0x29B1);
+    assert_eq!(aiprotodsl::checksum::crc32(b"123456789"), 0xCBF4_3926);
+
+    let dsl = "message Frame {\n\ttag: u8;\n\tdata: u8[9];\n\tsum: checksum(crc16_ccitt, from: data);\n\tcrc: checksum(crc32);\n}\n";
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let data = b"123456789".iter().map(|&b| Value::U8(b)).collect();
+    // Checksums are computed on encode; a value given for them is ignored.
+    let values = HashMap::from([
+        ("tag".to_string(), Value::U8(0xA5)),
+        ("data".to_string(), Value::List(data)),
+        ("sum".to_string(), Value::U16(1)),
+    ]);
+    let bytes = codec.encode_message("Frame", &values).unwrap();
+    assert_eq!(bytes.len(), 16);
+    assert_eq!(&bytes[10..12], &[0x29, 0xB1]);
+    let crc = aiprotodsl::checksum::crc32(&bytes[..12]);
+    assert_eq!(&bytes[12..], &crc.to_be_bytes());
+
+    let decoded = codec.decode_message("Frame", &bytes).unwrap();
+    assert_eq!(decoded.get("sum"), Some(&Value::U16(0x29B1)));
+    assert_eq!(decoded.get("crc"), Some(&Value::U32(crc)));
+    assert_eq!(codec.encode_message("Frame", &decoded).unwrap(), bytes);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Frame").unwrap(), bytes.len());
+
+    // A corrupted byte fails the checksum covering it.
+    let mut bad = bytes.clone();
+    bad[0] ^= 1;
+    let err = codec.decode_message("Frame", &bad).unwrap_err().to_string();
+    assert!(err.contains("field crc: checksum(crc32)"), "{}", err);
+    let mut bad = bytes.clone();
+    bad[3] ^= 1;
+    let err = codec.decode_message("Frame", &bad).unwrap_err().to_string();
+    assert!(err.contains("field sum: checksum(crc16_ccitt)"), "{}", err);
+    assert!(parse("message M { c: checksum(md5); }").is_err());
+}
+
+#[test]
+fn test_decode_message_from_reader() {
+    /// Hands out at most two bytes per read, like a slow socket.
+    struct Trickle(std::io::Cursor<Vec<u8>>);
+    impl std::io::Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(2);
+            self.0.read(&mut buf[..n])
+        }
+    }
+
+    let dsl = r#"
+        message Plot {
+          fspec: bitmap(8, 7) -> (0: a, 1: b, 2: c, 3: d, 4: e, 5: f, 6: g, 7: h);
+          a: optional<u8>;
+          b: optional<u16>;
+          c: optional<u8>;
+          d: optional<u8>;
+          e: optional<u8>;
+          f: optional<u8>;
+          g: optional<u8>;
+          h: optional<u32>;
+          name: cstring;
+          tail: list<u8>;
+        }
+    "#;
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap(), Endianness::Big);
+    let first = HashMap::from([
+        ("a".to_string(), Value::List(vec![Value::U8(1)])),
+        ("h".to_string(), Value::List(vec![Value::U32(0xDEAD_BEEF)])),
+        ("name".to_string(), Value::Bytes(b"north".to_vec())),
+        ("tail".to_string(), Value::List(vec![Value::U8(7), Value::U8(8)])),
+    ]);
+    let second = HashMap::from([
+        ("b".to_string(), Value::List(vec![Value::U16(0x1234)])),
+        ("name".to_string(), Value::Bytes(vec![])),
+        ("tail".to_string(), Value::List(vec![])),
+    ]);
+    let mut stream = codec.encode_message("Plot", &first).unwrap();
+    let first_len = stream.len();
+    stream.extend(codec.encode_message("Plot", &second).unwrap());
+    stream.push(0x99);
+
+    let mut reader = std::io::BufReader::with_capacity(3, Trickle(std::io::Cursor::new(stream.clone())));
+    let decoded = codec.decode_message_from("Plot", &mut reader).unwrap();
+    assert_eq!(decoded, codec.decode_message("Plot", &stream).unwrap());
+    // The reader is left at the next message, then at the byte after it.
+    let decoded = codec.decode_message_from("Plot", &mut reader).unwrap();
+    assert_eq!(decoded, codec.decode_message("Plot", &stream[first_len..]).unwrap());
+    let mut rest = Vec::new();
+    std::io::Read::read_to_end(&mut reader, &mut rest).unwrap();
+    assert_eq!(rest, [0x99]);
+
+    // The stream ends inside a message.
+    let mut short = std::io::Cursor::new(stream[..first_len - 1].to_vec());
+    assert!(matches!(codec.decode_message_from("Plot", &mut short), Err(CodecError::Truncated { .. })));
+}
+
+#[test]
+fn test_validate_all_reports_every_failing_field() {
+    let dsl = r#"
+        message Report {
+          a: u8 [0..10];
+          b: u8;
+          c: u16 [100..200];
+          d: u8 [(1, 2, 3)];
+          check { a + b == 20 }
+        }
+    "#;
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap(), Endianness::Big);
+    assert!(codec.validate_all("Report", &[5, 15, 0, 150, 2]).is_empty());
+
+    let mut failures = codec.validate_all("Report", &[50, 1, 0, 10, 9]);
+    failures.sort_by(|x, y| x.0.cmp(&y.0));
+    let names: Vec<&str> = failures.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(names, ["Report", "a", "c", "d"]);
+    assert!(failures.iter().all(|(_, e)| matches!(e, CodecError::Validation(_))));
+    // decode_message stops at the first of them.
+    assert!(codec.decode_message("Report", &[50, 1, 0, 10, 9]).unwrap_err().to_string().contains("field a"));
+
+    // A decode that cannot finish is the only entry.
+    let failures = codec.validate_all("Report", &[1, 2]);
+    assert_eq!(failures.len(), 1);
+    assert!(matches!(failures[0], (ref n, CodecError::Truncated { .. }) if n == "Report"));
+}
+
+#[test]
+fn test_quantum_scaling() {
+    let dsl = r#"
+        message Track {
+          rho: u16 [0..2560] quantum "1/256 NM";
+          vx: i16 quantum "2^(-10) NM/s";
+          code: u8;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let bytes = [0x02, 0x80, 0xFE, 0x00, 7];
+
+    // Off by default: raw integers.
+    let raw = Codec::new(resolved.clone(), Endianness::Big);
+    let values = raw.decode_message("Track", &bytes).unwrap();
+    assert_eq!(values.get("rho"), Some(&Value::U16(640)));
+    assert_eq!(values.get("vx"), Some(&Value::I16(-512)));
+
+    let codec = Codec::new(resolved, Endianness::Big).with_quantum_scaling(true);
+    let values = codec.decode_message("Track", &bytes).unwrap();
+    assert_eq!(values.get("rho"), Some(&Value::Double(2.5)));
+    assert_eq!(values.get("vx"), Some(&Value::Double(-0.5)));
+    assert_eq!(values.get("code"), Some(&Value::U8(7)));
+    assert_eq!(codec.encode_message("Track", &values).unwrap(), bytes);
+    // Physical values are rounded to the nearest raw step; raw integers are still accepted.
+    let mut physical = values.clone();
+    physical.insert("rho".to_string(), Value::Double(2.501));
+    physical.insert("vx".to_string(), Value::I16(-512));
+    assert_eq!(codec.encode_message("Track", &physical).unwrap(), bytes);
+    // Constraints apply to the raw value: 2560 / 256 = 10 NM at most.
+    assert!(codec.decode_message("Track", &[0x0A, 0x01, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn test_numeric_value_conversions() {
+    let dsl = r#"
+        message Sample {
+          ratio: double;
+          gain: float;
+          level: u8;
+          offset: i16;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+    let mut values = HashMap::new();
+    values.insert("ratio".to_string(), Value::I32(7));
+    values.insert("gain".to_string(), Value::U16(3));
+    values.insert("level".to_string(), Value::Double(9.9));
+    values.insert("offset".to_string(), Value::Float(-2.5));
+    let bytes = codec.encode_message("Sample", &values).unwrap();
+    let decoded = codec.decode_message("Sample", &bytes).unwrap();
+    assert_eq!(decoded.get("ratio"), Some(&Value::Double(7.0)));
+    assert_eq!(decoded.get("gain"), Some(&Value::Float(3.0)));
+    // Floats given for integer fields are truncated toward zero.
+    assert_eq!(decoded.get("level"), Some(&Value::U8(9)));
+    assert_eq!(decoded.get("offset"), Some(&Value::I16(-2)));
+
+    assert_eq!(Value::I64(-3).as_f64(), Some(-3.0));
+    assert_eq!(Value::U8(200).as_f32(), Some(200.0));
+    assert_eq!(Value::Double(1.5).as_f32(), Some(1.5));
+    assert_eq!(Value::Double(-7.9).as_i64(), Some(-7));
+    assert_eq!(Value::Float(7.9).as_u64(), Some(7));
+    assert_eq!(Value::Bool(true).as_f64(), None);
+}
+
+#[test]
+fn test_decode_limits() {
+    let dsl = r#"
+        struct Inner { x: u8; }
+        struct Middle { inner: Inner; }
+        message Blob { data: list<u8>; }
+        message Nested { m: Middle; }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let limits = DecodeLimits { max_list_len: 1024, max_recursion_depth: 2, max_total_bytes: 64 };
+    let codec = Codec::new_with_limits(resolved.clone(), Endianness::Big, limits);
+    let shallow = Codec::new_with_limits(resolved.clone(), Endianness::Big, DecodeLimits { max_recursion_depth: 1, ..limits });
+    let unbounded = Codec::new(resolved.clone(), Endianness::Big);
+
+    // A count of 0xFFFFFFFF is rejected before any element is read.
+    let bytes = [0xFF, 0xFF, 0xFF, 0xFF, 1, 2, 3];
+    assert!(matches!(unbounded.decode_message("Blob", &bytes), Err(CodecError::Truncated { .. })));
+    let err = codec.decode_message("Blob", &bytes).unwrap_err();
+    assert!(matches!(err, CodecError::Validation(ref m) if m.contains("max_list_len 1024")), "{}", err);
+    assert_eq!(codec.decode_message("Blob", &[0, 0, 0, 2, 7, 8]).unwrap().get("data"), Some(&Value::List(vec![Value::U8(7), Value::U8(8)])));
+
+    // Middle is depth 1, Inner depth 2.
+    assert!(codec.decode_message("Nested", &[5]).is_ok());
+    let err = shallow.decode_message("Nested", &[5]).unwrap_err();
+    assert!(err.to_string().contains("max_recursion_depth 1"), "{}", err);
+
+    // The limit applies to the message, not to the bytes after it.
+    let mut long = vec![0, 0, 0, 61];
+    long.extend([0u8; 61]);
+    let err = codec.decode_message("Blob", &long).unwrap_err();
+    assert!(matches!(err, CodecError::Validation(ref m) if m.contains("max_total_bytes 64")), "{}", err);
+    assert!(codec.decode_message("Blob", &[0u8; 65]).is_ok());
+
+    // In a frame, each record is held to the limit on its own.
+    let small = Codec::new_with_limits(resolved, Endianness::Big, DecodeLimits { max_total_bytes: 8, ..limits });
+    let frame_bytes: Vec<u8> = [0, 0, 0, 2, 7, 8].repeat(4);
+    let result = frame::decode_frame(&small, "Blob", &frame_bytes, None).unwrap();
+    assert_eq!(result.messages.len(), 4);
+    assert!(result.removed.is_empty());
+    let mut frame_bytes = frame_bytes;
+    frame_bytes.extend([0, 0, 0, 5, 1, 2, 3, 4, 5]);
+    let result = frame::decode_frame(&small, "Blob", &frame_bytes, None).unwrap();
+    assert_eq!(result.messages.len(), 4);
+    assert_eq!(result.removed.len(), 1);
+    assert_eq!(result.removed[0].byte_range, (24, 33));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_json_roundtrip() {
+    let dsl = r#"
+        struct Pos { x: i16; y: i16; }
+        message Plot {
+          id: u16;
+          name: string(2);
+          pos: Pos;
+          levels: u8[2];
+          ratio: double;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+    let bytes = [0x12, 0x34, b'A', b'B', 0xFF, 0xFE, 0, 3, 1, 2, 0x3F, 0xF8, 0, 0, 0, 0, 0, 0];
+    let json = codec.decode_message_to_json("Plot", &bytes).unwrap();
+    assert_eq!(json["id"], serde_json::json!({ "U16": 4660 }));
+    assert_eq!(json["name"], serde_json::json!({ "Bytes": [65, 66] }));
+    assert_eq!(json["pos"]["Struct"]["x"], serde_json::json!({ "I16": -2 }));
+    assert_eq!(json["levels"], serde_json::json!({ "List": [{ "U8": 1 }, { "U8": 2 }] }));
+    assert_eq!(json["ratio"], serde_json::json!({ "Double": 1.5 }));
+    assert_eq!(codec.encode_message_from_json("Plot", &json).unwrap(), bytes);
+
+    // Untagged numbers are not values.
+    let err = codec.encode_message_from_json("Plot", &serde_json::json!({ "id": 4660 })).unwrap_err();
+    assert!(matches!(err, CodecError::Validation(ref m) if m.starts_with("JSON:")), "{}", err);
+}
+
+#[test]
+fn test_bytes_list() {
+    let dsl = r#"
+        struct Entry { id: u8; value: u16; }
+        message Packet {
+            kind: u8;
+            size: length_of<u16>(entries);
+            entries: bytes_list(size)<Entry>;
+            tail: u8;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let mut codec = Codec::new(resolved.clone(), Endianness::Big);
+    let entry = |id: u8, value: u16| Value::Struct(HashMap::from([("id".to_string(), Value::U8(id)), ("value".to_string(), Value::U16(value))]));
+    let mut values = HashMap::from([
+        ("kind".to_string(), Value::U8(1)),
+        ("size".to_string(), Value::U16(6)),
+        ("entries".to_string(), Value::List(vec![entry(7, 0x0102), entry(8, 0x0304)])),
+        ("tail".to_string(), Value::U8(0xEE)),
+    ]);
+    let bytes = codec.encode_message("Packet", &values).unwrap();
+    assert_eq!(bytes, [1, 0, 6, 7, 1, 2, 8, 3, 4, 0xEE]);
+    let decoded = codec.decode_message("Packet", &bytes).unwrap();
+    assert_eq!(decoded.get("entries"), values.get("entries"));
+    assert_eq!(decoded.get("tail"), Some(&Value::U8(0xEE)));
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Packet").unwrap(), bytes.len());
+    assert!(validate_message_in_place(&bytes, 0, &resolved, WalkEndianness::Big, "Packet").is_ok());
+    let mut zeroed = bytes.clone();
+    zero_padding_reserved_in_place(&mut zeroed, 0, &resolved, WalkEndianness::Big, "Packet").unwrap();
+    assert_eq!(zeroed, bytes);
+    assert_eq!(resolved.max_size("Packet"), Some(4 + 65535));
+
+    // The byte length must match the elements on encode, and hold whole elements on decode.
+    values.insert("size".to_string(), Value::U16(5));
+    let err = codec.encode_message("Packet", &values).unwrap_err();
+    assert!(err.to_string().contains("elements take 6 bytes but size is 5"), "{}", err);
+    let err = codec.decode_message("Packet", &[1, 0, 4, 7, 1, 2, 8, 3, 4, 0xEE]).unwrap_err();
+    assert!(err.to_string().contains("overruns the byte length 4"), "{}", err);
+    assert!(matches!(codec.decode_message("Packet", &[1, 0, 9, 7, 1, 2]), Err(CodecError::Truncated { .. })));
+
+    // With auto_length the size follows the elements.
+    codec.encode_options.auto_length = true;
+    values.insert("entries".to_string(), Value::List(vec![entry(9, 5)]));
+    assert_eq!(codec.encode_message("Packet", &values).unwrap(), [1, 0, 3, 9, 0, 5, 0xEE]);
+}
+
+#[test]
+fn test_aligned_sub_byte_sized_int_packs() {
+    // A `u8(4)` starting on a byte boundary shares its byte with the bits after it, on encode as on decode.
+    let dsl = "message M { d: u8(4); e: bitfield(4); f: u8; }";
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap(), Endianness::Big);
+    let values = HashMap::from([
+        ("d".to_string(), Value::U8(5)),
+        ("e".to_string(), Value::U64(0xA)),
+        ("f".to_string(), Value::U8(0x33)),
+    ]);
+    let bytes = codec.encode_message("M", &values).unwrap();
+    assert_eq!(bytes, [0xA5, 0x33]);
+    assert_eq!(codec.decode_message("M", &bytes).unwrap(), values);
+}
+
+#[test]
+fn test_bit_order() {
+    let dsl = "message Flags { a: bitfield(3); d: u8(4); b: bitfield(1); c: u16(12); }";
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let values = HashMap::from([
+        ("a".to_string(), Value::U64(0b101)),
+        ("b".to_string(), Value::U64(1)),
+        ("c".to_string(), Value::U16(0xABC)),
+        ("d".to_string(), Value::U8(5)),
+    ]);
+    // Default: bit 0 first, low value bits first.
+    let lsb = Codec::new(resolved.clone(), Endianness::Big);
+    assert_eq!(lsb.bit_order, BitOrder::Lsb0);
+    let bytes = lsb.encode_message("Flags", &values).unwrap();
+    assert_eq!(bytes, [0b1010_1101, 0x0A, 0xBC]);
+    assert_eq!(lsb.decode_message("Flags", &bytes).unwrap(), values);
+
+    let msb = Codec::new(resolved, Endianness::Big).with_bit_order(BitOrder::Msb0);
+    let bytes = msb.encode_message("Flags", &values).unwrap();
+    assert_eq!(bytes, [0b1010_1011, 0x0A, 0xBC]);
+    assert_eq!(msb.decode_message("Flags", &bytes).unwrap(), values);
+}
+
+#[test]
+fn test_enum_field() {
+    let dsl = r#"
+enum StatusKind : u16 {
+  OK = 0;
+  FAILED = 0x0102;
+}
+message Report {
+  status: enum<StatusKind>;
+  tail: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    let values = codec.decode_message("Report", &[0x01, 0x02, 9]).unwrap();
+    let failed = Value::Enum { name: "StatusKind".to_string(), variant: Some("FAILED".to_string()), raw: 0x0102 };
+    assert_eq!(values.get("status"), Some(&failed));
+    assert_eq!(codec.encode_message("Report", &values).unwrap(), [0x01, 0x02, 9]);
+
+    // A value no variant has still decodes, without a name.
+    let values = codec.decode_message("Report", &[0x00, 0x07, 9]).unwrap();
+    let unknown = Value::Enum { name: "StatusKind".to_string(), variant: None, raw: 7 };
+    assert_eq!(values.get("status"), Some(&unknown));
+
+    // Encoding takes the variant name or the raw integer.
+    let by_name = HashMap::from([("status".to_string(), Value::Bytes(b"OK".to_vec())), ("tail".to_string(), Value::U8(1))]);
+    assert_eq!(codec.encode_message("Report", &by_name).unwrap(), [0, 0, 1]);
+    let by_raw = HashMap::from([("status".to_string(), Value::U32(0x0102)), ("tail".to_string(), Value::U8(1))]);
+    assert_eq!(codec.encode_message("Report", &by_raw).unwrap(), [0x01, 0x02, 1]);
+    let bad_name = HashMap::from([("status".to_string(), Value::Bytes(b"GONE".to_vec())), ("tail".to_string(), Value::U8(1))]);
+    assert!(matches!(codec.encode_message("Report", &bad_name), Err(CodecError::Validation(_))));
+    let too_wide = HashMap::from([("status".to_string(), Value::U32(0x10000)), ("tail".to_string(), Value::U8(1))]);
+    assert!(matches!(codec.encode_message("Report", &too_wide), Err(CodecError::Validation(_))));
+
+    let err = ResolvedProtocol::resolve(parse("message M { s: enum<Missing>; }").unwrap()).unwrap_err();
+    assert!(err.contains("no enum named Missing"), "{}", err);
+}
+
+#[test]
+fn test_select_field() {
+    let dsl = r#"
+struct Foo { a: u8; }
+struct Bar { x: u16; y: u16; }
+struct Raw { data: remaining; }
+message Rec {
+  kind: u8;
+  body: select<kind> { 1: Foo, 0x02: Bar, default: Raw };
+}
+message Strict {
+  kind: u8;
+  body: select<kind> { 1: Foo, 2: Bar };
+  tail: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let rec = |kind: u8, body: &[(&str, Value)]| {
+        let body = body.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+        HashMap::from([("kind".to_string(), Value::U8(kind)), ("body".to_string(), Value::Struct(body))])
+    };
+
+    let foo = rec(1, &[("a", Value::U8(7))]);
+    let bytes = codec.encode_message("Rec", &foo).unwrap();
+    assert_eq!(bytes, [1, 7]);
+    assert_eq!(codec.decode_message("Rec", &bytes).unwrap(), foo);
+
+    let bar = rec(2, &[("x", Value::U16(0x0102)), ("y", Value::U16(3))]);
+    let bytes = codec.encode_message("Rec", &bar).unwrap();
+    assert_eq!(bytes, [2, 0x01, 0x02, 0, 3]);
+    assert_eq!(codec.decode_message("Rec", &bytes).unwrap(), bar);
+    let walk = WalkEndianness::from(Endianness::Big);
+    assert_eq!(message_extent(&bytes, 0, &resolved, walk, "Rec").unwrap(), 5);
+    assert_eq!(BinaryWalker::new(&bytes, &resolved, walk).read_field("Rec", "body.y").unwrap(), 3);
+
+    // A kind with no case takes the default.
+    let raw = rec(9, &[("data", Value::Bytes(vec![0xAA, 0xBB]))]);
+    let bytes = codec.encode_message("Rec", &raw).unwrap();
+    assert_eq!(bytes, [9, 0xAA, 0xBB]);
+    assert_eq!(codec.decode_message("Rec", &bytes).unwrap(), raw);
+
+    // Without a default it is an error, on decode, encode and walk.
+    let err = codec.decode_message("Strict", &[3, 0, 0]).unwrap_err();
+    assert!(matches!(&err, CodecError::Validation(m) if m.contains("no case for value 3")), "{:?}", err);
+    assert!(codec.encode_message("Strict", &rec(3, &[])).is_err());
+    assert!(message_extent(&[3, 0, 0], 0, &resolved, walk, "Strict").is_err());
+    assert_eq!(message_extent(&[1, 7, 0], 0, &resolved, walk, "Strict").unwrap(), 3);
+}
+
+#[test]
+fn test_decode_frame_trailing_range() {
+    let dsl = "message Rec { a: u8; b: u16; }";
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    // Two records, then the first two bytes of a third.
+    let frame_bytes = [1, 0, 2, 3, 0, 4, 5, 0];
+    let result = frame::decode_frame(&codec, "Rec", &frame_bytes, None).unwrap();
+    assert_eq!(result.messages.len(), 2);
+    assert!(result.removed.iter().all(|r| matches!(r.cause, RemovalReason::Truncated { .. })));
+    assert_eq!(result.trailing_range, (6, 8));
+    assert_eq!(&frame_bytes[result.trailing_range.0..result.trailing_range.1], [5, 0]);
+
+    // Offsets count the transport header; a frame of whole records leaves nothing.
+    let result = frame::decode_frame(&codec, "Rec", &[0xEE, 1, 0, 2], Some(1)).unwrap();
+    assert_eq!(result.messages.len(), 1);
+    assert_eq!(result.trailing_range, (4, 4));
+
+    let mut stream = frame::StreamDecoder::new(&codec, "Rec");
+    let result = stream.push(&frame_bytes).unwrap();
+    assert_eq!(result.messages.len(), 2);
+    assert_eq!(result.trailing_range, (6, 8));
+    assert_eq!(stream.pending(), [5, 0]);
+}
+
+#[test]
+fn test_decode_frame_selected_mixed_records() {
+    let dsl = r#"
+transport {
+  block: u8;
+}
+inner_transport {
+  kind: u8;
+}
+payload {
+  messages: Short, Long;
+  selector: kind -> 1: Short, 2: Long;
+}
+message Short { a: u8; }
+message Long { a: u8; b: u16; }
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    // A Long, a Short, then a record of kind 9.
+    let frame_bytes = [7, 2, 0x10, 0x01, 0x02, 1, 0x20, 9, 0xAA, 0xBB];
+    let result = frame::decode_frame_selected(&codec, &resolved, &frame_bytes, Some(1)).unwrap();
+    let names: Vec<&str> = result.messages.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["Long", "Short"]);
+    assert_eq!(result.messages[0].values.get("b"), Some(&Value::U16(0x0102)));
+    assert_eq!(result.messages[0].byte_range, (1, 5));
+    assert_eq!(result.messages[1].values.get("a"), Some(&Value::U8(0x20)));
+    assert_eq!(result.messages[1].byte_range, (5, 7));
+    assert!(result.removed.is_empty());
+    assert_eq!(result.unknown.len(), 1);
+    assert_eq!(result.unknown[0].header.get("kind"), Some(&Value::U8(9)));
+    assert_eq!(result.unknown[0].byte_range, (7, 10));
+
+    // Plain decode_frame never reports unknown records.
+    assert!(frame::decode_frame(&codec, "Short", &frame_bytes, Some(1)).unwrap().unknown.is_empty());
+}
+
+#[test]
+fn test_zero_spare_bits_only() {
+    let dsl = "message Flags { kind: bitfield(4); spare: padding(4, bits); mode: bitfield(2); rest: padding(6, bits); tail: u8; }";
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    // Bits are packed LSB first: kind is the low nibble of byte 0 and spare its high nibble.
+    let mut bytes = [0xA5, 0xFE, 0xFF];
+    zero_padding_reserved_in_place(&mut bytes, 0, &resolved, WalkEndianness::Big, "Flags").unwrap();
+    assert_eq!(bytes, [0x05, 0x02, 0xFF]);
+
+    let codec = Codec::new(resolved, Endianness::Big);
+    let values = codec.decode_message("Flags", &[0xA5, 0xFE, 0xFF]).unwrap();
+    assert_eq!(codec.encode_message("Flags", &values).unwrap(), bytes);
+}
+
+#[test]
+fn test_write_field_in_place() {
+    let dsl = r#"
+struct Pos { x: u16; y: u16; }
+message Track {
+  flags: u8;
+  number: u16;
+  quality: u16(12);
+  mode: bitfield(4);
+  spare: padding(4, bits);
+  pos: optional<Pos>;
+  tail: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let mut values = HashMap::from([
+        ("flags".to_string(), Value::U8(0x81)),
+        ("number".to_string(), Value::U16(0x1234)),
+        ("quality".to_string(), Value::U16(0x0ABC)),
+        ("mode".to_string(), Value::U64(5)),
+        ("spare".to_string(), Value::Padding),
+        ("pos".to_string(), Value::List(vec![])),
+        ("tail".to_string(), Value::U8(0xEE)),
+    ]);
+    let mut bytes = codec.encode_message("Track", &values).unwrap();
+    let write = |bytes: &mut Vec<u8>, field: &str, value: u64| {
+        write_field_in_place(bytes, 0, &resolved, WalkEndianness::Big, "Track", field, value)
+    };
+
+    write(&mut bytes, "flags", 0).unwrap();
+    write(&mut bytes, "number", 0xBEEF).unwrap();
+    write(&mut bytes, "quality", 0x123).unwrap();
+    write(&mut bytes, "mode", 9).unwrap();
+    values.insert("flags".to_string(), Value::U8(0));
+    values.insert("number".to_string(), Value::U16(0xBEEF));
+    values.insert("quality".to_string(), Value::U16(0x123));
+    values.insert("mode".to_string(), Value::U64(9));
+    assert_eq!(bytes, codec.encode_message("Track", &values).unwrap());
+    assert_eq!(codec.decode_message("Track", &bytes).unwrap(), values);
+
+    assert!(matches!(write(&mut bytes, "quality", 0x1000), Err(CodecError::Validation(_))));
+    assert!(matches!(write(&mut bytes, "flags", 256), Err(CodecError::Validation(_))));
+    // The struct is behind an absent optional.
+    let err = write(&mut bytes, "pos.x", 1).unwrap_err();
+    assert!(err.to_string().contains("not present"), "{}", err);
+    assert!(matches!(write(&mut bytes, "pos", 1), Err(CodecError::Validation(_))));
+}
+
+#[test]
+fn test_unknown_bytes_roundtrip_and_lint() {
+    let dsl = "message Probe {\n\tid: u8;\n\tmystery: unknown(4);\n\tcrc: u16;\n}\n";
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    // Unlike padding, the unknown bytes survive a decode/encode roundtrip.
+    let bytes = [7, 0xde, 0xad, 0xbe, 0xef, 0x12, 0x34];
+    let values = codec.decode_message("Probe", &bytes).unwrap();
+    assert_eq!(values.get("mystery"), Some(&Value::Bytes(vec![0xde, 0xad, 0xbe, 0xef])));
+    assert_eq!(codec.encode_message("Probe", &values).unwrap(), bytes);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Probe").unwrap(), bytes.len());
+    assert!(matches!(codec.decode_message("Probe", &bytes[..4]), Err(CodecError::Truncated { .. })));
+
+    let mut wrong = values.clone();
+    wrong.insert("mystery".to_string(), Value::Bytes(vec![1, 2]));
+    assert!(matches!(codec.encode_message("Probe", &wrong), Err(CodecError::Validation(_))));
+    assert!(parse("message M { x: unknown(0); }").is_err());
+
+    let msgs = lint(dsl);
+    let incomplete: Vec<_> = msgs.iter().filter(|m| m.rule == LintRule::IncompleteField).collect();
+    assert_eq!(incomplete.len(), 1, "{:?}", msgs);
+    assert_eq!((incomplete[0].line, incomplete[0].column), (3, 2));
+    assert_eq!(incomplete[0].severity, Severity::Warning);
+    assert!(incomplete[0].message.contains("`mystery`"), "{}", incomplete[0].message);
+}
+
+#[test]
+fn test_optional_with_wide_presence_flag() {
+    let dsl = r#"
+        message Opt {
+          a: optional(u16)<u32>;
+          b: optional<u8>;
+          tail: u8;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    // A flag of 0x0100 is non-zero only in its first byte: both bytes must be read.
+    let present = [0x01, 0x00, 0xde, 0xad, 0xbe, 0xef, 0x00, 0x07];
+    let absent = [0x00, 0x00, 0x01, 0x05, 0x07];
+    for (bytes, a) in [(&present[..], Value::U32(0xdeadbeef)), (&absent[..], Value::List(vec![]))] {
+        let (consumed, values) = codec.decode_message_with_extent("Opt", bytes);
+        let values = values.unwrap();
+        assert_eq!(values.get("a"), Some(&a));
+        assert_eq!(values.get("tail"), Some(&Value::U8(7)));
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(message_extent(bytes, 0, &resolved, WalkEndianness::Big, "Opt").unwrap(), consumed);
+    }
+    // Encode takes a present optional as a one-element list or as the bare decoded value.
+    let canonical = [0x00, 0x01, 0xde, 0xad, 0xbe, 0xef, 0x00, 0x07];
+    assert_eq!(codec.encode_message("Opt", &codec.decode_message("Opt", &present).unwrap()).unwrap(), canonical);
+    let values = |a: Vec<Value>, b: Vec<Value>| {
+        HashMap::from([
+            ("a".to_string(), Value::List(a)),
+            ("b".to_string(), Value::List(b)),
+            ("tail".to_string(), Value::U8(7)),
+        ])
+    };
+    assert_eq!(codec.encode_message("Opt", &values(vec![], vec![Value::U8(5)])).unwrap(), absent);
+    let encoded = codec.encode_message("Opt", &values(vec![Value::U32(0xdeadbeef)], vec![])).unwrap();
+    assert_eq!(encoded, canonical);
+
+    assert!(parse("message M { a: optional(i16)<u8>; }").is_err());
+}
+
+#[test]
+fn test_max_size_bounded_and_unbounded() {
+    let dsl = r#"
+        struct Item {
+          id: u16;
+          extra: optional<u8>;
+        }
+        message Bounded {
+          n: count_of(items) [0..4];
+          items: list<Item>;
+          tag: u8;
+        }
+        struct Pair {
+          a: u8;
+          b: u8;
+        }
+        message Sized {
+          n: u8;
+          xs: Pair[n];
+        }
+        message Open {
+          items: list<u8>;
+        }
+        message Fixed {
+          a: u32;
+          b: u16(12);
+          c: bitfield(4);
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    // count_of (4 bytes) + list count (4) + 4 items of id, flag and extra (4 bytes each) + tag.
+    assert_eq!(resolved.max_size("Bounded"), Some(25));
+    let item = Value::Struct(HashMap::from([
+        ("id".to_string(), Value::U16(1)),
+        ("extra".to_string(), Value::List(vec![Value::U8(2)])),
+    ]));
+    let worst = HashMap::from([
+        ("n".to_string(), Value::U32(4)),
+        ("items".to_string(), Value::List(vec![item; 4])),
+        ("tag".to_string(), Value::U8(0)),
+    ]);
+    assert_eq!(codec.encode_message("Bounded", &worst).unwrap().len(), 25);
+
+    // An array counted by a u8 field holds at most 255 elements.
+    assert_eq!(resolved.max_size("Sized"), Some(1 + 255 * 2));
+    assert_eq!(resolved.max_size("Fixed"), Some(6));
+    assert_eq!(resolved.max_size("Open"), None);
+    assert_eq!(resolved.max_size("Missing"), None);
+}
+
+#[test]
+fn test_decode_message_with_field_override() {
+    use std::io::Read;
+    let dsl = r#"
+        message Odd {
+          kind: u8;
+          stamp: u16;
+          level: u8 [0..9];
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    // On the wire `stamp` is really 3 bytes; the callback reads it, the other fields decode as declared.
+    let bytes = [1, 0x01, 0x02, 0x03, 7];
+    let mut offered = Vec::new();
+    let values = codec
+        .decode_message_with("Odd", &bytes, |name, r| {
+            offered.push(name.to_string());
+            (name == "stamp").then(|| {
+                let mut buf = [0u8; 3];
+                r.read_exact(&mut buf).map_err(CodecError::Io)?;
+                Ok(Value::U32(u32::from_be_bytes([0, buf[0], buf[1], buf[2]])))
+            })
+        })
+        .unwrap();
+    assert_eq!(offered, ["kind", "stamp", "level"]);
+    assert_eq!(values.get("stamp"), Some(&Value::U32(0x010203)));
+    assert_eq!(values.get("level"), Some(&Value::U8(7)));
+
+    // Overridden values are validated; callback errors name the field.
+    let level = |v: Result<Value, CodecError>| {
+        let mut v = Some(v);
+        move |name: &str, _: &mut std::io::Cursor<&[u8]>| if name == "level" { v.take() } else { None }
+    };
+    assert!(matches!(codec.decode_message_with("Odd", &[1, 0, 0, 0], level(Ok(Value::U8(10)))), Err(CodecError::Validation(_))));
+    let err = codec
+        .decode_message_with("Odd", &[1, 0, 0, 0], level(Err(CodecError::Validation("bad level".to_string()))))
+        .unwrap_err();
+    assert!(err.to_string().contains("field level") && err.to_string().contains("bad level"), "{}", err);
 }
 
 #[test]
-fn test_validation_constraint() {
-    let protocol = parse(WITH_CONSTRAINTS).expect("parse");
-    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
-    let codec = Codec::new(resolved, Endianness::Little);
+fn test_empty_messages_and_structs() {
+    let dsl = r#"
+        struct Nothing {}
+        struct OnlyPresence {
+          fspec: presence_bits(1);
+        }
+        message Empty {}
+        message Holder {
+          a: bitfield(4);
+          n: Nothing;
+          b: bitfield(4);
+          p: OnlyPresence;
+          fspec: bitmap(7, 7);
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let endianness = WalkEndianness::Big;
+
+    // An empty message encodes to nothing and decodes from nothing, even with bytes after it.
+    let empty = codec.encode_message("Empty", &HashMap::new()).unwrap();
+    assert!(empty.is_empty());
+    assert_eq!(codec.decode_message_with_extent("Empty", &[]).0, 0);
+    assert!(codec.decode_message("Empty", &[0xff]).unwrap().is_empty());
+    assert_eq!(message_extent(&[], 0, &resolved, endianness, "Empty").unwrap(), 0);
+    assert!(validate_message_in_place(&[], 0, &resolved, endianness, "Empty").is_ok());
+    assert!(zero_padding_reserved_in_place(&mut [], 0, &resolved, endianness, "Empty").is_ok());
+    assert_eq!(resolved.max_size("Empty"), Some(0));
+
+    // An empty struct between bit fields does not break the bit group; presence fields without any
+    // following optional still take their bytes.
+    let values = HashMap::from([
+        ("a".to_string(), Value::U64(1)),
+        ("n".to_string(), Value::Struct(HashMap::new())),
+        ("b".to_string(), Value::U64(2)),
+        ("p".to_string(), Value::Struct(HashMap::from([("fspec".to_string(), Value::U64(0))]))),
+    ]);
+    let bytes = codec.encode_message("Holder", &values).unwrap();
+    assert_eq!(bytes, [0x21, 0x00, 0x00]);
+    let (consumed, decoded) = codec.decode_message_with_extent("Holder", &bytes);
+    assert_eq!(consumed, 3);
+    let decoded = decoded.unwrap();
+    assert_eq!(decoded.get("n"), Some(&Value::Struct(HashMap::new())));
+    assert_eq!(decoded.get("b"), Some(&Value::U64(2)));
+    assert_eq!(message_extent(&bytes, 0, &resolved, endianness, "Holder").unwrap(), 3);
+    assert!(validate_message_in_place(&bytes, 0, &resolved, endianness, "Holder").is_ok());
+    assert!(BinaryWalker::new(&bytes, &resolved, endianness).first_violation("Holder").unwrap().is_none());
+
+    // A list of empty elements would take its (up to 2^32 - 1) count on trust.
+    let err = ResolvedProtocol::resolve(parse("struct Nothing {} message L { xs: list<Nothing>; }").unwrap()).unwrap_err();
+    assert!(err.contains("L.xs: list elements have zero size"), "{}", err);
+    assert!(ResolvedProtocol::resolve(parse("struct Nothing {} message R { xs: rep_list<Nothing>; }").unwrap()).is_ok());
+    // A corrupt count fails as truncated instead of reserving memory for it.
+    let small = Codec::new(ResolvedProtocol::resolve(parse("message S { xs: list<u32>; }").unwrap()).unwrap(), Endianness::Big);
+    assert!(matches!(small.decode_message("S", &[0x7f, 0xff, 0xff, 0xff, 0]), Err(CodecError::Truncated { .. })));
+}
 
-    let mut values = HashMap::new();
-    values.insert("kind".to_string(), Value::U8(5));
-    values.insert("value".to_string(), Value::U32(500));
-    let encoded = codec.encode_message("Bounded", &values).expect("encode");
-    let decoded = codec.decode_message("Bounded", &encoded).expect("decode");
-    assert_eq!(decoded.get("kind").and_then(Value::as_u64), Some(5));
+#[test]
+fn test_endianness_from_version_field() {
+    let dsl = r#"
+        inner_transport {
+          version: u8;
+        }
+        endianness from version { 1: big, 2: little }
+        message Reading {
+          id: u16;
+          value: u32;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
 
-    // Decode invalid data (kind = 20 out of range) - should fail validation but still consume bytes
-    let mut bad = encoded.clone();
-    if bad.len() >= 1 {
-        bad[0] = 20;
+    // One frame holding a version 1 (big endian) and a version 2 (little endian) record.
+    let frame_bytes = [
+        0x01, 0x12, 0x34, 0x00, 0x00, 0x56, 0x78, //
+        0x02, 0x34, 0x12, 0x78, 0x56, 0x00, 0x00,
+    ];
+    let result = frame::decode_frame(&codec, "Reading", &frame_bytes, None).unwrap();
+    assert!(result.removed.is_empty());
+    assert_eq!(result.messages.len(), 2);
+    for msg in &result.messages {
+        assert_eq!(msg.values.get("id").and_then(Value::as_u64), Some(0x1234));
+        assert_eq!(msg.values.get("value").and_then(Value::as_u64), Some(0x5678));
     }
-    let (consumed, result) = codec.decode_message_with_extent("Bounded", &bad);
-    assert!(consumed > 0);
-    assert!(result.is_err());
+    assert_eq!(frame::encode_frame_with_compliant_only(&codec, "Reading", &result, None, None).unwrap(), frame_bytes);
+
+    // Decoding the parts separately: the header picks the order the message is decoded in.
+    let (_, header) = codec.decode_inner_transport_with_extent(&frame_bytes[7..]);
+    let order = codec.endianness_for(&header.unwrap()).unwrap();
+    assert_eq!(order, Some(Endianness::Little));
+    let values = codec.decode_message_as("Reading", &frame_bytes[8..], Endianness::Little).unwrap();
+    assert_eq!(values.get("id").and_then(Value::as_u64), Some(0x1234));
+    assert_eq!(codec.encode_message_as("Reading", &values, Endianness::Little).unwrap(), &frame_bytes[8..]);
+
+    // An unmapped version is rejected.
+    let unmapped = HashMap::from([("version".to_string(), Value::U8(3))]);
+    assert!(matches!(codec.endianness_for(&unmapped), Err(CodecError::Validation(_))));
+    let result = frame::decode_frame(&codec, "Reading", &[0x03, 0, 0, 0, 0, 0, 0], None).unwrap();
+    assert!(result.messages.is_empty());
+    assert_eq!(result.removed.len(), 1);
+
+    // The switch also applies inside a message, to the fields after the version.
+    let dsl = r#"
+        endianness from version { 1: big, 0x02: little }
+        message Versioned {
+          version: u8;
+          value: u16;
+        }
+    "#;
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap(), Endianness::Big);
+    let v1 = codec.decode_message("Versioned", &[0x01, 0x12, 0x34]).unwrap();
+    let v2 = codec.decode_message("Versioned", &[0x02, 0x34, 0x12]).unwrap();
+    assert_eq!(v1.get("value").and_then(Value::as_u64), Some(0x1234));
+    assert_eq!(v2.get("value").and_then(Value::as_u64), Some(0x1234));
+    assert_eq!(codec.encode_message("Versioned", &v2).unwrap(), [0x02, 0x34, 0x12]);
+    assert!(codec.decode_message("Versioned", &[0x05, 0x12, 0x34]).is_err());
+
+    // The walkers follow the switch too: splitting records whose extent depends on the byte order...
+    let dsl = r#"
+        inner_transport {
+          version: u8;
+        }
+        endianness from version { 1: big, 2: little }
+        message Reading {
+          data: list<u8>;
+        }
+    "#;
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap(), Endianness::Big);
+    let frame_bytes = [
+        0x02, 0x01, 0x00, 0x00, 0x00, 0xAA, //
+        0x02, 0x02, 0x00, 0x00, 0x00, 0xBB, 0xCC,
+    ];
+    let result = frame::decode_frame(&codec, "Reading", &frame_bytes, None).unwrap();
+    assert!(result.removed.is_empty());
+    assert_eq!(result.messages.len(), 2);
+    let ranges = frame::split_only(&codec, "Reading", &frame_bytes, None).unwrap();
+    assert_eq!(ranges, vec![(1, 6), (7, 13)]);
+
+    // ...and walking a message that switches after its version field.
+    let dsl = r#"
+        endianness from version { 1: big, 2: little }
+        message M {
+          version: u8;
+          data: list<u8>;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    for bytes in [&[0x02, 0x02, 0x00, 0x00, 0x00, 0xBB, 0xCC][..], &[0x01, 0x00, 0x00, 0x00, 0x01, 0xAA]] {
+        let (consumed, values) = codec.decode_message_with_extent("M", bytes);
+        assert!(values.is_ok());
+        assert_eq!(message_extent(bytes, 0, &resolved, WalkEndianness::Big, "M").unwrap(), consumed);
+        validate_message_in_place(bytes, 0, &resolved, WalkEndianness::Big, "M").unwrap();
+    }
+
+    // Writing in place uses the order the record switched to.
+    let dsl = r#"
+        endianness from version { 1: big, 2: little }
+        message Versioned {
+          version: u8;
+          x: u16;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let mut bytes = [0x02, 0x00, 0x00];
+    write_field_in_place(&mut bytes, 0, &resolved, WalkEndianness::Big, "Versioned", "x", 0x1234).unwrap();
+    assert_eq!(bytes, [0x02, 0x34, 0x12]);
+    assert_eq!(codec.decode_message("Versioned", &bytes).unwrap().get("x").and_then(Value::as_u64), Some(0x1234));
+
+    // A switch inside an embedded message ends with it: the parent's later fields keep the outer order.
+    let dsl = r#"
+        endianness from version { 1: big, 2: little }
+        message Inner {
+          version: u8;
+          x: u16;
+        }
+        message Outer {
+          inner: Inner;
+          after: u16;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let bytes = [0x02, 0x34, 0x12, 0x12, 0x34];
+    let values = codec.decode_message("Outer", &bytes).unwrap();
+    assert_eq!(values.get("after").and_then(Value::as_u64), Some(0x1234));
+    assert_eq!(codec.encode_message("Outer", &values).unwrap(), bytes);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Outer").unwrap(), bytes.len());
+
+    let err = ResolvedProtocol::resolve(parse("endianness from missing { 1: big }\nmessage M { a: u8; }").unwrap()).unwrap_err();
+    assert!(err.contains("endianness from missing"), "{}", err);
+    assert!(parse("endianness from a { 1: big, 1: little }\nmessage M { a: u8; }").is_err());
 }
 
 #[test]
-fn test_validation_constraint_multiple_intervals() {
-    // Range can be a concatenation of intervals; value valid if in any interval
-    let src = r#"
-message MultiRange {
-  code: u8 [0..2, 10..15, 100..100];
+fn test_diff_records_reports_nested_field() {
+    let dsl = r#"
+        struct Position {
+          x: i16;
+          y: i16;
+        }
+        message Track {
+          id: u16;
+          pos: Position;
+          history: list<Position>;
+        }
+    "#;
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap(), Endianness::Big);
+    let pos = |x: i16, y: i16| Value::Struct(HashMap::from([("x".to_string(), Value::I16(x)), ("y".to_string(), Value::I16(y))]));
+    let original = HashMap::from([
+        ("id".to_string(), Value::U16(7)),
+        ("pos".to_string(), pos(1, 2)),
+        ("history".to_string(), Value::List(vec![pos(0, 0), pos(1, 1)])),
+    ]);
+    let decoded = codec.decode_message("Track", &codec.encode_message("Track", &original).unwrap()).unwrap();
+    assert!(diff_records(&original, &decoded).is_empty());
+
+    let mut changed = decoded.clone();
+    changed.insert("pos".to_string(), pos(1, -2));
+    assert_eq!(
+        diff_records(&decoded, &changed),
+        [FieldDiff { path: "pos.y".to_string(), left: Some(Value::I16(2)), right: Some(Value::I16(-2)) }]
+    );
+
+    // List elements are compared by index; missing fields and elements are reported one-sided.
+    changed.insert("history".to_string(), Value::List(vec![pos(0, 0), pos(1, 5), pos(2, 2)]));
+    changed.remove("id");
+    let paths: Vec<(String, bool, bool)> =
+        diff_records(&decoded, &changed).into_iter().map(|d| (d.path, d.left.is_some(), d.right.is_some())).collect();
+    assert_eq!(
+        paths,
+        [
+            ("history[1].y".to_string(), true, true),
+            ("history[2]".to_string(), false, true),
+            ("id".to_string(), true, false),
+            ("pos.y".to_string(), true, true),
+        ]
+    );
 }
-"#;
-    let protocol = parse(src).expect("parse");
-    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
-    let codec = Codec::new(resolved, Endianness::Little);
 
-    for (val, valid) in [(0u8, true), (2, true), (5, false), (10, true), (15, true), (20, false), (100, true)] {
-        let mut values = HashMap::new();
-        values.insert("code".to_string(), Value::U8(val));
-        let encoded = codec.encode_message("MultiRange", &values).expect("encode");
-        let result = codec.decode_message("MultiRange", &encoded);
-        if valid {
-            let decoded = result.expect("decode");
-            assert_eq!(decoded.get("code").and_then(Value::as_u64), Some(val as u64));
-        } else {
-            assert!(result.is_err(), "value {} should fail validation", val);
+#[test]
+fn test_length_prefixed_list_roundtrip() {
+    let dsl = r#"
+        message Record {
+          kind: u8;
+          payload: remaining;
         }
-    }
+        message Batch {
+          id: u8;
+          records: length_prefixed_list(u16)<Record>;
+          crc: u16;
+        }
+    "#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let record = |kind: u8, payload: &[u8]| {
+        Value::Struct(HashMap::from([
+            ("kind".to_string(), Value::U8(kind)),
+            ("payload".to_string(), Value::Bytes(payload.to_vec())),
+        ]))
+    };
+    let values = HashMap::from([
+        ("id".to_string(), Value::U8(9)),
+        ("records".to_string(), Value::List(vec![record(1, &[0xaa, 0xbb]), record(2, &[])])),
+        ("crc".to_string(), Value::U16(0xbeef)),
+    ]);
+    let bytes = codec.encode_message("Batch", &values).unwrap();
+    // `remaining` stops at each element's length, so the field after the list still decodes.
+    assert_eq!(bytes, [9, 0, 0, 0, 2, 0, 3, 1, 0xaa, 0xbb, 0, 1, 2, 0xbe, 0xef]);
+    assert_eq!(codec.decode_message("Batch", &bytes).unwrap(), values);
+    assert_eq!(message_extent(&bytes, 0, &resolved, WalkEndianness::Big, "Batch").unwrap(), bytes.len());
+    assert!(validate_message_in_place(&bytes, 0, &resolved, WalkEndianness::Big, "Batch").is_ok());
+
+    // An element needing more bytes than its length is rejected, as is one leaving bytes unread.
+    let dsl = r#"
+        struct Pair {
+          a: u16;
+          b: u16;
+        }
+        message Pairs {
+          items: length_prefixed_list(u8)<Pair>;
+        }
+    "#;
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap(), Endianness::Big);
+    assert!(codec.decode_message("Pairs", &[0, 0, 0, 1, 4, 0, 1, 0, 2]).is_ok());
+    let overrun = codec.decode_message("Pairs", &[0, 0, 0, 1, 3, 0, 1, 0, 2]).unwrap_err();
+    assert!(overrun.to_string().contains("overruns its length 3"), "{}", overrun);
+    let underrun = codec.decode_message("Pairs", &[0, 0, 0, 1, 5, 0, 1, 0, 2, 0]).unwrap_err();
+    assert!(underrun.to_string().contains("length is 5"), "{}", underrun);
+    assert!(parse("message M { items: length_prefixed_list(i16)<u8>; }").is_err());
 }
 
 #[test]
-fn test_frame_decode_multiple_messages() {
-    let protocol = parse(SIMPLE_PROTO).expect("parse");
+fn test_bcd_swapped_and_reverse_bits() {
+    let dsl = r#"
+message Legacy {
+  serial: bcd(2);
+  reading: bcd_swapped(2);
+  flags: reverse_bits<u8>;
+  word: reverse_bits<u16>;
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
     let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
-    let codec = Codec::new(resolved, Endianness::Little);
-
-    let mut v1 = HashMap::new();
-    v1.insert("id".to_string(), Value::U8(1));
-    v1.insert("len".to_string(), Value::U16(0));
-    v1.insert("data".to_string(), Value::List(vec![]));
-    let mut v2 = HashMap::new();
-    v2.insert("id".to_string(), Value::U8(2));
-    v2.insert("len".to_string(), Value::U16(0));
-    v2.insert("data".to_string(), Value::List(vec![]));
+    let codec = Codec::new(resolved, Endianness::Big);
 
-    let b1 = codec.encode_message("Simple", &v1).expect("encode");
-    let b2 = codec.encode_message("Simple", &v2).expect("encode");
-    let frame_bytes: Vec<u8> = b1.into_iter().chain(b2.into_iter()).collect();
+    // serial 1234; reading 1234 with swapped nibbles; 0x01 is 0x80 bit-reversed; 0x80 0x00 is 0x0100.
+    let bytes = [0x12, 0x34, 0x21, 0x43, 0x01, 0x80, 0x00];
+    let decoded = codec.decode_message("Legacy", &bytes).expect("decode");
+    assert_eq!(decoded.get("serial"), Some(&Value::U64(1234)));
+    assert_eq!(decoded.get("reading"), Some(&Value::U64(1234)));
+    assert_eq!(decoded.get("flags"), Some(&Value::U8(0x80)));
+    assert_eq!(decoded.get("word"), Some(&Value::U16(0x0100)));
+    assert_eq!(codec.encode_message("Legacy", &decoded).expect("encode"), bytes);
+
+    assert!(codec.decode_message("Legacy", &[0x1A, 0x34, 0x21, 0x43, 0x01, 0x80, 0x00]).is_err(), "0xA is not a digit");
+    let mut too_big = decoded.clone();
+    too_big.insert("serial".to_string(), Value::U64(12345));
+    assert!(codec.encode_message("Legacy", &too_big).is_err());
+}
 
-    let result = frame::decode_frame(&codec, "Simple", &frame_bytes, None).expect("frame decode");
-    assert_eq!(result.messages.len(), 2);
-    assert_eq!(result.removed.len(), 0);
+#[test]
+fn test_random_values_roundtrip() {
+    let dsl = r#"
+enum Kind {
+  A = 1;
+  B = 7;
+}
+struct Point {
+  x: i16 [-100..100];
+  y: u16(12);
+  z: bitfield(4);
+  pad: padding(4, bits);
+}
+message Mixed {
+  kind: Kind;
+  mode: u8 [(1, 2)];
+  level: u16 [0..10] when mode == 1 [500..600] when mode == 2;
+  extra: u32 if mode == 2;
+  n: count_of(points);
+  points: Point[n];
+  tags: list<u8>;
+  history: rep_list<Point>;
+  ext: octets_fx;
+  tod: timestamp(u32(24), "1/128 s");
+  ratio: double;
+  fixed: Point[2];
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let mut max_points = 0;
+    for seed in 0..200 {
+        let values = aiprotodsl::value::random_message_values(&resolved, "Mixed", seed).expect("message");
+        let mode = values.get("mode").and_then(Value::as_u64).expect("mode");
+        assert_eq!(values.contains_key("extra"), mode == 2, "seed {}: condition", seed);
+        let points = values.get("points").and_then(Value::as_list).map_or(0, |l| l.len());
+        assert_eq!(values.get("n").and_then(Value::as_u64), Some(points as u64), "seed {}: count_of", seed);
+        max_points = max_points.max(points);
+        let encoded = codec.encode_message("Mixed", &values).expect("encode");
+        let decoded = codec.decode_message("Mixed", &encoded).expect("decode");
+        assert_eq!(decoded, values, "seed {}", seed);
+    }
+    assert!(max_points > 0, "some seeds generate array elements");
+    assert!(aiprotodsl::value::random_message_values(&resolved, "Missing", 0).is_none());
 }
 
 #[test]
@@ -175,6 +2634,39 @@ message M { x: u8; }
     assert_eq!(encoded[3], 0x34);
 }
 
+#[test]
+fn test_field_defaults_on_encode() {
+    let resolved = ResolvedProtocol::resolve(parse(WITH_TRANSPORT).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    // `version: u8 = 1` is written when the caller leaves it out, and a given value wins.
+    let tv = HashMap::from([("length".to_string(), Value::U32(3))]);
+    assert_eq!(codec.encode_transport(&tv).unwrap(), [b'P', b'A', b'C', b'K', 1, 0, 0, 0, 3, 0, 0]);
+    let tv = HashMap::from([("version".to_string(), Value::U8(2)), ("length".to_string(), Value::U32(3))]);
+    assert_eq!(codec.encode_transport(&tv).unwrap()[4], 2);
+
+    let src = "message M { kind: u8 = 7; level: i16 = -2; rest: u8; }";
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(src).unwrap()).unwrap(), Endianness::Big);
+    let values = HashMap::from([("rest".to_string(), Value::U8(9))]);
+    assert_eq!(codec.encode_message("M", &values).unwrap(), [7, 0xFF, 0xFE, 9]);
+    let decoded = codec.decode_message("M", &[7, 0xFF, 0xFE, 9]).unwrap();
+    assert_eq!(decoded.get("kind"), Some(&Value::U8(7)));
+    assert_eq!(decoded.get("level"), Some(&Value::I16(-2)));
+}
+
+#[test]
+fn test_decode_transport_checks_magic() {
+    let resolved = ResolvedProtocol::resolve(parse(WITH_TRANSPORT).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    let header = codec.decode_transport(b"PACK\x01\x00\x00\x00\x03\x00\x00").unwrap();
+    assert_eq!(header.get("magic"), Some(&Value::Bytes(b"PACK".to_vec())));
+    assert_eq!(header.get("length"), Some(&Value::U32(3)));
+
+    let err = codec.decode_transport(b"PACX\x01\x00\x00\x00\x03\x00\x00").unwrap_err();
+    assert!(matches!(&err, CodecError::Validation(m) if m.starts_with("magic mismatch: expected")), "{:?}", err);
+}
+
 // --- Walk-only tests (no full decode/encode) ---
 
 #[test]
@@ -270,6 +2762,145 @@ fn test_walk_remove_message_in_place() {
     validate_message_in_place(&frame_bytes, 0, &resolved, endianness, "Simple").expect("valid");
 }
 
+#[test]
+fn test_walk_remove_message_in_place_updating() {
+    let protocol = parse(SIMPLE_PROTO).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Little);
+    let endianness = WalkEndianness::from(Endianness::Little);
+
+    // Block header: total length (u32, header included) then record count (u8).
+    let mut records = Vec::new();
+    for (id, data) in [(1u8, vec![0xAAu8]), (2, vec![0xBB, 0xCC]), (3, vec![])] {
+        let mut v = HashMap::new();
+        v.insert("id".to_string(), Value::U8(id));
+        v.insert("len".to_string(), Value::U16(data.len() as u16));
+        v.insert("data".to_string(), Value::List(data.into_iter().map(Value::U8).collect()));
+        records.push(codec.encode_message("Simple", &v).expect("encode"));
+    }
+    let body: Vec<u8> = records.concat();
+    let mut block = ((5 + body.len()) as u32).to_le_bytes().to_vec();
+    block.push(3);
+    block.extend_from_slice(&body);
+
+    let start = 5 + records[0].len();
+    let len = message_extent(&block, start, &resolved, endianness, "Simple").expect("extent");
+    assert_eq!(len, records[1].len());
+    let counters = [(0, BaseType::U32, CounterKind::Length), (4, BaseType::U8, CounterKind::Count)];
+    let new_len = remove_message_in_place_updating(&mut block, start, len, endianness, &counters).expect("remove");
+    block.truncate(new_len);
+
+    assert_eq!(u32::from_le_bytes(block[0..4].try_into().unwrap()) as usize, block.len());
+    assert_eq!(block[4], 2);
+    assert_eq!(&block[5..], [records[0].as_slice(), records[2].as_slice()].concat().as_slice());
+
+    // A counter inside the removed range or one that would underflow leaves the buffer unchanged.
+    let before = block.clone();
+    let err = remove_message_in_place_updating(&mut block, 5, 1, endianness, &[(5, BaseType::U8, CounterKind::Count)]);
+    assert!(err.is_err());
+    let body_len = block.len() - 5;
+    let err = remove_message_in_place_updating(&mut block, 5, body_len, endianness, &[(4, BaseType::U8, CounterKind::Length)]);
+    assert!(err.is_err());
+    assert_eq!(block, before);
+}
+
+#[test]
+fn test_walk_nth_record_range() {
+    let protocol = parse(SIMPLE_PROTO).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Little);
+    let endianness = WalkEndianness::from(Endianness::Little);
+
+    let mut frame_bytes = Vec::new();
+    let mut ranges = Vec::new();
+    for (id, data_len) in [(1u8, 0usize), (2, 3), (3, 1)] {
+        let mut v = HashMap::new();
+        v.insert("id".to_string(), Value::U8(id));
+        v.insert("len".to_string(), Value::U16(data_len as u16));
+        v.insert("data".to_string(), Value::List(vec![Value::U8(0x55); data_len]));
+        let b = codec.encode_message("Simple", &v).expect("encode");
+        ranges.push((frame_bytes.len(), frame_bytes.len() + b.len()));
+        frame_bytes.extend_from_slice(&b);
+    }
+
+    for (n, expected) in ranges.iter().enumerate() {
+        let mut w = BinaryWalker::new(&frame_bytes, &resolved, endianness);
+        let range = w.nth_record_range("Simple", n).expect("nth record");
+        assert_eq!(range, *expected);
+        assert_eq!(w.position(), expected.1);
+        assert_eq!(frame_bytes[range.0], n as u8 + 1);
+    }
+
+    let mut w = BinaryWalker::new(&frame_bytes, &resolved, endianness);
+    assert!(w.nth_record_range("Simple", 3).is_err());
+}
+
+#[test]
+fn test_walk_messages_iterator() {
+    let protocol = parse(SIMPLE_PROTO).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Little);
+    let endianness = WalkEndianness::from(Endianness::Little);
+
+    let mut frame_bytes = Vec::new();
+    for (id, data_len) in [(1u8, 2usize), (2, 0), (3, 5)] {
+        let mut v = HashMap::new();
+        v.insert("id".to_string(), Value::U8(id));
+        v.insert("len".to_string(), Value::U16(data_len as u16));
+        v.insert("data".to_string(), Value::List(vec![Value::U8(0x55); data_len]));
+        frame_bytes.extend(codec.encode_message("Simple", &v).expect("encode"));
+    }
+
+    let mut w = BinaryWalker::new(&frame_bytes, &resolved, endianness);
+    let spans: Vec<(usize, usize)> = w.messages("Simple").collect::<Result<_, _>>().expect("spans");
+    assert_eq!(spans.len(), 3);
+    assert_eq!(spans[0].0, 0);
+    assert!(spans.windows(2).all(|p| p[0].1 == p[1].0), "contiguous: {:?}", spans);
+    assert_eq!(spans[2].1, frame_bytes.len());
+    assert_eq!(w.position(), frame_bytes.len());
+
+    // A cut record ends the iteration with its error.
+    let mut w = BinaryWalker::new(&frame_bytes[..frame_bytes.len() - 1], &resolved, endianness);
+    let results: Vec<_> = w.messages("Simple").collect();
+    assert_eq!(results.len(), 3);
+    assert!(matches!(results[2], Err(CodecError::Truncated { .. })));
+}
+
+#[test]
+fn test_walk_read_field() {
+    let dsl = r#"
+struct Polar {
+  rho: u16;
+  theta: u16;
+}
+message Plot {
+  mode: u8;
+  fspec: bitmap(3, 7) -> (0: sac, 1: pos, 2: extra);
+  sac: optional<u8>;
+  pos: optional<Polar>;
+  extra: optional<u8>;
+  bearing: i16 if mode == 1;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap();
+    // mode 0 so no bearing; sac and pos present, extra absent.
+    let bytes = [0, 0xC0, 7, 0x01, 0x02, 0x03, 0x04];
+    let read = |path: &str| BinaryWalker::new(&bytes, &resolved, WalkEndianness::Big).read_field("Plot", path);
+    assert_eq!(read("sac").unwrap(), 7);
+    assert_eq!(read("mode").unwrap(), 0);
+    assert_eq!(read("pos.rho").unwrap(), 0x0102);
+    assert_eq!(read("pos.theta").unwrap(), 0x0304);
+    assert!(matches!(read("bearing"), Err(CodecError::Validation(e)) if e.contains("not present")));
+    assert!(matches!(read("extra"), Err(CodecError::Validation(e)) if e.contains("not present")));
+    assert!(matches!(read("pos.phi"), Err(CodecError::UnknownField(_))));
+    assert!(matches!(read("nope"), Err(CodecError::UnknownField(_))));
+
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+    let values = codec.decode_message("Plot", &bytes).unwrap();
+    let Some(Value::Struct(pos)) = values.get("pos") else { panic!("pos: {:?}", values.get("pos")) };
+    assert_eq!(pos.get("rho"), Some(&Value::U16(0x0102)));
+}
+
 // --- Presence bits (ASN.1-style bitmap) ---
 
 const PRESENCE_BITS_PROTO: &str = r#"
@@ -318,6 +2949,44 @@ fn test_presence_bits_encode_decode() {
     assert_eq!(decoded2.get("b"), Some(&Value::List(vec![])));
 }
 
+#[test]
+fn test_presence_bits_stop_bit() {
+    let dsl = r#"
+message Chained {
+  flags: presence_bits(1, stop: 1);
+  a: optional<u8>;
+  b: optional<u8>;
+  c: optional<u8>;
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    // Stop bit (bit 1) clear: b and c are absent even though the wire bits for them are set.
+    let data = [0b1101u8, 7];
+    let decoded = codec.decode_message("Chained", &data).expect("decode");
+    assert_eq!(decoded.get("a"), Some(&Value::U8(7)));
+    assert_eq!(decoded.get("b"), Some(&Value::List(vec![])));
+    assert_eq!(decoded.get("c"), Some(&Value::List(vec![])));
+    assert_eq!(message_extent(&data, 0, &resolved, WalkEndianness::Big, "Chained").expect("extent"), 2);
+
+    // Encoding c sets the stop bit; a sits below it, b and c move up one bit.
+    let mut v = HashMap::new();
+    v.insert("a".to_string(), Value::List(vec![]));
+    v.insert("b".to_string(), Value::List(vec![]));
+    v.insert("c".to_string(), Value::List(vec![Value::U8(9)]));
+    let encoded = codec.encode_message("Chained", &v).expect("encode");
+    assert_eq!(encoded, vec![0b1010, 9]);
+    let decoded = codec.decode_message("Chained", &encoded).expect("decode");
+    assert_eq!(decoded.get("a"), Some(&Value::List(vec![])));
+    assert_eq!(decoded.get("c"), Some(&Value::U8(9)));
+
+    assert!(parse("message M { flags: presence_bits(1, stop: 8); }")
+        .and_then(ResolvedProtocol::resolve)
+        .is_err());
+}
+
 // --- Bitmap presence (variable-length presence bitmap; e.g. ASTERIX uses 7 presence + 1 FX per block) ---
 
 const FSPEC_PROTO: &str = r#"
@@ -348,6 +3017,132 @@ fn test_fspec_encode_decode() {
     assert_eq!(decoded.get("b"), Some(&Value::U16(0x1234)));
 }
 
+#[test]
+fn test_timestamp_asterix_time_of_day() {
+    // ASTERIX time of day: 3 bytes, 1/128 s since midnight.
+    let src = r#"
+message Plot {
+  tod: timestamp(u32(24), "1/128 s");
+}
+"#;
+    let protocol = parse(src).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    // 12:34:56.5 = 45296.5 s = 5797952 ticks = 0x587840
+    let wire = [0x58, 0x78, 0x40];
+    let decoded = codec.decode_message("Plot", &wire).expect("decode");
+    match decoded.get("tod") {
+        Some(Value::Timestamp { raw, seconds }) => {
+            assert_eq!(*raw, 5797952);
+            assert_eq!(*seconds, 45296.5);
+            assert_eq!(format_seconds_as_tod(*seconds), "12:34:56.500");
+        }
+        other => panic!("expected timestamp, got {:?}", other),
+    }
+    assert_eq!(value_to_dump(&resolved, "Plot", "tod", &decoded["tod"], 0), "12:34:56.500 (5797952)");
+
+    // Encode from seconds writes the raw ticks back.
+    let mut v = HashMap::new();
+    v.insert("tod".to_string(), Value::Double(45296.5));
+    assert_eq!(codec.encode_message("Plot", &v).expect("encode"), wire.to_vec());
+    let encoded = codec.encode_message("Plot", &decoded).expect("re-encode");
+    assert_eq!(encoded, wire.to_vec());
+    assert_eq!(message_extent(&encoded, 0, &resolved, WalkEndianness::Big, "Plot").expect("extent"), 3);
+}
+
+#[test]
+fn test_walk_record_extent_uses_selector() {
+    let dsl = r#"
+transport {
+  magic: magic("AB");
+  cat: u8;
+  len: u16;
+}
+payload {
+  messages: Short, Long;
+  selector: cat -> 1: Short, 2: Long, default: RawRecord;
+}
+message Short { a: u8; }
+message Long { a: u8; b: u32; }
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let data = [0x01, 0x00, 0x00, 0x00, 0x09, 0xFF];
+
+    let (extent, name) = record_extent(&data, 0, &resolved, WalkEndianness::Big, b"AB\x02\x00\x09").expect("extent");
+    assert_eq!((extent, name), (5, "Long"));
+    let (extent, name) = record_extent(&data, 1, &resolved, WalkEndianness::Big, b"AB\x01\x00\x09").expect("extent");
+    assert_eq!((extent, name), (1, "Short"));
+    let (extent, name) = record_extent(&data, 2, &resolved, WalkEndianness::Big, b"AB\x30\x00\x09").expect("extent");
+    assert_eq!((extent, name), (4, "RawRecord"));
+    assert!(record_extent(&data, 0, &resolved, WalkEndianness::Big, b"AB").is_err(), "transport too short");
+}
+
+#[test]
+fn test_message_layout_json_cat048() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/asterix_family.dsl");
+    let src = std::fs::read_to_string(&path).expect("read asterix_family.dsl");
+    let resolved = ResolvedProtocol::resolve(parse(&src).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+    let json = codec.message_layout_json("Cat048Record").expect("layout");
+
+    assert!(json.starts_with(r#"{"message":"Cat048Record","fields":["#), "{}", json);
+    assert!(json.contains(r#"{"name":"fspec","type":"bitmap(28, 7)","width_bits":null,"offset_bits":0,"optional":false"#), "{}", json);
+    assert!(json.contains(
+        r#"{"name":"i048_010","type":"optional<DataSourceId>","width_bits":null,"offset_bits":null,"optional":true,"constraint":null,"quantum":null,"doc":"Data Source Identifier (SAC/SIC)"}"#
+    ));
+    assert!(json.contains(r#"{"name":"i048_010.sac","type":"u8","width_bits":8,"offset_bits":null,"optional":true,"constraint":"[0..255]""#));
+    assert!(json.contains(r#"{"name":"i048_140.tod","type":"u32(24)","width_bits":24,"offset_bits":null,"optional":true,"constraint":"[0..16777215]","quantum":"1/128 s""#));
+    assert!(matches!(codec.message_layout_json("NoSuchMessage"), Err(CodecError::UnknownStruct(_))));
+
+    // Offsets are known up to the first variable-size field.
+    let dsl = "struct P { x: u16(12); y: bitfield(4); } message M { a: u8; p: P; data: list<u8>; b: u8; }";
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve"), Endianness::Big);
+    let json = codec.message_layout_json("M").expect("layout");
+    assert!(json.contains(r#""name":"p","type":"P","width_bits":16,"offset_bits":8,"#), "{}", json);
+    assert!(json.contains(r#""name":"p.y","type":"bitfield(4)","width_bits":4,"offset_bits":20,"#), "{}", json);
+    assert!(json.contains(r#""name":"b","type":"u8","width_bits":8,"offset_bits":null,"#), "{}", json);
+}
+
+#[test]
+fn test_kaitai_export_snapshots() {
+    let resolved = ResolvedProtocol::resolve(parse(SIMPLE_PROTO).unwrap()).unwrap();
+    let expected = "\
+# Kaitai Struct schema exported by aiprotodsl::codegen::to_kaitai.
+meta:
+  id: protocol
+  endian: be
+  bit-endian: le
+types:
+  simple:
+    seq:
+      - id: id
+        type: u1
+      - id: len
+        type: u2
+      - id: data_count
+        type: u4
+      - id: data
+        type: u1
+        repeat: expr
+        repeat-expr: data_count
+";
+    assert_eq!(codegen::to_kaitai(&resolved), expected);
+
+    // The ASTERIX export is checked in next to the DSL; regenerate it with UPDATE_SNAPSHOTS=1.
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let src = std::fs::read_to_string(dir.join("asterix_family.dsl")).expect("read asterix_family.dsl");
+    let ksy = codegen::to_kaitai(&ResolvedProtocol::resolve(parse(&src).unwrap()).unwrap());
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(dir.join("asterix_family.ksy"), &ksy).expect("write asterix_family.ksy");
+    }
+    let snapshot = std::fs::read_to_string(dir.join("asterix_family.ksy")).expect("read asterix_family.ksy");
+    assert!(ksy == snapshot, "asterix_family.ksy is out of date (UPDATE_SNAPSHOTS=1 cargo test)");
+    assert!(ksy.contains("        if: fspec.size > 1 and (fspec[1] & 0x80) != 0\n"));
+    assert!(ksy.contains("      # fspec: bitmap(1, 0) in a partly read byte is not supported by the Kaitai export\n"));
+}
+
 #[test]
 fn test_asterix_family_parse() {
     let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/asterix_family.dsl");
@@ -410,7 +3205,7 @@ fn test_asterix_family_parse() {
     let cat002 = resolved.get_message("Cat002Record").unwrap();
     // Cat002 has 11 optional fields (010, 000, 020, 030, 041, 050, 060, 070, 100, 090, 080)
     let cat002_optionals: Vec<&str> = cat002.fields.iter()
-        .filter(|f| matches!(f.type_spec, TypeSpec::Optional(_)))
+        .filter(|f| matches!(f.type_spec, TypeSpec::Optional(..)))
         .map(|f| f.name.as_str())
         .collect();
     assert_eq!(cat002_optionals.len(), 11);
@@ -448,6 +3243,146 @@ fn test_asterix_family_parse() {
     assert_eq!(child_040, Some("MeasuredPositionPolar"), "i048_040 is optional MeasuredPositionPolar");
 }
 
+#[test]
+fn test_asterix_family_selector_values() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/asterix_family.dsl");
+    let src = std::fs::read_to_string(&path).expect("read asterix_family.dsl");
+    let resolved = ResolvedProtocol::resolve(parse(&src).expect("parse")).expect("resolve");
+    let values = resolved.selector_values();
+    let categories: Vec<i64> = values.iter().filter_map(|(lit, _, _)| lit.as_i64()).collect();
+    assert_eq!(categories, vec![1, 2, 34, 48, 240]);
+    assert_eq!(values[3].0, Literal::Int(48));
+    assert_eq!(values[3].1, "Cat048Record");
+    assert!(values.iter().all(|(_, _, is_list)| *is_list), "all categories use list<...>");
+
+    let resolved_no_payload = ResolvedProtocol::resolve(parse(SIMPLE_PROTO).expect("parse")).expect("resolve");
+    assert!(resolved_no_payload.selector_values().is_empty());
+}
+
+#[test]
+fn test_enum_field_base_width() {
+    let dsl = r#"
+enum Color : u16 {
+  Red = 1;
+  Blue = 0x0200;
+}
+message Paint {
+  c: Color;
+  tail: u8;
+}
+"#;
+    let protocol = parse(dsl).expect("parse");
+    let resolved = ResolvedProtocol::resolve(protocol).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    let bytes = [0x02, 0x00, 0x07];
+    let decoded = codec.decode_message("Paint", &bytes).expect("decode");
+    assert_eq!(decoded.get("c"), Some(&Value::U16(0x0200)));
+    assert_eq!(decoded.get("tail"), Some(&Value::U8(7)));
+    assert_eq!(codec.encode_message("Paint", &decoded).expect("encode"), bytes);
+
+    assert!(codec.decode_message("Paint", &[0x00, 0x02, 0x07]).is_err(), "0x0002 is not a Color");
+    let mut bad = decoded.clone();
+    bad.insert("c".to_string(), Value::U16(3));
+    assert!(codec.encode_message("Paint", &bad).is_err());
+}
+
+#[test]
+fn test_selector_default_raw_record_roundtrip() {
+    let dsl = r#"
+transport {
+  category: u8;
+  length: u16;
+}
+payload {
+  messages: Known;
+  selector: category -> 1: Known, default: RawRecord;
+}
+message Known {
+  x: u8;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved.clone(), Endianness::Big);
+
+    // Category 62 is not modeled: the record bytes must survive decode/re-encode untouched.
+    let block: Vec<u8> = vec![62, 0x00, 0x08, 0xde, 0xad, 0xbe, 0xef, 0x01];
+    let transport_values = codec.decode_transport(&block).expect("transport");
+    let msg_name = resolved.message_for_transport_values(&transport_values).expect("raw fallback");
+    assert_eq!(msg_name, "RawRecord");
+
+    let result = frame::decode_frame(&codec, msg_name, &block, Some(3)).expect("decode_frame");
+    assert_eq!(result.messages.len(), 1);
+    assert_eq!(result.messages[0].byte_range, (3, 8));
+    assert_eq!(result.messages[0].values.get("data"), Some(&Value::Bytes(block[3..].to_vec())));
+
+    let out = frame::encode_frame_with_compliant_only(&codec, msg_name, &result, Some(&transport_values), Some(3))
+        .expect("encode");
+    assert_eq!(out, block);
+
+    // Mapped categories are unaffected.
+    let mut tv = HashMap::new();
+    tv.insert("category".to_string(), Value::U8(1));
+    assert_eq!(resolved.message_for_transport_values(&tv), Some("Known"));
+}
+
+#[test]
+fn test_encode_record_fills_transport_length() {
+    let dsl = r#"
+transport {
+  category: u8;
+  length: length_of<u16>(record);
+  body: length_of<u8>(message);
+  n: count_of<u8>(items);
+  items_len: length_of<u8>(items);
+}
+message Rec {
+  a: u8;
+  items: list<u16>;
+}
+"#;
+    let resolved = ResolvedProtocol::resolve(parse(dsl).expect("parse")).expect("resolve");
+    let codec = Codec::new(resolved, Endianness::Big);
+
+    let mut transport_values = HashMap::new();
+    transport_values.insert("category".to_string(), Value::U8(48));
+    transport_values.insert("length".to_string(), Value::U16(0)); // stale value is overwritten
+    let mut values = HashMap::new();
+    values.insert("a".to_string(), Value::U8(1));
+    values.insert("items".to_string(), Value::List(vec![Value::U16(0x1234), Value::U16(0x5678)]));
+
+    let bytes = codec.encode_record(&transport_values, "Rec", &values).expect("encode_record");
+    assert_eq!(
+        bytes,
+        vec![48, 0x00, 0x0F, 0x09, 0x02, 0x08, 0x01, 0x00, 0x00, 0x00, 0x02, 0x12, 0x34, 0x56, 0x78]
+    );
+
+    let header = codec.decode_transport(&bytes).expect("transport");
+    assert_eq!(header.get("length").and_then(Value::as_u64), Some(bytes.len() as u64));
+    assert_eq!(header.get("body").and_then(Value::as_u64), Some(bytes.len() as u64 - 6));
+    assert_eq!(header.get("n").and_then(Value::as_u64), Some(2));
+    let result = frame::decode_frame(&codec, "Rec", &bytes, Some(6)).expect("decode_frame");
+    assert_eq!(result.messages.len(), 1);
+    assert!(result.removed.is_empty());
+    assert_eq!(result.messages[0].values.get("a"), Some(&Value::U8(1)));
+
+    // A record too long for its length field is rejected.
+    values.insert("items".to_string(), Value::List(vec![Value::U16(0); 200]));
+    assert!(codec.encode_record(&transport_values, "Rec", &values).is_err());
+
+    // An absent optional measures 0.
+    let dsl = "transport { len: length_of<u16>(body); }\nmessage M { a: u8; body: optional<u32>; }";
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl).unwrap()).unwrap(), Endianness::Big);
+    let values = HashMap::from([("a".to_string(), Value::U8(1)), ("body".to_string(), Value::List(vec![]))]);
+    let bytes = codec.encode_record(&HashMap::new(), "M", &values).expect("encode_record");
+    assert_eq!(&bytes[..2], &[0, 0]);
+
+    // A transport size naming no message field is rejected when resolving.
+    let err = ResolvedProtocol::resolve(parse("transport { n: count_of<u8>(itmes); }\nmessage M { items: list<u8>; }").unwrap())
+        .unwrap_err();
+    assert!(err.contains("count_of(itmes)"), "{}", err);
+}
+
 /// Decode frame 1 CAT048 block (bitmap 0xFD 0xF7 0x02 => I048/130 absent). Verifies mapping is applied so we skip 130 and decode past 161.
 #[test]
 fn test_cat048_frame1_130_absent_decode() {
@@ -480,8 +3415,8 @@ fn test_cat048_frame1_130_absent_decode() {
     }
 }
 
-/// Run decode_pcap on a pcap and return stderr summary (block count, decoded, removed, known/unknown cats).
-fn run_decode_pcap(pcap_path: &str, dsl_path: &str) -> (String, std::process::Output) {
+/// Run decode_pcap on a pcap (plus any extra flags) and return stderr summary (block count, decoded, removed, known/unknown cats).
+fn run_decode_pcap(pcap_path: &str, dsl_path: &str, extra: &[&str]) -> (String, std::process::Output) {
     let bin = std::env::current_dir()
         .ok()
         .and_then(|cwd| {
@@ -493,6 +3428,7 @@ fn run_decode_pcap(pcap_path: &str, dsl_path: &str) -> (String, std::process::Ou
         .expect("decode_pcap binary not found (run cargo build --bin decode_pcap)");
     let out = std::process::Command::new(&bin)
         .args([pcap_path, dsl_path])
+        .args(extra)
         .output()
         .expect("run decode_pcap");
     (String::from_utf8_lossy(&out.stderr).into_owned(), out)
@@ -509,7 +3445,7 @@ fn test_asterix_pcap_vs_wireshark_consistency() {
     if !pcap.exists() {
         return;
     }
-    let (stderr, out) = run_decode_pcap(pcap.to_string_lossy().as_ref(), &dsl_str);
+    let (stderr, out) = run_decode_pcap(pcap.to_string_lossy().as_ref(), &dsl_str, &[]);
     assert!(out.status.success(), "decode_pcap should succeed: {}", stderr);
     assert!(
         stderr.contains("asterix blocks") && stderr.contains("CAT034") && stderr.contains("CAT048"),
@@ -524,6 +3460,87 @@ fn test_asterix_pcap_vs_wireshark_consistency() {
     );
 }
 
+#[test]
+fn test_decode_pcap_json_output() {
+    let manifest = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let dsl = manifest.join("examples/asterix_family.dsl");
+    let pcap = manifest.join("assets/cat_034_048.pcap");
+    if !pcap.exists() {
+        return;
+    }
+    let (stderr, out) = run_decode_pcap(pcap.to_string_lossy().as_ref(), dsl.to_string_lossy().as_ref(), &["--json"]);
+    assert!(out.status.success(), "decode_pcap --json should succeed: {}", stderr);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    let decoded = stderr.lines().find_map(|l| l.strip_prefix("decoded records: ")).expect("decoded records in summary");
+    assert_eq!(lines.len().to_string(), decoded.trim(), "one JSON line per decoded record");
+    assert!(
+        lines[0].starts_with(r#"{"packet":1,"udp_offset":0,"category":48,"message":"Cat048Record","byte_range":[3,48],"values":{"fspec":"fdf702","i048_010":{"sac":25,"sic":201},"#),
+        "unexpected first record: {}",
+        lines[0]
+    );
+    assert!(lines.iter().all(|l| l.starts_with('{') && l.ends_with("}}")), "every line is one record object");
+
+    // Names and hex strings go through the same escaper as the layout JSON.
+    assert_eq!(aiprotodsl::json_str("a\"b\\c\n\u{1}"), r#""a\"b\\c\n\u0001""#);
+}
+
+#[test]
+fn test_decode_pcap_emit_test_output() {
+    // One raw-IPv4 UDP packet carrying a CAT007 block with two `enum<Mode>` records: Busy (2) and 9 (no variant).
+    let dsl_src = "transport {\n\tcategory: u8 [0..255];\n\tlength: length_of<u16>(record) [0..65535];\n}\n\n\
+        payload {\n\tmessages: Status;\n\tselector: category -> 7: list<Status>;\n}\n\n\
+        enum Mode {\n\tIdle = 1;\n\tBusy = 2;\n}\n\n\
+        message Status {\n\tmode: enum<Mode>;\n\tlabel: u8;\n}\n";
+    let payload = [7u8, 0, 7, 0x02, 0x41, 0x09, 0x22];
+    let mut udp = vec![0x03, 0xe8, 0x07, 0xd0];
+    udp.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(&payload);
+    let mut ip = vec![0x45, 0];
+    ip.extend_from_slice(&(20 + udp.len() as u16).to_be_bytes());
+    ip.extend_from_slice(&[0, 0, 0, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+    ip.extend_from_slice(&udp);
+    let mut pcap = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0, 101, 0, 0, 0];
+    pcap.extend_from_slice(&[0; 8]);
+    pcap.extend_from_slice(&(ip.len() as u32).to_le_bytes());
+    pcap.extend_from_slice(&(ip.len() as u32).to_le_bytes());
+    pcap.extend_from_slice(&ip);
+
+    let dir = tempfile::tempdir().unwrap();
+    let (dsl, pcap_path) = (dir.path().join("enum.dsl"), dir.path().join("enum.pcap"));
+    std::fs::write(&dsl, dsl_src).unwrap();
+    std::fs::write(&pcap_path, &pcap).unwrap();
+    let (stderr, out) = run_decode_pcap(pcap_path.to_string_lossy().as_ref(), dsl.to_string_lossy().as_ref(), &["--emit-test=2"]);
+    assert!(out.status.success(), "decode_pcap --emit-test should succeed: {}", stderr);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains(&format!("const CAPTURE_DSL: &str = r\"{}\";", dsl_src)), "DSL embedded: {}", stdout);
+    let first = r#"#[test]
+fn capture_cat007_packet1_offset0_record0() {
+    let resolved = ResolvedProtocol::resolve(parse(CAPTURE_DSL).unwrap()).unwrap();
+    let codec = Codec::new(resolved, Endianness::Big);
+    let bytes: &[u8] = &[0x02, 0x41];
+    let values = codec.decode_message("Status", bytes).unwrap();
+    let expected: HashMap<String, Value> = HashMap::from([
+        ("label".to_string(), Value::U8(65)),
+        ("mode".to_string(), Value::Enum { name: "Mode".to_string(), variant: Some("Busy".to_string()), raw: 2 }),
+    ]);
+    assert_eq!(values, expected);
+}
+"#;
+    assert!(stdout.contains(first), "emitted test for record 0: {}", stdout);
+    assert!(
+        stdout.contains(r#"("mode".to_string(), Value::Enum { name: "Mode".to_string(), variant: None, raw: 9 }),"#),
+        "emitted enum value with no variant: {}",
+        stdout
+    );
+
+    // The emitted expectation is what the library decodes for those bytes.
+    let codec = Codec::new(ResolvedProtocol::resolve(parse(dsl_src).unwrap()).unwrap(), Endianness::Big);
+    let values = codec.decode_message("Status", &[0x02, 0x41]).unwrap();
+    assert_eq!(values.get("mode"), Some(&Value::Enum { name: "Mode".to_string(), variant: Some("Busy".to_string()), raw: 2 }));
+}
+
 #[test]
 fn test_dsl_lint_tabs_and_one_field_per_line() {
     // Compliant: tabs only, one field per line
@@ -544,11 +3561,11 @@ fn test_dsl_lint_tabs_and_one_field_per_line() {
     // Two fields on one line -> OneFieldPerLine
     let two_fields = "message M {\n\tx: u8; y: u16;\n}\n";
     let msgs = lint(two_fields);
-    assert!(
-        msgs.iter().any(|m| m.rule == LintRule::OneFieldPerLine),
-        "two fields on one line should trigger OneFieldPerLine: {:?}",
-        msgs
-    );
+    let one_field = msgs.iter().find(|m| m.rule == LintRule::OneFieldPerLine);
+    let Some(m) = one_field else { panic!("two fields on one line should trigger OneFieldPerLine: {:?}", msgs) };
+    // The span underlines the second field.
+    assert_eq!((m.line, m.col_start, m.col_end), (2, 9, 16));
+    assert_eq!(&two_fields.lines().nth(1).unwrap()[m.col_start - 1..m.col_end - 1], "y: u16;");
 }
 
 /// Nested bitmap structs: message with optional struct, each struct has its own bitmap and optional nested struct, up to depth 5.